@@ -0,0 +1,17 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use instant_clip_tokenizer::{Token, Tokenizer};
+use libfuzzer_sys::fuzz_target;
+
+static TOKENIZER: OnceLock<Tokenizer> = OnceLock::new();
+
+fuzz_target!(|ids: Vec<u16>| {
+    let tokenizer = TOKENIZER.get_or_init(Tokenizer::new);
+    let tokens = ids
+        .into_iter()
+        .filter_map(|id| Token::from_u16(id, tokenizer))
+        .collect::<Vec<_>>();
+    tokenizer.decode(tokens);
+});
@@ -0,0 +1,18 @@
+#![no_main]
+
+use std::sync::OnceLock;
+
+use instant_clip_tokenizer::Tokenizer;
+use libfuzzer_sys::fuzz_target;
+
+static TOKENIZER: OnceLock<Tokenizer> = OnceLock::new();
+
+fuzz_target!(|data: &[u8]| {
+    let tokenizer = TOKENIZER.get_or_init(Tokenizer::new);
+    let text = String::from_utf8_lossy(data);
+    let mut tokens = Vec::new();
+    tokenizer.encode(&text, &mut tokens);
+
+    // Every token produced must be decodable again
+    tokenizer.decode(tokens);
+});
@@ -0,0 +1,19 @@
+#![no_main]
+
+use instant_clip_tokenizer::{Token, Tokenizer};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|input: (u16, &[u8])| {
+    let (max_vocabulary_size, data) = input;
+    let Ok(tokenizer) = Tokenizer::with_vocabulary(data, max_vocabulary_size) else {
+        return;
+    };
+
+    // Exercise the resulting tables: every valid token must be decodable and encoding must
+    // terminate for the vocabulary data itself
+    let end_of_text = tokenizer.end_of_text().to_u16();
+    let tokens = (0..=end_of_text).filter_map(|id| Token::from_u16(id, &tokenizer));
+    tokenizer.decode(tokens);
+    let mut tokens = Vec::new();
+    tokenizer.encode(&String::from_utf8_lossy(data), &mut tokens);
+});
@@ -0,0 +1,54 @@
+/*
+ * Example of using the tokenizer from C.
+ *
+ * Build the static library using `cargo build --release -p instant-clip-tokenizer-c`, then
+ * compile this example, e.g.:
+ *
+ *   cc -I instant-clip-tokenizer-c/include instant-clip-tokenizer-c/examples/encode.c \
+ *       target/release/libinstant_clip_tokenizer_c.a -lpthread -ldl -lm -o encode
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#include "instant_clip_tokenizer.h"
+
+int main(void) {
+    InstantClipTokenizer *tokenizer = instant_clip_tokenizer_new();
+
+    const char *text = "A person riding a motorcycle";
+    uint16_t *tokens;
+    size_t len = instant_clip_tokenizer_encode(tokenizer, text, strlen(text), &tokens);
+    for (size_t i = 0; i < len; i++) {
+        printf("%u ", tokens[i]);
+    }
+    printf("\n");
+
+    char *decoded;
+    size_t decoded_len;
+    char *error = NULL;
+    if (instant_clip_tokenizer_decode(tokenizer, tokens, len, &decoded, &decoded_len, &error)) {
+        printf("%s\n", decoded);
+        instant_clip_tokenizer_text_free(decoded, decoded_len);
+    }
+    instant_clip_tokenizer_tokens_free(tokens, len);
+
+    const char *texts[] = {"Hi there", "How are you?"};
+    size_t text_lens[] = {strlen(texts[0]), strlen(texts[1])};
+    size_t context_length = instant_clip_tokenizer_context_length(tokenizer);
+    uint16_t *batch = malloc(2 * context_length * sizeof(uint16_t));
+    if (instant_clip_tokenizer_tokenize_batch(tokenizer, texts, text_lens, 2, context_length, batch,
+                                              &error)) {
+        printf("%u %u %u %u\n", batch[0], batch[1], batch[2], batch[3]);
+    }
+
+    if (!instant_clip_tokenizer_tokenize_batch(tokenizer, texts, text_lens, 2, 2, batch, &error)) {
+        printf("error: %s\n", error);
+        instant_clip_tokenizer_error_free(error);
+    }
+
+    free(batch);
+    instant_clip_tokenizer_free(tokenizer);
+    return 0;
+}
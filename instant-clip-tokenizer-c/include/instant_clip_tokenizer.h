@@ -0,0 +1,99 @@
+/*
+ * C API of the `instant-clip-tokenizer` crate, implemented in `src/lib.rs`.
+ *
+ * Memory returned by these functions is allocated by Rust and must be released using the matching
+ * `instant_clip_tokenizer_*_free()` function. Functions that can fail return `false` or `NULL`
+ * and, if `error` is not `NULL`, store an error message in `*error`, which must be released using
+ * `instant_clip_tokenizer_error_free()`.
+ */
+
+#ifndef INSTANT_CLIP_TOKENIZER_H
+#define INSTANT_CLIP_TOKENIZER_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A text tokenizer for the CLIP neural network. A tokenizer can be used from several threads. */
+typedef struct InstantClipTokenizer InstantClipTokenizer;
+
+/*
+ * Create a new tokenizer using the vocabulary data bundled with this library.
+ *
+ * Creating a new tokenizer is expensive, so it is recommended to create the tokenizer once and
+ * then reuse it.
+ */
+InstantClipTokenizer *instant_clip_tokenizer_new(void);
+
+/*
+ * Create a new tokenizer by reading the vocabulary data from the given path.
+ *
+ * All formats supported by `Tokenizer::from_path` in the Rust crate can be used. Returns `NULL`
+ * if the vocabulary data can't be loaded.
+ */
+InstantClipTokenizer *instant_clip_tokenizer_load(const char *path, uint16_t max_vocabulary_size,
+                                                  char **error);
+
+/* Release a tokenizer. */
+void instant_clip_tokenizer_free(InstantClipTokenizer *tokenizer);
+
+/*
+ * Encode `text_len` bytes of `text` as a sequence of tokens, not including the `<start_of_text>`
+ * and `<end_of_text>` marker tokens.
+ *
+ * Stores a pointer to the tokens in `*tokens` and returns their number. The tokens must be
+ * released using `instant_clip_tokenizer_tokens_free()`.
+ */
+size_t instant_clip_tokenizer_encode(const InstantClipTokenizer *tokenizer, const char *text,
+                                     size_t text_len, uint16_t **tokens);
+
+/* Release tokens returned by `instant_clip_tokenizer_encode()`. */
+void instant_clip_tokenizer_tokens_free(uint16_t *tokens, size_t len);
+
+/*
+ * Tokenize a batch of `count` input strings, the `i`-th consisting of `text_lens[i]` bytes
+ * starting at `texts[i]`.
+ *
+ * Writes a row-major matrix of shape `(count, context_length)` to `out`. Each row consists of
+ * the `<start_of_text>` marker token, the encoded text, the `<end_of_text>` marker token and
+ * padding zeros. Returns `false` if `context_length < 3`.
+ */
+bool instant_clip_tokenizer_tokenize_batch(const InstantClipTokenizer *tokenizer,
+                                           const char *const *texts, const size_t *text_lens,
+                                           size_t count, size_t context_length, uint16_t *out,
+                                           char **error);
+
+/*
+ * Convert `len` tokens back to a textual representation.
+ *
+ * Stores a pointer to the NUL-terminated text in `*text` and its length in `*text_len`. The text
+ * must be released using `instant_clip_tokenizer_text_free()`. Returns `false` if `tokens`
+ * contains an invalid token.
+ */
+bool instant_clip_tokenizer_decode(const InstantClipTokenizer *tokenizer, const uint16_t *tokens,
+                                   size_t len, char **text, size_t *text_len, char **error);
+
+/* Release a text returned by `instant_clip_tokenizer_decode()`. */
+void instant_clip_tokenizer_text_free(char *text, size_t text_len);
+
+/* Release an error message. */
+void instant_clip_tokenizer_error_free(char *error);
+
+/* Returns the special `<start_of_text>` marker token. */
+uint16_t instant_clip_tokenizer_start_of_text(const InstantClipTokenizer *tokenizer);
+
+/* Returns the special `<end_of_text>` marker token. */
+uint16_t instant_clip_tokenizer_end_of_text(const InstantClipTokenizer *tokenizer);
+
+/* Returns the default context length of the tokenizer's model. */
+size_t instant_clip_tokenizer_context_length(const InstantClipTokenizer *tokenizer);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
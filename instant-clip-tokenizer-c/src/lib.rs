@@ -0,0 +1,269 @@
+//! C bindings for the `instant-clip-tokenizer` crate.
+//!
+//! Building this crate produces a static library exporting the functions declared in
+//! `include/instant_clip_tokenizer.h`. Besides C, this is what the Go package in
+//! `instant-clip-tokenizer-go` links against. See `examples/encode.c` for an example.
+//!
+//! Memory returned by these functions is allocated by Rust and must be released using the
+//! matching `instant_clip_tokenizer_*_free()` function. Functions that can fail return `false` or
+//! `NULL` and, if `error` is not `NULL`, store an error message in `*error`.
+
+use std::ffi::{c_char, CStr, CString};
+use std::{io, ptr, slice};
+
+use instant_clip_tokenizer::{Token, Tokenizer};
+
+/// Create a new tokenizer using the vocabulary data bundled with this library.
+///
+/// Note that creating a new tokenizer is expensive, so it is recommended to create the tokenizer
+/// once and then reuse it. The tokenizer must be released using
+/// [`instant_clip_tokenizer_free`].
+#[no_mangle]
+pub extern "C" fn instant_clip_tokenizer_new() -> *mut Tokenizer {
+    Box::into_raw(Box::new(Tokenizer::new()))
+}
+
+/// Create a new tokenizer by reading the vocabulary data from the given path.
+///
+/// All formats supported by `Tokenizer::from_path` in the Rust crate can be used. Returns `NULL`
+/// if the vocabulary data can't be loaded.
+///
+/// # Safety
+///
+/// `path` must be a valid NUL-terminated string, and `error` must be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_load(
+    path: *const c_char,
+    max_vocabulary_size: u16,
+    error: *mut *mut c_char,
+) -> *mut Tokenizer {
+    let result = CStr::from_ptr(path)
+        .to_str()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))
+        .and_then(|path| Tokenizer::from_path(path, max_vocabulary_size));
+    match result {
+        Ok(tokenizer) => Box::into_raw(Box::new(tokenizer)),
+        Err(err) => {
+            set_error(error, err);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release a tokenizer created by [`instant_clip_tokenizer_new`] or
+/// [`instant_clip_tokenizer_load`].
+///
+/// # Safety
+///
+/// `tokenizer` must be `NULL` or a tokenizer that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_free(tokenizer: *mut Tokenizer) {
+    if !tokenizer.is_null() {
+        drop(Box::from_raw(tokenizer));
+    }
+}
+
+/// Encode `text_len` bytes of `text` as a sequence of tokens.
+///
+/// Stores a pointer to the tokens in `*tokens` and returns their number. The encoded token
+/// sequence does not include the special `<start_of_text>` and `<end_of_text>` marker tokens.
+/// Invalid UTF-8 in `text` is replaced by the replacement character `U+FFFD`. The tokens must be
+/// released using [`instant_clip_tokenizer_tokens_free`].
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer, `text` must be valid for reads of `text_len` bytes and
+/// `tokens` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_encode(
+    tokenizer: *const Tokenizer,
+    text: *const c_char,
+    text_len: usize,
+    tokens: *mut *mut u16,
+) -> usize {
+    let text = String::from_utf8_lossy(slice_or_empty(text.cast::<u8>(), text_len));
+    let mut encoded = Vec::with_capacity(text.len());
+    (*tokenizer).encode(&text, &mut encoded);
+    let encoded = encoded
+        .into_iter()
+        .map(Token::to_u16)
+        .collect::<Box<[u16]>>();
+    let len = encoded.len();
+    *tokens = Box::into_raw(encoded).cast();
+    len
+}
+
+/// Release tokens returned by [`instant_clip_tokenizer_encode`].
+///
+/// # Safety
+///
+/// `tokens` must be `NULL` or have been returned by [`instant_clip_tokenizer_encode`] together
+/// with `len`, and must not have been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_tokens_free(tokens: *mut u16, len: usize) {
+    if !tokens.is_null() {
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(tokens, len)));
+    }
+}
+
+/// Tokenize a batch of `count` input strings, writing the tokens to `out`.
+///
+/// The `i`-th text consists of the `text_lens[i]` bytes starting at `texts[i]`. Each row of
+/// `context_length` tokens consists of the `<start_of_text>` marker token, the encoded text, the
+/// `<end_of_text>` marker token and padding zeros, exactly like `tokenize_batch` in the Rust
+/// crate. The rows are written to `out` one after another, resulting in a row-major matrix of
+/// shape `(count, context_length)`. Invalid UTF-8 in `texts` is replaced by the replacement
+/// character `U+FFFD`. Returns `false` if `context_length < 3`.
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer, `texts` and `text_lens` must be valid for reads of
+/// `count` elements, each text must be valid for reads of its length, `out` must be valid for
+/// writes of `count * context_length` tokens and `error` must be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_tokenize_batch(
+    tokenizer: *const Tokenizer,
+    texts: *const *const c_char,
+    text_lens: *const usize,
+    count: usize,
+    context_length: usize,
+    out: *mut u16,
+    error: *mut *mut c_char,
+) -> bool {
+    let texts = slice_or_empty(texts, count)
+        .iter()
+        .zip(slice_or_empty(text_lens, count))
+        .map(|(&text, &len)| String::from_utf8_lossy(slice_or_empty(text.cast::<u8>(), len)))
+        .collect::<Vec<_>>();
+    let result = match (*tokenizer).try_tokenize_batch(texts.iter().map(|t| &**t), context_length) {
+        Ok(result) => result,
+        Err(err) => {
+            set_error(error, err);
+            return false;
+        }
+    };
+    if let Some(result) = result.as_slice() {
+        ptr::copy_nonoverlapping(result.as_ptr(), out, result.len());
+    }
+    true
+}
+
+/// Convert `len` tokens back to a textual representation.
+///
+/// Stores a pointer to the text in `*text` and its length in bytes in `*text_len`. The text is
+/// NUL-terminated, but may contain NUL bytes if those were encoded. It must be released using
+/// [`instant_clip_tokenizer_text_free`]. Returns `false` if `tokens` contains an invalid token.
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer, `tokens` must be valid for reads of `len` elements,
+/// `text` and `text_len` must be valid for writes and `error` must be `NULL` or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_decode(
+    tokenizer: *const Tokenizer,
+    tokens: *const u16,
+    len: usize,
+    text: *mut *mut c_char,
+    text_len: *mut usize,
+    error: *mut *mut c_char,
+) -> bool {
+    let tokenizer = &*tokenizer;
+    let decoded = slice_or_empty(tokens, len)
+        .iter()
+        .map(|&t| {
+            Token::from_u16(t, tokenizer).ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, format!("invalid token: {t}"))
+            })
+        })
+        .collect::<io::Result<Vec<_>>>()
+        .and_then(|tokens| tokenizer.try_decode(tokens));
+    match decoded {
+        Ok(decoded) => {
+            let mut bytes = decoded.into_bytes();
+            *text_len = bytes.len();
+            bytes.push(0);
+            *text = Box::into_raw(bytes.into_boxed_slice()).cast();
+            true
+        }
+        Err(err) => {
+            set_error(error, err);
+            false
+        }
+    }
+}
+
+/// Release a text returned by [`instant_clip_tokenizer_decode`].
+///
+/// # Safety
+///
+/// `text` must be `NULL` or have been returned by [`instant_clip_tokenizer_decode`] together with
+/// `text_len`, and must not have been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_text_free(text: *mut c_char, text_len: usize) {
+    if !text.is_null() {
+        // Include the NUL terminator
+        drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+            text.cast::<u8>(),
+            text_len + 1,
+        )));
+    }
+}
+
+/// Release an error message returned by one of the other functions.
+///
+/// # Safety
+///
+/// `error` must be `NULL` or an error message that has not been released yet.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_error_free(error: *mut c_char) {
+    if !error.is_null() {
+        drop(CString::from_raw(error));
+    }
+}
+
+/// Returns the special `<start_of_text>` marker token.
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_start_of_text(tokenizer: *const Tokenizer) -> u16 {
+    (*tokenizer).start_of_text().to_u16()
+}
+
+/// Returns the special `<end_of_text>` marker token.
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_end_of_text(tokenizer: *const Tokenizer) -> u16 {
+    (*tokenizer).end_of_text().to_u16()
+}
+
+/// Returns the default context length of the tokenizer's model.
+///
+/// # Safety
+///
+/// `tokenizer` must be a valid tokenizer.
+#[no_mangle]
+pub unsafe extern "C" fn instant_clip_tokenizer_context_length(
+    tokenizer: *const Tokenizer,
+) -> usize {
+    (*tokenizer).context_length()
+}
+
+unsafe fn set_error(error: *mut *mut c_char, err: io::Error) {
+    if !error.is_null() {
+        let message = err.to_string().replace('\0', "");
+        *error = CString::new(message).unwrap_or_default().into_raw();
+    }
+}
+
+/// Like `slice::from_raw_parts()`, but also accepts `NULL` if `len` is `0`.
+unsafe fn slice_or_empty<'a, T>(data: *const T, len: usize) -> &'a [T] {
+    match len {
+        0 => &[],
+        _ => slice::from_raw_parts(data, len),
+    }
+}
@@ -0,0 +1,164 @@
+//! Tokenize the lines of text files or of stdin, writing one line of output per input line.
+//!
+//! This works like `python -m instant_clip_tokenizer`, but only depends on `std`, so it can also
+//! be built for `wasm32-wasip1` and run sandboxed, e.g. using wasmtime:
+//!
+//! ```sh
+//! cargo build -p instant-clip-tokenizer-cli --release --target wasm32-wasip1
+//! wasmtime run --dir . target/wasm32-wasip1/release/instant-clip-tokenizer.wasm captions.txt
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+use std::{env, process};
+
+use instant_clip_tokenizer::{Token, Tokenizer};
+
+const USAGE: &str = "\
+Usage: instant-clip-tokenizer [OPTIONS] [FILE ...]
+
+Tokenize each line of the given files (or stdin) for the CLIP neural network.
+
+Options:
+  --tokens              write readable tokens (e.g. hi</w>) instead of token ids
+  --context-length N    add the marker tokens and pad or truncate to N tokens
+  --vocabulary FILE     read the vocabulary from FILE instead of using the bundled one
+  -h, --help            show this help";
+
+struct Args {
+    tokens: bool,
+    context_length: Option<usize>,
+    vocabulary: Option<PathBuf>,
+    files: Vec<PathBuf>,
+}
+
+fn main() {
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return;
+        }
+        Err(err) => {
+            eprintln!("error: {err}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+    if let Err(err) = run(&args) {
+        // The reader of our output went away, e.g. when piping into `head`
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            return;
+        }
+        eprintln!("error: {err}");
+        process::exit(1);
+    }
+}
+
+/// Parse the command-line arguments, returning `None` if help was requested.
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut parsed = Args {
+        tokens: false,
+        context_length: None,
+        vocabulary: None,
+        files: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--tokens" => parsed.tokens = true,
+            "--context-length" => {
+                let value = args.next().ok_or("--context-length needs a value")?;
+                match value.parse() {
+                    Ok(context_length) if context_length >= 3 => {
+                        parsed.context_length = Some(context_length)
+                    }
+                    _ => return Err("--context-length must be a number of at least 3".into()),
+                }
+            }
+            "--vocabulary" => {
+                let value = args.next().ok_or("--vocabulary needs a value")?;
+                parsed.vocabulary = Some(value.into());
+            }
+            "--" => parsed.files.extend(args.by_ref().map(PathBuf::from)),
+            _ if arg.starts_with('-') => {
+                return Err(format!("unknown option {arg}"));
+            }
+            _ => parsed.files.push(arg.into()),
+        }
+    }
+    Ok(Some(parsed))
+}
+
+fn run(args: &Args) -> io::Result<()> {
+    let tokenizer = Tokenizer::new_with_overrides(args.vocabulary.as_deref())?;
+    let mut out = BufWriter::new(io::stdout().lock());
+    if args.files.is_empty() {
+        tokenize(&tokenizer, io::stdin().lock(), &mut out, args)?;
+    }
+    for path in &args.files {
+        let file = File::open(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+        tokenize(&tokenizer, BufReader::new(file), &mut out, args)?;
+    }
+    out.flush()
+}
+
+fn tokenize(
+    tokenizer: &Tokenizer,
+    mut reader: impl BufRead,
+    out: &mut impl Write,
+    args: &Args,
+) -> io::Result<()> {
+    // Lines are read as bytes, so that lines with invalid UTF-8 are tokenized as they are
+    let mut line = Vec::new();
+    let mut tokens = Vec::new();
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let text = line.strip_suffix(b"\n").unwrap_or(&line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+
+        tokens.clear();
+        match args.context_length {
+            Some(context_length) => {
+                tokens.push(tokenizer.start_of_text());
+                tokenizer.encode_bytes(text, &mut tokens);
+                tokens.truncate(context_length - 1);
+                tokens.push(tokenizer.end_of_text());
+            }
+            None => tokenizer.encode_bytes(text, &mut tokens),
+        }
+
+        for (i, &token) in tokens.iter().enumerate() {
+            if i > 0 {
+                out.write_all(b" ")?;
+            }
+            match args.tokens {
+                true => out.write_all(readable_token(tokenizer, token).as_bytes())?,
+                false => write!(out, "{}", token.to_u16())?,
+            }
+        }
+        // Padding following the `<end_of_text>` marker token isn't shown for readable tokens
+        if let (Some(context_length), false) = (args.context_length, args.tokens) {
+            for _ in tokens.len()..context_length {
+                out.write_all(b" 0")?;
+            }
+        }
+        out.write_all(b"\n")?;
+        line.clear();
+    }
+    Ok(())
+}
+
+fn readable_token(tokenizer: &Tokenizer, token: Token) -> String {
+    if token == tokenizer.start_of_text() {
+        return "<start_of_text>".to_owned();
+    } else if token == tokenizer.end_of_text() {
+        return "<end_of_text>".to_owned();
+    }
+    let piece = tokenizer.decode([token]);
+    // Tokens ending a word are decoded with a trailing space
+    match piece.strip_suffix(' ') {
+        Some(piece) => format!("{piece}</w>"),
+        None => piece,
+    }
+}
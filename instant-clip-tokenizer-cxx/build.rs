@@ -0,0 +1,6 @@
+fn main() {
+    cxx_build::bridge("src/lib.rs")
+        .flag_if_supported("-std=c++14")
+        .compile("instant-clip-tokenizer-cxx");
+    println!("cargo:rerun-if-changed=src/lib.rs");
+}
@@ -0,0 +1,34 @@
+// Example of using the tokenizer from C++.
+//
+// Build the static library using `cargo build --release -p instant-clip-tokenizer-cxx`, then
+// compile this example with the generated headers, e.g.:
+//
+//   c++ -std=c++14 -I target/cxxbridge instant-clip-tokenizer-cxx/examples/encode.cpp \
+//       target/release/libinstant_clip_tokenizer_cxx.a -lpthread -ldl -o encode
+
+#include <cstdint>
+#include <iostream>
+#include <string>
+#include <vector>
+
+#include "instant-clip-tokenizer-cxx/src/lib.rs.h"
+
+int main() {
+    auto tokenizer = instant_clip_tokenizer::new_tokenizer();
+
+    std::vector<uint16_t> tokens;
+    tokenizer->encode("A person riding a motorcycle", tokens);
+    for (auto token : tokens) {
+        std::cout << token << " ";
+    }
+    std::cout << std::endl;
+
+    std::vector<std::string> texts = {"Hi there", "How are you?"};
+    std::vector<uint16_t> batch;
+    tokenizer->tokenize_batch(texts, 77, batch);
+    std::cout << batch.size() / 77 << " rows" << std::endl;
+
+    rust::Slice<const uint16_t> slice(tokens.data(), tokens.size());
+    std::cout << std::string(tokenizer->decode(slice)) << std::endl;
+    return 0;
+}
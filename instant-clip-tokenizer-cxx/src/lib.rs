@@ -0,0 +1,129 @@
+//! C++ bindings for the `instant-clip-tokenizer` crate, generated using [cxx](https://cxx.rs/).
+//!
+//! Building this crate produces a static library together with the generated header
+//! `instant-clip-tokenizer-cxx/src/lib.rs.h` (in cxx's output directory below `target/`), which
+//! declares the functions below in the `instant_clip_tokenizer` namespace. See
+//! `examples/encode.cpp` for an example.
+
+use std::io;
+use std::pin::Pin;
+
+use cxx::{CxxString, CxxVector};
+
+#[cxx::bridge(namespace = "instant_clip_tokenizer")]
+mod ffi {
+    extern "Rust" {
+        /// A text tokenizer for the CLIP neural network.
+        type Tokenizer;
+
+        /// Create a new `Tokenizer` using the vocabulary data bundled with this library.
+        ///
+        /// Note that creating a new `Tokenizer` is expensive, so it is recommended to create the
+        /// `Tokenizer` once and then reuse it.
+        fn new_tokenizer() -> Box<Tokenizer>;
+
+        /// Create a new `Tokenizer` by reading the vocabulary data from the given path.
+        ///
+        /// All formats supported by `Tokenizer::from_path` in the Rust crate can be used. Throws
+        /// `rust::Error` if the vocabulary data can't be loaded.
+        fn load_tokenizer(path: &str, max_vocabulary_size: u16) -> Result<Box<Tokenizer>>;
+
+        /// Encode a `text` input as a sequence of tokens, appending them to `out`.
+        ///
+        /// The encoded token sequence does not include the special `<start_of_text>` and
+        /// `<end_of_text>` marker tokens.
+        fn encode(self: &Tokenizer, text: &str, out: Pin<&mut CxxVector<u16>>);
+
+        /// Tokenize a batch of multiple input strings, appending them to `out`.
+        ///
+        /// Each row of `context_length` tokens consists of the `<start_of_text>` marker token,
+        /// the encoded text, the `<end_of_text>` marker token and padding zeros, exactly like
+        /// `tokenize_batch` in the Rust crate. The rows are appended to `out` one after another,
+        /// resulting in a row-major matrix of shape `(texts.size(), context_length)`. Invalid
+        /// UTF-8 in `texts` is replaced by the replacement character `U+FFFD`. Throws
+        /// `rust::Error` if `context_length < 3`.
+        fn tokenize_batch(
+            self: &Tokenizer,
+            texts: &CxxVector<CxxString>,
+            context_length: usize,
+            out: Pin<&mut CxxVector<u16>>,
+        ) -> Result<()>;
+
+        /// Convert a sequence of `tokens` back to a textual representation.
+        ///
+        /// Throws `rust::Error` if `tokens` contains an invalid token.
+        fn decode(self: &Tokenizer, tokens: &[u16]) -> Result<String>;
+
+        /// Returns the special `<start_of_text>` marker token.
+        fn start_of_text(self: &Tokenizer) -> u16;
+
+        /// Returns the special `<end_of_text>` marker token.
+        fn end_of_text(self: &Tokenizer) -> u16;
+    }
+}
+
+/// A text tokenizer for the CLIP neural network.
+pub struct Tokenizer {
+    inner: instant_clip_tokenizer::Tokenizer,
+}
+
+fn new_tokenizer() -> Box<Tokenizer> {
+    Box::new(Tokenizer {
+        inner: instant_clip_tokenizer::Tokenizer::new(),
+    })
+}
+
+fn load_tokenizer(path: &str, max_vocabulary_size: u16) -> io::Result<Box<Tokenizer>> {
+    Ok(Box::new(Tokenizer {
+        inner: instant_clip_tokenizer::Tokenizer::from_path(path, max_vocabulary_size)?,
+    }))
+}
+
+impl Tokenizer {
+    fn encode(&self, text: &str, mut out: Pin<&mut CxxVector<u16>>) {
+        let mut tokens = Vec::with_capacity(text.len());
+        self.inner.encode(text, &mut tokens);
+        for token in tokens {
+            out.as_mut().push(token.to_u16());
+        }
+    }
+
+    fn tokenize_batch(
+        &self,
+        texts: &CxxVector<CxxString>,
+        context_length: usize,
+        mut out: Pin<&mut CxxVector<u16>>,
+    ) -> io::Result<()> {
+        let texts = texts
+            .iter()
+            .map(|text| text.to_string_lossy())
+            .collect::<Vec<_>>();
+        let result = self
+            .inner
+            .try_tokenize_batch(texts.iter().map(|text| &**text), context_length)?;
+        for &id in result.iter() {
+            out.as_mut().push(id);
+        }
+        Ok(())
+    }
+
+    fn decode(&self, tokens: &[u16]) -> io::Result<String> {
+        let tokens = tokens
+            .iter()
+            .map(|&t| {
+                instant_clip_tokenizer::Token::from_u16(t, &self.inner).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid token: {t}"))
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        self.inner.try_decode(tokens)
+    }
+
+    fn start_of_text(&self) -> u16 {
+        self.inner.start_of_text().to_u16()
+    }
+
+    fn end_of_text(&self) -> u16 {
+        self.inner.end_of_text().to_u16()
+    }
+}
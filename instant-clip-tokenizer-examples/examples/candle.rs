@@ -0,0 +1,64 @@
+//! Compute CLIP text embeddings with [candle](https://github.com/huggingface/candle).
+//!
+//! This example expects the path to the `model.safetensors` file of the
+//! `openai/clip-vit-base-patch32` model from the HuggingFace Hub as its first argument, followed by
+//! one or more texts to embed:
+//!
+//! ```sh
+//! cargo run --manifest-path instant-clip-tokenizer-examples/Cargo.toml --example candle -- \
+//!     model.safetensors "a photo of a cat"
+//! ```
+
+use std::env;
+
+use candle_core::{DType, Device, IndexOp, Module, Tensor};
+use candle_nn::VarBuilder;
+use candle_transformers::models::clip::text_model::{ClipTextConfig, ClipTextTransformer};
+
+use instant_clip_tokenizer::Tokenizer;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let weights = args
+        .next()
+        .ok_or("need the path to the model weights as first argument")?;
+    let texts = args.collect::<Vec<_>>();
+    if texts.is_empty() {
+        return Err("need at least one text to embed".into());
+    }
+
+    let device = Device::Cpu;
+    let config = ClipTextConfig::vit_base_patch32();
+    let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights], DType::F32, &device)? };
+    let text_model = ClipTextTransformer::new(vb.pp("text_model"), &config)?;
+    let text_projection = candle_nn::linear_no_bias(
+        config.embed_dim,
+        config.projection_dim,
+        vb.pp("text_projection"),
+    )?;
+
+    let tokenizer = Tokenizer::new();
+    let context_length = config.max_position_embeddings;
+    let ids = tokenizer.tokenize_batch(texts.iter().map(|s| s.as_str()), context_length);
+
+    // candle has no `u16` dtype, but `u32` works for embedding lookups
+    let input_ids = Tensor::from_iter(ids.iter().map(|&id| u32::from(id)), &device)?
+        .reshape((texts.len(), context_length))?;
+    let hidden_states = text_model.forward_with_mask(&input_ids, usize::MAX)?;
+
+    // CLIP pools the hidden state at the position of the `<end_of_text>` token
+    let positions = tokenizer.eot_positions_matrix(ids.view())?;
+    let mut pooled = Vec::with_capacity(texts.len());
+    for (i, position) in positions.into_iter().enumerate() {
+        pooled.push(hidden_states.i((i, position))?);
+    }
+    let embeddings = text_projection.forward(&Tensor::stack(&pooled, 0)?)?;
+    let norm = embeddings.sqr()?.sum_keepdim(1)?.sqrt()?;
+    let embeddings = embeddings.broadcast_div(&norm)?;
+
+    for (text, embedding) in texts.iter().zip(embeddings.to_vec2::<f32>()?) {
+        println!("\"{text}\": {:?}...", &embedding[..8]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! Run a CLIP text model exported to ONNX with [tract](https://github.com/sonos/tract).
+//!
+//! This example expects the path to an ONNX export of a CLIP text model taking `input_ids` and
+//! `attention_mask` inputs of type `i64` and shape `(1, 77)` (as produced by e.g. HuggingFace
+//! Optimum) as its first argument, followed by the text to embed:
+//!
+//! ```sh
+//! cargo run --manifest-path instant-clip-tokenizer-examples/Cargo.toml --example tract -- \
+//!     text_model.onnx "a photo of a cat"
+//! ```
+
+use std::env;
+
+use tract_onnx::prelude::*;
+
+use instant_clip_tokenizer::Tokenizer;
+
+const CONTEXT_LENGTH: usize = 77;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let mut args = env::args().skip(1);
+    let model = args
+        .next()
+        .ok_or("need the path to the ONNX model as first argument")?;
+    let text = args
+        .next()
+        .ok_or("need the text to embed as second argument")?;
+
+    let model = tract_onnx::onnx()
+        .model_for_path(model)?
+        .with_input_fact(0, i64::fact([1, CONTEXT_LENGTH]).into())?
+        .with_input_fact(1, i64::fact([1, CONTEXT_LENGTH]).into())?
+        .into_optimized()?
+        .into_runnable()?;
+
+    let tokenizer = Tokenizer::new();
+    let (input_ids, attention_mask) =
+        tokenizer.tokenize_batch_with_attention_mask::<i64, _>([text.as_str()], CONTEXT_LENGTH);
+    let input_ids = Tensor::from_shape(&[1, CONTEXT_LENGTH], input_ids.as_slice().unwrap())?;
+    let attention_mask =
+        Tensor::from_shape(&[1, CONTEXT_LENGTH], attention_mask.as_slice().unwrap())?;
+
+    let outputs = model.run(tvec!(input_ids.into(), attention_mask.into()))?;
+    for (i, output) in outputs.iter().enumerate() {
+        println!("Output {i}: shape {:?}", output.shape());
+    }
+
+    Ok(())
+}
@@ -0,0 +1 @@
+//! Examples running CLIP text models using `instant-clip-tokenizer`, see the `examples` directory.
@@ -0,0 +1,3 @@
+module github.com/instant-labs/instant-clip-tokenizer/instant-clip-tokenizer-go
+
+go 1.21
@@ -0,0 +1,192 @@
+// Package instantclip provides Go bindings for Instant CLIP Tokenizer, a fast text tokenizer for
+// the CLIP neural network.
+//
+// The package uses cgo to call the C API of the Rust implementation, so it produces exactly the
+// same tokens as the Rust crate and the Python package. Build the static library before building
+// this package:
+//
+//	cargo build --release -p instant-clip-tokenizer-c
+package instantclip
+
+/*
+#cgo CFLAGS: -I${SRCDIR}/../instant-clip-tokenizer-c/include
+#cgo LDFLAGS: ${SRCDIR}/../target/release/libinstant_clip_tokenizer_c.a -lm
+#cgo linux LDFLAGS: -lpthread -ldl
+#include <stdlib.h>
+#include "instant_clip_tokenizer.h"
+*/
+import "C"
+
+import (
+	"errors"
+	"runtime"
+	"unsafe"
+)
+
+// Tokenizer is a text tokenizer for the CLIP neural network. It is safe for concurrent use.
+type Tokenizer struct {
+	ptr *C.InstantClipTokenizer
+}
+
+// New creates a Tokenizer using the vocabulary data bundled with the library.
+//
+// Creating a Tokenizer is expensive, so it is recommended to create it once and then reuse it.
+func New() *Tokenizer {
+	return wrap(C.instant_clip_tokenizer_new())
+}
+
+// Load creates a Tokenizer by reading the vocabulary data from the given file or directory.
+//
+// All formats supported by Tokenizer::from_path in the Rust crate can be used.
+func Load(path string, maxVocabularySize uint16) (*Tokenizer, error) {
+	cPath := C.CString(path)
+	defer C.free(unsafe.Pointer(cPath))
+	var cErr *C.char
+	ptr := C.instant_clip_tokenizer_load(cPath, C.uint16_t(maxVocabularySize), &cErr)
+	if ptr == nil {
+		return nil, takeError(cErr)
+	}
+	return wrap(ptr), nil
+}
+
+func wrap(ptr *C.InstantClipTokenizer) *Tokenizer {
+	t := &Tokenizer{ptr: ptr}
+	runtime.SetFinalizer(t, (*Tokenizer).Close)
+	return t
+}
+
+// Close releases the memory held by the Tokenizer. It is called automatically when the
+// Tokenizer is garbage collected, and the Tokenizer must not be used afterwards.
+func (t *Tokenizer) Close() {
+	if t.ptr != nil {
+		C.instant_clip_tokenizer_free(t.ptr)
+		t.ptr = nil
+	}
+	runtime.SetFinalizer(t, nil)
+}
+
+// Encode encodes text as a sequence of token ids.
+//
+// The encoded token sequence does not include the special <start_of_text> and <end_of_text>
+// marker tokens. Invalid UTF-8 in text is replaced by the replacement character U+FFFD.
+func (t *Tokenizer) Encode(text string) []uint16 {
+	var tokens *C.uint16_t
+	n := C.instant_clip_tokenizer_encode(t.ptr, cString(text), C.size_t(len(text)), &tokens)
+	defer C.instant_clip_tokenizer_tokens_free(tokens, n)
+	runtime.KeepAlive(t)
+	if n == 0 {
+		return []uint16{}
+	}
+	return append([]uint16(nil), unsafe.Slice((*uint16)(unsafe.Pointer(tokens)), int(n))...)
+}
+
+// TokenizeBatch tokenizes a batch of texts, returning one row of contextLength token ids per
+// text.
+//
+// Each row consists of the <start_of_text> marker token, the encoded text, the <end_of_text>
+// marker token and padding zeros, exactly like tokenize_batch in the Rust crate. The rows share
+// a single backing array. An error is returned if contextLength < 3.
+func (t *Tokenizer) TokenizeBatch(texts []string, contextLength int) ([][]uint16, error) {
+	if contextLength < 3 {
+		return nil, errors.New("context length must be at least 3")
+	}
+
+	// cgo doesn't allow passing Go memory containing Go pointers, so copy the texts to C memory
+	total := 0
+	for _, text := range texts {
+		total += len(text)
+	}
+	data := C.malloc(C.size_t(total + 1))
+	defer C.free(data)
+	buf := unsafe.Slice((*byte)(data), total+1)
+	pointers := make([]*C.char, len(texts)+1)
+	lens := make([]C.size_t, len(texts)+1)
+	offset := 0
+	for i, text := range texts {
+		copy(buf[offset:], text)
+		pointers[i] = (*C.char)(unsafe.Pointer(&buf[offset]))
+		lens[i] = C.size_t(len(text))
+		offset += len(text)
+	}
+
+	out := make([]uint16, len(texts)*contextLength+1)
+	var cErr *C.char
+	ok := C.instant_clip_tokenizer_tokenize_batch(
+		t.ptr,
+		&pointers[0],
+		&lens[0],
+		C.size_t(len(texts)),
+		C.size_t(contextLength),
+		(*C.uint16_t)(unsafe.Pointer(&out[0])),
+		&cErr,
+	)
+	runtime.KeepAlive(t)
+	if !ok {
+		return nil, takeError(cErr)
+	}
+
+	rows := make([][]uint16, len(texts))
+	for i := range rows {
+		rows[i] = out[i*contextLength : (i+1)*contextLength : (i+1)*contextLength]
+	}
+	return rows, nil
+}
+
+// Decode converts a sequence of token ids back to a textual representation.
+//
+// An error is returned if tokens contains an invalid token.
+func (t *Tokenizer) Decode(tokens []uint16) (string, error) {
+	var tokensPtr *C.uint16_t
+	if len(tokens) > 0 {
+		tokensPtr = (*C.uint16_t)(unsafe.Pointer(&tokens[0]))
+	}
+	var text *C.char
+	var textLen C.size_t
+	var cErr *C.char
+	ok := C.instant_clip_tokenizer_decode(
+		t.ptr,
+		tokensPtr,
+		C.size_t(len(tokens)),
+		&text,
+		&textLen,
+		&cErr,
+	)
+	runtime.KeepAlive(t)
+	if !ok {
+		return "", takeError(cErr)
+	}
+	defer C.instant_clip_tokenizer_text_free(text, textLen)
+	return C.GoStringN(text, C.int(textLen)), nil
+}
+
+// StartOfText returns the special <start_of_text> marker token.
+func (t *Tokenizer) StartOfText() uint16 {
+	defer runtime.KeepAlive(t)
+	return uint16(C.instant_clip_tokenizer_start_of_text(t.ptr))
+}
+
+// EndOfText returns the special <end_of_text> marker token.
+func (t *Tokenizer) EndOfText() uint16 {
+	defer runtime.KeepAlive(t)
+	return uint16(C.instant_clip_tokenizer_end_of_text(t.ptr))
+}
+
+// ContextLength returns the default context length of the tokenizer's model, e.g. 77 for CLIP.
+func (t *Tokenizer) ContextLength() int {
+	defer runtime.KeepAlive(t)
+	return int(C.instant_clip_tokenizer_context_length(t.ptr))
+}
+
+// cString returns a pointer to the bytes of s without copying them, which is fine as long as
+// the C side doesn't retain or modify the pointer.
+func cString(s string) *C.char {
+	if len(s) == 0 {
+		return nil
+	}
+	return (*C.char)(unsafe.Pointer(unsafe.StringData(s)))
+}
+
+func takeError(cErr *C.char) error {
+	defer C.instant_clip_tokenizer_error_free(cErr)
+	return errors.New(C.GoString(cErr))
+}
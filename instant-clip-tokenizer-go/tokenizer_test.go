@@ -0,0 +1,86 @@
+package instantclip
+
+import (
+	"reflect"
+	"testing"
+)
+
+func TestEncode(t *testing.T) {
+	tokenizer := New()
+	defer tokenizer.Close()
+
+	tokens := tokenizer.Encode("Hello world!!!")
+	if want := []uint16{3306, 1002, 995}; !reflect.DeepEqual(tokens, want) {
+		t.Errorf("Encode() = %v, want %v", tokens, want)
+	}
+	if tokens := tokenizer.Encode(""); len(tokens) != 0 {
+		t.Errorf("Encode(\"\") = %v, want no tokens", tokens)
+	}
+}
+
+func TestTokenizeBatch(t *testing.T) {
+	tokenizer := New()
+	defer tokenizer.Close()
+
+	rows, err := tokenizer.TokenizeBatch([]string{"Hi", "How are you?", "I'm fine, thanks!"}, 6)
+	if err != nil {
+		t.Fatal(err)
+	}
+	want := [][]uint16{
+		{49406, 1883, 49407, 0, 0, 0},
+		{49406, 829, 631, 592, 286, 49407},
+		{49406, 328, 880, 3797, 267, 49407},
+	}
+	if !reflect.DeepEqual(rows, want) {
+		t.Errorf("TokenizeBatch() = %v, want %v", rows, want)
+	}
+
+	rows, err = tokenizer.TokenizeBatch([]string{"", "Hi"}, tokenizer.ContextLength())
+	if err != nil {
+		t.Fatal(err)
+	}
+	if len(rows) != 2 || len(rows[1]) != 77 || rows[0][1] != tokenizer.EndOfText() {
+		t.Errorf("TokenizeBatch() = %v", rows)
+	}
+
+	if rows, err := tokenizer.TokenizeBatch(nil, 77); err != nil || len(rows) != 0 {
+		t.Errorf("TokenizeBatch(nil) = %v, %v", rows, err)
+	}
+	if _, err := tokenizer.TokenizeBatch([]string{"Hi"}, 2); err == nil {
+		t.Error("TokenizeBatch() must reject context lengths below 3")
+	}
+}
+
+func TestDecode(t *testing.T) {
+	tokenizer := New()
+	defer tokenizer.Close()
+
+	text, err := tokenizer.Decode([]uint16{320, 2533, 6765, 320, 10297})
+	if err != nil {
+		t.Fatal(err)
+	}
+	if want := "a person riding a motorcycle "; text != want {
+		t.Errorf("Decode() = %q, want %q", text, want)
+	}
+	if _, err := tokenizer.Decode([]uint16{65535}); err == nil {
+		t.Error("Decode() must reject invalid tokens")
+	}
+}
+
+func TestLoad(t *testing.T) {
+	tokenizer, err := Load("../instant-clip-tokenizer/bpe_simple_vocab_16e6.txt", 49408)
+	if err != nil {
+		t.Fatal(err)
+	}
+	defer tokenizer.Close()
+	if tokens := tokenizer.Encode("Hi there"); !reflect.DeepEqual(tokens, []uint16{1883, 997}) {
+		t.Errorf("Encode() = %v", tokens)
+	}
+	if tokenizer.StartOfText() != 49406 {
+		t.Errorf("StartOfText() = %v", tokenizer.StartOfText())
+	}
+
+	if _, err := Load("does-not-exist.txt", 49408); err == nil {
+		t.Error("Load() must fail for missing files")
+	}
+}
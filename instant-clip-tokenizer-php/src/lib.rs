@@ -0,0 +1,111 @@
+//! PHP bindings for the `instant-clip-tokenizer` crate, built using
+//! [ext-php-rs](https://github.com/davidcole1340/ext-php-rs).
+//!
+//! Building this crate produces a PHP extension providing the `InstantClipTokenizer\Tokenizer`
+//! class. Load it using `extension=/path/to/libinstant_clip_tokenizer_php.so` in `php.ini`, or
+//! `php -d extension=...`. See `test/test.php` for an example.
+
+#![cfg_attr(windows, feature(abi_vectorcall))]
+
+use std::io;
+
+use ext_php_rs::prelude::*;
+use instant_clip_tokenizer::Token;
+
+/// A text tokenizer for the CLIP neural network.
+#[php_class(name = "InstantClipTokenizer\\Tokenizer")]
+pub struct Tokenizer {
+    inner: instant_clip_tokenizer::Tokenizer,
+}
+
+#[php_impl]
+impl Tokenizer {
+    /// Create a new `Tokenizer` using the vocabulary data bundled with this extension.
+    ///
+    /// Note that creating a new `Tokenizer` is expensive, so it is recommended to create the
+    /// `Tokenizer` once and then reuse it.
+    pub fn __construct() -> Self {
+        Self {
+            inner: instant_clip_tokenizer::Tokenizer::new(),
+        }
+    }
+
+    /// Create a new `Tokenizer` by reading the vocabulary data from the given file or directory.
+    ///
+    /// All formats supported by `Tokenizer::from_path` in the Rust crate can be used. Throws an
+    /// exception if the vocabulary data can't be loaded.
+    pub fn load(path: &str, max_vocabulary_size: u16) -> PhpResult<Self> {
+        Ok(Self {
+            inner: instant_clip_tokenizer::Tokenizer::from_path(path, max_vocabulary_size)
+                .map_err(exception)?,
+        })
+    }
+
+    /// Encode a `text` input as an array of token ids.
+    ///
+    /// The encoded token sequence does not include the special `<start_of_text>` and
+    /// `<end_of_text>` marker tokens.
+    pub fn encode(&self, text: &str) -> Vec<u16> {
+        let mut tokens = Vec::with_capacity(text.len());
+        self.inner.encode(text, &mut tokens);
+        tokens.into_iter().map(Token::to_u16).collect()
+    }
+
+    /// Tokenize an array of input strings, returning an array of rows of token ids.
+    ///
+    /// Each row of `context_length` tokens (defaulting to 77) consists of the `<start_of_text>`
+    /// marker token, the encoded text, the `<end_of_text>` marker token and padding zeros, exactly
+    /// like `tokenize_batch` in the Rust and Python packages. Throws an exception if `context_length < 3`.
+    pub fn tokenize_batch(
+        &self,
+        texts: Vec<String>,
+        context_length: Option<usize>,
+    ) -> PhpResult<Vec<Vec<u16>>> {
+        let context_length = context_length.unwrap_or_else(|| self.inner.context_length());
+        let result = self
+            .inner
+            .try_tokenize_batch(texts.iter().map(String::as_str), context_length)
+            .map_err(exception)?;
+        Ok(result.rows().into_iter().map(|row| row.to_vec()).collect())
+    }
+
+    /// Convert an array of token ids back to a textual representation.
+    ///
+    /// Throws an exception if `tokens` contains an invalid token.
+    pub fn decode(&self, tokens: Vec<u16>) -> PhpResult<String> {
+        let tokens = tokens
+            .into_iter()
+            .map(|t| {
+                Token::from_u16(t, &self.inner).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, format!("invalid token: {t}"))
+                })
+            })
+            .collect::<io::Result<Vec<_>>>()
+            .map_err(exception)?;
+        self.inner.try_decode(tokens).map_err(exception)
+    }
+
+    /// Returns the default context length used by `tokenizeBatch`.
+    pub fn context_length(&self) -> usize {
+        self.inner.context_length()
+    }
+
+    /// Returns the special `<start_of_text>` marker token.
+    pub fn start_of_text(&self) -> u16 {
+        self.inner.start_of_text().to_u16()
+    }
+
+    /// Returns the special `<end_of_text>` marker token.
+    pub fn end_of_text(&self) -> u16 {
+        self.inner.end_of_text().to_u16()
+    }
+}
+
+fn exception(err: io::Error) -> PhpException {
+    PhpException::default(err.to_string())
+}
+
+#[php_module]
+pub fn get_module(module: ModuleBuilder) -> ModuleBuilder {
+    module
+}
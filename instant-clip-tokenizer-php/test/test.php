@@ -0,0 +1,36 @@
+<?php
+
+use InstantClipTokenizer\Tokenizer;
+
+function check($actual, $expected) {
+    if ($actual !== $expected) {
+        throw new Exception("expected " . json_encode($expected) . ", got " . json_encode($actual));
+    }
+    echo json_encode($actual), "\n";
+}
+
+$tokenizer = new Tokenizer();
+
+check($tokenizer->encode("Hello world!!!"), [3306, 1002, 995]);
+
+check($tokenizer->tokenizeBatch(["Hi", "How are you?", "I'm fine, thanks!"], 6), [
+    [49406, 1883, 49407, 0, 0, 0],
+    [49406, 829, 631, 592, 286, 49407],
+    [49406, 328, 880, 3797, 267, 49407],
+]);
+check(count($tokenizer->tokenizeBatch(["Hi"])[0]), 77);
+
+check($tokenizer->decode([320, 2533, 6765, 320, 10297]), "a person riding a motorcycle ");
+check($tokenizer->contextLength(), 77);
+check($tokenizer->startOfText(), 49406);
+check($tokenizer->endOfText(), 49407);
+
+try {
+    $tokenizer->tokenizeBatch(["Hi"], 2);
+    throw new Exception("context length must be validated");
+} catch (Exception $e) {
+    check($e->getMessage(), "context length must be at least 3");
+}
+
+$loaded = Tokenizer::load(__DIR__ . "/../../instant-clip-tokenizer/bpe_simple_vocab_16e6.txt", 49408);
+check($loaded->encode("Hi there"), [1883, 997]);
@@ -0,0 +1,3 @@
+from ._instant_clip_tokenizer import *
+from ._instant_clip_tokenizer import __version__
+from .vectorizer import ClipTokenVectorizer
@@ -0,0 +1,89 @@
+"""Tokenize the lines of text files or of stdin, writing one line of output per input line.
+
+Usage: python -m instant_clip_tokenizer [--tokens] [--context-length N] [--long-clip] [FILE ...]
+"""
+
+import argparse
+import sys
+
+from . import Tokenizer
+
+BATCH_SIZE = 1024
+
+
+def main(argv=None):
+    parser = argparse.ArgumentParser(
+        prog="python -m instant_clip_tokenizer",
+        description="Tokenize each line of the given files (or stdin) for the CLIP neural network.",
+    )
+    parser.add_argument("files", nargs="*", metavar="FILE", help="files to read instead of stdin")
+    parser.add_argument(
+        "--tokens",
+        action="store_true",
+        help="write readable tokens (e.g. hi</w>) instead of token ids",
+    )
+    parser.add_argument(
+        "--context-length",
+        type=int,
+        metavar="N",
+        help="add the marker tokens and pad or truncate to N tokens, like tokenize_batch",
+    )
+    parser.add_argument(
+        "--long-clip",
+        action="store_true",
+        help="use the vocabulary and context length of Long-CLIP models",
+    )
+    args = parser.parse_args(argv)
+    if args.context_length is not None and args.context_length < 3:
+        parser.error("--context-length must be at least 3")
+
+    tokenizer = Tokenizer.long_clip() if args.long_clip else Tokenizer()
+    out = sys.stdout
+    if args.files:
+        for filename in args.files:
+            with open(filename, "rb") as file:
+                tokenize(tokenizer, file, out, args)
+    else:
+        tokenize(tokenizer, sys.stdin.buffer, out, args)
+
+
+def tokenize(tokenizer, file, out, args):
+    # Lines are passed as bytes, so that lines with invalid UTF-8 are tokenized as they are
+    batch = []
+    for line in file:
+        batch.append(line.rstrip(b"\r\n"))
+        if len(batch) == BATCH_SIZE:
+            write_batch(tokenizer, batch, out, args)
+            batch.clear()
+    if batch:
+        write_batch(tokenizer, batch, out, args)
+
+
+def write_batch(tokenizer, batch, out, args):
+    if args.context_length is None:
+        rows = [tokenizer.encode(line) for line in batch]
+    else:
+        rows = tokenizer.tokenize_batch(batch, context_length=args.context_length).tolist()
+    for row in rows:
+        if args.tokens:
+            # Padding following the `<end_of_text>` marker token isn't shown
+            if args.context_length is not None:
+                row = row[: row.index(tokenizer.end_of_text()) + 1]
+            out.write(" ".join(readable_token(tokenizer, token) for token in row))
+        else:
+            out.write(" ".join(map(str, row)))
+        out.write("\n")
+
+
+def readable_token(tokenizer, token):
+    if token == tokenizer.start_of_text():
+        return "<start_of_text>"
+    if token == tokenizer.end_of_text():
+        return "<end_of_text>"
+    piece = tokenizer.decode([token])
+    # Tokens ending a word are decoded with a trailing space
+    return piece[:-1] + "</w>" if piece.endswith(" ") else piece
+
+
+if __name__ == "__main__":
+    main()
@@ -0,0 +1,91 @@
+"""A scikit-learn compatible transformer turning texts into bag-of-tokens count matrices."""
+
+import numpy as np
+
+from ._instant_clip_tokenizer import Tokenizer
+
+
+class ClipTokenVectorizer:
+    """Convert texts (`str` or `bytes`) to a sparse matrix of CLIP token counts.
+
+    This works like scikit-learn's `CountVectorizer`, but uses the tokens of a CLIP `Tokenizer`
+    as features, so that classical models can be trained on exactly the token space seen by
+    CLIP. The vocabulary is fixed, so `fit` doesn't learn anything and the resulting matrices
+    always have a column for every token of the vocabulary:
+
+    ```python
+    pipeline = make_pipeline(ClipTokenVectorizer(), LogisticRegression())
+    pipeline.fit(captions, labels)
+    ```
+
+    `tokenizer` defaults to `Tokenizer()`. If `binary` is true, all non-zero counts are set to 1.
+    The resulting `scipy.sparse.csr_matrix` has the given `dtype`. Requires `scipy`.
+    """
+
+    def __init__(self, tokenizer=None, binary=False, dtype=np.int64):
+        self.tokenizer = tokenizer
+        self.binary = binary
+        self.dtype = dtype
+
+    def fit(self, raw_documents, y=None):
+        """Does nothing, as the vocabulary is fixed. Returns the vectorizer itself."""
+        return self
+
+    def transform(self, raw_documents):
+        """Count the tokens of each text in `raw_documents`, returning a sparse matrix of shape
+        `(len(raw_documents), vocab_size)`."""
+        import scipy.sparse
+
+        if isinstance(raw_documents, (str, bytes)):
+            raise ValueError("expected an iterable of texts, got a single text")
+        tokenizer = self._tokenizer()
+        data, indices, indptr = tokenizer.token_counts(list(raw_documents))
+        if self.binary:
+            data = np.ones_like(data)
+        return scipy.sparse.csr_matrix(
+            (data.astype(self.dtype, copy=False), indices, indptr),
+            shape=(len(indptr) - 1, tokenizer.vocab_size()),
+        )
+
+    def fit_transform(self, raw_documents, y=None):
+        """Equivalent to `fit(raw_documents).transform(raw_documents)`."""
+        return self.fit(raw_documents, y).transform(raw_documents)
+
+    def get_params(self, deep=True):
+        """Returns the parameters of this vectorizer, for use with scikit-learn's `clone`."""
+        return {"tokenizer": self.tokenizer, "binary": self.binary, "dtype": self.dtype}
+
+    def set_params(self, **params):
+        """Set the parameters of this vectorizer. Returns the vectorizer itself."""
+        for key, value in params.items():
+            if key not in self.get_params():
+                raise ValueError(f"invalid parameter {key!r} for ClipTokenVectorizer")
+            setattr(self, key, value)
+        return self
+
+    def __sklearn_is_fitted__(self):
+        return True
+
+    def __getstate__(self):
+        # Tokenizers can't be pickled directly, so store their binary representation instead
+        state = self.__dict__.copy()
+        if self.tokenizer is not None:
+            state["tokenizer"] = self.tokenizer.to_bytes()
+        state.pop("_default_tokenizer", None)
+        return state
+
+    def __setstate__(self, state):
+        if state["tokenizer"] is not None:
+            state["tokenizer"] = Tokenizer.from_buffer(state["tokenizer"])
+        self.__dict__.update(state)
+
+    def __repr__(self):
+        return f"ClipTokenVectorizer(binary={self.binary!r}, dtype={self.dtype!r})"
+
+    def _tokenizer(self):
+        if self.tokenizer is not None:
+            return self.tokenizer
+        # Creating a tokenizer is expensive, so the default one is only created once
+        if getattr(self, "_default_tokenizer", None) is None:
+            self._default_tokenizer = Tokenizer()
+        return self._default_tokenizer
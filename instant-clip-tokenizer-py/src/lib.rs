@@ -1,15 +1,24 @@
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::{slice, thread};
 
-use numpy::{IntoPyArray, PyArray2};
-use pyo3::exceptions::PyValueError;
+use numpy::ndarray::{s, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2, PyReadonlyArray2};
+use pyo3::buffer::PyBuffer;
+use pyo3::exceptions::{PyKeyError, PyValueError};
 use pyo3::prelude::*;
-use pyo3::pybacked::PyBackedStr;
+use pyo3::pybacked::{PyBackedBytes, PyBackedStr};
+use pyo3::types::{PyBytes, PyDict};
 
 #[pymodule]
-#[pyo3(name = "instant_clip_tokenizer")]
+#[pyo3(name = "_instant_clip_tokenizer")]
 fn instant_clip_tokenizer_py(_py: Python, m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add("__version__", env!("CARGO_PKG_VERSION"))?;
     m.add_class::<Tokenizer>()?;
+    m.add_class::<Explanation>()?;
+    m.add_class::<MergeStep>()?;
+    m.add_class::<DatasetsMapper>()?;
+    m.add_class::<Tokenization>()?;
     Ok(())
 }
 
@@ -34,6 +43,19 @@ impl Tokenizer {
         }
     }
 
+    /// Create a new `Tokenizer` for Long-CLIP models using the vocabulary data bundled with this
+    /// library.
+    ///
+    /// Long-CLIP models use the same vocabulary as the original CLIP models, but a context length
+    /// of 248 tokens, which this `Tokenizer` uses as the default `context_length` of
+    /// `tokenize_batch`.
+    #[staticmethod]
+    fn long_clip() -> Self {
+        Tokenizer {
+            inner: instant_clip_tokenizer::Tokenizer::long_clip(),
+        }
+    }
+
     /// Create a new `Tokenizer` by reading the vocabulary data from the given filename.
     ///
     /// The data must be in the format used by the original CLIP tokenizer implementation from
@@ -51,6 +73,74 @@ impl Tokenizer {
         })
     }
 
+    /// Create a new `Tokenizer` by reading data written by `save` from the given filename.
+    ///
+    /// Loading a `Tokenizer` this way is much faster than parsing the original vocabulary data, so
+    /// it is recommended when many worker processes each need their own `Tokenizer`.
+    #[staticmethod]
+    fn load_binary(filename: &str) -> PyResult<Self> {
+        Ok(Self {
+            inner: instant_clip_tokenizer::Tokenizer::load_binary(BufReader::new(File::open(
+                filename,
+            )?))?,
+        })
+    }
+
+    /// Create a new `Tokenizer` from data written by `save` or `to_bytes`, read directly from
+    /// `data`.
+    ///
+    /// `data` can be any object supporting the buffer protocol, e.g. `bytes` or the `buf` of a
+    /// `multiprocessing.shared_memory.SharedMemory` block. The data is read in place without being
+    /// copied, so the serialized tokenizer can be written to shared memory once and then be used to
+    /// create a `Tokenizer` in each worker process:
+    ///
+    /// ```python
+    /// # In the main process
+    /// data = tokenizer.to_bytes()
+    /// shm = shared_memory.SharedMemory(create=True, size=len(data))
+    /// shm.buf[:len(data)] = data
+    ///
+    /// # In each worker process, given the name of the shared memory block
+    /// shm = shared_memory.SharedMemory(name=name)
+    /// tokenizer = Tokenizer.from_buffer(shm.buf)
+    /// ```
+    ///
+    /// Note that each `Tokenizer` still builds its own lookup tables from the data. When worker
+    /// processes are started using the `fork` start method, a `Tokenizer` created in the main
+    /// process before starting the workers is shared between them without any copying.
+    #[staticmethod]
+    fn from_buffer(py: Python<'_>, data: PyBuffer<u8>) -> PyResult<Self> {
+        if !data.is_c_contiguous() {
+            return Err(PyValueError::new_err("buffer is not contiguous"));
+        }
+        // SAFETY: the buffer is contiguous and kept alive by `data`, and it is only read while the
+        // GIL is held
+        let bytes =
+            unsafe { std::slice::from_raw_parts(data.buf_ptr() as *const u8, data.len_bytes()) };
+        let inner = instant_clip_tokenizer::Tokenizer::load_binary(bytes)?;
+        data.release(py);
+        Ok(Self { inner })
+    }
+
+    /// Save this `Tokenizer` to the given filename in a compact binary format.
+    ///
+    /// The resulting file can be loaded using `Tokenizer.load_binary`.
+    fn save(&self, filename: &str) -> PyResult<()> {
+        let mut writer = BufWriter::new(File::create(filename)?);
+        self.inner.save_binary(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Returns this `Tokenizer` in the compact binary format written by `save`, as `bytes`.
+    ///
+    /// The result can be loaded using `Tokenizer.from_buffer`.
+    fn to_bytes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut data = Vec::new();
+        self.inner.save_binary(&mut data)?;
+        Ok(PyBytes::new_bound(py, &data))
+    }
+
     /// Tokenize one or multiple input strings.
     ///
     /// Each given input string is encoded using the `encode` method and the numeric representation
@@ -59,10 +149,11 @@ impl Tokenizer {
     /// appended to each text.
     ///
     /// The individual input strings are lowercased before being tokenized, but otherwise no
-    /// pre-processing is performed.
+    /// pre-processing is performed. Inputs can also be given as `bytes`, see `encode`.
     ///
-    /// `context_length` is the maximum number of tokens per each text and defaults to `77` which is
-    /// the correct value for all current CLIP models. If tokenization results in less than
+    /// `context_length` is the maximum number of tokens per each text and defaults to the value
+    /// returned by `context_length()`, which is `77` for the original CLIP models and `248` for
+    /// tokenizers created using `Tokenizer.long_clip`. If tokenization results in less than
     /// `context_length` tokens the resulting row will be padded with trailing zeros. If tokenizing
     /// an input text results in too many tokens, the token sequence will be truncated to fit within
     /// the resulting row of length `context_length`, always including the `<start_of_text>` and
@@ -75,33 +166,127 @@ impl Tokenizer {
         input: TokenizeBatchInput,
         context_length: Option<usize>,
     ) -> PyResult<Bound<'py, PyArray2<u16>>> {
-        let context_length = context_length.unwrap_or(77);
+        let context_length = context_length.unwrap_or(self.inner.context_length());
         if context_length < 3 {
             return Err(PyValueError::new_err("context_length is less than 3"));
         }
-        let result = match input {
-            TokenizeBatchInput::Single(text) => self.inner.tokenize_batch([&*text], context_length),
-            TokenizeBatchInput::Multiple(texts) => self
-                .inner
-                .tokenize_batch(texts.iter().map(|s| &**s), context_length),
-        };
+        let result = input.tokenize(&self.inner, context_length);
         Ok(result.into_pyarray_bound(py))
     }
 
+    /// Tokenize one or multiple input strings without blocking the running `asyncio` event loop.
+    ///
+    /// This works like `tokenize_batch`, but the tokenization runs on a separate thread which does
+    /// not hold the GIL, and the resulting numpy array is returned through an awaitable `Future`
+    /// bound to the running event loop:
+    ///
+    /// ```python
+    /// tokens = await tokenizer.tokenize_batch_async(texts)
+    /// ```
+    ///
+    /// Must be called from a coroutine running in an event loop.
+    fn tokenize_batch_async<'py>(
+        &self,
+        py: Python<'py>,
+        input: TokenizeBatchInput,
+        context_length: Option<usize>,
+    ) -> PyResult<Bound<'py, PyAny>> {
+        let context_length = context_length.unwrap_or(self.inner.context_length());
+        if context_length < 3 {
+            return Err(PyValueError::new_err("context_length is less than 3"));
+        }
+
+        let event_loop = py
+            .import_bound("asyncio")?
+            .call_method0("get_running_loop")?;
+        let future = event_loop.call_method0("create_future")?;
+        let (event_loop_ref, future_ref) = (event_loop.unbind(), future.clone().unbind());
+        let tokenizer = self.inner.clone();
+        thread::spawn(move || {
+            let result = input.tokenize(&tokenizer, context_length);
+            Python::with_gil(|py| {
+                // The future can only be completed from the event loop's thread. If the event loop
+                // has been closed in the meantime, nobody is waiting for the result anymore.
+                let _ = wrap_pyfunction_bound!(set_future_result, py).and_then(|callback| {
+                    event_loop_ref.call_method1(
+                        py,
+                        "call_soon_threadsafe",
+                        (callback, future_ref, result.into_pyarray_bound(py)),
+                    )
+                });
+                drop(input);
+            });
+        });
+        Ok(future)
+    }
+
     /// Encode a `text` input as a sequence of tokens.
     ///
     /// The encoded token sequence does not include the special `<start_of_text>` and
     /// `<end_of_text>` marker tokens. When these are needed you can either use the `tokenize_batch`
     /// method instead, or add them manually by using the `start_of_text` and `end_of_text` methods.
-    fn encode(&self, text: &str) -> Vec<u16> {
+    ///
+    /// `text` can also be given as `bytes`, which don't need to be valid UTF-8. Invalid byte
+    /// sequences are then encoded as their raw bytes, instead of being changed by decoding the
+    /// `bytes` to `str` beforehand.
+    fn encode(&self, text: Text) -> Vec<u16> {
         let mut tokens = Vec::with_capacity(text.len());
-        self.inner.encode(text, &mut tokens);
+        text.encode(&self.inner, &mut tokens);
         tokens
             .into_iter()
             .map(instant_clip_tokenizer::Token::to_u16)
             .collect()
     }
 
+    /// Encode a `text` input as a one-dimensional numpy array of tokens.
+    ///
+    /// This works like `encode`, but avoids creating a Python `int` object for every token, which
+    /// is considerably faster when encoding large numbers of texts.
+    fn encode_array<'py>(&self, py: Python<'py>, text: Text) -> Bound<'py, PyArray1<u16>> {
+        self.encode(text).into_pyarray_bound(py)
+    }
+
+    /// Count how often every token occurs in each of the given `texts` (`str` or `bytes`).
+    ///
+    /// The counts are returned as the `(data, indices, indptr)` arrays of a sparse matrix in
+    /// compressed sparse row format, with a row for every text and a column for every token of
+    /// the vocabulary (see `vocab_size`), which can be passed to `scipy.sparse.csr_matrix`. Like
+    /// `encode`, this doesn't add the marker tokens. Use `ClipTokenVectorizer` for a scikit-learn
+    /// compatible transformer producing these matrices.
+    fn token_counts<'py>(
+        &self,
+        py: Python<'py>,
+        texts: Vec<Text>,
+    ) -> (
+        Bound<'py, PyArray1<i64>>,
+        Bound<'py, PyArray1<i64>>,
+        Bound<'py, PyArray1<i64>>,
+    ) {
+        let (data, indices, indptr) = py.allow_threads(|| {
+            let (mut data, mut indices, mut indptr) = (Vec::new(), Vec::new(), vec![0]);
+            let mut tokens = Vec::new();
+            for text in &texts {
+                tokens.clear();
+                text.encode(&self.inner, &mut tokens);
+                tokens.sort_unstable();
+                let mut rest = &tokens[..];
+                while let Some(&token) = rest.first() {
+                    let count = rest.iter().take_while(|&&other| other == token).count();
+                    data.push(count as i64);
+                    indices.push(i64::from(token.to_u16()));
+                    rest = &rest[count..];
+                }
+                indptr.push(data.len() as i64);
+            }
+            (data, indices, indptr)
+        });
+        (
+            data.into_pyarray_bound(py),
+            indices.into_pyarray_bound(py),
+            indptr.into_pyarray_bound(py),
+        )
+    }
+
     /// Convert a sequence of `tokens` back to a textual representation.
     ///
     /// Due to the way whitespace and lowercasing is handled a sequence of tokens will not always be
@@ -109,14 +294,174 @@ impl Tokenizer {
     /// `decode(encode(text)) == text` does not always hold true. Hence, this function is mostly
     /// useful for debugging purposes.
     fn decode(&self, tokens: Vec<u16>) -> PyResult<String> {
-        let tokens = tokens
+        self.decode_tokens(&tokens)
+    }
+
+    /// Convert each row of a two-dimensional numpy array of `tokens` back to a textual
+    /// representation.
+    ///
+    /// This is intended for decoding the result of `tokenize_batch`: every row is decoded up to
+    /// its first `<end_of_text>` marker token, so that the padding following it is ignored. A
+    /// leading `<start_of_text>` marker token is skipped. Rows not containing an `<end_of_text>`
+    /// marker token are decoded in full.
+    ///
+    /// See `decode` for caveats about the decoded texts.
+    fn decode_batch(&self, tokens: PyReadonlyArray2<'_, u16>) -> PyResult<Vec<String>> {
+        let start_of_text = self.inner.start_of_text().to_u16();
+        tokens
+            .as_array()
+            .rows()
             .into_iter()
-            .map(|t| {
-                instant_clip_tokenizer::Token::from_u16(t, &self.inner)
-                    .ok_or_else(|| PyValueError::new_err(format!("invalid token: {t}")))
+            .map(|row| {
+                let row = row.to_vec();
+                let end = self.inner.end_of_text_position(&row).unwrap_or(row.len());
+                let start = usize::from(row.first() == Some(&start_of_text) && end > 0);
+                self.decode_tokens(&row[start..end])
             })
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(self.inner.decode(tokens))
+            .collect()
+    }
+
+    /// Returns the position of the first `<end_of_text>` marker token in each row of a
+    /// two-dimensional numpy array of `tokens`, as a numpy array of `int64`.
+    ///
+    /// CLIP models pool the final hidden state at these positions, e.g. using
+    /// `hidden_states[np.arange(len(positions)), positions]` on the result of the text encoder for
+    /// the rows returned by `tokenize_batch`. Raises `ValueError` if a row does not contain the
+    /// `<end_of_text>` marker token.
+    fn eot_positions<'py>(
+        &self,
+        py: Python<'py>,
+        tokens: PyReadonlyArray2<'_, u16>,
+    ) -> PyResult<Bound<'py, PyArray1<i64>>> {
+        let positions = self
+            .inner
+            .eot_positions_matrix(tokens.as_array())
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+        Ok(positions
+            .into_iter()
+            .map(|position| position as i64)
+            .collect::<Vec<_>>()
+            .into_pyarray_bound(py))
+    }
+
+    /// Show how a `text` (`str` or `bytes`) is tokenized, e.g. for debugging prompts.
+    ///
+    /// In Jupyter notebooks, the result is displayed as a colored span for each token, showing the
+    /// token's id when hovering over it. Printing the result lists the text of every token
+    /// together with its id:
+    ///
+    /// ```python
+    /// >>> print(tokenizer.show("Hi there"))
+    /// "hi " 1883 | "there " 997
+    /// ```
+    fn show(&self, text: Text) -> Tokenization {
+        let mut tokens = Vec::with_capacity(text.len());
+        text.encode(&self.inner, &mut tokens);
+        Tokenization {
+            html: instant_clip_tokenizer::viz::tokens_to_html(&self.inner, &tokens),
+            pieces: tokens
+                .iter()
+                .map(|&token| self.inner.decode([token]))
+                .collect(),
+            tokens: tokens.iter().map(|token| token.to_u16()).collect(),
+        }
+    }
+
+    /// Returns a callable for tokenizing a HuggingFace `datasets.Dataset` using
+    /// `Dataset.map(batched=True)`.
+    ///
+    /// The callable tokenizes the texts (`str` or `bytes`) in the given `column` of each batch like
+    /// `tokenize_batch`, without holding the GIL, and returns the `input_ids` and `attention_mask`
+    /// columns as two-dimensional numpy arrays of `int64`. The attention mask contains `1` for
+    /// every position holding an actual token, including the marker tokens, and `0` for padding:
+    ///
+    /// ```python
+    /// dataset = dataset.map(tokenizer.datasets_map("caption"), batched=True)
+    /// ```
+    ///
+    /// The callable can be pickled, so it also works with `num_proc` and dataset caching.
+    #[pyo3(signature = (column = "text", context_length = None))]
+    fn datasets_map(
+        &self,
+        column: &str,
+        context_length: Option<usize>,
+    ) -> PyResult<DatasetsMapper> {
+        let context_length = context_length.unwrap_or(self.inner.context_length());
+        if context_length < 3 {
+            return Err(PyValueError::new_err("context_length is less than 3"));
+        }
+        Ok(DatasetsMapper {
+            inner: self.inner.clone(),
+            column: column.to_owned(),
+            context_length,
+        })
+    }
+
+    /// Explain how a single `word` is encoded, listing every merge step that leads to its final
+    /// segmentation.
+    ///
+    /// The word is lowercased like by `encode` (unless this `Tokenizer` doesn't lowercase its
+    /// input), and should be a single word as split by the tokenizer, e.g. `"motorcycle"` rather
+    /// than a whole sentence. Printing the result shows every step on its own line:
+    ///
+    /// ```python
+    /// >>> print(tokenizer.explain("Hi"))
+    /// "hi"
+    ///   initial: "h" "i</w>"
+    ///   1. "h" + "i</w>" -> "hi</w>" (rank 1371, token 1883): "hi</w>"
+    /// ```
+    fn explain(&self, word: &str) -> Explanation {
+        let explanation = self.inner.explain(word);
+        let pieces = |tokens: &[instant_clip_tokenizer::Token]| {
+            tokens
+                .iter()
+                .map(|&token| explanation.piece_of(token).to_owned())
+                .collect()
+        };
+        Explanation {
+            word: explanation.word.clone(),
+            initial: explanation.initial.iter().map(|t| t.to_u16()).collect(),
+            initial_pieces: pieces(&explanation.initial),
+            steps: explanation
+                .steps
+                .iter()
+                .map(|step| MergeStep {
+                    first: step.first.to_u16(),
+                    second: step.second.to_u16(),
+                    rank: step.rank,
+                    result: step.result.to_u16(),
+                    merged_piece: explanation.piece_of(step.result).to_owned(),
+                    occurrences: step.occurrences,
+                    tokens: step.tokens.iter().map(|t| t.to_u16()).collect(),
+                    pieces: pieces(&step.tokens),
+                })
+                .collect(),
+            tokens: explanation.tokens.iter().map(|t| t.to_u16()).collect(),
+            pieces: explanation.pieces.clone(),
+            text: explanation.to_string(),
+        }
+    }
+
+    /// A fingerprint identifying the tokens produced by this `Tokenizer`, as a string of 16
+    /// hexadecimal digits.
+    ///
+    /// Tokenizers with different vocabularies, lowercasing or default context lengths have
+    /// different fingerprints, while the fingerprint of a given tokenizer is the same across
+    /// processes, platforms and versions of this package. Recording it together with tokenized
+    /// datasets allows detecting datasets produced by an incompatible tokenizer.
+    #[getter]
+    fn fingerprint(&self) -> String {
+        self.inner.fingerprint()
+    }
+
+    /// Returns the number of tokens in the vocabulary, including the marker tokens.
+    fn vocab_size(&self) -> usize {
+        self.inner.vocab_info().vocab_size()
+    }
+
+    /// Returns the context length of the model this `Tokenizer` is intended for.
+    fn context_length(&self) -> usize {
+        self.inner.context_length()
     }
 
     /// Returns the special `<start_of_text>` marker token.
@@ -130,10 +475,318 @@ impl Tokenizer {
     }
 }
 
+impl Tokenizer {
+    fn decode_tokens(&self, tokens: &[u16]) -> PyResult<String> {
+        let tokens = tokens
+            .iter()
+            .map(|&t| {
+                instant_clip_tokenizer::Token::from_u16(t, &self.inner)
+                    .ok_or_else(|| PyValueError::new_err(format!("invalid token: {t}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(self.inner.decode(tokens))
+    }
+}
+
+/// The result of `Tokenizer.explain`.
+#[pyclass(frozen)]
+struct Explanation {
+    /// The word after lowercasing.
+    #[pyo3(get)]
+    word: String,
+    /// The tokens for the individual bytes of the word, before any merge rule is applied.
+    #[pyo3(get)]
+    initial: Vec<u16>,
+    /// The textual representation of each token in `initial`.
+    #[pyo3(get)]
+    initial_pieces: Vec<String>,
+    /// The merge steps, in the order they were applied, as a list of `MergeStep`.
+    #[pyo3(get)]
+    steps: Vec<MergeStep>,
+    /// The final tokens of the word.
+    #[pyo3(get)]
+    tokens: Vec<u16>,
+    /// The textual representation of each token in `tokens`.
+    #[pyo3(get)]
+    pieces: Vec<String>,
+    text: String,
+}
+
+#[pymethods]
+impl Explanation {
+    fn __str__(&self) -> &str {
+        &self.text
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Explanation(word={:?}, pieces={:?}, steps={})",
+            self.word,
+            self.pieces,
+            self.steps.len()
+        )
+    }
+}
+
+/// A single step of an `Explanation`, merging all occurrences of a pair of adjacent tokens.
+#[pyclass(frozen)]
+#[derive(Clone)]
+struct MergeStep {
+    /// The first token of the merged pair.
+    #[pyo3(get)]
+    first: u16,
+    /// The second token of the merged pair.
+    #[pyo3(get)]
+    second: u16,
+    /// The rank of the applied merge rule. Rules with lower rank are applied first.
+    #[pyo3(get)]
+    rank: u32,
+    /// The token the pair was merged into.
+    #[pyo3(get)]
+    result: u16,
+    /// The textual representation of `result`.
+    #[pyo3(get)]
+    merged_piece: String,
+    /// The number of times the pair occurred in the word, and was therefore merged in this step.
+    #[pyo3(get)]
+    occurrences: usize,
+    /// The tokens of the word after this step.
+    #[pyo3(get)]
+    tokens: Vec<u16>,
+    /// The textual representation of each token in `tokens`.
+    #[pyo3(get)]
+    pieces: Vec<String>,
+}
+
+#[pymethods]
+impl MergeStep {
+    fn __repr__(&self) -> String {
+        format!(
+            "MergeStep(rank={}, merged_piece={:?}, pieces={:?})",
+            self.rank, self.merged_piece, self.pieces
+        )
+    }
+}
+
+/// The result of `Tokenizer.show`.
+#[pyclass(frozen)]
+struct Tokenization {
+    /// The tokens of the text.
+    #[pyo3(get)]
+    tokens: Vec<u16>,
+    /// The text represented by each token in `tokens`, as returned by `decode`.
+    #[pyo3(get)]
+    pieces: Vec<String>,
+    html: String,
+}
+
+#[pymethods]
+impl Tokenization {
+    /// Returns the tokens as colored HTML spans, for display in Jupyter notebooks.
+    fn _repr_html_(&self) -> &str {
+        &self.html
+    }
+
+    fn __str__(&self) -> String {
+        self.pieces
+            .iter()
+            .zip(&self.tokens)
+            .map(|(piece, token)| format!("{piece:?} {token}"))
+            .collect::<Vec<_>>()
+            .join(" | ")
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Tokenization(tokens={:?})", self.tokens)
+    }
+}
+
+/// The callable returned by `Tokenizer.datasets_map`.
+#[pyclass(frozen, module = "instant_clip_tokenizer")]
+struct DatasetsMapper {
+    inner: instant_clip_tokenizer::Tokenizer,
+    column: String,
+    context_length: usize,
+}
+
+#[pymethods]
+impl DatasetsMapper {
+    /// Create a new `DatasetsMapper` from the data written by `Tokenizer.to_bytes`, for
+    /// unpickling. Use `Tokenizer.datasets_map` instead.
+    #[new]
+    fn new(data: &[u8], column: String, context_length: usize) -> PyResult<Self> {
+        if context_length < 3 {
+            return Err(PyValueError::new_err("context_length is less than 3"));
+        }
+        Ok(DatasetsMapper {
+            inner: instant_clip_tokenizer::Tokenizer::load_binary(data)?,
+            column,
+            context_length,
+        })
+    }
+
+    fn __call__<'py>(
+        &self,
+        py: Python<'py>,
+        batch: &Bound<'py, PyDict>,
+    ) -> PyResult<Bound<'py, PyDict>> {
+        let texts = batch
+            .get_item(&self.column)?
+            .ok_or_else(|| PyKeyError::new_err(self.column.clone()))?;
+        let input = TokenizeBatchInput::Multiple(texts.extract()?);
+        let (input_ids, attention_mask) = py
+            .allow_threads(|| input.tokenize_with_attention_mask(&self.inner, self.context_length))
+            .map_err(|err| PyValueError::new_err(err.to_string()))?;
+
+        let result = PyDict::new_bound(py);
+        result.set_item("input_ids", input_ids.into_pyarray_bound(py))?;
+        result.set_item("attention_mask", attention_mask.into_pyarray_bound(py))?;
+        Ok(result)
+    }
+
+    fn __reduce__<'py>(
+        slf: &Bound<'py, Self>,
+    ) -> PyResult<(Bound<'py, PyAny>, (Bound<'py, PyBytes>, String, usize))> {
+        let py = slf.py();
+        let this = slf.get();
+        let mut data = Vec::new();
+        this.inner.save_binary(&mut data)?;
+        Ok((
+            slf.get_type().into_any(),
+            (
+                PyBytes::new_bound(py, &data),
+                this.column.clone(),
+                this.context_length,
+            ),
+        ))
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DatasetsMapper(column={:?}, context_length={})",
+            self.column, self.context_length
+        )
+    }
+}
+
+/// Completes `future` with `result`, unless it has been cancelled while the result was computed.
+#[pyfunction]
+fn set_future_result(future: &Bound<'_, PyAny>, result: &Bound<'_, PyAny>) -> PyResult<()> {
+    if !future.call_method0("done")?.is_truthy()? {
+        future.call_method1("set_result", (result,))?;
+    }
+    Ok(())
+}
+
 #[derive(FromPyObject)]
 enum TokenizeBatchInput {
+    #[pyo3(transparent, annotation = "str | bytes")]
+    Single(Text),
+    #[pyo3(transparent, annotation = "list[str | bytes]")]
+    Multiple(Vec<Text>),
+}
+
+impl TokenizeBatchInput {
+    fn texts(&self) -> &[Text] {
+        match self {
+            TokenizeBatchInput::Single(text) => slice::from_ref(text),
+            TokenizeBatchInput::Multiple(texts) => texts,
+        }
+    }
+
+    /// Returns the texts as `str`, if there are no `bytes` inputs.
+    fn as_strs(&self) -> Option<Vec<&str>> {
+        self.texts().iter().map(Text::as_str).collect()
+    }
+
+    fn tokenize(
+        &self,
+        tokenizer: &instant_clip_tokenizer::Tokenizer,
+        context_length: usize,
+    ) -> Array2<u16> {
+        // Without `bytes` inputs, the batch method can skip encoding duplicate texts
+        match self.as_strs() {
+            Some(texts) => tokenizer.tokenize_batch(texts, context_length),
+            None => self.tokenize_rows(tokenizer, context_length).0,
+        }
+    }
+
+    /// Tokenize like `tokenize`, also returning the attention mask, both converted to `int64`.
+    fn tokenize_with_attention_mask(
+        &self,
+        tokenizer: &instant_clip_tokenizer::Tokenizer,
+        context_length: usize,
+    ) -> io::Result<(Array2<i64>, Array2<i64>)> {
+        if let Some(texts) = self.as_strs() {
+            return tokenizer
+                .try_tokenize_batch_with_attention_mask::<i64, _>(texts, context_length);
+        }
+
+        let (input_ids, lengths) = self.tokenize_rows(tokenizer, context_length);
+        let mut attention_mask = Array2::<i64>::zeros(input_ids.dim());
+        for (mut mask_row, length) in attention_mask.rows_mut().into_iter().zip(lengths) {
+            mask_row.slice_mut(s![..length]).fill(1);
+        }
+        Ok((input_ids.mapv(i64::from), attention_mask))
+    }
+
+    /// Tokenize every text into a row of its own, returning the rows and the number of tokens in
+    /// each row.
+    fn tokenize_rows(
+        &self,
+        tokenizer: &instant_clip_tokenizer::Tokenizer,
+        context_length: usize,
+    ) -> (Array2<u16>, Vec<usize>) {
+        let texts = self.texts();
+        let mut result = Array2::zeros((texts.len(), context_length));
+        let mut lengths = Vec::with_capacity(texts.len());
+        let mut tokens = Vec::new();
+        for (text, mut row) in texts.iter().zip(result.rows_mut()) {
+            tokens.clear();
+            tokens.push(tokenizer.start_of_text());
+            text.encode(tokenizer, &mut tokens);
+            tokens.truncate(context_length - 1);
+            tokens.push(tokenizer.end_of_text());
+            for (value, token) in row.iter_mut().zip(&tokens) {
+                *value = token.to_u16();
+            }
+            lengths.push(tokens.len());
+        }
+        (result, lengths)
+    }
+}
+
+#[derive(FromPyObject)]
+enum Text {
     #[pyo3(transparent, annotation = "str")]
-    Single(PyBackedStr),
-    #[pyo3(transparent, annotation = "list[str]")]
-    Multiple(Vec<PyBackedStr>),
+    Str(PyBackedStr),
+    #[pyo3(transparent, annotation = "bytes")]
+    Bytes(PyBackedBytes),
+}
+
+impl Text {
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Text::Str(text) => Some(&**text),
+            Text::Bytes(_) => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Text::Str(text) => text.len(),
+            Text::Bytes(text) => text.len(),
+        }
+    }
+
+    fn encode(
+        &self,
+        tokenizer: &instant_clip_tokenizer::Tokenizer,
+        out: &mut Vec<instant_clip_tokenizer::Token>,
+    ) {
+        match self {
+            Text::Str(text) => tokenizer.encode(text, out),
+            Text::Bytes(text) => tokenizer.encode_bytes(text, out),
+        }
+    }
 }
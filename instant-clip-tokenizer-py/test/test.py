@@ -1,3 +1,11 @@
+import asyncio
+import os
+import pickle
+import subprocess
+import sys
+import tempfile
+from multiprocessing import shared_memory
+
 import numpy as np
 
 import instant_clip_tokenizer
@@ -19,13 +27,140 @@ def main():
     assert np.array_equal(tokens, expected)
     print(tokens)
 
+    tokens = asyncio.run(tokenize_batch_async(tokenizer))
+    assert np.array_equal(tokens, expected)
+    print(tokens)
+
     tokens = tokenizer.encode("Hello world!!!")
     assert tokens == [3306, 1002, 995]
     print(tokens)
 
+    tokens = tokenizer.encode_array("Hello world!!!")
+    assert tokens.dtype == np.uint16
+    assert np.array_equal(tokens, np.array([3306, 1002, 995]))
+    print(tokens)
+
+    assert tokenizer.encode(b"Hello world!!!") == [3306, 1002, 995]
+    tokens = tokenizer.encode(b"caf\xe9")
+    assert tokens[:1] == tokenizer.encode("caf") and len(tokens) == 2
+    tokens = tokenizer.tokenize_batch(["Hi", b"Hi", b"\xff"], context_length=4)
+    assert np.array_equal(tokens[0], tokens[1])
+    assert tokens[2, 0] == 49406 and tokens[2, 2] == 49407
+
     decoded = tokenizer.decode([320, 2533, 6765, 320, 10297])
     assert decoded == "a person riding a motorcycle "
     print(decoded)
 
+    tokens = tokenizer.tokenize_batch(["Hi", "A person riding a motorcycle"], context_length=10)
+    decoded = tokenizer.decode_batch(tokens)
+    assert decoded == ["hi ", "a person riding a motorcycle "]
+    print(decoded)
+
+    positions = tokenizer.eot_positions(tokens)
+    assert positions.dtype == np.int64
+    assert positions.tolist() == [2, 6]
+    try:
+        tokenizer.eot_positions(tokens[:, :3])
+        assert False, "expected ValueError"
+    except ValueError:
+        pass
+
+    explanation = tokenizer.explain("Hi")
+    assert explanation.word == "hi"
+    assert explanation.initial_pieces == ["h", "i</w>"]
+    assert explanation.tokens == [1883]
+    assert explanation.pieces == ["hi</w>"]
+    step = explanation.steps[0]
+    assert (step.rank, step.result, step.merged_piece) == (1371, 1883, "hi</w>")
+    assert step.pieces == ["hi</w>"]
+    assert str(explanation).startswith('"hi"\n  initial: "h" "i</w>"')
+    assert len(tokenizer.explain("motorcycle").steps) > 1
+
+    tokenization = tokenizer.show("Hi <there>")
+    assert tokenization.tokens == [1883, 283, 997, 285]
+    assert tokenization.pieces == ["hi ", "< ", "there ", "> "]
+    assert str(tokenization) == '"hi " 1883 | "< " 283 | "there " 997 | "> " 285'
+    assert 'title="1883"' in tokenization._repr_html_() and "&lt;" in tokenization._repr_html_()
+
+    mapper = tokenizer.datasets_map("caption", context_length=6)
+    batch = {"caption": ["Hi", b"How are you?"], "id": [1, 2]}
+    columns = mapper(batch)
+    assert sorted(columns) == ["attention_mask", "input_ids"]
+    assert columns["input_ids"].dtype == np.int64
+    assert columns["input_ids"].tolist() == [[49406, 1883, 49407, 0, 0, 0], [49406, 829, 631, 592, 286, 49407]]
+    assert columns["attention_mask"].tolist() == [[1, 1, 1, 0, 0, 0], [1, 1, 1, 1, 1, 1]]
+    # Tokens following a literal `<end_of_text>` marker are not padding
+    for caption in ["Hi <end_of_text> there", b"Hi <end_of_text> there"]:
+        columns = tokenizer.datasets_map("caption", context_length=7)({"caption": [caption]})
+        assert columns["input_ids"].tolist() == [[49406, 1883, 49407, 997, 49407, 0, 0]]
+        assert columns["attention_mask"].tolist() == [[1, 1, 1, 1, 1, 0, 0]]
+    columns = mapper(batch)
+    unpickled = pickle.loads(pickle.dumps(mapper))
+    assert np.array_equal(unpickled(batch)["input_ids"], columns["input_ids"])
+    try:
+        tokenizer.datasets_map()(batch)
+        assert False, "expected KeyError"
+    except KeyError:
+        pass
+
+    vectorizer = instant_clip_tokenizer.ClipTokenVectorizer()
+    counts = vectorizer.fit_transform(["Hello world!!! Hello", b"", "Hi"])
+    assert counts.shape == (3, 49408)
+    assert counts.dtype == np.int64
+    assert counts[0, 3306] == 2 and counts[0, 1002] == 1 and counts.getnnz(axis=1).tolist() == [3, 0, 1]
+    assert counts[2, 1883] == 1
+    data, indices, indptr = tokenizer.token_counts(["Hi Hi"])
+    assert (data.tolist(), indices.tolist(), indptr.tolist()) == ([2], [1883], [0, 1])
+    binary = vectorizer.set_params(binary=True).transform(["Hello Hello"])
+    assert binary[0, 3306] == 1
+    unpickled = pickle.loads(pickle.dumps(instant_clip_tokenizer.ClipTokenVectorizer(tokenizer)))
+    assert (unpickled.transform(["Hi"]) != counts[2]).nnz == 0
+
+    output = subprocess.run(
+        [sys.executable, "-m", "instant_clip_tokenizer"],
+        input=b"Hello world!!!\nHi\n",
+        capture_output=True,
+        check=True,
+    ).stdout
+    assert output == b"3306 1002 995\n1883\n"
+    output = subprocess.run(
+        [sys.executable, "-m", "instant_clip_tokenizer", "--tokens", "--context-length", "4"],
+        input=b"Hello world!!!\n",
+        capture_output=True,
+        check=True,
+    ).stdout
+    assert output == b"<start_of_text> hello</w> world</w> <end_of_text>\n"
+
+    long_clip = instant_clip_tokenizer.Tokenizer.long_clip()
+    assert len(tokenizer.fingerprint) == 16
+    assert long_clip.fingerprint != tokenizer.fingerprint
+    assert isinstance(instant_clip_tokenizer.__version__, str)
+    assert long_clip.context_length() == 248
+    tokens = long_clip.tokenize_batch(" ".join(["a"] * 300))
+    assert tokens.shape == (1, 248)
+    assert tokens[0, 247] == 49407
+    assert tokenizer.tokenize_batch(" ".join(["a"] * 300)).shape == (1, 77)
+
+    with tempfile.TemporaryDirectory() as tmp:
+        filename = os.path.join(tmp, "tokenizer.bin")
+        tokenizer.save(filename)
+        loaded = instant_clip_tokenizer.Tokenizer.load_binary(filename)
+        assert loaded.encode("Hello world!!!") == [3306, 1002, 995]
+        assert loaded.end_of_text() == tokenizer.end_of_text()
+
+    data = tokenizer.to_bytes()
+    shm = shared_memory.SharedMemory(create=True, size=len(data))
+    try:
+        shm.buf[:len(data)] = data
+        loaded = instant_clip_tokenizer.Tokenizer.from_buffer(shm.buf)
+        assert loaded.encode("Hello world!!!") == [3306, 1002, 995]
+        assert loaded.fingerprint == tokenizer.fingerprint
+    finally:
+        shm.close()
+        shm.unlink()
+
+async def tokenize_batch_async(tokenizer):
+    return await tokenizer.tokenize_batch_async(["Hi", "How are you?", "I'm fine, thanks!"], context_length=6)
+
 if __name__ == '__main__':
     main()
@@ -0,0 +1,90 @@
+//! Measures heap allocations performed by the tokenizer using dhat.
+//!
+//! Run with `cargo bench --bench allocations --all-features`. Every scenario reports the number of
+//! allocations and allocated bytes per operation, which should only ever go down. Constructing a
+//! tokenizer additionally reports its peak heap usage compared to the memory it retains.
+
+use instant_clip_tokenizer::Tokenizer;
+
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+const ITERATIONS: u64 = 1000;
+
+fn main() {
+    let _profiler = dhat::Profiler::builder().testing().build();
+
+    // Measured first, so that the peak isn't affected by earlier allocations
+    let data = include_str!("../bpe_simple_vocab_16e6.txt");
+    let tokenizer = Tokenizer::with_vocabulary_str(data, 49408).unwrap();
+    let stats = dhat::HeapStats::get();
+    println!(
+        "{:<20} {:>8.1} MB peak {:>16.1} MB retained",
+        "with_vocabulary",
+        stats.max_bytes as f64 / 1e6,
+        stats.curr_bytes as f64 / 1e6,
+    );
+    drop(tokenizer);
+
+    let tokenizer = Tokenizer::new();
+
+    for (name, text) in [
+        ("short", "Hello Båstad"),
+        ("realistic", "A person riding a motorcycle"),
+        (
+            "long word",
+            "donaudampfschifffahrtsgesellschaftskapitänsmütze",
+        ),
+    ] {
+        let mut tokens = Vec::with_capacity(100);
+        measure(&format!("encode {name}"), "encode", ITERATIONS, || {
+            for _ in 0..ITERATIONS {
+                tokens.clear();
+                tokenizer.encode(text, &mut tokens);
+            }
+        });
+        let mut buffer = [0; 77];
+        measure(&format!("encode_into {name}"), "encode", ITERATIONS, || {
+            for _ in 0..ITERATIONS {
+                tokenizer.encode_into(text, &mut buffer);
+            }
+        });
+    }
+
+    measure(
+        "tokenize_batch",
+        "row",
+        ITERATIONS * TEXTS.len() as u64,
+        || {
+            for _ in 0..ITERATIONS {
+                tokenizer.tokenize_batch(TEXTS, 77);
+            }
+        },
+    );
+}
+
+/// Runs `f` and reports the allocations it performed, divided by the `count` of operations `f`
+/// performs.
+fn measure(name: &str, unit: &str, count: u64, mut f: impl FnMut()) {
+    // Warm up so that buffers reused across calls don't count towards the result
+    f();
+    let before = dhat::HeapStats::get();
+    f();
+    let after = dhat::HeapStats::get();
+    println!(
+        "{name:<20} {:>8.2} allocations/{unit} {:>10.1} bytes/{unit}",
+        (after.total_blocks - before.total_blocks) as f64 / count as f64,
+        (after.total_bytes - before.total_bytes) as f64 / count as f64,
+    );
+}
+
+const TEXTS: [&str; 8] = [
+    "a very typical bus station",
+    "young confused girl standing in front of a wardrobe",
+    "interior design of modern living room with fireplace in a new house",
+    "traditional ornamental floral paisley bandanna .",
+    "actor arrives for the premiere of the film",
+    "photo of a deer and wildfire",
+    "this is real fast food !",
+    "a pencil drawing of a zebra and her baby .",
+];
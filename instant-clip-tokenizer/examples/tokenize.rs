@@ -12,10 +12,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let mut tokens = Vec::with_capacity(input.len());
     tokenizer.encode(&input, &mut tokens);
-    let tokens_readable = tokens
-        .iter()
-        .map(|token| tokenizer.decode([*token]))
-        .collect::<Vec<_>>();
+    let tokens_readable = tokenizer.encode_to_string_tokens(&input);
     println!("Result: {tokens:?} <-> {tokens_readable:?}");
 
     let decoded = tokenizer.decode(tokens);
@@ -0,0 +1,163 @@
+use std::io;
+
+use crate::{telemetry, EncodeOptions, Tokenizer};
+
+impl Tokenizer {
+    /// Tokenize a batch of multiple input strings, returning the token ids together with the
+    /// attention mask, the number of tokens and whether the text was truncated for each text.
+    ///
+    /// This works like [`tokenize_batch_with_attention_mask`], but also returns the information
+    /// that would otherwise have to be derived from the resulting matrices, e.g. to find the
+    /// position of the `<end_of_text>` token used for pooling, or to warn about truncated inputs.
+    ///
+    /// [`tokenize_batch_with_attention_mask`]: Tokenizer::tokenize_batch_with_attention_mask
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 3`, or one of the `texts` exceeds the input limits configured
+    /// using [`TokenizerOptions`]. Use [`try_tokenize_batch_full`] to handle these cases without
+    /// panicking.
+    ///
+    /// [`TokenizerOptions`]: crate::TokenizerOptions
+    /// [`try_tokenize_batch_full`]: Tokenizer::try_tokenize_batch_full
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ndarray::array;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let batch = tokenizer.tokenize_batch_full::<i64, _>(["Hi", "How are you?"], 5);
+    /// assert_eq!(batch.input_ids, array![
+    ///     [49406, 1883, 49407, 0, 0],
+    ///     [49406, 829, 631, 592, 49407],
+    /// ]);
+    /// assert_eq!(batch.attention_mask, array![[1, 1, 1, 0, 0], [1, 1, 1, 1, 1]]);
+    /// assert_eq!(batch.lengths, [3, 5]);
+    /// assert_eq!(batch.truncated, [false, true]);
+    /// ```
+    pub fn tokenize_batch_full<'a, T, I>(&self, texts: I, context_length: usize) -> BatchEncoding<T>
+    where
+        T: Copy + From<u16>,
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        self.try_tokenize_batch_full(texts, context_length)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Tokenize a batch of multiple input strings into a [`BatchEncoding`], returning an error
+    /// instead of panicking.
+    ///
+    /// This works like [`tokenize_batch_full`], but returns an error instead of panicking if
+    /// `context_length < 3` or one of the `texts` exceeds the input limits.
+    ///
+    /// [`tokenize_batch_full`]: Tokenizer::tokenize_batch_full
+    ///
+    /// # Errors
+    ///
+    /// If `context_length < 3`, or one of the `texts` exceeds the input limits (see
+    /// [`try_encode`]), then an error of kind [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// [`try_encode`]: Tokenizer::try_encode
+    pub fn try_tokenize_batch_full<'a, T, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+    ) -> io::Result<BatchEncoding<T>>
+    where
+        T: Copy + From<u16>,
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        if context_length < 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "context length must be at least 3",
+            ));
+        }
+        let timer = telemetry::BatchTimer::start();
+        let texts = texts.into_iter();
+        let mut input_ids = ndarray::Array2::<u16>::zeros((texts.len(), context_length));
+        let rows = self.fill_rows(texts, &mut input_ids, &EncodeOptions::new(), true)?;
+        let mut lengths = Vec::with_capacity(rows.len());
+        let mut truncated = Vec::with_capacity(rows.len());
+        for encoded in rows {
+            // Without a work budget, encoding can't fail
+            let (length, was_truncated) = encoded?;
+            lengths.push(length);
+            truncated.push(was_truncated);
+        }
+
+        let mut attention_mask = ndarray::Array2::from_elem(input_ids.dim(), T::from(0));
+        for (mut mask_row, &length) in attention_mask.rows_mut().into_iter().zip(&lengths) {
+            mask_row.slice_mut(ndarray::s![..length]).fill(T::from(1));
+        }
+        timer.finish();
+        Ok(BatchEncoding {
+            input_ids: input_ids.mapv(T::from),
+            attention_mask,
+            lengths,
+            truncated,
+        })
+    }
+}
+
+/// The result of tokenizing a batch of texts, as returned by [`Tokenizer::tokenize_batch_full`].
+///
+/// The matrices have one row per text, and the vectors one element per text.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BatchEncoding<T> {
+    /// The token ids, with the marker tokens added and padded with `0`, as returned by
+    /// [`Tokenizer::tokenize_batch`].
+    pub input_ids: ndarray::Array2<T>,
+    /// The attention mask, `1` for every position holding an actual token (including the marker
+    /// tokens) and `0` for every padding position.
+    pub attention_mask: ndarray::Array2<T>,
+    /// The number of tokens of each text, including the marker tokens. The `<end_of_text>` token
+    /// of a text is at position `lengths[row] - 1`.
+    pub lengths: Vec<usize>,
+    /// Whether tokens of each text were discarded to fit into the context length.
+    pub truncated: Vec<bool>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_batch_full() {
+        let tokenizer = Tokenizer::new();
+        let long = "a ".repeat(100);
+        let texts = [
+            "An astronaut riding a horse",
+            "",
+            &long,
+            "",
+            "Hi <end_of_text> there",
+        ];
+        let batch = tokenizer.tokenize_batch_full::<u16, _>(texts, 77);
+        let (input_ids, attention_mask) =
+            tokenizer.tokenize_batch_with_attention_mask::<u16, _>(texts, 77);
+        assert_eq!(batch.input_ids, input_ids);
+        assert_eq!(batch.attention_mask, attention_mask);
+        assert_eq!(batch.lengths, [7, 2, 77, 2, 5]);
+        assert_eq!(batch.truncated, [false, false, true, false, false]);
+        for (row, &length) in batch.lengths.iter().enumerate() {
+            assert_eq!(batch.input_ids[[row, length - 1]], 49407);
+        }
+
+        // Texts filling the context length exactly aren't truncated
+        let text = "a ".repeat(75);
+        let batch = tokenizer.tokenize_batch_full::<i64, _>([&*text], 77);
+        assert_eq!(batch.lengths, [77]);
+        assert_eq!(batch.truncated, [false]);
+
+        let empty = tokenizer.tokenize_batch_full::<i64, _>([], 77);
+        assert_eq!(empty.input_ids.dim(), (0, 77));
+        assert!(empty.lengths.is_empty());
+        assert!(tokenizer
+            .try_tokenize_batch_full::<i64, _>(["Hi"], 2)
+            .is_err());
+    }
+}
@@ -0,0 +1,415 @@
+use std::collections::hash_map::Entry;
+use std::io::{self, Read, Write};
+
+use crate::cache::{fnv1a, FNV_OFFSET_BASIS};
+use crate::{HashMap, PreTokenizer, Token, Tokenizer, WordBoundary};
+
+/// Identifies the binary format, followed by a single format version byte.
+const MAGIC: &[u8; 4] = b"ICTB";
+const VERSION: u8 = 1;
+
+impl Tokenizer {
+    /// Write this `Tokenizer` to `writer` in a compact binary format.
+    ///
+    /// Loading a `Tokenizer` from the binary format using [`load_binary`] is much faster than
+    /// parsing the original vocabulary data, which makes it useful for applications that need to
+    /// start up quickly, e.g. when many worker processes each need their own `Tokenizer`.
+    ///
+    /// The binary format is specific to this crate. It contains a version marker, so that future
+    /// releases of this crate can reject data written in an incompatible format.
+    ///
+    /// The written data only depends on the vocabulary, not on how this `Tokenizer` was
+    /// constructed or on the randomly seeded hash tables used internally. It is byte-identical
+    /// across runs and machines, so it can be used in reproducible builds or as a cache key.
+    ///
+    /// [`load_binary`]: Tokenizer::load_binary
+    ///
+    /// # Errors
+    ///
+    /// If writing to `writer` fails, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut data = Vec::new();
+    /// tokenizer.save_binary(&mut data).unwrap();
+    /// let tokenizer = Tokenizer::load_binary(data.as_slice()).unwrap();
+    /// ```
+    pub fn save_binary(&self, mut writer: impl Write) -> io::Result<()> {
+        writer.write_all(&self.binary_data())
+    }
+
+    /// Returns a fingerprint identifying the tokens produced by this `Tokenizer`.
+    ///
+    /// The fingerprint is a 16-digit hexadecimal hash of the vocabulary, the word boundary scheme,
+    /// the pre-tokenizer, whether texts are lowercased and the default context length. It is
+    /// stable across processes, platforms and releases of this crate, so recording it together
+    /// with tokenized data allows detecting data produced by a different tokenizer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// assert_eq!(tokenizer.fingerprint(), Tokenizer::new().fingerprint());
+    /// assert_ne!(tokenizer.fingerprint(), Tokenizer::long_clip().fingerprint());
+    /// ```
+    pub fn fingerprint(&self) -> String {
+        let mut hash = self.vocabulary_hash();
+        hash = fnv1a(hash, &[u8::from(self.lowercase)]);
+        hash = fnv1a(hash, &(self.context_length as u64).to_le_bytes());
+        // Only hashed if not the default, so that existing fingerprints stay the same
+        if self.pre_tokenizer == PreTokenizer::Graphemes {
+            hash = fnv1a(hash, b"graphemes");
+        }
+        format!("{hash:016x}")
+    }
+
+    /// Returns a hash of the vocabulary and the word boundary scheme, ignoring the options.
+    pub(crate) fn vocabulary_hash(&self) -> u64 {
+        // Skip the magic bytes and format version, which don't affect the tokens. The number of
+        // merge rules preceding the special marker tokens is only hashed for extended
+        // vocabularies, so that existing hashes stay the same.
+        let mut data = Vec::new();
+        self.write_tables(&mut data, false);
+        fnv1a(FNV_OFFSET_BASIS, &data)
+    }
+
+    fn binary_data(&self) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        self.write_tables(&mut data, true);
+        data
+    }
+
+    /// Append the vocabulary to `data`, always including the number of merge rules preceding the
+    /// special marker tokens if `always_split` is set, otherwise only for extended vocabularies.
+    fn write_tables(&self, data: &mut Vec<u8>, always_split: bool) {
+        let mut merge_rules = self
+            .vocabulary
+            .merge_rules
+            .iter()
+            .map(|(&pair, &result_token)| (result_token, pair))
+            .collect::<Vec<_>>();
+        merge_rules.sort_unstable();
+
+        let vocabulary = &self.vocabulary;
+        let merges_before_special = merge_rules
+            .iter()
+            .filter(|(result_token, _)| *result_token < vocabulary.start_of_text)
+            .count();
+        let extended = merges_before_special < merge_rules.len();
+
+        data.reserve(5 + 2 * 256 + 4 + 4 * merge_rules.len());
+        data.push(match vocabulary.word_boundary {
+            WordBoundary::EndOfWordSuffix => 0,
+            WordBoundary::SpacePrefix => 1,
+        });
+        if always_split || extended {
+            data.extend_from_slice(&(merges_before_special as u32).to_le_bytes());
+        }
+        for token in vocabulary.byte_to_token.iter() {
+            data.extend_from_slice(&token.0.to_le_bytes());
+        }
+        data.extend_from_slice(&(merge_rules.len() as u32).to_le_bytes());
+        for (_, (first, second)) in merge_rules {
+            data.extend_from_slice(&first.0.to_le_bytes());
+            data.extend_from_slice(&second.0.to_le_bytes());
+        }
+    }
+
+    /// Create a new `Tokenizer` by reading data written by [`save_binary`] from `reader`.
+    ///
+    /// [`save_binary`]: Tokenizer::save_binary
+    ///
+    /// # Errors
+    ///
+    /// If the data is not in the binary format written by [`save_binary`] or reading from
+    /// `reader` fails, then an error is returned.
+    pub fn load_binary(mut reader: impl Read) -> io::Result<Tokenizer> {
+        let mut header = [0; 5];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid_data("not a binary tokenizer file"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid_data("unsupported binary tokenizer format version"));
+        }
+        let mut word_boundary = [0; 1];
+        reader.read_exact(&mut word_boundary)?;
+        let word_boundary = match word_boundary[0] {
+            0 => WordBoundary::EndOfWordSuffix,
+            1 => WordBoundary::SpacePrefix,
+            _ => return Err(invalid_data("invalid word boundary scheme")),
+        };
+        let merges_before_special = read_u32(&mut reader)?;
+        let end_of_word = word_boundary == WordBoundary::EndOfWordSuffix;
+
+        // Whether each token id is known, to validate the merge rules
+        let mut known = vec![false; usize::from(u16::MAX) + 1];
+        let mut byte_to_token = Box::new([Token(0); 256]);
+        for token in byte_to_token.iter_mut() {
+            *token = Token(read_u16(&mut reader)?);
+            if token.0 >= 256 {
+                return Err(invalid_data("invalid byte token"));
+            }
+            if known[usize::from(token.0)] {
+                return Err(invalid_data("duplicate byte token"));
+            }
+            known[usize::from(token.0)] = true;
+            if end_of_word {
+                known[usize::from(token.0 + 256)] = true;
+            }
+        }
+        let mut token_index = word_boundary.base_tokens();
+
+        let count = read_u32(&mut reader)?;
+        if merges_before_special > count {
+            return Err(invalid_data("invalid number of merge rules"));
+        }
+        let mut start_of_text = None;
+        let mut merge_rules = HashMap::default();
+        for i in 0..count {
+            if i == merges_before_special {
+                // Skip the special marker tokens
+                start_of_text = Some(token_index);
+                token_index = token_index
+                    .checked_add(2)
+                    .ok_or_else(|| invalid_data("too many merge rules"))?;
+            }
+            let first = Token(read_u16(&mut reader)?);
+            let second = Token(read_u16(&mut reader)?);
+            if !known[usize::from(first.0)]
+                || !known[usize::from(second.0)]
+                || token_index >= u16::MAX - 1
+            {
+                return Err(invalid_data("invalid merge rule"));
+            }
+            // A repeated merge rule is shadowed by its first occurrence, like when parsing the
+            // vocabulary data
+            if let Entry::Vacant(entry) = merge_rules.entry((first, second)) {
+                entry.insert(Token(token_index));
+                known[usize::from(token_index)] = true;
+                token_index += 1;
+            }
+        }
+
+        Tokenizer::from_tables(
+            byte_to_token,
+            merge_rules,
+            start_of_text.unwrap_or(token_index),
+            word_boundary,
+        )
+    }
+}
+
+fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut buf = [0; 2];
+    reader.read_exact(&mut buf)?;
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut buf = [0; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ExtendOptions, TokenizerOptions, VocabExportFormat, DEFAULT_MAX_VOCABULARY_SIZE};
+
+    #[test]
+    fn roundtrip() {
+        let tokenizer = Tokenizer::new();
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+        let loaded = Tokenizer::load_binary(data.as_slice()).unwrap();
+
+        assert_eq!(
+            loaded.vocabulary.byte_to_token,
+            tokenizer.vocabulary.byte_to_token
+        );
+        assert_eq!(
+            loaded.vocabulary.merge_rules,
+            tokenizer.vocabulary.merge_rules
+        );
+        assert_eq!(loaded.vocabulary.decoder(), tokenizer.vocabulary.decoder());
+        assert_eq!(loaded.start_of_text(), tokenizer.start_of_text());
+        assert_eq!(loaded.end_of_text(), tokenizer.end_of_text());
+
+        let mut tokens = Vec::new();
+        loaded.encode("A person riding a motorcycle", &mut tokens);
+        assert_eq!(tokens, [320, 2533, 6765, 320, 10297].map(Token));
+    }
+
+    #[test]
+    fn roundtrip_space_prefix() {
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+        let loaded = Tokenizer::load_binary(data.as_slice()).unwrap();
+
+        assert_eq!(loaded.word_boundary(), WordBoundary::SpacePrefix);
+        assert_eq!(loaded.vocabulary.decoder(), tokenizer.vocabulary.decoder());
+        assert_eq!(loaded.end_of_text(), Token(260));
+        let mut tokens = Vec::new();
+        loaded.encode("the the", &mut tokens);
+        assert_eq!(tokens, [83, 257, 258].map(Token));
+    }
+
+    #[test]
+    fn fingerprint() {
+        let tokenizer = Tokenizer::new();
+        let fingerprint = tokenizer.fingerprint();
+        assert_eq!(fingerprint.len(), 16);
+
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+        let loaded = Tokenizer::load_binary(data.as_slice()).unwrap();
+        assert_eq!(loaded.fingerprint(), fingerprint);
+
+        let uncased = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        assert_ne!(uncased.fingerprint(), fingerprint);
+        let smaller =
+            Tokenizer::with_vocabulary_str(include_str!("../bpe_simple_vocab_16e6.txt"), 40000)
+                .unwrap();
+        assert_ne!(smaller.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn bundled_binary_vocabulary() {
+        // Bundled for `Tokenizer::new` instead of the text file, regenerate when the format
+        // changes
+        let tokenizer = Tokenizer::with_vocabulary_str(
+            include_str!("../bpe_simple_vocab_16e6.txt"),
+            DEFAULT_MAX_VOCABULARY_SIZE,
+        )
+        .unwrap();
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+        assert!(data == include_bytes!("../bpe_simple_vocab_16e6.bin"));
+    }
+
+    #[test]
+    fn deterministic() {
+        // Each hash table is seeded differently, and the tables are filled in different orders
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let tokenizers = [
+            Tokenizer::new(),
+            Tokenizer::with_vocabulary_str(data, DEFAULT_MAX_VOCABULARY_SIZE).unwrap(),
+            Tokenizer::with_vocabulary_str(data, DEFAULT_MAX_VOCABULARY_SIZE).unwrap(),
+        ];
+        let corpus = ["acetylsalicylic acid", "acetylsalicylic acid tablets"];
+        let options = ExtendOptions::new().min_frequency(1).merges(20);
+        let artifacts = |tokenizer: &Tokenizer| {
+            let mut data = Vec::new();
+            tokenizer.save_binary(&mut data).unwrap();
+            let mut table = Vec::new();
+            tokenizer
+                .export_vocab(&mut table, VocabExportFormat::Tsv)
+                .unwrap();
+            (data, table, tokenizer.raw_tables().to_json())
+        };
+
+        let expected = artifacts(&tokenizers[0]);
+        let extended = tokenizers[0].extend_vocabulary(corpus, &options).unwrap();
+        let expected_extended = artifacts(&extended);
+        assert_ne!(expected_extended, expected);
+        for tokenizer in &tokenizers {
+            assert!(artifacts(tokenizer) == expected);
+            let extended = tokenizer.extend_vocabulary(corpus, &options).unwrap();
+            assert!(artifacts(&extended) == expected_extended);
+        }
+    }
+
+    #[test]
+    fn too_many_merge_rules() {
+        let mut data = Vec::new();
+        Tokenizer::new().save_binary(&mut data).unwrap();
+        // Merge rules of distinct pairs of byte tokens, filling up the token ids
+        let pairs = (0..512u16).flat_map(|first| (0..512u16).map(move |second| (first, second)));
+        let table = |merges_before_special: u32, count: u32| {
+            let mut table = data[..10 + 2 * 256].to_vec();
+            table[6..10].copy_from_slice(&merges_before_special.to_le_bytes());
+            table.extend_from_slice(&count.to_le_bytes());
+            for (first, second) in pairs.clone().take(count as usize) {
+                table.extend_from_slice(&first.to_le_bytes());
+                table.extend_from_slice(&second.to_le_bytes());
+            }
+            table
+        };
+
+        // No room left for the special marker tokens, following or preceding merge rules
+        let count = u32::from(u16::MAX - 1 - 512);
+        for (merges_before_special, count) in [(count, count), (count, count + 1)] {
+            let loaded = Tokenizer::load_binary(table(merges_before_special, count).as_slice());
+            assert!(matches!(loaded, Err(err) if err.kind() == io::ErrorKind::InvalidData));
+        }
+        let loaded = Tokenizer::load_binary(table(count - 2, count - 2).as_slice()).unwrap();
+        assert_eq!(loaded.end_of_text(), Token(u16::MAX - 2));
+    }
+
+    #[test]
+    fn invalid() {
+        let tokenizer = Tokenizer::new();
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+
+        let mut wrong_magic = data.clone();
+        wrong_magic[0] = b'X';
+        assert!(Tokenizer::load_binary(wrong_magic.as_slice()).is_err());
+
+        let mut wrong_version = data.clone();
+        wrong_version[4] = VERSION + 1;
+        assert!(Tokenizer::load_binary(wrong_version.as_slice()).is_err());
+
+        let mut wrong_word_boundary = data.clone();
+        wrong_word_boundary[5] = 2;
+        assert!(Tokenizer::load_binary(wrong_word_boundary.as_slice()).is_err());
+
+        // More merge rules preceding the special marker tokens than there are merge rules
+        let mut wrong_split = data.clone();
+        wrong_split[6..10].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(Tokenizer::load_binary(wrong_split.as_slice()).is_err());
+
+        let truncated = &data[..data.len() - 1];
+        assert!(Tokenizer::load_binary(truncated).is_err());
+
+        // Merge rule referencing a token that doesn't exist yet
+        let mut invalid_merge = data.clone();
+        let offset = 10 + 2 * 256 + 4;
+        invalid_merge[offset..offset + 2].copy_from_slice(&1000u16.to_le_bytes());
+        assert!(Tokenizer::load_binary(invalid_merge.as_slice()).is_err());
+    }
+
+    #[test]
+    fn duplicate_merge_rules() {
+        let tokenizer = Tokenizer::new();
+        let mut data = Vec::new();
+        tokenizer.save_binary(&mut data).unwrap();
+        // The last merge rule repeating the one before is ignored
+        let len = data.len();
+        data.copy_within(len - 8..len - 4, len - 4);
+        let loaded = Tokenizer::load_binary(data.as_slice()).unwrap();
+        assert_eq!(
+            loaded.vocabulary.merge_rules.len(),
+            tokenizer.vocabulary.merge_rules.len() - 1
+        );
+        assert_eq!(loaded.end_of_text(), Token(49406));
+    }
+}
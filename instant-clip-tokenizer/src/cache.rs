@@ -0,0 +1,158 @@
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::Tokenizer;
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` from vocabulary data, caching the result on disk.
+    ///
+    /// This works like [`with_vocabulary_bytes`], but stores the resulting `Tokenizer` in the
+    /// binary format written by [`save_binary`] in the user's cache directory, keyed by a hash of
+    /// `data` and `max_vocabulary_size`. Later calls with the same arguments, including those from
+    /// other processes, load the cached `Tokenizer` instead of parsing the vocabulary data again,
+    /// which makes repeated startups of short-lived processes considerably faster.
+    ///
+    /// The cache directory is `$XDG_CACHE_HOME/instant-clip-tokenizer`, falling back to
+    /// `~/.cache/instant-clip-tokenizer` (or `%LOCALAPPDATA%\instant-clip-tokenizer` on Windows).
+    /// Caching is best-effort: if the cache directory can't be determined or written to, or a
+    /// cached file can't be read, the vocabulary data is simply parsed.
+    ///
+    /// [`with_vocabulary_bytes`]: Tokenizer::with_vocabulary_bytes
+    /// [`save_binary`]: Tokenizer::save_binary
+    ///
+    /// # Errors
+    ///
+    /// If `data` is not valid UTF-8 or the data format is incorrect, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// static VOCABULARY_DATA: &[u8] = include_bytes!("../bpe_simple_vocab_16e6.txt");
+    /// let tokenizer = Tokenizer::with_vocabulary_cached(VOCABULARY_DATA, 49408).unwrap();
+    /// ```
+    pub fn with_vocabulary_cached(data: &[u8], max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        match cache_dir() {
+            Some(dir) => load_cached(&dir, data, max_vocabulary_size),
+            None => Tokenizer::with_vocabulary_bytes(data, max_vocabulary_size),
+        }
+    }
+}
+
+fn load_cached(dir: &Path, data: &[u8], max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+    let mut hash = fnv1a(FNV_OFFSET_BASIS, data);
+    hash = fnv1a(hash, &max_vocabulary_size.to_le_bytes());
+    let path = dir.join(format!("{hash:016x}.bin"));
+
+    // Cached files written by an incompatible version of this crate are rejected by
+    // `load_binary`, and overwritten below
+    if let Ok(tokenizer) = File::open(&path).and_then(|f| Tokenizer::load_binary(BufReader::new(f)))
+    {
+        return Ok(tokenizer);
+    }
+
+    let tokenizer = Tokenizer::with_vocabulary_bytes(data, max_vocabulary_size)?;
+    let _ = store(&tokenizer, dir, &path);
+    Ok(tokenizer)
+}
+
+/// Writes `tokenizer` to `path` atomically, so that concurrent processes never see partial files.
+fn store(tokenizer: &Tokenizer, dir: &Path, path: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let result = File::create(&temp_path).and_then(|f| {
+        let mut writer = BufWriter::new(f);
+        tokenizer.save_binary(&mut writer)?;
+        writer.flush()
+    });
+    match result.and_then(|()| fs::rename(&temp_path, path)) {
+        Ok(()) => Ok(()),
+        Err(err) => {
+            let _ = fs::remove_file(&temp_path);
+            Err(err)
+        }
+    }
+}
+
+fn cache_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CACHE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            if cfg!(windows) {
+                env::var_os("LOCALAPPDATA").map(PathBuf::from)
+            } else {
+                env::var_os("HOME").map(|home| Path::new(&home).join(".cache"))
+            }
+        })?;
+    Some(base.join("instant-clip-tokenizer"))
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+
+/// A simple hash function that, unlike the hashers from `std` and `ahash`, is stable across
+/// processes and versions.
+pub(crate) fn fnv1a(mut hash: u64, data: &[u8]) -> u64 {
+    for &byte in data {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Token;
+
+    #[test]
+    fn load_cached() {
+        let dir = env::temp_dir().join(format!(
+            "instant-clip-tokenizer-cache-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        let data = include_bytes!("../bpe_simple_vocab_16e6.txt");
+
+        let tokenizer = super::load_cached(&dir, data, 49408).unwrap();
+        let files = fs::read_dir(&dir).unwrap().collect::<Vec<_>>();
+        assert_eq!(files.len(), 1);
+        let path = files[0].as_ref().unwrap().path();
+
+        // Loading again uses the cached file
+        let cached = super::load_cached(&dir, data, 49408).unwrap();
+        assert_eq!(
+            cached.vocabulary.merge_rules,
+            tokenizer.vocabulary.merge_rules
+        );
+        let mut tokens = Vec::new();
+        cached.encode("Hi there", &mut tokens);
+        assert_eq!(tokens, [1883, 997].map(Token));
+
+        // A different vocabulary size results in a different cache entry
+        let smaller = super::load_cached(&dir, data, 1000).unwrap();
+        assert_eq!(smaller.end_of_text(), Token(999));
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+
+        // Corrupt cache entries are replaced
+        fs::write(&path, b"garbage").unwrap();
+        let reparsed = super::load_cached(&dir, data, 49408).unwrap();
+        assert_eq!(reparsed.end_of_text(), Token(49407));
+        assert!(Tokenizer::load_binary(File::open(&path).unwrap()).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn fnv1a() {
+        // Reference values of the 64-bit FNV-1a hash function
+        assert_eq!(super::fnv1a(FNV_OFFSET_BASIS, b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(super::fnv1a(FNV_OFFSET_BASIS, b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(
+            super::fnv1a(FNV_OFFSET_BASIS, b"foobar"),
+            0x8594_4171_f739_67e8
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+use crate::{
+    PreTokenizer, Tokenizer, TokenizerOptions, VocabularyPreset, DEFAULT_MAX_VOCABULARY_SIZE,
+};
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` as described by the JSON configuration file at `path`.
+    ///
+    /// This allows deployments to treat the tokenizer as configuration rather than code, e.g. to
+    /// switch between model variants. The configuration is an object with the following keys, all
+    /// of which are optional:
+    ///
+    /// * `vocabulary` - Either the name of a [`VocabularyPreset`] bundled with this crate, e.g.
+    ///   `"openai-v1"` (requires the `openai-vocabulary-file` crate feature), or the path of
+    ///   vocabulary data in any format supported by [`from_path`], relative to the configuration
+    ///   file. Defaults to `"openai-v1"`.
+    /// * `max_vocabulary_size` - The maximum vocabulary size passed to [`from_path`] when loading
+    ///   vocabulary data from a path. Defaults to `49408`, the size of CLIP's vocabulary.
+    /// * `pre_tokenizer` - Either `"clip"` or `"graphemes"`, see [`PreTokenizer`].
+    /// * `lowercase`, `context_length`, `max_input_bytes`, `max_input_words`, `text_cache` - The
+    ///   corresponding [`TokenizerOptions`]. `null` can be used for the input limits to leave them
+    ///   unlimited.
+    ///
+    /// For example:
+    ///
+    /// ```json
+    /// {
+    ///     "vocabulary": "openai-v1",
+    ///     "context_length": 248,
+    ///     "max_input_bytes": 65536
+    /// }
+    /// ```
+    ///
+    /// Unknown keys are rejected rather than ignored, so that misspelled or unsupported settings
+    /// don't go unnoticed.
+    ///
+    /// [`from_path`]: Tokenizer::from_path
+    ///
+    /// # Errors
+    ///
+    /// If the configuration file can't be read, isn't valid JSON, contains unknown keys or values
+    /// of the wrong type, or the vocabulary can't be loaded, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// # let dir = std::env::temp_dir().join(format!("config-doctest-{}", std::process::id()));
+    /// # std::fs::create_dir_all(&dir).unwrap();
+    /// let path = dir.join("tokenizer-config.json");
+    /// std::fs::write(&path, r#"{"vocabulary": "openai-v1", "context_length": 248}"#).unwrap();
+    /// let tokenizer = Tokenizer::from_config(&path).unwrap();
+    /// assert_eq!(tokenizer.context_length(), 248);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn from_config(path: impl AsRef<Path>) -> io::Result<Tokenizer> {
+        let path = path.as_ref();
+        let config = serde_json::from_slice::<Value>(&fs::read(path)?).map_err(invalid_config)?;
+        let config = config
+            .as_object()
+            .ok_or_else(|| invalid_config("configuration must be a JSON object"))?;
+
+        let mut options = TokenizerOptions::new();
+        let mut max_vocabulary_size = DEFAULT_MAX_VOCABULARY_SIZE;
+        let mut vocabulary = VocabularyPreset::OpenAiV1.name();
+        for (key, value) in config {
+            match key.as_str() {
+                "vocabulary" => vocabulary = value.as_str().ok_or_else(|| wrong_type(key))?,
+                "max_vocabulary_size" => {
+                    max_vocabulary_size = value
+                        .as_u64()
+                        .and_then(|size| u16::try_from(size).ok())
+                        .ok_or_else(|| wrong_type(key))?
+                }
+                "lowercase" => {
+                    options = options.lowercase(value.as_bool().ok_or_else(|| wrong_type(key))?)
+                }
+                "context_length" => options = options.context_length(usize_value(key, value)?),
+                "max_input_bytes" => options.max_input_bytes = optional_usize_value(key, value)?,
+                "max_input_words" => options.max_input_words = optional_usize_value(key, value)?,
+                "pre_tokenizer" => {
+                    let pre_tokenizer = match value.as_str() {
+                        Some("clip") => PreTokenizer::Clip,
+                        Some("graphemes") => PreTokenizer::Graphemes,
+                        _ => return Err(wrong_type(key)),
+                    };
+                    options = options.pre_tokenizer(pre_tokenizer);
+                }
+                "text_cache" => options = options.text_cache(usize_value(key, value)?),
+                _ => return Err(invalid_config(format!("unknown configuration key `{key}`"))),
+            }
+        }
+
+        let tokenizer = match VocabularyPreset::from_name(vocabulary) {
+            #[cfg(feature = "openai-vocabulary-file")]
+            Some(preset) => Tokenizer::from_preset(preset),
+            #[cfg(not(feature = "openai-vocabulary-file"))]
+            Some(_) => {
+                return Err(invalid_config(format!(
+                    "the {vocabulary} vocabulary requires the `openai-vocabulary-file` feature"
+                )))
+            }
+            None => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                Tokenizer::from_path(dir.join(vocabulary), max_vocabulary_size)?
+            }
+        };
+        Ok(Tokenizer::from_vocabulary(tokenizer.vocabulary(), options))
+    }
+}
+
+fn usize_value(key: &str, value: &Value) -> io::Result<usize> {
+    value
+        .as_u64()
+        .and_then(|value| usize::try_from(value).ok())
+        .ok_or_else(|| wrong_type(key))
+}
+
+fn optional_usize_value(key: &str, value: &Value) -> io::Result<Option<usize>> {
+    match value {
+        Value::Null => Ok(None),
+        _ => usize_value(key, value).map(Some),
+    }
+}
+
+fn wrong_type(key: &str) -> io::Error {
+    invalid_config(format!("invalid value for configuration key `{key}`"))
+}
+
+fn invalid_config(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    /// A temporary directory that is removed when dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> TempDir {
+            let dir = std::env::temp_dir().join(format!("{name}-{}", std::process::id()));
+            fs::create_dir_all(&dir).unwrap();
+            TempDir(dir)
+        }
+
+        fn load(&self, config: &str) -> io::Result<Tokenizer> {
+            let path = self.0.join("config.json");
+            fs::write(&path, config).unwrap();
+            Tokenizer::from_config(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn from_config() {
+        let dir = TempDir::new("from-config");
+        let tokenizer = dir.load("{}").unwrap();
+        assert_eq!(tokenizer.fingerprint(), Tokenizer::new().fingerprint());
+
+        let config = r#"{
+            "vocabulary": "openai-v1",
+            "lowercase": false,
+            "pre_tokenizer": "graphemes",
+            "context_length": 248,
+            "max_input_bytes": 4,
+            "max_input_words": null,
+            "text_cache": 100
+        }"#;
+        let tokenizer = dir.load(config).unwrap();
+        assert_eq!(tokenizer.context_length(), 248);
+        assert!(!tokenizer.lowercase);
+        assert_eq!(tokenizer.pre_tokenizer(), PreTokenizer::Graphemes);
+        assert_eq!(tokenizer.max_input_bytes, Some(4));
+        assert_eq!(tokenizer.max_input_words, None);
+        assert_eq!(tokenizer.text_cache.unwrap().capacity(), 100);
+
+        // Vocabulary paths are relative to the configuration file
+        fs::write(
+            dir.0.join("vocab.txt"),
+            include_str!("../bpe_simple_vocab_16e6.txt"),
+        )
+        .unwrap();
+        let tokenizer = dir
+            .load(r#"{"vocabulary": "vocab.txt", "max_vocabulary_size": 1000}"#)
+            .unwrap();
+        assert_eq!(tokenizer.vocab_info().vocab_size(), 1000);
+        assert_eq!(tokenizer.preset(), None);
+    }
+
+    #[test]
+    fn invalid() {
+        let dir = TempDir::new("invalid-config");
+        for config in [
+            "",
+            "[]",
+            r#"{"context_length": "77"}"#,
+            r#"{"context_length": -1}"#,
+            r#"{"max_vocabulary_size": 70000}"#,
+            r#"{"lowercase": 1}"#,
+            r#"{"pre_tokenizer": "unicode"}"#,
+            r#"{"vocabulary": "missing.txt"}"#,
+        ] {
+            assert!(dir.load(config).is_err(), "{config}");
+        }
+        let err = dir.load(r#"{"padding": "zeros"}"#).err().unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unknown configuration key `padding`");
+    }
+}
@@ -0,0 +1,380 @@
+use std::borrow::Cow;
+use std::fmt;
+
+use crate::{HashMap, Tokenizer, Word};
+
+impl Tokenizer {
+    /// Measure how well the vocabulary covers the words in a corpus of `texts`.
+    ///
+    /// Every word (as split by [`pre_tokenize`]) is assigned a [`Script`] based on its first
+    /// letter, and the report contains statistics for each script found in the corpus: the average
+    /// number of tokens per word, the share of words split into many tokens and the most
+    /// fragmented frequent words. The script of a word is a rough proxy for its language, so a
+    /// high fragmentation for a script indicates that the vocabulary is not well suited for
+    /// languages written in it.
+    ///
+    /// [`pre_tokenize`]: Tokenizer::pre_tokenize
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{CoverageOptions, Script, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let report = tokenizer.analyze_coverage(
+    ///     ["a dog on the beach", "собака на пляже"],
+    ///     &CoverageOptions::new(),
+    /// );
+    /// let latin = report.script(Script::Latin).unwrap();
+    /// let cyrillic = report.script(Script::Cyrillic).unwrap();
+    /// assert_eq!(latin.tokens_per_word(), 1.0);
+    /// assert!(cyrillic.tokens_per_word() > 2.0);
+    /// ```
+    pub fn analyze_coverage<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+        options: &CoverageOptions,
+    ) -> CoverageReport {
+        // Number of occurrences and number of tokens for each distinct word
+        let mut words = HashMap::<String, (usize, usize)>::new();
+        let mut tokens = Vec::new();
+        for text in texts {
+            let text = match self.lowercase {
+                true => Cow::Owned(text.to_lowercase()),
+                false => Cow::Borrowed(text),
+            };
+            for (word, range) in self.pre_tokenize(&text) {
+                if let Some((count, _)) = words.get_mut(word) {
+                    *count += 1;
+                    continue;
+                }
+                let follows_whitespace = text[..range.start].ends_with(char::is_whitespace);
+                let text_word = Word::Text {
+                    word,
+                    follows_whitespace,
+                };
+                self.encode_word(text_word, &mut tokens);
+                words.insert(word.to_owned(), (1, tokens.len()));
+            }
+        }
+
+        let mut scripts = HashMap::<Script, ScriptCoverage>::new();
+        for (word, (count, tokens)) in words {
+            let script = Script::of_word(&word);
+            let coverage = scripts.entry(script).or_insert_with(|| ScriptCoverage {
+                script,
+                words: 0,
+                distinct_words: 0,
+                tokens: 0,
+                fragmented_words: 0,
+                most_fragmented: Vec::new(),
+            });
+            coverage.words += count;
+            coverage.distinct_words += 1;
+            coverage.tokens += count * tokens;
+            if tokens >= options.fragmented_tokens {
+                coverage.fragmented_words += count;
+            }
+            if count >= options.min_count {
+                coverage.most_fragmented.push(FragmentedWord {
+                    word,
+                    count,
+                    tokens,
+                });
+            }
+        }
+
+        let mut scripts = scripts.into_values().collect::<Vec<_>>();
+        scripts.sort_by(|a, b| b.words.cmp(&a.words).then(a.script.cmp(&b.script)));
+        for coverage in &mut scripts {
+            coverage.most_fragmented.sort_by(|a, b| {
+                (b.tokens, b.count)
+                    .cmp(&(a.tokens, a.count))
+                    .then_with(|| a.word.cmp(&b.word))
+            });
+            coverage.most_fragmented.truncate(options.max_examples);
+        }
+        CoverageReport { scripts }
+    }
+}
+
+/// Options for [`Tokenizer::analyze_coverage`].
+#[derive(Clone, Debug)]
+pub struct CoverageOptions {
+    fragmented_tokens: usize,
+    min_count: usize,
+    max_examples: usize,
+}
+
+impl CoverageOptions {
+    /// Create a new `CoverageOptions` with default values.
+    pub fn new() -> CoverageOptions {
+        CoverageOptions {
+            fragmented_tokens: 4,
+            min_count: 2,
+            max_examples: 20,
+        }
+    }
+
+    /// Set the number of tokens from which on a word counts as fragmented. Defaults to `4`.
+    pub fn fragmented_tokens(mut self, fragmented_tokens: usize) -> CoverageOptions {
+        self.fragmented_tokens = fragmented_tokens;
+        self
+    }
+
+    /// Set the number of occurrences from which on a word is listed in
+    /// [`ScriptCoverage::most_fragmented`]. Defaults to `2`.
+    pub fn min_count(mut self, min_count: usize) -> CoverageOptions {
+        self.min_count = min_count;
+        self
+    }
+
+    /// Set the maximum number of words listed in [`ScriptCoverage::most_fragmented`] for each
+    /// script. Defaults to `20`.
+    pub fn max_examples(mut self, max_examples: usize) -> CoverageOptions {
+        self.max_examples = max_examples;
+        self
+    }
+}
+
+impl Default for CoverageOptions {
+    fn default() -> CoverageOptions {
+        CoverageOptions::new()
+    }
+}
+
+/// The result of [`Tokenizer::analyze_coverage`].
+///
+/// The `Display` implementation renders a human-readable table with one row per script.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct CoverageReport {
+    /// Statistics for each script found in the corpus, most frequent script first.
+    pub scripts: Vec<ScriptCoverage>,
+}
+
+impl CoverageReport {
+    /// Returns the statistics for `script`, if the corpus contains any words in it.
+    pub fn script(&self, script: Script) -> Option<&ScriptCoverage> {
+        self.scripts
+            .iter()
+            .find(|coverage| coverage.script == script)
+    }
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "script      words  tokens/word  fragmented")?;
+        for coverage in &self.scripts {
+            write!(
+                f,
+                "\n{:<10} {:>6}  {:>11.2}  {:>9.1}%",
+                coverage.script.name(),
+                coverage.words,
+                coverage.tokens_per_word(),
+                coverage.fragmented_share() * 100.0
+            )?;
+            if !coverage.most_fragmented.is_empty() {
+                let examples = coverage
+                    .most_fragmented
+                    .iter()
+                    .map(|word| format!("{} ({})", word.word, word.tokens))
+                    .collect::<Vec<_>>();
+                write!(f, "  {}", examples.join(", "))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Coverage statistics for the words of a single [`Script`], see
+/// [`Tokenizer::analyze_coverage`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct ScriptCoverage {
+    /// The script of the words.
+    pub script: Script,
+    /// The total number of words.
+    pub words: usize,
+    /// The number of distinct words.
+    pub distinct_words: usize,
+    /// The total number of tokens the words are encoded as.
+    pub tokens: usize,
+    /// The number of words encoded as at least [`CoverageOptions::fragmented_tokens`] tokens.
+    pub fragmented_words: usize,
+    /// The distinct words occurring at least [`CoverageOptions::min_count`] times that are
+    /// encoded as the most tokens, most fragmented first.
+    pub most_fragmented: Vec<FragmentedWord>,
+}
+
+impl ScriptCoverage {
+    /// Returns the average number of tokens per word.
+    pub fn tokens_per_word(&self) -> f64 {
+        self.tokens as f64 / self.words as f64
+    }
+
+    /// Returns the share of words encoded as at least [`CoverageOptions::fragmented_tokens`]
+    /// tokens, between `0.0` and `1.0`.
+    pub fn fragmented_share(&self) -> f64 {
+        self.fragmented_words as f64 / self.words as f64
+    }
+}
+
+/// A word listed in [`ScriptCoverage::most_fragmented`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct FragmentedWord {
+    /// The word, lowercased if the tokenizer lowercases its input.
+    pub word: String,
+    /// The number of occurrences of the word in the corpus.
+    pub count: usize,
+    /// The number of tokens the word is encoded as.
+    pub tokens: usize,
+}
+
+/// The writing system of a word, see [`Tokenizer::analyze_coverage`].
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum Script {
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Devanagari,
+    Bengali,
+    Thai,
+    Georgian,
+    Hangul,
+    Hiragana,
+    Katakana,
+    Han,
+    /// Letters of any script not listed above.
+    Other,
+    /// Words without any letters, e.g. numbers, punctuation and emoji.
+    Common,
+}
+
+impl Script {
+    /// Returns the name of the script.
+    pub fn name(self) -> &'static str {
+        match self {
+            Script::Latin => "Latin",
+            Script::Greek => "Greek",
+            Script::Cyrillic => "Cyrillic",
+            Script::Armenian => "Armenian",
+            Script::Hebrew => "Hebrew",
+            Script::Arabic => "Arabic",
+            Script::Devanagari => "Devanagari",
+            Script::Bengali => "Bengali",
+            Script::Thai => "Thai",
+            Script::Georgian => "Georgian",
+            Script::Hangul => "Hangul",
+            Script::Hiragana => "Hiragana",
+            Script::Katakana => "Katakana",
+            Script::Han => "Han",
+            Script::Other => "Other",
+            Script::Common => "Common",
+        }
+    }
+
+    /// Returns the script of the first letter in `word`.
+    fn of_word(word: &str) -> Script {
+        word.chars()
+            .map(Script::of_char)
+            .find(|&script| script != Script::Common)
+            .unwrap_or(Script::Common)
+    }
+
+    fn of_char(c: char) -> Script {
+        match c {
+            'a'..='z' | 'A'..='Z' | '\u{c0}'..='\u{24f}' | '\u{1e00}'..='\u{1eff}' => match c {
+                '\u{d7}' | '\u{f7}' => Script::Common,
+                _ => Script::Latin,
+            },
+            '\u{370}'..='\u{3ff}' | '\u{1f00}'..='\u{1fff}' => Script::Greek,
+            '\u{400}'..='\u{52f}' => Script::Cyrillic,
+            '\u{530}'..='\u{58f}' => Script::Armenian,
+            '\u{590}'..='\u{5ff}' => Script::Hebrew,
+            '\u{600}'..='\u{6ff}' | '\u{750}'..='\u{77f}' => Script::Arabic,
+            '\u{900}'..='\u{97f}' => Script::Devanagari,
+            '\u{980}'..='\u{9ff}' => Script::Bengali,
+            '\u{e00}'..='\u{e7f}' => Script::Thai,
+            '\u{10a0}'..='\u{10ff}' => Script::Georgian,
+            '\u{1100}'..='\u{11ff}' | '\u{3130}'..='\u{318f}' | '\u{ac00}'..='\u{d7af}' => {
+                Script::Hangul
+            }
+            '\u{3040}'..='\u{309f}' => Script::Hiragana,
+            '\u{30a0}'..='\u{30ff}' => Script::Katakana,
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => Script::Han,
+            c if c.is_alphabetic() => Script::Other,
+            _ => Script::Common,
+        }
+    }
+}
+
+impl fmt::Display for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn analyze_coverage() {
+        let tokenizer = Tokenizer::new();
+        let texts = [
+            "A dog on the beach",
+            "Ein Hund am Strand",
+            "Собака на пляже",
+            "Собака в парке",
+            "42 🦀🦀",
+        ];
+        let options = CoverageOptions::new().min_count(2).max_examples(1);
+        let report = tokenizer.analyze_coverage(texts, &options);
+        let scripts = report
+            .scripts
+            .iter()
+            .map(|coverage| coverage.script)
+            .collect::<Vec<_>>();
+        assert_eq!(scripts, [Script::Latin, Script::Cyrillic, Script::Common]);
+
+        let latin = report.script(Script::Latin).unwrap();
+        assert_eq!(latin.words, 9);
+        assert_eq!(latin.distinct_words, 9);
+        assert!(latin.most_fragmented.is_empty());
+
+        let cyrillic = report.script(Script::Cyrillic).unwrap();
+        assert_eq!(cyrillic.words, 6);
+        assert_eq!(cyrillic.distinct_words, 5);
+        assert!(cyrillic.tokens_per_word() > latin.tokens_per_word());
+        assert!(cyrillic.fragmented_share() > 0.5);
+        let word = &cyrillic.most_fragmented[0];
+        assert_eq!((word.word.as_str(), word.count), ("собака", 2));
+        assert!(word.tokens >= 4);
+
+        let common = report.script(Script::Common).unwrap();
+        // Numbers are split into individual digits
+        assert_eq!(common.words, 3);
+        assert_eq!(report.script(Script::Han).map(|c| c.words), None);
+
+        let rendered = report.to_string();
+        assert!(rendered.starts_with("script      words  tokens/word  fragmented\nLatin"));
+        assert!(rendered.contains("собака ("));
+    }
+
+    #[test]
+    fn script_of_word() {
+        assert_eq!(Script::of_word("café"), Script::Latin);
+        assert_eq!(Script::of_word("'s"), Script::Latin);
+        assert_eq!(Script::of_word("123"), Script::Common);
+        assert_eq!(Script::of_word("×"), Script::Common);
+        assert_eq!(Script::of_word("東京"), Script::Han);
+        assert_eq!(Script::of_word("ひらがな"), Script::Hiragana);
+        assert_eq!(Script::of_word("한국어"), Script::Hangul);
+        assert_eq!(Script::of_word("ሰላም"), Script::Other);
+    }
+}
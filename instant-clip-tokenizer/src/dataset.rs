@@ -0,0 +1,749 @@
+//! Tokenizing large caption datasets into sharded `.npy` files.
+//!
+//! This module is only available with the `dataset` [crate feature](crate#crate-features). It
+//! streams captions from a JSONL, CSV or plain text file (or a Parquet file, using
+//! `tokenize_parquet_dataset` with the `parquet` crate feature), tokenizes them in parallel and
+//! writes the results to a directory of `.npy` files, each containing a two-dimensional `uint16`
+//! array of shape `(rows, context_length)` as produced by `Tokenizer::tokenize_batch`. Memory usage
+//! is bounded by the shard and batch sizes, independent of the size of the dataset.
+//!
+//! Row `i` of the concatenated shards always corresponds to record `i` of the input. An
+//! `index.json` file describing the shards is written to the output directory as well:
+//!
+//! ```json
+//! {"context_length":77,"rows":3,"missing":0,"shards":[{"file":"shard-00000.npy","first_row":0,"rows":3}]}
+//! ```
+//!
+//! # Examples
+//!
+//! ```no_run
+//! # use std::fs::File;
+//! # use std::io::{self, BufReader};
+//! # use instant_clip_tokenizer::dataset::{self, DatasetOptions, InputFormat};
+//! # use instant_clip_tokenizer::Tokenizer;
+//! # fn main() -> io::Result<()> {
+//! let tokenizer = Tokenizer::new();
+//! let input = BufReader::new(File::open("captions.jsonl")?);
+//! let options = DatasetOptions::new()
+//!     .format(InputFormat::Jsonl)
+//!     .column("caption");
+//! let summary = dataset::tokenize_dataset(&tokenizer, input, "tokens/", &options)?;
+//! println!("wrote {} rows in {} shards", summary.rows, summary.shards.len());
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+
+use crate::{EncodeOptions, Tokenizer};
+
+/// The format of the input data read by [`tokenize_dataset`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum InputFormat {
+    /// Every line is a caption.
+    Text,
+    /// Every line is a JSON object, with the caption stored in the field selected by
+    /// [`DatasetOptions::column`].
+    Jsonl,
+    /// Comma-separated values with a header row, with the caption stored in the column selected by
+    /// [`DatasetOptions::column`]. Quoted fields may contain commas, line breaks and escaped (`""`)
+    /// quotes.
+    Csv,
+}
+
+/// Options for [`tokenize_dataset`].
+#[derive(Clone, Debug)]
+pub struct DatasetOptions {
+    format: InputFormat,
+    column: String,
+    context_length: Option<usize>,
+    shard_size: usize,
+    batch_size: usize,
+    threads: usize,
+}
+
+impl DatasetOptions {
+    /// Create the default options.
+    ///
+    /// By default, the input is read as plain text, the `caption` column is used for JSONL and CSV
+    /// input, rows have a length of [`Tokenizer::context_length`] tokens, shards contain up to
+    /// 1,000,000 rows, and all available CPU cores are used.
+    pub fn new() -> DatasetOptions {
+        DatasetOptions {
+            format: InputFormat::Text,
+            column: "caption".to_owned(),
+            context_length: None,
+            shard_size: 1_000_000,
+            batch_size: 65_536,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    /// Set the format of the input data.
+    pub fn format(mut self, format: InputFormat) -> DatasetOptions {
+        self.format = format;
+        self
+    }
+
+    /// Set the name of the field (for JSONL input) or column (for CSV input) holding the captions.
+    pub fn column(mut self, column: impl Into<String>) -> DatasetOptions {
+        self.column = column.into();
+        self
+    }
+
+    /// Set the number of tokens per row, including the marker tokens.
+    ///
+    /// Defaults to the context length of the tokenizer, so this only needs to be set to produce
+    /// rows for a model different from the one the tokenizer was configured for.
+    pub fn context_length(mut self, context_length: usize) -> DatasetOptions {
+        self.context_length = Some(context_length);
+        self
+    }
+
+    /// Set the maximum number of rows per shard.
+    pub fn shard_size(mut self, shard_size: usize) -> DatasetOptions {
+        self.shard_size = shard_size;
+        self
+    }
+
+    /// Set the number of captions read from the input before tokenizing them in parallel.
+    pub fn batch_size(mut self, batch_size: usize) -> DatasetOptions {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Set the number of threads used for tokenizing.
+    pub fn threads(mut self, threads: usize) -> DatasetOptions {
+        self.threads = threads;
+        self
+    }
+}
+
+impl Default for DatasetOptions {
+    fn default() -> DatasetOptions {
+        DatasetOptions::new()
+    }
+}
+
+/// A summary of the output written by [`tokenize_dataset`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct DatasetSummary {
+    /// The total number of rows, which is the number of records in the input.
+    pub rows: usize,
+    /// The number of records without a caption, which were tokenized as empty texts.
+    pub missing: usize,
+    /// The shards written to the output directory, in order.
+    pub shards: Vec<Shard>,
+}
+
+/// A single `.npy` file written by [`tokenize_dataset`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Shard {
+    /// The path of the file.
+    pub path: PathBuf,
+    /// The index of the first row of this shard within the whole dataset.
+    pub first_row: usize,
+    /// The number of rows in this shard.
+    pub rows: usize,
+}
+
+/// Tokenize all captions read from `input`, writing the results to `output_dir`.
+///
+/// Every caption is tokenized like a single text passed to `Tokenizer::tokenize_batch` with the
+/// configured context length (the context length of `tokenizer` by default), and the resulting
+/// rows are written to `shard-00000.npy`, `shard-00001.npy` and so on, followed by an `index.json`
+/// file describing the shards. `output_dir` is created if it doesn't exist yet. Records where the
+/// caption is missing or `null` are tokenized as empty texts, so that rows stay aligned with the
+/// input records.
+///
+/// # Errors
+///
+/// If any of the options are invalid (e.g. `context_length < 3`), the input is malformed (e.g.
+/// invalid JSON, or a CSV header without the selected column), or reading or writing fails, then
+/// an error is returned. Shards written before the error occurred are left in place.
+pub fn tokenize_dataset(
+    tokenizer: &Tokenizer,
+    input: impl BufRead,
+    output_dir: impl AsRef<Path>,
+    options: &DatasetOptions,
+) -> io::Result<DatasetSummary> {
+    let context_length = validate(tokenizer, options)?;
+    let mut records = Records::new(input, options)?;
+    write_dataset(
+        tokenizer,
+        || records.next_caption(),
+        output_dir.as_ref(),
+        context_length,
+        options,
+    )
+}
+
+/// Tokenize all captions in the Parquet file at `input`, writing the results to `output_dir`.
+///
+/// This is only available with the `parquet` [crate feature](crate#crate-features). It works like
+/// [`tokenize_dataset`], but reads the captions from the top-level column selected by
+/// [`DatasetOptions::column`] (the configured input format is ignored). Only this column is
+/// decoded, so other columns such as image embeddings don't slow down reading. Non-string values
+/// are converted to their textual representation.
+///
+/// # Errors
+///
+/// If any of the options are invalid, the file is not a valid Parquet file or doesn't contain the
+/// selected column, or reading or writing fails, then an error is returned. Shards written before
+/// the error occurred are left in place.
+#[cfg(feature = "parquet")]
+pub fn tokenize_parquet_dataset(
+    tokenizer: &Tokenizer,
+    input: impl AsRef<Path>,
+    output_dir: impl AsRef<Path>,
+    options: &DatasetOptions,
+) -> io::Result<DatasetSummary> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use parquet::record::Field;
+    use parquet::schema::types::Type;
+
+    let context_length = validate(tokenizer, options)?;
+    let reader = SerializedFileReader::new(File::open(input)?)?;
+    let schema = reader.metadata().file_metadata().schema();
+    let column = schema
+        .get_fields()
+        .iter()
+        .find(|field| field.name() == options.column && field.is_primitive())
+        .ok_or_else(|| {
+            invalid_data(format!(
+                "Parquet file has no top-level column `{}`",
+                options.column
+            ))
+        })?;
+    let projection = Type::group_type_builder(schema.name())
+        .with_fields(vec![column.clone()])
+        .build()?;
+
+    let mut rows = reader.get_row_iter(Some(projection))?;
+    let next_caption = || -> io::Result<_> {
+        let row = match rows.next() {
+            Some(row) => row?,
+            None => return Ok(None),
+        };
+        Ok(Some(
+            match row.get_column_iter().next().map(|(_, field)| field) {
+                None | Some(Field::Null) => None,
+                Some(Field::Str(caption)) => Some(caption.clone()),
+                Some(Field::Bytes(bytes)) => {
+                    Some(String::from_utf8_lossy(bytes.data()).into_owned())
+                }
+                Some(other) => Some(other.to_string()),
+            },
+        ))
+    };
+    write_dataset(
+        tokenizer,
+        next_caption,
+        output_dir.as_ref(),
+        context_length,
+        options,
+    )
+}
+
+/// Checks that `options` are valid, returning the context length to use.
+fn validate(tokenizer: &Tokenizer, options: &DatasetOptions) -> io::Result<usize> {
+    let context_length = options
+        .context_length
+        .unwrap_or_else(|| tokenizer.context_length());
+    if context_length < 3 {
+        return Err(invalid_input("context length must be at least 3"));
+    }
+    if options.shard_size == 0 || options.batch_size == 0 || options.threads == 0 {
+        return Err(invalid_input(
+            "shard size, batch size and threads must be at least 1",
+        ));
+    }
+    Ok(context_length)
+}
+
+/// Tokenize the captions returned by `next_caption` until it returns `None`, writing the results
+/// to `output_dir`. A caption of `Some(None)` denotes a record without a caption.
+fn write_dataset(
+    tokenizer: &Tokenizer,
+    mut next_caption: impl FnMut() -> io::Result<Option<Option<String>>>,
+    output_dir: &Path,
+    context_length: usize,
+    options: &DatasetOptions,
+) -> io::Result<DatasetSummary> {
+    fs::create_dir_all(output_dir)?;
+    let mut summary = DatasetSummary {
+        rows: 0,
+        missing: 0,
+        shards: Vec::new(),
+    };
+    let mut shard = Vec::new();
+    let mut batch = Vec::with_capacity(options.batch_size);
+    loop {
+        let shard_rows = shard.len() / context_length;
+        batch.clear();
+        let limit = options.batch_size.min(options.shard_size - shard_rows);
+        while batch.len() < limit {
+            match next_caption()? {
+                Some(Some(caption)) => batch.push(caption),
+                Some(None) => {
+                    summary.missing += 1;
+                    batch.push(String::new());
+                }
+                None => break,
+            }
+        }
+
+        let start = shard.len();
+        shard.resize(start + batch.len() * context_length, 0);
+        tokenize_parallel(
+            tokenizer,
+            &batch,
+            &mut shard[start..],
+            context_length,
+            options.threads,
+        );
+        summary.rows += batch.len();
+
+        let shard_rows = shard.len() / context_length;
+        let done = batch.len() < limit;
+        if shard_rows == options.shard_size || (done && shard_rows > 0) {
+            let path = output_dir.join(format!("shard-{:05}.npy", summary.shards.len()));
+            let mut writer = BufWriter::new(File::create(&path)?);
+            write_npy(&mut writer, &shard, context_length)?;
+            writer.flush()?;
+            summary.shards.push(Shard {
+                path,
+                first_row: summary.rows - shard_rows,
+                rows: shard_rows,
+            });
+            shard.clear();
+        }
+        if done {
+            break;
+        }
+    }
+
+    write_index(&output_dir.join("index.json"), &summary, context_length)?;
+    Ok(summary)
+}
+
+/// Tokenize `texts` into the rows of `out` using `threads` threads.
+fn tokenize_parallel(
+    tokenizer: &Tokenizer,
+    texts: &[String],
+    out: &mut [u16],
+    context_length: usize,
+    threads: usize,
+) {
+    // Targets without thread support, such as WASI, report a single available thread
+    if threads == 1 {
+        for (text, row) in texts.iter().zip(out.chunks_mut(context_length)) {
+            tokenizer.encode_row(text, row, &EncodeOptions::new());
+        }
+        return;
+    }
+
+    let per_thread = ((texts.len() + threads - 1) / threads).max(1);
+    thread::scope(|scope| {
+        let chunks = out.chunks_mut(per_thread * context_length);
+        for (texts, out) in texts.chunks(per_thread).zip(chunks) {
+            scope.spawn(move || {
+                for (text, row) in texts.iter().zip(out.chunks_mut(context_length)) {
+                    tokenizer.encode_row(text, row, &EncodeOptions::new());
+                }
+            });
+        }
+    });
+}
+
+/// Writes `data` as a two-dimensional `uint16` array in the `.npy` format (version 1.0).
+fn write_npy(writer: &mut impl Write, data: &[u16], columns: usize) -> io::Result<()> {
+    let header = format!(
+        "{{'descr': '<u2', 'fortran_order': False, 'shape': ({}, {}), }}",
+        data.len() / columns,
+        columns
+    );
+    // The header is padded with spaces such that the data starts at a multiple of 64 bytes
+    let padded_len = (10 + header.len() + 1 + 63) / 64 * 64 - 10;
+    let header = format!("{header:<0$}\n", padded_len - 1);
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for &value in data {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_index(path: &Path, summary: &DatasetSummary, context_length: usize) -> io::Result<()> {
+    let shards = summary
+        .shards
+        .iter()
+        .map(|shard| {
+            serde_json::json!({
+                "file": shard.path.file_name().map(|name| name.to_string_lossy()),
+                "first_row": shard.first_row,
+                "rows": shard.rows,
+            })
+        })
+        .collect::<Vec<_>>();
+    let index = serde_json::json!({
+        "context_length": context_length,
+        "rows": summary.rows,
+        "missing": summary.missing,
+        "shards": shards,
+    });
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &index)?;
+    writer.write_all(b"\n")?;
+    writer.flush()
+}
+
+/// Reads captions from the input, one record at a time.
+struct Records<R> {
+    input: R,
+    format: InputFormat,
+    column: String,
+    /// The index of the selected column, for CSV input.
+    column_index: usize,
+    line: String,
+    line_number: usize,
+}
+
+impl<R: BufRead> Records<R> {
+    fn new(input: R, options: &DatasetOptions) -> io::Result<Records<R>> {
+        let mut records = Records {
+            input,
+            format: options.format,
+            column: options.column.clone(),
+            column_index: 0,
+            line: String::new(),
+            line_number: 0,
+        };
+        if records.format == InputFormat::Csv {
+            let header = records.next_csv_record()?.unwrap_or_default();
+            records.column_index = header
+                .iter()
+                .position(|name| *name == records.column)
+                .ok_or_else(|| {
+                    invalid_data(format!("CSV header has no column `{}`", records.column))
+                })?;
+        }
+        Ok(records)
+    }
+
+    /// Returns the caption of the next record, `Some(None)` if the record has no caption, or
+    /// `None` at the end of the input.
+    fn next_caption(&mut self) -> io::Result<Option<Option<String>>> {
+        match self.format {
+            InputFormat::Text => Ok(self
+                .next_line()?
+                .then(|| Some(self.trimmed_line().to_owned()))),
+            InputFormat::Jsonl => loop {
+                if !self.next_line()? {
+                    return Ok(None);
+                }
+                let line = self.trimmed_line();
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value = serde_json::from_str::<serde_json::Value>(line).map_err(|err| {
+                    invalid_data(format!("invalid JSON on line {}: {err}", self.line_number))
+                })?;
+                return Ok(Some(match value.get(&self.column) {
+                    None | Some(serde_json::Value::Null) => None,
+                    Some(serde_json::Value::String(caption)) => Some(caption.clone()),
+                    Some(other) => Some(other.to_string()),
+                }));
+            },
+            InputFormat::Csv => Ok(self.next_csv_record()?.map(|mut fields| {
+                if fields.len() > self.column_index {
+                    Some(fields.swap_remove(self.column_index))
+                } else {
+                    None
+                }
+            })),
+        }
+    }
+
+    fn next_csv_record(&mut self) -> io::Result<Option<Vec<String>>> {
+        if !self.next_line()? {
+            return Ok(None);
+        }
+        let mut fields = Vec::new();
+        let mut field = String::new();
+        let mut quoted = false;
+        loop {
+            let line = self.trimmed_line();
+            let mut chars = line.chars().peekable();
+            while let Some(c) = chars.next() {
+                match (c, quoted) {
+                    ('"', true) if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    ('"', true) => quoted = false,
+                    ('"', false) if field.is_empty() => quoted = true,
+                    (',', false) => fields.push(std::mem::take(&mut field)),
+                    (c, _) => field.push(c),
+                }
+            }
+            if !quoted {
+                break;
+            }
+
+            // A quoted field continues on the next line
+            field.push('\n');
+            if !self.next_line()? {
+                return Err(invalid_data(format!(
+                    "unterminated quoted field on line {}",
+                    self.line_number
+                )));
+            }
+        }
+        fields.push(field);
+        Ok(Some(fields))
+    }
+
+    /// Reads the next line into `self.line`, returning `false` at the end of the input.
+    fn next_line(&mut self) -> io::Result<bool> {
+        self.line.clear();
+        self.line_number += 1;
+        Ok(self.input.read_line(&mut self.line)? > 0)
+    }
+
+    /// Returns the current line without the trailing line break.
+    fn trimmed_line(&self) -> &str {
+        let line = self.line.strip_suffix('\n').unwrap_or(&self.line);
+        line.strip_suffix('\r').unwrap_or(line)
+    }
+}
+
+fn invalid_input(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    fn output_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "instant-clip-tokenizer-dataset-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    /// Reads a `.npy` file written by `write_npy`, returning its shape and data.
+    fn read_npy(path: &Path) -> ((usize, usize), Vec<u16>) {
+        let data = fs::read(path).unwrap();
+        assert_eq!(&data[..8], b"\x93NUMPY\x01\x00");
+        let header_len = u16::from_le_bytes([data[8], data[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&data[10..10 + header_len]).unwrap();
+        let shape = header.split("'shape': (").nth(1).unwrap();
+        let shape = shape.split(')').next().unwrap();
+        let mut shape = shape.split(", ").map(|n| n.parse().unwrap());
+        let shape = (shape.next().unwrap(), shape.next().unwrap());
+        let values = data[10 + header_len..]
+            .chunks(2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .collect();
+        (shape, values)
+    }
+
+    #[test]
+    fn jsonl_shards() {
+        let tokenizer = Tokenizer::new();
+        let input = concat!(
+            r#"{"caption": "Hi", "url": "a"}"#,
+            "\n",
+            r#"{"url": "b"}"#,
+            "\n\n",
+            r#"{"caption": "How are you?"}"#,
+            "\n",
+            r#"{"caption": "Hi"}"#,
+            "\n",
+            r#"{"caption": "I'm fine, thanks!"}"#,
+        );
+        let dir = output_dir("jsonl");
+        let options = DatasetOptions::new()
+            .format(InputFormat::Jsonl)
+            .context_length(6)
+            .shard_size(2)
+            .batch_size(1)
+            .threads(2);
+        let summary = tokenize_dataset(&tokenizer, input.as_bytes(), &dir, &options).unwrap();
+        assert_eq!(summary.rows, 5);
+        assert_eq!(summary.missing, 1);
+        let shards = summary
+            .shards
+            .iter()
+            .map(|shard| (shard.first_row, shard.rows))
+            .collect::<Vec<_>>();
+        assert_eq!(shards, [(0, 2), (2, 2), (4, 1)]);
+
+        let (shape, first) = read_npy(&dir.join("shard-00000.npy"));
+        assert_eq!(shape, (2, 6));
+        assert_eq!(
+            first,
+            [49406, 1883, 49407, 0, 0, 0, 49406, 49407, 0, 0, 0, 0]
+        );
+        let (shape, last) = read_npy(&dir.join("shard-00002.npy"));
+        assert_eq!(shape, (1, 6));
+        assert_eq!(last, [49406, 328, 880, 3797, 267, 49407]);
+
+        let index = fs::read_to_string(dir.join("index.json")).unwrap();
+        let index = serde_json::from_str::<serde_json::Value>(&index).unwrap();
+        assert_eq!(index["rows"], 5);
+        assert_eq!(index["shards"][1]["file"], "shard-00001.npy");
+        assert_eq!(index["shards"][1]["first_row"], 2);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn csv_matches_tokenize_batch() {
+        let tokenizer = Tokenizer::new();
+        let input =
+            "id,caption\r\n1,\"A person, riding \"\"a\"\"\nmotorcycle\"\r\n2,Hi there\r\n3\r\n";
+        let dir = output_dir("csv");
+        let options = DatasetOptions::new()
+            .format(InputFormat::Csv)
+            .context_length(10);
+        let summary = tokenize_dataset(&tokenizer, input.as_bytes(), &dir, &options).unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.missing, 1);
+        assert_eq!(summary.shards.len(), 1);
+
+        let (shape, rows) = read_npy(&summary.shards[0].path);
+        assert_eq!(shape, (3, 10));
+        let mut expected = vec![0; 30];
+        for (text, row) in ["A person, riding \"a\"\nmotorcycle", "Hi there", ""]
+            .iter()
+            .zip(expected.chunks_mut(10))
+        {
+            tokenizer.encode_row(text, row, &EncodeOptions::new());
+        }
+        assert_eq!(rows, expected);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn invalid_input() {
+        let tokenizer = Tokenizer::new();
+        let dir = output_dir("invalid");
+        let csv = DatasetOptions::new().format(InputFormat::Csv);
+        let err = tokenize_dataset(&tokenizer, &b"id,text\n"[..], &dir, &csv).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let err =
+            tokenize_dataset(&tokenizer, &b"id,caption\n1,\"Hi\n"[..], &dir, &csv).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let jsonl = DatasetOptions::new().format(InputFormat::Jsonl);
+        let err = tokenize_dataset(&tokenizer, &b"{\"caption\": \n"[..], &dir, &jsonl).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let text = DatasetOptions::new().context_length(2);
+        let err = tokenize_dataset(&tokenizer, &b"Hi\n"[..], &dir, &text).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(feature = "parquet")]
+    #[test]
+    fn parquet() {
+        use std::sync::Arc;
+
+        use parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let dir = output_dir("parquet");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("captions.parquet");
+        let schema = "message schema { REQUIRED INT64 id; OPTIONAL BYTE_ARRAY caption (UTF8); }";
+        let schema = Arc::new(parse_message_type(schema).unwrap());
+        let file = File::create(&path).unwrap();
+        let mut writer = SerializedFileWriter::new(file, schema, Default::default()).unwrap();
+        let mut row_group = writer.next_row_group().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        let ids = column.typed::<Int64Type>();
+        ids.write_batch(&[1, 2, 3], None, None).unwrap();
+        column.close().unwrap();
+        let mut column = row_group.next_column().unwrap().unwrap();
+        let captions = [ByteArray::from("Hi"), ByteArray::from("How are you?")];
+        let captions_column = column.typed::<ByteArrayType>();
+        captions_column
+            .write_batch(&captions, Some(&[1, 0, 1]), None)
+            .unwrap();
+        column.close().unwrap();
+        row_group.close().unwrap();
+        writer.close().unwrap();
+
+        let tokenizer = Tokenizer::new();
+        let output = dir.join("tokens");
+        let options = DatasetOptions::new().context_length(6);
+        let summary = tokenize_parquet_dataset(&tokenizer, &path, &output, &options).unwrap();
+        assert_eq!(summary.rows, 3);
+        assert_eq!(summary.missing, 1);
+        let (shape, rows) = read_npy(&summary.shards[0].path);
+        assert_eq!(shape, (3, 6));
+        assert_eq!(
+            rows,
+            [
+                49406, 1883, 49407, 0, 0, 0, //
+                49406, 49407, 0, 0, 0, 0, //
+                49406, 829, 631, 592, 286, 49407,
+            ]
+        );
+
+        let options = DatasetOptions::new().column("text");
+        let err = tokenize_parquet_dataset(&tokenizer, &path, &output, &options).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn long_clip_context_length() {
+        let tokenizer = Tokenizer::long_clip();
+        let dir = output_dir("long-clip");
+        let input = ["a"; 300].join(" ");
+        let summary =
+            tokenize_dataset(&tokenizer, input.as_bytes(), &dir, &DatasetOptions::new()).unwrap();
+        let (shape, row) = read_npy(&summary.shards[0].path);
+        assert_eq!(shape, (1, 248));
+        assert_eq!(row[247], 49407);
+        let index = fs::read_to_string(dir.join("index.json")).unwrap();
+        assert!(index.contains(r#""context_length":248"#));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn empty_input() {
+        let tokenizer = Tokenizer::new();
+        let dir = output_dir("empty");
+        let summary = tokenize_dataset(&tokenizer, &b""[..], &dir, &DatasetOptions::new()).unwrap();
+        assert_eq!(summary.rows, 0);
+        assert!(summary.shards.is_empty());
+        assert!(dir.join("index.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
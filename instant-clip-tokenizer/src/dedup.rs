@@ -0,0 +1,510 @@
+//! Similarity metrics and MinHash and SimHash signatures over token sequences for near-duplicate
+//! detection.
+//!
+//! Everything in this module operates on the token ids produced by a [`Tokenizer`], so that
+//! captions are compared exactly as the model sees them: texts only differing in case or
+//! whitespace are identical. The exact metrics ([`jaccard`], [`containment`] and
+//! [`longest_common_span`]) are cheap for short texts like captions, while signatures scale to
+//! large datasets. All hash functions are deterministic, so signatures computed in different
+//! processes (or by different versions of this crate) can be compared.
+//!
+//! # Examples
+//!
+//! ```
+//! # use instant_clip_tokenizer::dedup::MinHasher;
+//! # use instant_clip_tokenizer::Tokenizer;
+//! let tokenizer = Tokenizer::new();
+//! let hasher = MinHasher::new(128, 2);
+//! let a = hasher.signature_for_text(&tokenizer, "A dog running on the beach");
+//! let b = hasher.signature_for_text(&tokenizer, "a dog  running on the beach!");
+//! let c = hasher.signature_for_text(&tokenizer, "Mountains at sunset");
+//! assert!(a.similarity(&b) > 0.5);
+//! assert!(a.similarity(&c) < 0.1);
+//! ```
+
+use std::cmp::Ordering;
+use std::mem;
+use std::ops::Range;
+
+use crate::cache::{fnv1a, FNV_OFFSET_BASIS};
+use crate::{Token, Tokenizer};
+
+/// Returns the Jaccard similarity of the sets of distinct tokens in `a` and `b`.
+///
+/// This is the number of distinct tokens occurring in both sequences divided by the number of
+/// distinct tokens occurring in either, between `0.0` and `1.0`. Two empty sequences have a
+/// similarity of `1.0`.
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::dedup::jaccard;
+/// # use instant_clip_tokenizer::Tokenizer;
+/// let tokenizer = Tokenizer::new();
+/// let (mut a, mut b) = (Vec::new(), Vec::new());
+/// tokenizer.encode("a red car", &mut a);
+/// tokenizer.encode("A car, red", &mut b);
+/// // "," is the only token not in both sequences
+/// assert_eq!(jaccard(&a, &b), 0.75);
+/// ```
+pub fn jaccard(a: &[Token], b: &[Token]) -> f64 {
+    TokenSet::new(a).jaccard(&TokenSet::new(b))
+}
+
+/// Returns the fraction of distinct tokens in `a` that also occur in `b`.
+///
+/// Unlike [`jaccard`] this is asymmetric: it is `1.0` if all tokens of `a` occur in `b`, no
+/// matter how many other tokens `b` contains. This is useful for scoring how well a caption
+/// covers a query. An empty `a` has a containment of `1.0`.
+pub fn containment(a: &[Token], b: &[Token]) -> f64 {
+    TokenSet::new(a).containment(&TokenSet::new(b))
+}
+
+/// Returns the longest contiguous run of tokens occurring in both `a` and `b`.
+///
+/// Returns the ranges of the run in `a` and `b` respectively, or `None` if the sequences have no
+/// tokens in common. If there are several runs of the same length, the first one in `a` is
+/// returned. This takes time proportional to the product of the lengths of the sequences.
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::dedup::longest_common_span;
+/// # use instant_clip_tokenizer::Tokenizer;
+/// let tokenizer = Tokenizer::new();
+/// let (mut a, mut b) = (Vec::new(), Vec::new());
+/// tokenizer.encode("a photo of a dog on a beach", &mut a);
+/// tokenizer.encode("my dog on a beach at sunset", &mut b);
+/// let (in_a, in_b) = longest_common_span(&a, &b).unwrap();
+/// assert_eq!(a[in_a.clone()], b[in_b.clone()]);
+/// assert_eq!((in_a, in_b), (4..8, 1..5));
+/// ```
+pub fn longest_common_span(a: &[Token], b: &[Token]) -> Option<(Range<usize>, Range<usize>)> {
+    // Length of the longest common run ending at the current position in `a` and each position
+    // in `b`, only keeping the row for the previous position in `a`
+    let mut previous = vec![0; b.len() + 1];
+    let mut current = vec![0; b.len() + 1];
+    let (mut len, mut end_a, mut end_b) = (0, 0, 0);
+    for (i, token_a) in a.iter().enumerate() {
+        for (j, token_b) in b.iter().enumerate() {
+            current[j + 1] = match token_a == token_b {
+                true => previous[j] + 1,
+                false => 0,
+            };
+            if current[j + 1] > len {
+                (len, end_a, end_b) = (current[j + 1], i + 1, j + 1);
+            }
+        }
+        mem::swap(&mut previous, &mut current);
+    }
+
+    match len {
+        0 => None,
+        _ => Some((end_a - len..end_a, end_b - len..end_b)),
+    }
+}
+
+/// Token overlap between a query and a candidate, computed by [`overlaps`].
+#[derive(Clone, Debug, PartialEq)]
+#[non_exhaustive]
+pub struct Overlap {
+    /// The [`jaccard`] similarity of the query and the candidate.
+    pub jaccard: f64,
+    /// The [`containment`] of the query in the candidate.
+    pub containment: f64,
+    /// The length of the [`longest_common_span`] of the query and the candidate.
+    pub longest_common_span: usize,
+}
+
+/// Compute the token overlap between a `query` and each of the `candidates`.
+///
+/// This is equivalent to calling [`jaccard`], [`containment`] and [`longest_common_span`] for each
+/// candidate, but only processes the query once.
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::dedup::overlaps;
+/// # use instant_clip_tokenizer::Tokenizer;
+/// let tokenizer = Tokenizer::new();
+/// let encode = |text| {
+///     let mut tokens = Vec::new();
+///     tokenizer.encode(text, &mut tokens);
+///     tokens
+/// };
+/// let query = encode("red car");
+/// let captions = [encode("a red car parked outside"), encode("a blue bicycle")];
+/// let overlaps = overlaps(&query, captions.iter().map(Vec::as_slice));
+/// assert_eq!(overlaps[0].containment, 1.0);
+/// assert_eq!(overlaps[1].longest_common_span, 0);
+/// ```
+pub fn overlaps<'a>(
+    query: &[Token],
+    candidates: impl IntoIterator<Item = &'a [Token]>,
+) -> Vec<Overlap> {
+    let query_set = TokenSet::new(query);
+    let mut candidate_set = TokenSet::default();
+    candidates
+        .into_iter()
+        .map(|candidate| {
+            candidate_set.assign(candidate);
+            Overlap {
+                jaccard: query_set.jaccard(&candidate_set),
+                containment: query_set.containment(&candidate_set),
+                longest_common_span: longest_common_span(query, candidate)
+                    .map_or(0, |(span, _)| span.len()),
+            }
+        })
+        .collect()
+}
+
+/// The distinct tokens of a sequence, sorted for computing intersections in linear time.
+#[derive(Default)]
+struct TokenSet(Vec<Token>);
+
+impl TokenSet {
+    fn new(tokens: &[Token]) -> TokenSet {
+        let mut set = TokenSet::default();
+        set.assign(tokens);
+        set
+    }
+
+    fn assign(&mut self, tokens: &[Token]) {
+        self.0.clear();
+        self.0.extend_from_slice(tokens);
+        self.0.sort_unstable();
+        self.0.dedup();
+    }
+
+    fn intersection_len(&self, other: &TokenSet) -> usize {
+        let (mut a, mut b) = (self.0.iter().peekable(), other.0.iter().peekable());
+        let mut len = 0;
+        while let (Some(x), Some(y)) = (a.peek(), b.peek()) {
+            match x.cmp(y) {
+                Ordering::Less => {
+                    a.next();
+                }
+                Ordering::Greater => {
+                    b.next();
+                }
+                Ordering::Equal => {
+                    len += 1;
+                    a.next();
+                    b.next();
+                }
+            }
+        }
+        len
+    }
+
+    fn jaccard(&self, other: &TokenSet) -> f64 {
+        let intersection = self.intersection_len(other);
+        let union = self.0.len() + other.0.len() - intersection;
+        match union {
+            0 => 1.0,
+            _ => intersection as f64 / union as f64,
+        }
+    }
+
+    fn containment(&self, other: &TokenSet) -> f64 {
+        match self.0.len() {
+            0 => 1.0,
+            len => self.intersection_len(other) as f64 / len as f64,
+        }
+    }
+}
+
+/// Computes MinHash signatures over token n-gram shingles.
+///
+/// The fraction of equal values in the signatures of two token sequences is an estimate of the
+/// Jaccard similarity of their sets of shingles.
+#[derive(Clone, Debug)]
+pub struct MinHasher {
+    seeds: Vec<u64>,
+    shingle_size: usize,
+}
+
+impl MinHasher {
+    /// Create a new `MinHasher` computing signatures of `num_hashes` values over shingles of
+    /// `shingle_size` consecutive tokens.
+    ///
+    /// More hash values result in more accurate similarity estimates, at the cost of larger
+    /// signatures. Token sequences shorter than `shingle_size` form a single shingle.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_hashes` or `shingle_size` is `0`.
+    pub fn new(num_hashes: usize, shingle_size: usize) -> MinHasher {
+        assert!(num_hashes > 0, "number of hashes must be at least 1");
+        assert!(shingle_size > 0, "shingle size must be at least 1");
+        let seeds = (0..num_hashes as u64)
+            .map(|i| mix(i.wrapping_add(0x9e37_79b9_7f4a_7c15)))
+            .collect();
+        MinHasher {
+            seeds,
+            shingle_size,
+        }
+    }
+
+    /// Compute the MinHash signature of a sequence of `tokens`.
+    pub fn signature(&self, tokens: &[Token]) -> MinHashSignature {
+        let mut values = vec![u64::MAX; self.seeds.len()];
+        for shingle in shingle_hashes(tokens, self.shingle_size) {
+            for (value, seed) in values.iter_mut().zip(&self.seeds) {
+                *value = (*value).min(mix(shingle ^ seed));
+            }
+        }
+        MinHashSignature { values }
+    }
+
+    /// Compute the MinHash signature of `text`, encoded using [`Tokenizer::encode`].
+    pub fn signature_for_text(&self, tokenizer: &Tokenizer, text: &str) -> MinHashSignature {
+        let mut tokens = Vec::new();
+        tokenizer.encode(text, &mut tokens);
+        self.signature(&tokens)
+    }
+}
+
+/// A MinHash signature computed by [`MinHasher`].
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct MinHashSignature {
+    values: Vec<u64>,
+}
+
+impl MinHashSignature {
+    /// Returns the individual hash values of this signature.
+    pub fn values(&self) -> &[u64] {
+        &self.values
+    }
+
+    /// Estimate the Jaccard similarity of the shingles underlying this and the `other` signature.
+    ///
+    /// Returns a value between `0.0` (no shingles in common) and `1.0` (identical sets of
+    /// shingles). Both signatures must have been computed by a [`MinHasher`] with the same
+    /// parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the signatures have different lengths.
+    pub fn similarity(&self, other: &MinHashSignature) -> f64 {
+        assert_eq!(
+            self.values.len(),
+            other.values.len(),
+            "signatures have different lengths"
+        );
+        let equal = self
+            .values
+            .iter()
+            .zip(&other.values)
+            .filter(|(a, b)| a == b)
+            .count();
+        equal as f64 / self.values.len() as f64
+    }
+
+    /// Returns one hash per band of `rows_per_band` consecutive values, for locality-sensitive
+    /// hashing.
+    ///
+    /// Signatures sharing at least one band hash are candidate near-duplicates. This allows
+    /// finding near-duplicates in large datasets by bucketing signatures by their band hashes,
+    /// instead of comparing all pairs of signatures. Fewer rows per band find more candidates.
+    /// Trailing values not filling a whole band are ignored.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rows_per_band` is `0`.
+    pub fn band_hashes(&self, rows_per_band: usize) -> impl Iterator<Item = u64> + '_ {
+        assert!(rows_per_band > 0, "rows per band must be at least 1");
+        self.values
+            .chunks_exact(rows_per_band)
+            .enumerate()
+            .map(|(band, values)| {
+                // Include the band index, so that equal values in different bands don't collide
+                let hash = fnv1a(FNV_OFFSET_BASIS, &(band as u64).to_le_bytes());
+                values
+                    .iter()
+                    .fold(hash, |hash, value| fnv1a(hash, &value.to_le_bytes()))
+            })
+    }
+}
+
+/// Compute the 64-bit SimHash of a sequence of `tokens` over shingles of `shingle_size`
+/// consecutive tokens.
+///
+/// Similar token sequences result in hashes differing in few bits, see [`simhash_distance`].
+/// Compared to MinHash signatures, SimHashes are much more compact but less accurate.
+///
+/// # Panics
+///
+/// Panics if `shingle_size` is `0`.
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::dedup::{simhash, simhash_distance};
+/// # use instant_clip_tokenizer::Tokenizer;
+/// let tokenizer = Tokenizer::new();
+/// let hash = |text| {
+///     let mut tokens = Vec::new();
+///     tokenizer.encode(text, &mut tokens);
+///     simhash(&tokens, 1)
+/// };
+/// let distance = simhash_distance(hash("Hi there"), hash("HI THERE"));
+/// assert_eq!(distance, 0);
+/// ```
+pub fn simhash(tokens: &[Token], shingle_size: usize) -> u64 {
+    assert!(shingle_size > 0, "shingle size must be at least 1");
+    let mut counts = [0i64; 64];
+    for shingle in shingle_hashes(tokens, shingle_size) {
+        let hash = mix(shingle);
+        for (bit, count) in counts.iter_mut().enumerate() {
+            *count += if hash & (1 << bit) != 0 { 1 } else { -1 };
+        }
+    }
+    counts
+        .iter()
+        .enumerate()
+        .filter(|(_, &count)| count > 0)
+        .fold(0, |hash, (bit, _)| hash | (1 << bit))
+}
+
+/// Returns the number of bits in which two SimHashes computed by [`simhash`] differ.
+pub fn simhash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// Returns the hashes of all shingles of `shingle_size` consecutive tokens.
+fn shingle_hashes(tokens: &[Token], shingle_size: usize) -> impl Iterator<Item = u64> + '_ {
+    let shingle_size = shingle_size.min(tokens.len()).max(1);
+    tokens.windows(shingle_size).map(|shingle| {
+        shingle.iter().fold(FNV_OFFSET_BASIS, |hash, token| {
+            fnv1a(hash, &token.to_u16().to_le_bytes())
+        })
+    })
+}
+
+/// The finalizer of the SplitMix64 generator, which mixes all bits of `x` well.
+fn mix(mut x: u64) -> u64 {
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        Tokenizer::new().encode(text, &mut tokens);
+        tokens
+    }
+
+    #[test]
+    fn jaccard() {
+        let a = encode("a photo of a cat");
+        let b = encode("a cat photo");
+        let c = encode("mountains at sunset");
+        assert_eq!(super::jaccard(&a, &b), 0.75);
+        assert_eq!(super::jaccard(&a, &a), 1.0);
+        assert_eq!(super::jaccard(&a, &c), 0.0);
+        assert_eq!(super::jaccard(&a, &[]), 0.0);
+        assert_eq!(super::jaccard(&[], &[]), 1.0);
+    }
+
+    #[test]
+    fn containment() {
+        let a = encode("a cat");
+        let b = encode("a photo of a cat");
+        assert_eq!(super::containment(&a, &b), 1.0);
+        assert_eq!(super::containment(&b, &a), 0.5);
+        assert_eq!(super::containment(&[], &a), 1.0);
+        assert_eq!(super::containment(&a, &[]), 0.0);
+    }
+
+    #[test]
+    fn longest_common_span() {
+        let tokens = [1, 2, 3, 4, 5, 1, 2].map(Token);
+        let span = |other: &[u16]| {
+            let other = other.iter().copied().map(Token).collect::<Vec<_>>();
+            super::longest_common_span(&tokens, &other)
+        };
+        assert_eq!(span(&[9, 3, 4, 5, 9]), Some((2..5, 1..4)));
+        assert_eq!(span(&[1, 2, 9, 1, 2, 3]), Some((0..3, 3..6)));
+        assert_eq!(span(&[9, 2]), Some((1..2, 1..2)));
+        assert_eq!(span(&[9, 8]), None);
+        assert_eq!(span(&[]), None);
+        assert_eq!(super::longest_common_span(&[], &tokens), None);
+    }
+
+    #[test]
+    fn overlaps() {
+        let query = encode("a cat");
+        let candidates = [
+            encode("a photo of a cat"),
+            encode("a cat"),
+            encode("mountains"),
+        ];
+        let overlaps = super::overlaps(&query, candidates.iter().map(Vec::as_slice));
+        assert_eq!(overlaps.len(), 3);
+        for (overlap, candidate) in overlaps.iter().zip(&candidates) {
+            assert_eq!(overlap.jaccard, super::jaccard(&query, candidate));
+            assert_eq!(overlap.containment, super::containment(&query, candidate));
+        }
+        assert_eq!(overlaps[0].longest_common_span, 2);
+        assert_eq!(overlaps[1].jaccard, 1.0);
+        assert_eq!(overlaps[2].containment, 0.0);
+        assert_eq!(overlaps[2].longest_common_span, 0);
+    }
+
+    #[test]
+    fn minhash() {
+        let hasher = MinHasher::new(256, 2);
+        let a = hasher.signature(&encode("a photo of a cat sitting on a red sofa"));
+        let b = hasher.signature(&encode("a photo of a cat sitting on a blue sofa"));
+        let c = hasher.signature(&encode("an aerial view of a harbor at night"));
+        assert_eq!(a.values().len(), 256);
+        assert_eq!(a.similarity(&a), 1.0);
+        // 9 shingles each, 7 of them shared, so the Jaccard similarity is 7 / 11
+        let similarity = a.similarity(&b);
+        assert!((0.5..0.8).contains(&similarity), "{similarity}");
+        assert!(a.similarity(&c) < 0.1);
+
+        // Signatures are deterministic
+        let tokens = encode("A photo of a cat sitting on a red sofa");
+        assert_eq!(a, MinHasher::new(256, 2).signature(&tokens));
+        assert_eq!(hasher.signature(&[]), hasher.signature(&[]));
+    }
+
+    #[test]
+    fn band_hashes() {
+        let hasher = MinHasher::new(20, 1);
+        let a = hasher.signature(&encode("a b c d e f g h"));
+        let b = hasher.signature(&encode("a b c d e f g h"));
+        let c = hasher.signature(&encode("i j k l m n o p"));
+        assert_eq!(a.band_hashes(4).count(), 5);
+        assert_eq!(a.band_hashes(3).count(), 6);
+        assert!(a.band_hashes(4).eq(b.band_hashes(4)));
+        let shared = a
+            .band_hashes(4)
+            .filter(|&hash| c.band_hashes(4).any(|other| other == hash));
+        assert_eq!(shared.count(), 0);
+    }
+
+    #[test]
+    fn simhash() {
+        let a = super::simhash(&encode("a photo of a cat sitting on a red sofa"), 1);
+        let b = super::simhash(&encode("a photo of a cat sitting on a blue sofa"), 1);
+        let c = super::simhash(&encode("an aerial view of a harbor at night"), 1);
+        assert!(simhash_distance(a, b) < simhash_distance(a, c));
+        assert_eq!(simhash_distance(a, a), 0);
+        assert_eq!(super::simhash(&[], 3), 0);
+    }
+
+    #[test]
+    fn short_sequences() {
+        let hasher = MinHasher::new(16, 5);
+        let tokens = encode("hi there");
+        assert_eq!(shingle_hashes(&tokens, 5).count(), 1);
+        assert_ne!(hasher.signature(&tokens), hasher.signature(&[]));
+    }
+}
@@ -0,0 +1,296 @@
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{Token, Tokenizer, Word, WordBoundary};
+
+impl Tokenizer {
+    /// Tokenize a single `text`, returning the tokens together with their offsets and masks.
+    ///
+    /// The tokens are the same as one row of [`tokenize_batch`]: the `<start_of_text>` marker, the
+    /// tokens of `text` (truncated to fit), the `<end_of_text>` marker, and zeros as padding up to
+    /// `context_length` tokens. Like [`encode`], this ignores the input limits configured using
+    /// [`TokenizerOptions`](crate::TokenizerOptions).
+    ///
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 3`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let encoding = tokenizer.tokenize("Hi there", 5);
+    /// assert_eq!(encoding.ids, [49406, 1883, 997, 49407, 0]);
+    /// assert_eq!(encoding.offsets, [0..0, 0..2, 3..8, 0..0, 0..0]);
+    /// assert_eq!(encoding.attention_mask, [1, 1, 1, 1, 0]);
+    /// assert_eq!(encoding.special_tokens_mask, [1, 0, 0, 1, 0]);
+    /// assert_eq!(encoding.truncated_tokens, 0);
+    /// ```
+    pub fn tokenize(&self, text: &str, context_length: usize) -> Encoding {
+        assert!(context_length >= 3, "context length must be at least 3");
+        let mut encoded = vec![(self.start_of_text(), Range::default())];
+        self.encode_with_offsets(text, &mut encoded);
+        let (mut tokens, mut offsets): (Vec<_>, Vec<_>) = encoded.into_iter().unzip();
+        let truncated_tokens = tokens.len().saturating_sub(context_length - 1);
+        tokens.truncate(context_length - 1);
+        offsets.truncate(context_length - 1);
+        tokens.push(self.end_of_text());
+        offsets.push(0..0);
+
+        let len = tokens.len();
+        let is_special =
+            |token: &Token| *token == self.start_of_text() || *token == self.end_of_text();
+        let mut encoding = Encoding {
+            ids: tokens.iter().map(|token| token.to_u16()).collect(),
+            offsets,
+            attention_mask: vec![1; len],
+            special_tokens_mask: tokens
+                .iter()
+                .map(|token| u8::from(is_special(token)))
+                .collect(),
+            truncated_tokens,
+        };
+        encoding.ids.resize(context_length, 0);
+        encoding.offsets.resize(context_length, 0..0);
+        encoding.attention_mask.resize(context_length, 0);
+        encoding.special_tokens_mask.resize(context_length, 0);
+        encoding
+    }
+
+    /// Tokenize each of the `texts` like [`tokenize`](Tokenizer::tokenize).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 3`.
+    pub fn tokenize_batch_encodings<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+        context_length: usize,
+    ) -> Vec<Encoding> {
+        texts
+            .into_iter()
+            .map(|text| self.tokenize(text, context_length))
+            .collect()
+    }
+
+    /// Encode `text` like [`encode`], appending every token together with the byte range of
+    /// `text` it was produced from to `out`.
+    ///
+    /// This allows mapping tokens back to the input, e.g. for highlighting the parts of a caption
+    /// that don't fit into the context length. Tokens representing part of a multi-byte character
+    /// get the range of the whole character.
+    ///
+    /// The offsets always refer to `text` as passed in, not to the lowercased copy that is
+    /// actually encoded. Lowercasing changes the length of some characters (e.g. `"İ"` becomes
+    /// `"i̇"`, and the Kelvin sign `"K"` becomes `"k"`), so offsets into the lowercased copy can't
+    /// be used to index `text`. Tokens produced from part of such a character get the range of
+    /// the whole original character.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let text = "Hi there, acetylsalicylic!!";
+    /// let mut encoded = Vec::new();
+    /// tokenizer.encode_with_offsets(text, &mut encoded);
+    /// let pieces = encoded.into_iter().map(|(_, range)| &text[range]).collect::<Vec<_>>();
+    /// assert_eq!(pieces, ["Hi", "there", ",", "acet", "yl", "sal", "icy", "lic", "!!"]);
+    /// ```
+    pub fn encode_with_offsets(&self, text: &str, out: &mut impl Extend<(Token, Range<usize>)>) {
+        // The range of the original character for every byte of the lowercased text
+        let (lowercased, char_ranges) = match self.lowercase {
+            true => {
+                let lowercased = text.to_lowercase();
+                let mut char_ranges = Vec::with_capacity(lowercased.len());
+                for (start, c) in text.char_indices() {
+                    // `str::to_lowercase` only differs from `char::to_lowercase` for the final
+                    // sigma, which has the same length as the regular lowercase sigma
+                    let len = c.to_lowercase().map(char::len_utf8).sum::<usize>();
+                    let range = start..start + c.len_utf8();
+                    char_ranges.extend((0..len).map(|_| range.clone()));
+                }
+                (lowercased, char_ranges)
+            }
+            false => {
+                let mut char_ranges = Vec::with_capacity(text.len());
+                for (start, c) in text.char_indices() {
+                    let range = start..start + c.len_utf8();
+                    char_ranges.extend((0..c.len_utf8()).map(|_| range.clone()));
+                }
+                (text.to_owned(), char_ranges)
+            }
+        };
+        let original_range = |range: Range<usize>| match range.is_empty() {
+            true => {
+                let start = char_ranges.get(range.start).map_or(text.len(), |r| r.start);
+                start..start
+            }
+            false => char_ranges[range.start].start..char_ranges[range.end - 1].end,
+        };
+
+        let space_prefix = self.vocabulary.word_boundary == WordBoundary::SpacePrefix;
+        let mut word_tokens = Vec::new();
+        for (word, range) in self.split_words(&lowercased, true) {
+            let (text_word, follows_whitespace) = match word {
+                Word::Marker(token) => {
+                    out.extend([(token, original_range(range))]);
+                    continue;
+                }
+                Word::Text {
+                    word,
+                    follows_whitespace,
+                } => (word, follows_whitespace),
+            };
+
+            self.encode_word(word, &mut word_tokens);
+            // Positions within the word, skipping the space prepended to words following
+            // whitespace and the `</w>` suffix of the last token, which aren't part of the text
+            let mut pos = match space_prefix && follows_whitespace {
+                true => -1,
+                false => 0,
+            };
+            for &token in &word_tokens {
+                let len = self.token_bytes(token).map_or(0, <[u8]>::len) as isize;
+                let clamp =
+                    |pos: isize| range.start + pos.clamp(0, text_word.len() as isize) as usize;
+                out.extend([(token, original_range(clamp(pos)..clamp(pos + len)))]);
+                pos += len;
+            }
+        }
+    }
+}
+
+/// The tokens of a single text together with information about them, as returned by
+/// [`Tokenizer::tokenize`].
+///
+/// All fields have one entry per token, including the special marker tokens and padding. With
+/// the `serde` crate feature, `Encoding` implements `Serialize`, e.g. for writing results to JSON
+/// or MessagePack directly.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct Encoding {
+    /// The token ids, starting with `<start_of_text>` and padded with zeros after
+    /// `<end_of_text>`.
+    pub ids: Vec<u16>,
+    /// The byte range of the input text each token was produced from, see
+    /// [`Tokenizer::encode_with_offsets`]. The special marker tokens added around the text and
+    /// padding have empty ranges at the start of the text.
+    pub offsets: Vec<Range<usize>>,
+    /// `1` for tokens and `0` for padding.
+    pub attention_mask: Vec<u8>,
+    /// `1` for the `<start_of_text>` and `<end_of_text>` marker tokens, and `0` otherwise.
+    pub special_tokens_mask: Vec<u8>,
+    /// The number of tokens of the text that were discarded because they didn't fit into the
+    /// context length.
+    pub truncated_tokens: usize,
+}
+
+impl Encoding {
+    /// Returns the number of tokens, excluding padding.
+    pub fn len(&self) -> usize {
+        self.attention_mask
+            .iter()
+            .filter(|&&mask| mask == 1)
+            .count()
+    }
+
+    /// Returns `true` if there are no tokens, which is never the case for encodings returned by
+    /// [`Tokenizer::tokenize`].
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether tokens of the text were discarded because they didn't fit into the context
+    /// length.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated_tokens > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::TokenizerOptions;
+
+    fn offsets(tokenizer: &Tokenizer, text: &str) -> Vec<String> {
+        let mut encoded = Vec::new();
+        tokenizer.encode_with_offsets(text, &mut encoded);
+        let (tokens, offsets): (Vec<_>, Vec<_>) = encoded.into_iter().unzip();
+        let mut expected = Vec::new();
+        tokenizer.encode(text, &mut expected);
+        assert_eq!(tokens, expected, "{text}");
+        offsets
+            .into_iter()
+            .map(|range| text[range].to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn tokenize() {
+        let tokenizer = Tokenizer::new();
+        let encoding = tokenizer.tokenize("a photo of a motorcycle", 5);
+        assert_eq!(encoding.ids, [49406, 320, 1125, 539, 49407]);
+        assert_eq!(encoding.offsets, [0..0, 0..1, 2..7, 8..10, 0..0]);
+        assert_eq!(encoding.len(), 5);
+        assert_eq!(encoding.truncated_tokens, 2);
+        assert!(encoding.is_truncated());
+
+        let encodings = tokenizer.tokenize_batch_encodings(["Hi", ""], 4);
+        assert_eq!(encodings[0].ids, [49406, 1883, 49407, 0]);
+        assert_eq!(encodings[1].ids, [49406, 49407, 0, 0]);
+        assert_eq!(encodings[1].len(), 2);
+        assert!(!encodings[1].is_truncated());
+    }
+
+    #[test]
+    fn encode_with_offsets() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            offsets(&tokenizer, "Hi there, acetylsalicylic!!"),
+            ["Hi", "there", ",", "acet", "yl", "sal", "icy", "lic", "!!"]
+        );
+        // Characters changing their length when lowercased
+        assert_eq!(offsets(&tokenizer, "İstanbul"), ["İ", "İ", "stanbul"]);
+        assert_eq!(offsets(&tokenizer, "ΟΔΥΣΣΕΥΣ")[12..], ["Υ", "Υ", "Σ", "Σ"]);
+        assert_eq!(offsets(&tokenizer, "\u{212A}elvin"), ["\u{212A}elvin"]);
+        assert_eq!(offsets(&tokenizer, "STRAẞE"), ["STRA", "ẞ", "ẞ", "E"]);
+        assert_eq!(offsets(&tokenizer, "ȺB"), ["Ⱥ", "Ⱥ", "Ⱥ", "B"]);
+        // Tokens representing parts of a character
+        assert_eq!(offsets(&tokenizer, "🦀"), ["🦀", "🦀"]);
+        assert_eq!(
+            offsets(&tokenizer, "<start_of_text>Hi<end_of_text>"),
+            ["<start_of_text>", "Hi", "<end_of_text>"]
+        );
+
+        let cased = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        assert_eq!(offsets(&cased, "Hello hello"), ["H", "ello", "hello"]);
+
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        assert_eq!(offsets(&tokenizer, "the the"), ["t", "he", "the"]);
+    }
+
+    #[cfg(all(feature = "serde", feature = "huggingface"))]
+    #[test]
+    fn serialize() {
+        let encoding = Tokenizer::new().tokenize("Hi", 4);
+        assert_eq!(
+            serde_json::to_string(&encoding).unwrap(),
+            r#"{"ids":[49406,1883,49407,0],"offsets":[{"start":0,"end":0},{"start":0,"end":2},{"start":0,"end":0},{"start":0,"end":0}],"attention_mask":[1,1,1,0],"special_tokens_mask":[1,0,1,0],"truncated_tokens":0}"#
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::ops::ControlFlow;
+
+use crate::{Token, Tokenizer, Word, WordBoundary};
+
+impl Tokenizer {
+    /// Explain how a single `word` is encoded, listing every merge step that leads to its final
+    /// segmentation.
+    ///
+    /// `word` is encoded as one word as produced by [`pre_tokenize`], i.e. it is lowercased
+    /// (unless disabled using [`TokenizerOptions::lowercase`]) but not split into several words.
+    /// For vocabularies using [`WordBoundary::SpacePrefix`], a leading space marks the word as
+    /// following whitespace. The tokens of the explanation are exactly those produced by
+    /// [`encode`] for the word.
+    ///
+    /// [`pre_tokenize`]: Tokenizer::pre_tokenize
+    /// [`encode`]: Tokenizer::encode
+    /// [`TokenizerOptions::lowercase`]: crate::TokenizerOptions::lowercase
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let explanation = tokenizer.explain("Hi");
+    /// assert_eq!(explanation.steps.len(), 1);
+    /// assert_eq!(explanation.steps[0].result.to_u16(), 1883);
+    /// println!("{explanation}");
+    /// // "hi"
+    /// //   initial: "h" "i</w>"
+    /// //   1. "h" + "i</w>" -> "hi</w>" (rank 1371, token 1883): "hi</w>"
+    /// ```
+    pub fn explain(&self, word: &str) -> Explanation {
+        let word = match self.lowercase {
+            true => word.to_lowercase(),
+            false => word.to_owned(),
+        };
+        let follows_whitespace =
+            self.vocabulary.word_boundary == WordBoundary::SpacePrefix && word.starts_with(' ');
+        self.explain_word(word, follows_whitespace)
+    }
+
+    /// Explain how every word of `text` is encoded, like [`explain`] does for a single word.
+    ///
+    /// `text` is lowercased and split into words exactly like [`encode`] does, so the tokens of
+    /// the explanations together are the tokens produced by [`encode`] for `text`. This makes it
+    /// easy to find the word and merge step where the output diverges from a different
+    /// implementation. The `<start_of_text>` and `<end_of_text>` markers written out in `text` are
+    /// explained as a single token without any merge steps.
+    ///
+    /// [`explain`]: Tokenizer::explain
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let explanations = tokenizer.explain_text("Hi there!");
+    /// let words = explanations.iter().map(|e| e.word.as_str()).collect::<Vec<_>>();
+    /// assert_eq!(words, ["hi", "there", "!"]);
+    /// for explanation in &explanations {
+    ///     println!("{explanation}");
+    /// }
+    /// ```
+    pub fn explain_text(&self, text: &str) -> Vec<Explanation> {
+        let text = match self.lowercase {
+            true => Cow::Owned(text.to_lowercase()),
+            false => Cow::Borrowed(text),
+        };
+        let space_prefix = self.vocabulary.word_boundary == WordBoundary::SpacePrefix;
+        self.split_words(&text, true)
+            .map(|(word, range)| match word {
+                Word::Marker(token) => {
+                    let piece = self.piece(token);
+                    Explanation {
+                        word: text[range].to_owned(),
+                        initial: vec![token],
+                        steps: Vec::new(),
+                        tokens: vec![token],
+                        pieces: vec![piece.clone()],
+                        known_pieces: BTreeMap::from([(token, piece)]),
+                    }
+                }
+                Word::Text {
+                    word,
+                    follows_whitespace,
+                } => {
+                    // Like for `explain`, words following whitespace are written with a leading
+                    // space
+                    let follows_whitespace = space_prefix && follows_whitespace;
+                    let word = match follows_whitespace {
+                        true => format!(" {word}"),
+                        false => word.to_owned(),
+                    };
+                    self.explain_word(word, follows_whitespace)
+                }
+            })
+            .collect()
+    }
+
+    /// Explain how the already lowercased `word` is encoded. If `follows_whitespace` is true,
+    /// `word` starts with the space marking it as following whitespace.
+    fn explain_word(&self, word: String, follows_whitespace: bool) -> Explanation {
+        let stripped = match follows_whitespace {
+            true => &word[1..],
+            false => &word[..],
+        };
+
+        let mut tokens = Vec::new();
+        self.initial_word_tokens(stripped.as_bytes(), follows_whitespace, &mut tokens);
+        let initial = tokens.clone();
+        let mut steps = Vec::new();
+        let _ =
+            self.apply_merge_rules_with(&mut tokens, |first, second, result, merged, tokens| {
+                steps.push(MergeStep {
+                    first,
+                    second,
+                    rank: self.merge_rank(first, second).unwrap_or_default(),
+                    result,
+                    occurrences: merged,
+                    tokens: tokens.to_vec(),
+                });
+                ControlFlow::Continue(())
+            });
+
+        let known_pieces = initial
+            .iter()
+            .chain(steps.iter().map(|step| &step.result))
+            .map(|&token| (token, self.piece(token)))
+            .collect::<BTreeMap<_, _>>();
+        Explanation {
+            pieces: tokens
+                .iter()
+                .map(|&token| known_pieces[&token].clone())
+                .collect(),
+            word,
+            initial,
+            steps,
+            tokens,
+            known_pieces,
+        }
+    }
+}
+
+/// The result of [`Tokenizer::explain`].
+///
+/// The `Display` implementation renders every merge step on its own line, showing the tokens as
+/// text.
+#[derive(Clone, Debug)]
+pub struct Explanation {
+    /// The word after lowercasing.
+    pub word: String,
+    /// The tokens for the individual bytes of the word, before any merge rule is applied.
+    pub initial: Vec<Token>,
+    /// The merge steps, in the order they were applied.
+    pub steps: Vec<MergeStep>,
+    /// The final tokens of the word.
+    pub tokens: Vec<Token>,
+    /// The textual representation of each token in `tokens`.
+    pub pieces: Vec<String>,
+    /// The textual representation of every token occurring in the explanation, ordered so that
+    /// the `Debug` output is the same in every run.
+    known_pieces: BTreeMap<Token, String>,
+}
+
+impl Explanation {
+    /// Returns the textual representation of a `token` occurring in this explanation, e.g. the
+    /// tokens of a [`MergeStep`], or an empty string for any other token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let explanation = Tokenizer::new().explain("Hi");
+    /// let step = &explanation.steps[0];
+    /// assert_eq!(explanation.piece_of(step.first), "h");
+    /// assert_eq!(explanation.piece_of(step.result), "hi</w>");
+    /// ```
+    pub fn piece_of(&self, token: Token) -> &str {
+        self.known_pieces.get(&token).map_or("", String::as_str)
+    }
+}
+
+/// A single step of [`Explanation`], merging all occurrences of a pair of adjacent tokens.
+#[derive(Clone, Debug)]
+pub struct MergeStep {
+    /// The first token of the merged pair.
+    pub first: Token,
+    /// The second token of the merged pair.
+    pub second: Token,
+    /// The rank of the applied merge rule, see [`Tokenizer::merge_rank`].
+    pub rank: u32,
+    /// The token the pair was merged into.
+    pub result: Token,
+    /// The number of times the pair occurred in the word, and was therefore merged in this step.
+    pub occurrences: usize,
+    /// The tokens of the word after this step.
+    pub tokens: Vec<Token>,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pieces = |tokens: &[Token]| {
+            tokens
+                .iter()
+                .map(|token| format!("{:?}", self.piece_of(*token)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        write!(f, "{:?}\n  initial: {}", self.word, pieces(&self.initial))?;
+        for (i, step) in self.steps.iter().enumerate() {
+            write!(
+                f,
+                "\n  {}. {:?} + {:?} -> {:?} (rank {}, token {}",
+                i + 1,
+                self.piece_of(step.first),
+                self.piece_of(step.second),
+                self.piece_of(step.result),
+                step.rank,
+                step.result.to_u16()
+            )?;
+            if step.occurrences > 1 {
+                write!(f, ", {} times", step.occurrences)?;
+            }
+            write!(f, "): {}", pieces(&step.tokens))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explain() {
+        let tokenizer = Tokenizer::new();
+        for word in ["Hi", "motorcycle", "aaaaaaaa", "🦀", "!!!", ""] {
+            let explanation = tokenizer.explain(word);
+            let mut expected = Vec::new();
+            tokenizer.encode(word, &mut expected);
+            assert_eq!(explanation.tokens, expected, "{word}");
+            assert_eq!(explanation.initial.len(), word.len());
+            assert_eq!(
+                explanation
+                    .steps
+                    .last()
+                    .map_or(&explanation.initial, |s| &s.tokens),
+                &explanation.tokens
+            );
+
+            // Each step removes one token per merged occurrence
+            let mut len = explanation.initial.len();
+            for step in &explanation.steps {
+                len -= step.occurrences;
+                assert_eq!(step.tokens.len(), len);
+                assert_eq!(
+                    tokenizer.merge_rank(step.first, step.second),
+                    Some(step.rank)
+                );
+            }
+        }
+
+        let explanation = tokenizer.explain("aaaaaaaa");
+        assert_eq!(explanation.steps[0].occurrences, 3);
+        assert_eq!(explanation.pieces.concat(), "aaaaaaaa</w>");
+    }
+
+    #[test]
+    fn explain_text() {
+        let tokenizer = Tokenizer::new();
+        let text = "<start_of_text>A person riding a motorcycle 🦀!!";
+        let explanations = tokenizer.explain_text(text);
+        let tokens = explanations
+            .iter()
+            .flat_map(|explanation| explanation.tokens.iter().copied())
+            .collect::<Vec<_>>();
+        let mut expected = Vec::new();
+        tokenizer.encode(text, &mut expected);
+        assert_eq!(tokens, expected);
+        assert_eq!(explanations.len(), 7);
+        assert_eq!(explanations[0].pieces, ["<start_of_text>"]);
+        assert!(explanations[0].steps.is_empty());
+        assert_eq!(explanations[5].word, "motorcycle");
+        assert_eq!(
+            explanations[5].to_string(),
+            tokenizer.explain("Motorcycle").to_string()
+        );
+        assert!(tokenizer.explain_text("").is_empty());
+    }
+
+    #[test]
+    fn display() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(
+            tokenizer.explain("Hi").to_string(),
+            "\"hi\"\n  initial: \"h\" \"i</w>\"\n  1. \"h\" + \"i</w>\" -> \"hi</w>\" (rank 1371, token 1883): \"hi</w>\""
+        );
+    }
+
+    #[test]
+    fn space_prefix() {
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        let explanation = tokenizer.explain(" the");
+        assert_eq!(explanation.pieces, [" the"]);
+        assert_eq!(explanation.steps.len(), 3);
+        assert_eq!(tokenizer.explain("the").pieces, ["t", "he"]);
+
+        let explanations = tokenizer.explain_text("the the");
+        assert_eq!(explanations[0].pieces, ["t", "he"]);
+        assert_eq!(explanations[1].word, " the");
+        assert_eq!(explanations[1].pieces, [" the"]);
+    }
+}
@@ -0,0 +1,391 @@
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::{self, BufWriter, Write};
+
+use crate::{Tokenizer, WordBoundary};
+
+impl Tokenizer {
+    /// Write the vocabulary of this `Tokenizer` as a table to `writer`, e.g. for inspecting it in
+    /// a spreadsheet or joining it with the results of analyzing token embeddings.
+    ///
+    /// The table starts with a header row, followed by one row per entry of [`vocab_iter`], in
+    /// order of the token ids. It has the following columns:
+    ///
+    /// * `id` - The token id.
+    /// * `token_string` - The token as written in HuggingFace `vocab.json` files: bytes that
+    ///   aren't printable are mapped to printable characters like in byte-level BPE vocabulary
+    ///   data, and end-of-word tokens have a `</w>` suffix.
+    /// * `byte_escape` - The bytes the token represents, without the `</w>` suffix, escaped using
+    ///   [`std::ascii::escape_default`].
+    /// * `is_special` - `true` for the `<start_of_text>` and `<end_of_text>` marker tokens.
+    /// * `is_end_of_word` - `true` for end-of-word tokens. Vocabularies using
+    ///   [`WordBoundary::SpacePrefix`] don't have end-of-word tokens.
+    ///
+    /// [`vocab_iter`]: Tokenizer::vocab_iter
+    ///
+    /// # Errors
+    ///
+    /// If writing to `writer` fails, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, VocabExportFormat};
+    /// let tokenizer = Tokenizer::new();
+    /// let mut table = Vec::new();
+    /// tokenizer.export_vocab(&mut table, VocabExportFormat::Tsv).unwrap();
+    /// let table = String::from_utf8(table).unwrap();
+    /// let mut rows = table.lines();
+    /// assert_eq!(rows.next(), Some("id\ttoken_string\tbyte_escape\tis_special\tis_end_of_word"));
+    /// assert_eq!(rows.nth(1883), Some("1883\thi</w>\thi\tfalse\ttrue"));
+    /// ```
+    pub fn export_vocab(&self, writer: impl Write, format: VocabExportFormat) -> io::Result<()> {
+        let chars = byte_to_char();
+        let separator = format.separator();
+        let mut writer = BufWriter::new(writer);
+        writeln!(
+            writer,
+            "id{separator}token_string{separator}byte_escape{separator}is_special{separator}is_end_of_word"
+        )?;
+
+        for (token, bytes) in self.vocab_iter() {
+            let is_special = token == self.start_of_text() || token == self.end_of_text();
+            let (bytes, is_end_of_word) = match bytes.strip_suffix(b"</w>") {
+                Some(bytes) if self.vocabulary.word_boundary == WordBoundary::EndOfWordSuffix => {
+                    (bytes, true)
+                }
+                _ => (bytes, false),
+            };
+            let mut token_string = to_chars(bytes, &chars);
+            if is_end_of_word {
+                token_string.push_str("</w>");
+            }
+            let byte_escape = bytes
+                .iter()
+                .flat_map(|&byte| std::ascii::escape_default(byte))
+                .map(char::from)
+                .collect::<String>();
+
+            writeln!(
+                writer,
+                "{}{separator}{}{separator}{}{separator}{is_special}{separator}{is_end_of_word}",
+                token.to_u16(),
+                format.field(&token_string),
+                format.field(&byte_escape),
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Returns the tables defining how this `Tokenizer` encodes words, e.g. for generating an
+    /// implementation in another language or for the GPU from them.
+    ///
+    /// See [`RawTables`] for how the tables are used to encode a word. Splitting texts into words
+    /// (see [`pre_tokenize`]) and lowercasing them is not covered by the tables.
+    ///
+    /// [`pre_tokenize`]: Tokenizer::pre_tokenize
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tables = Tokenizer::new().raw_tables();
+    /// assert_eq!(tables.byte_to_token[usize::from(b'h')], 71);
+    /// assert_eq!(tables.merges[0], [72, 77, 512]);
+    /// assert_eq!(tables.end_of_text, 49407);
+    /// std::fs::write("clip-tables.json", tables.to_json()).unwrap();
+    /// # std::fs::remove_file("clip-tables.json").unwrap();
+    /// ```
+    pub fn raw_tables(&self) -> RawTables {
+        let vocabulary = &self.vocabulary;
+        let mut merges = vocabulary
+            .merge_rules
+            .iter()
+            .map(|(&(first, second), &result)| [first.0, second.0, result.0])
+            .collect::<Vec<_>>();
+        merges.sort_unstable_by_key(|&[first, second, _]| {
+            self.merge_rank(crate::Token(first), crate::Token(second))
+        });
+
+        RawTables {
+            byte_to_token: vocabulary
+                .byte_to_token
+                .iter()
+                .map(|token| token.0)
+                .collect(),
+            merges,
+            start_of_text: vocabulary.start_of_text.0,
+            end_of_text: vocabulary.end_of_text.0,
+            word_boundary: vocabulary.word_boundary,
+            vocab_size: vocabulary.vocab_size,
+        }
+    }
+}
+
+/// The tables defining how a [`Tokenizer`] encodes words, as returned by
+/// [`Tokenizer::raw_tables`].
+///
+/// A single word is encoded as follows:
+///
+/// 1. If the vocabulary uses [`WordBoundary::SpacePrefix`] and the word follows whitespace, a
+///    space is prepended to the word.
+/// 2. Every byte of the word is mapped to a token using `byte_to_token`. If the vocabulary uses
+///    [`WordBoundary::EndOfWordSuffix`], `256` is added to the token of the last byte to turn it
+///    into an end-of-word token.
+/// 3. As long as any pair of adjacent tokens occurs in `merges`, the pair occurring first in
+///    `merges` is selected, and all of its occurrences are replaced by the merged token from left
+///    to right.
+///
+/// The special marker strings `<start_of_text>` and `<end_of_text>` are encoded as the
+/// `start_of_text` and `end_of_text` tokens instead.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RawTables {
+    /// The token of each of the 256 byte values.
+    pub byte_to_token: Vec<u16>,
+    /// The merge rules as `[first, second, result]` token ids, ordered by rank, i.e. in the order
+    /// of priority.
+    pub merges: Vec<[u16; 3]>,
+    /// The special `<start_of_text>` marker token.
+    pub start_of_text: u16,
+    /// The special `<end_of_text>` marker token.
+    pub end_of_text: u16,
+    /// How word boundaries are represented in the vocabulary.
+    pub word_boundary: WordBoundary,
+    /// The number of tokens in the vocabulary. All token ids are below this number.
+    pub vocab_size: u16,
+}
+
+impl RawTables {
+    /// Serialize the tables as a JSON object.
+    ///
+    /// The object has the fields `byte_to_token` (an array of 256 token ids), `merges` (an array
+    /// of `[first, second, result]` arrays), `start_of_text`, `end_of_text`, `vocab_size` and
+    /// `word_boundary`, which is either `"end_of_word_suffix"` or `"space_prefix"`.
+    pub fn to_json(&self) -> String {
+        let word_boundary = match self.word_boundary {
+            WordBoundary::EndOfWordSuffix => "end_of_word_suffix",
+            WordBoundary::SpacePrefix => "space_prefix",
+        };
+        let byte_to_token = self
+            .byte_to_token
+            .iter()
+            .map(u16::to_string)
+            .collect::<Vec<_>>();
+
+        let mut json = format!(
+            "{{\"byte_to_token\":[{}],\"merges\":[",
+            byte_to_token.join(",")
+        );
+        for (i, [first, second, result]) in self.merges.iter().enumerate() {
+            let separator = if i == 0 { "" } else { "," };
+            let _ = write!(json, "{separator}[{first},{second},{result}]");
+        }
+        let _ = write!(
+            json,
+            "],\"start_of_text\":{},\"end_of_text\":{},\"vocab_size\":{},\"word_boundary\":\"{word_boundary}\"}}",
+            self.start_of_text, self.end_of_text, self.vocab_size
+        );
+        json
+    }
+}
+
+/// The format of the table written by [`Tokenizer::export_vocab`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum VocabExportFormat {
+    /// Comma-separated values. Fields containing commas or quotes are quoted as described in
+    /// [RFC 4180](https://www.rfc-editor.org/rfc/rfc4180).
+    Csv,
+    /// Tab-separated values. No field contains tabs or line breaks, so fields are never quoted.
+    Tsv,
+}
+
+impl VocabExportFormat {
+    fn separator(self) -> char {
+        match self {
+            VocabExportFormat::Csv => ',',
+            VocabExportFormat::Tsv => '\t',
+        }
+    }
+
+    fn field(self, value: &str) -> Cow<'_, str> {
+        match self {
+            VocabExportFormat::Csv if value.contains([',', '"']) => {
+                format!("\"{}\"", value.replace('"', "\"\"")).into()
+            }
+            _ => value.into(),
+        }
+    }
+}
+
+/// Returns the mapping of bytes to printable characters used by byte-level BPE vocabularies.
+pub(crate) fn byte_to_char() -> [char; 256] {
+    let mut chars = ['\0'; 256];
+    let mut next = 256;
+    for (byte, ch) in chars.iter_mut().enumerate() {
+        *ch = match byte as u8 {
+            b'!'..=b'~' | b'\xA1'..=b'\xAC' | b'\xAE'..=b'\xFF' => char::from(byte as u8),
+            _ => {
+                let ch = char::from_u32(next).unwrap();
+                next += 1;
+                ch
+            }
+        };
+    }
+    chars
+}
+
+pub(crate) fn to_chars(bytes: &[u8], chars: &[char; 256]) -> String {
+    bytes.iter().map(|&b| chars[usize::from(b)]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn export(tokenizer: &Tokenizer, format: VocabExportFormat) -> Vec<String> {
+        let mut table = Vec::new();
+        tokenizer.export_vocab(&mut table, format).unwrap();
+        String::from_utf8(table)
+            .unwrap()
+            .lines()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    #[test]
+    fn csv() {
+        let tokenizer = Tokenizer::new();
+        let rows = export(&tokenizer, VocabExportFormat::Csv);
+        assert_eq!(rows.len(), 1 + 49408);
+        assert_eq!(
+            rows[0],
+            "id,token_string,byte_escape,is_special,is_end_of_word"
+        );
+        assert_eq!(rows[1 + 11], r#"11,",",",",false,false"#);
+        assert_eq!(rows[1 + 1], r#"1,"""","\""",false,false"#);
+        // The tab byte, which is mapped to a printable character
+        assert_eq!(rows[1 + 453], r"453,ĉ</w>,\t,false,true");
+        assert_eq!(
+            rows[1 + 49406],
+            "49406,<start_of_text>,<start_of_text>,true,false"
+        );
+        assert_eq!(
+            rows[1 + 49407],
+            "49407,<end_of_text>,<end_of_text>,true,false"
+        );
+    }
+
+    #[test]
+    fn tsv() {
+        let tokenizer = Tokenizer::new();
+        let rows = export(&tokenizer, VocabExportFormat::Tsv);
+        assert_eq!(rows.len(), 1 + 49408);
+        assert!(rows.iter().all(|row| row.split('\t').count() == 5));
+        assert_eq!(rows[1 + 11], "11\t,\t,\tfalse\tfalse");
+        assert_eq!(rows[1 + 1], "1\t\"\t\\\"\tfalse\tfalse");
+    }
+
+    /// Encodes a single word following the steps described for `RawTables`.
+    fn encode_word(tables: &RawTables, word: &str, follows_whitespace: bool) -> Vec<u16> {
+        let mut bytes = Vec::new();
+        if follows_whitespace && tables.word_boundary == WordBoundary::SpacePrefix {
+            bytes.push(b' ');
+        }
+        bytes.extend_from_slice(word.as_bytes());
+        let mut tokens = bytes
+            .iter()
+            .map(|&b| tables.byte_to_token[usize::from(b)])
+            .collect::<Vec<_>>();
+        if tables.word_boundary == WordBoundary::EndOfWordSuffix {
+            *tokens.last_mut().unwrap() += 256;
+        }
+
+        while let Some(&[first, second, result]) = tables
+            .merges
+            .iter()
+            .find(|[first, second, _]| tokens.windows(2).any(|pair| pair == [*first, *second]))
+        {
+            let mut merged = Vec::with_capacity(tokens.len());
+            let mut i = 0;
+            while i < tokens.len() {
+                if tokens[i] == first && tokens.get(i + 1) == Some(&second) {
+                    merged.push(result);
+                    i += 2;
+                } else {
+                    merged.push(tokens[i]);
+                    i += 1;
+                }
+            }
+            tokens = merged;
+        }
+        tokens
+    }
+
+    #[test]
+    fn raw_tables() {
+        let tokenizer = Tokenizer::new();
+        let tables = tokenizer.raw_tables();
+        assert_eq!(tables.byte_to_token.len(), 256);
+        assert_eq!(tables.merges.len(), 49408 - 512 - 2);
+        assert_eq!((tables.start_of_text, tables.end_of_text), (49406, 49407));
+        for (rank, &[first, second, result]) in tables.merges.iter().enumerate() {
+            let (first, second) = (crate::Token(first), crate::Token(second));
+            assert_eq!(tokenizer.merge_rank(first, second), Some(rank as u32));
+            assert_eq!(usize::from(result), 512 + rank);
+        }
+
+        for word in [
+            "hi",
+            "motorcycle",
+            "aaaaaaaa",
+            "🦀",
+            "!!!",
+            "acetylsalicylic",
+        ] {
+            let mut expected = Vec::new();
+            tokenizer.encode(word, &mut expected);
+            let expected = expected.iter().map(|t| t.to_u16()).collect::<Vec<_>>();
+            assert_eq!(encode_word(&tables, word, false), expected, "{word}");
+        }
+
+        let json = tables.to_json();
+        assert!(json.starts_with("{\"byte_to_token\":[188,189,"));
+        assert!(json.contains("\"merges\":[[72,77,512],[83,71,513],"));
+        assert!(json.ends_with(
+            "\"start_of_text\":49406,\"end_of_text\":49407,\"vocab_size\":49408,\"word_boundary\":\"end_of_word_suffix\"}"
+        ));
+    }
+
+    #[cfg(feature = "huggingface")]
+    #[test]
+    fn raw_tables_json() {
+        let tables = Tokenizer::new().raw_tables();
+        let json = serde_json::from_str::<serde_json::Value>(&tables.to_json()).unwrap();
+        assert_eq!(json["byte_to_token"].as_array().unwrap().len(), 256);
+        assert_eq!(json["merges"][0], serde_json::json!([72, 77, 512]));
+        assert_eq!(
+            json["merges"].as_array().unwrap().len(),
+            tables.merges.len()
+        );
+    }
+
+    #[test]
+    fn space_prefix() {
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        let rows = export(&tokenizer, VocabExportFormat::Tsv);
+        assert!(rows[1..].iter().all(|row| row.ends_with("\tfalse")));
+        assert!(rows.contains(&"258\tĠthe\t the\tfalse\tfalse".to_owned()));
+
+        let tables = tokenizer.raw_tables();
+        assert_eq!(tables.word_boundary, WordBoundary::SpacePrefix);
+        assert_eq!(
+            tables.merges,
+            [[220, 83, 256], [71, 68, 257], [256, 257, 258]]
+        );
+        assert_eq!(encode_word(&tables, "the", true), [258]);
+        assert_eq!(encode_word(&tables, "the", false), [83, 257]);
+    }
+}
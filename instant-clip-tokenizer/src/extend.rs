@@ -0,0 +1,325 @@
+use std::borrow::Cow;
+use std::io;
+use std::sync::Arc;
+
+use once_cell::sync::OnceCell;
+
+use crate::text_cache::TextCache;
+use crate::{HashMap, Token, Tokenizer, Vocabulary, Word, WordBoundary};
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` whose vocabulary is extended with merge rules learned from a
+    /// domain-specific corpus of `texts`, e.g. medical terms or product names.
+    ///
+    /// The words of `texts` are encoded using the existing vocabulary, and then the most frequent
+    /// pair of adjacent tokens is repeatedly merged into a new token, like when learning the
+    /// original vocabulary. The new tokens get ids following the existing vocabulary, including
+    /// the special marker tokens, so all existing token ids (and thus e.g. the rows of a model's
+    /// token embedding table) stay valid. Texts are encoded exactly like before, except for words
+    /// to which one of the new merge rules applies.
+    ///
+    /// The resulting `Tokenizer` uses the same options as this one. Use [`save_binary`] to store
+    /// the extended vocabulary, as the other vocabulary formats can't represent tokens following
+    /// the special marker tokens.
+    ///
+    /// [`save_binary`]: Tokenizer::save_binary
+    ///
+    /// # Errors
+    ///
+    /// If the number of merge rules to learn doesn't fit into the 16-bit token ids together with
+    /// the existing vocabulary, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{ExtendOptions, Token, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let corpus = ["acetylsalicylic acid 100mg", "acetylsalicylic acid 500mg"];
+    /// let extended = tokenizer
+    ///     .extend_vocabulary(corpus, &ExtendOptions::new().merges(100))
+    ///     .unwrap();
+    /// assert!(extended.vocab_info().vocab_size() > 49408);
+    ///
+    /// let mut tokens = Vec::new();
+    /// extended.encode("Acetylsalicylic", &mut tokens);
+    /// assert_eq!(tokens.len(), 1);
+    /// assert!(tokens[0].to_u16() >= 49408);
+    /// assert_eq!(extended.end_of_text(), tokenizer.end_of_text());
+    /// ```
+    pub fn extend_vocabulary<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+        options: &ExtendOptions,
+    ) -> io::Result<Tokenizer> {
+        let vocabulary = &self.vocabulary;
+        if usize::from(vocabulary.vocab_size) + options.merges > usize::from(u16::MAX - 1) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "a vocabulary of {} tokens can't be extended by {} tokens",
+                    vocabulary.vocab_size, options.merges
+                ),
+            ));
+        }
+
+        // Number of occurrences of each distinct word
+        let space_prefix = vocabulary.word_boundary == WordBoundary::SpacePrefix;
+        let mut counts = HashMap::<(String, bool), usize>::default();
+        for text in texts {
+            let text = match self.lowercase {
+                true => Cow::Owned(text.to_lowercase()),
+                false => Cow::Borrowed(text),
+            };
+            for (word, _) in self.split_words(&text, true) {
+                // The marker tokens can't be merged with anything
+                let Word::Text {
+                    word,
+                    follows_whitespace,
+                } = word
+                else {
+                    continue;
+                };
+                let follows_whitespace = space_prefix && follows_whitespace;
+                *counts
+                    .entry((word.to_owned(), follows_whitespace))
+                    .or_default() += 1;
+            }
+        }
+
+        // Words already encoded as a single token can't be merged any further
+        let mut words = Vec::with_capacity(counts.len());
+        for ((word, follows_whitespace), count) in counts {
+            let mut tokens = Vec::new();
+            let word = Word::Text {
+                word: &word,
+                follows_whitespace,
+            };
+            self.encode_word(word, &mut tokens);
+            if tokens.len() > 1 {
+                words.push((tokens, count));
+            }
+        }
+
+        let mut merge_rules = vocabulary.merge_rules.clone();
+        let mut vocab_size = vocabulary.vocab_size;
+        let mut pair_counts = HashMap::<(Token, Token), usize>::default();
+        for _ in 0..options.merges {
+            pair_counts.clear();
+            for (tokens, count) in &words {
+                for pair in tokens.windows(2) {
+                    *pair_counts.entry((pair[0], pair[1])).or_default() += count;
+                }
+            }
+            // Break ties in favor of the pair with the lowest token ids, for deterministic results
+            let best = pair_counts
+                .iter()
+                .filter(|(_, &count)| count >= options.min_frequency)
+                .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then(b.cmp(a)));
+            let (first, second) = match best {
+                Some((&pair, _)) => pair,
+                None => break,
+            };
+
+            let result_token = Token(vocab_size);
+            vocab_size += 1;
+            merge_rules.insert((first, second), result_token);
+
+            for (tokens, _) in &mut words {
+                let mut i = 0;
+                while i + 1 < tokens.len() {
+                    if tokens[i] == first && tokens[i + 1] == second {
+                        tokens[i] = result_token;
+                        tokens.remove(i + 1);
+                    }
+                    i += 1;
+                }
+            }
+            words.retain(|(tokens, _)| tokens.len() > 1);
+        }
+
+        let vocabulary = Vocabulary {
+            byte_to_token: vocabulary.byte_to_token.clone(),
+            merge_rules,
+            start_of_text: vocabulary.start_of_text,
+            end_of_text: vocabulary.end_of_text,
+            decoder: OnceCell::new(),
+            word_boundary: vocabulary.word_boundary,
+            vocab_size,
+        };
+        Ok(Tokenizer {
+            vocabulary: Arc::new(vocabulary),
+            lowercase: self.lowercase,
+            context_length: self.context_length,
+            max_input_bytes: self.max_input_bytes,
+            max_input_words: self.max_input_words,
+            pre_tokenizer: self.pre_tokenizer,
+            text_cache: self
+                .text_cache
+                .as_ref()
+                .map(|cache| Arc::new(TextCache::new(cache.capacity()))),
+        })
+    }
+}
+
+/// Options for [`Tokenizer::extend_vocabulary`].
+#[derive(Clone, Debug)]
+pub struct ExtendOptions {
+    merges: usize,
+    min_frequency: usize,
+}
+
+impl ExtendOptions {
+    /// Create a new `ExtendOptions` with default values.
+    pub fn new() -> ExtendOptions {
+        ExtendOptions {
+            merges: 1000,
+            min_frequency: 2,
+        }
+    }
+
+    /// Set the maximum number of merge rules to learn, each adding one token to the vocabulary.
+    /// Defaults to `1000`.
+    ///
+    /// Fewer merge rules are learned if no pair of adjacent tokens occurs often enough in the
+    /// corpus, see [`min_frequency`](ExtendOptions::min_frequency).
+    pub fn merges(mut self, merges: usize) -> ExtendOptions {
+        self.merges = merges;
+        self
+    }
+
+    /// Set the number of occurrences in the corpus from which on a pair of adjacent tokens is
+    /// merged into a new token. Defaults to `2`.
+    pub fn min_frequency(mut self, min_frequency: usize) -> ExtendOptions {
+        self.min_frequency = min_frequency;
+        self
+    }
+}
+
+impl Default for ExtendOptions {
+    fn default() -> ExtendOptions {
+        ExtendOptions::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(tokenizer: &Tokenizer, text: &str) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        tokenizer.encode(text, &mut tokens);
+        tokens
+    }
+
+    #[test]
+    fn extend_vocabulary() {
+        let tokenizer = Tokenizer::new();
+        let corpus = [
+            "SKU ZQXV-4417 in stock",
+            "sku zqxv-4417 backordered",
+            "Acetylsalicylic acid",
+            "acetylsalicylic acid tablets",
+        ];
+        let extended = tokenizer
+            .extend_vocabulary(corpus, &ExtendOptions::new())
+            .unwrap();
+
+        // All existing tokens keep their ids
+        let vocab_size = extended.vocab_info().vocab_size();
+        assert!(vocab_size > 49408);
+        assert!(extended.vocab_iter().take(49408).eq(tokenizer.vocab_iter()));
+        assert_eq!(extended.vocab_iter().count(), vocab_size);
+        assert_eq!(extended.start_of_text(), tokenizer.start_of_text());
+        assert_eq!(extended.end_of_text(), tokenizer.end_of_text());
+        assert!(Token::from_u16(49408, &extended).is_some());
+        assert!(Token::from_u16(vocab_size as u16, &extended).is_none());
+
+        // The new tokens follow the existing merge rules
+        let (first, second, _) = extended
+            .vocabulary
+            .merge_rules
+            .iter()
+            .map(|(&(first, second), &result)| (first, second, result))
+            .find(|&(_, _, result)| result.to_u16() == 49408)
+            .unwrap();
+        assert_eq!(extended.merge_rank(first, second), Some(48894));
+
+        for word in ["acetylsalicylic", "zqxv"] {
+            assert_eq!(encode(&extended, word).len(), 1, "{word}");
+            assert!(encode(&tokenizer, word).len() > 1, "{word}");
+        }
+        for text in ["a person riding a motorcycle", "tablets", "4417"] {
+            assert_eq!(encode(&extended, text), encode(&tokenizer, text));
+        }
+        let text = "Acetylsalicylic acid (SKU ZQXV-4417)";
+        assert_eq!(
+            extended.decode(encode(&extended, text)),
+            tokenizer.decode(encode(&tokenizer, text))
+        );
+
+        // Extended vocabularies can be saved in the binary format
+        let mut data = Vec::new();
+        extended.save_binary(&mut data).unwrap();
+        let mut base_data = Vec::new();
+        tokenizer.save_binary(&mut base_data).unwrap();
+        // Written in the same format, with the same merge rules preceding the special marker
+        // tokens
+        assert_eq!(data[..10], base_data[..10]);
+        let loaded = Tokenizer::load_binary(data.as_slice()).unwrap();
+        assert_eq!(loaded.fingerprint(), extended.fingerprint());
+        assert_ne!(loaded.fingerprint(), tokenizer.fingerprint());
+        assert_eq!(loaded.vocabulary.decoder(), extended.vocabulary.decoder());
+        assert_eq!(
+            loaded.vocabulary.merge_rules,
+            extended.vocabulary.merge_rules
+        );
+        assert_eq!(loaded.end_of_text(), tokenizer.end_of_text());
+        assert_eq!(encode(&loaded, text), encode(&extended, text));
+
+        // Extending an extended vocabulary
+        let twice = extended
+            .extend_vocabulary(
+                ["hydroxychloroquine hydroxychloroquine"],
+                &ExtendOptions::new(),
+            )
+            .unwrap();
+        assert!(twice
+            .vocab_iter()
+            .take(vocab_size)
+            .eq(extended.vocab_iter()));
+        assert_eq!(encode(&twice, "hydroxychloroquine").len(), 1);
+    }
+
+    #[test]
+    fn options() {
+        let tokenizer = Tokenizer::new();
+        let corpus = ["acetylsalicylic"];
+        let extended = tokenizer
+            .extend_vocabulary(corpus, &ExtendOptions::new())
+            .unwrap();
+        assert_eq!(extended.vocab_info().vocab_size(), 49408);
+        assert_eq!(extended.fingerprint(), tokenizer.fingerprint());
+
+        let options = ExtendOptions::new().min_frequency(1).merges(2);
+        let extended = tokenizer.extend_vocabulary(corpus, &options).unwrap();
+        assert_eq!(extended.vocab_info().vocab_size(), 49410);
+
+        let options = ExtendOptions::new().merges(20_000);
+        assert!(tokenizer.extend_vocabulary(corpus, &options).is_err());
+    }
+
+    #[test]
+    fn space_prefix() {
+        let merges = "#version: 0.2\nĠ t\nh e\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        let extended = tokenizer
+            .extend_vocabulary(["the the the"], &ExtendOptions::new())
+            .unwrap();
+        // " the" occurs twice, while "the" at the start of the text occurs only once
+        assert_eq!(extended.vocab_info().vocab_size(), 261);
+        assert_eq!(encode(&extended, "the the").len(), 3);
+        assert_eq!(extended.decode(encode(&extended, "the the")), "the the");
+    }
+}
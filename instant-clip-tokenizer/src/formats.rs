@@ -0,0 +1,439 @@
+#[cfg(feature = "huggingface")]
+use std::fs;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+use crate::tiktoken::{from_tiktoken, is_tiktoken};
+use crate::Tokenizer;
+#[cfg(feature = "huggingface")]
+use crate::WordBoundary;
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` by reading vocabulary data in any supported format from `reader`.
+    ///
+    /// The format of the data is detected automatically. Supported are the text format used by the
+    /// original CLIP tokenizer implementation from OpenAI (see [`with_vocabulary`]), the
+    /// `.tiktoken` format used by OpenAI's `tiktoken` library, as well as the `tokenizer.json`
+    /// format used by HuggingFace (requires the `huggingface` crate feature).
+    ///
+    /// In the `.tiktoken` format, every line consists of a base64-encoded token and its rank. The
+    /// ranks are used as token ids, followed by the special marker tokens, and the first 256 ranks
+    /// must be the tokens of the individual bytes. Every further token is turned into a merge rule
+    /// by splitting it into two tokens of lower rank, using byte pair encoding. The resulting
+    /// vocabulary uses [`WordBoundary::SpacePrefix`](crate::WordBoundary::SpacePrefix), but texts
+    /// are still split into words like CLIP does, rather than using the patterns of `tiktoken`'s
+    /// encodings.
+    ///
+    /// `max_vocabulary_size` limits the size of the vocabulary for all formats. Vocabularies in
+    /// HuggingFace formats already have their intended size, so passing a smaller limit for these
+    /// results in an error.
+    ///
+    /// Note that creating a new `Tokenizer` is expensive, so it is recommended to create the
+    /// `Tokenizer` once and then reuse it.
+    ///
+    /// [`with_vocabulary`]: Tokenizer::with_vocabulary
+    ///
+    /// # Errors
+    ///
+    /// If the data format is not supported or incorrect, or reading from `reader` fails, then an
+    /// error is returned.
+    pub fn from_reader(mut reader: impl Read, max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        match data.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => from_tokenizer_json(&data, max_vocabulary_size),
+            _ if is_tiktoken(&data) => from_tiktoken(&data, max_vocabulary_size),
+            _ => Tokenizer::with_vocabulary_bytes(&data, max_vocabulary_size),
+        }
+    }
+
+    /// Create a new `Tokenizer` by reading vocabulary data in any supported format from `path`.
+    ///
+    /// If `path` is a file, this works like [`from_reader`]. If `path` is a directory, it must
+    /// contain either a HuggingFace `tokenizer.json` file, or a pair of `vocab.json` and
+    /// `merges.txt` files (both require the `huggingface` crate feature).
+    ///
+    /// [`from_reader`]: Tokenizer::from_reader
+    ///
+    /// # Errors
+    ///
+    /// If no supported vocabulary data is found at `path`, the data format is incorrect, or
+    /// reading the data fails, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use instant_clip_tokenizer::{Token, Tokenizer};
+    /// # fn main() -> io::Result<()> {
+    /// let tokenizer = Tokenizer::from_path("bpe_simple_vocab_16e6.txt", 49408)?;
+    /// assert_eq!(tokenizer.end_of_text().to_u16(), 49407);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn from_path(path: impl AsRef<Path>, max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        let path = path.as_ref();
+        if !path.is_dir() {
+            return Tokenizer::from_reader(File::open(path)?, max_vocabulary_size);
+        }
+
+        load_dir(path, max_vocabulary_size)
+    }
+
+    /// Create a new `Tokenizer` from a local snapshot of a HuggingFace model repository.
+    ///
+    /// `path` can either be a snapshot directory, or the cache directory of a whole repository
+    /// (e.g. `models--openai--clip-vit-base-patch32`, as created by `huggingface-cli download`),
+    /// in which case the snapshot referenced by `refs/main` is used. The tokenizer files are picked
+    /// up as described for [`from_path`]. The special tokens are read from
+    /// `special_tokens_map.json` or `tokenizer_config.json` if present, and checked against the
+    /// `<start_of_text>` and `<end_of_text>` marker tokens.
+    ///
+    /// This allows using models downloaded from the HuggingFace hub in environments without
+    /// network access.
+    ///
+    /// [`from_path`]: Tokenizer::from_path
+    ///
+    /// # Errors
+    ///
+    /// If no tokenizer files are found in the snapshot, the data format is incorrect, the special
+    /// tokens don't match the vocabulary, or reading the data fails, then an error is returned.
+    #[cfg(feature = "huggingface")]
+    pub fn from_dir(path: impl AsRef<Path>) -> io::Result<Tokenizer> {
+        let mut path = path.as_ref().to_owned();
+        if let Ok(revision) = fs::read_to_string(path.join("refs").join("main")) {
+            path = path.join("snapshots").join(revision.trim());
+        }
+        let special_tokens = SpecialTokens::read(&path)?;
+        load_dir_with(&path, u16::MAX, &special_tokens)
+    }
+}
+
+#[cfg(feature = "huggingface")]
+fn load_dir(path: &Path, max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+    load_dir_with(path, max_vocabulary_size, &SpecialTokens::default())
+}
+
+#[cfg(feature = "huggingface")]
+fn load_dir_with(
+    path: &Path,
+    max_vocabulary_size: u16,
+    special_tokens: &SpecialTokens,
+) -> io::Result<Tokenizer> {
+    let tokenizer_json = path.join("tokenizer.json");
+    if tokenizer_json.is_file() {
+        return from_tokenizer_json_with(
+            &fs::read(tokenizer_json)?,
+            max_vocabulary_size,
+            special_tokens,
+        );
+    }
+    let vocab_json = path.join("vocab.json");
+    let merges_txt = path.join("merges.txt");
+    if vocab_json.is_file() && merges_txt.is_file() {
+        return from_vocab_and_merges(
+            &fs::read(vocab_json)?,
+            &fs::read_to_string(merges_txt)?,
+            max_vocabulary_size,
+            special_tokens,
+        );
+    }
+    Err(io::Error::new(
+        io::ErrorKind::NotFound,
+        "directory contains neither tokenizer.json nor vocab.json and merges.txt",
+    ))
+}
+
+/// The strings HuggingFace uses for the `<start_of_text>` and `<end_of_text>` marker tokens.
+#[cfg(feature = "huggingface")]
+struct SpecialTokens {
+    start_of_text: String,
+    end_of_text: String,
+}
+
+#[cfg(feature = "huggingface")]
+impl SpecialTokens {
+    /// Read the special tokens from the configuration files in `dir`, if there are any.
+    fn read(dir: &Path) -> io::Result<SpecialTokens> {
+        fn content(token: &serde_json::Value) -> Option<String> {
+            // Tokens are either given directly, or as an object with additional attributes
+            token
+                .as_str()
+                .or_else(|| token.get("content")?.as_str())
+                .map(str::to_owned)
+        }
+
+        let mut special_tokens = SpecialTokens::default();
+        for name in ["special_tokens_map.json", "tokenizer_config.json"] {
+            let file = dir.join(name);
+            if !file.is_file() {
+                continue;
+            }
+            let json = serde_json::from_slice::<serde_json::Value>(&fs::read(file)?)
+                .map_err(invalid_data)?;
+            if let Some(token) = content(&json["bos_token"]) {
+                special_tokens.start_of_text = token;
+            }
+            if let Some(token) = content(&json["eos_token"]) {
+                special_tokens.end_of_text = token;
+            }
+            break;
+        }
+        Ok(special_tokens)
+    }
+}
+
+#[cfg(feature = "huggingface")]
+impl Default for SpecialTokens {
+    fn default() -> SpecialTokens {
+        SpecialTokens {
+            start_of_text: "<|startoftext|>".to_owned(),
+            end_of_text: "<|endoftext|>".to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "huggingface")]
+pub(crate) fn from_tokenizer_json(data: &[u8], max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+    from_tokenizer_json_with(data, max_vocabulary_size, &SpecialTokens::default())
+}
+
+#[cfg(feature = "huggingface")]
+fn from_tokenizer_json_with(
+    data: &[u8],
+    max_vocabulary_size: u16,
+    special_tokens: &SpecialTokens,
+) -> io::Result<Tokenizer> {
+    use serde_json::Value;
+
+    let json = serde_json::from_slice::<Value>(data).map_err(invalid_data)?;
+    let model = match json.get("model") {
+        Some(model) => model,
+        None if json.get("<|endoftext|>").is_some() => {
+            return Err(invalid_data(
+                "vocab.json must be loaded together with merges.txt",
+            ))
+        }
+        None => return Err(invalid_data("not a tokenizer.json file")),
+    };
+    if model["type"] != "BPE" {
+        return Err(invalid_data("tokenizer model must be BPE"));
+    }
+
+    // Merges are stored either as "first second" strings, or as pairs in newer versions
+    let merges = model["merges"]
+        .as_array()
+        .ok_or_else(|| invalid_data("missing merges"))?
+        .iter()
+        .map(|merge| match merge {
+            Value::String(merge) => Ok(merge.clone()),
+            Value::Array(pair) => match pair.as_slice() {
+                [Value::String(first), Value::String(second)] => Ok(format!("{first} {second}")),
+                _ => Err(invalid_data("invalid merge rule")),
+            },
+            _ => Err(invalid_data("invalid merge rule")),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+
+    // `from_lines` skips the version header of the OpenAI format
+    let expected_lines = Some(merges.len() + 1);
+    let lines = std::iter::once(Ok(String::new())).chain(merges.into_iter().map(Ok));
+    let tokenizer = Tokenizer::from_lines(
+        lines,
+        expected_lines,
+        max_vocabulary_size,
+        WordBoundary::default(),
+        |_| std::ops::ControlFlow::Continue(()),
+    )?;
+    check_vocab(&tokenizer, &model["vocab"], special_tokens)?;
+    Ok(tokenizer)
+}
+
+#[cfg(feature = "huggingface")]
+fn from_vocab_and_merges(
+    vocab_json: &[u8],
+    merges_txt: &str,
+    max_vocabulary_size: u16,
+    special_tokens: &SpecialTokens,
+) -> io::Result<Tokenizer> {
+    let vocab = serde_json::from_slice(vocab_json).map_err(invalid_data)?;
+    // Like the OpenAI format, merges.txt usually starts with a version header
+    let tokenizer = if merges_txt.starts_with("#version") {
+        Tokenizer::with_vocabulary_str(merges_txt, max_vocabulary_size)?
+    } else {
+        Tokenizer::with_vocabulary_str(&format!("\n{merges_txt}"), max_vocabulary_size)?
+    };
+    check_vocab(&tokenizer, &vocab, special_tokens)?;
+    Ok(tokenizer)
+}
+
+/// Checks that a HuggingFace vocabulary mapping strings to token ids is consistent with the
+/// tokens derived from the merge rules.
+#[cfg(feature = "huggingface")]
+fn check_vocab(
+    tokenizer: &Tokenizer,
+    vocab: &serde_json::Value,
+    special_tokens: &SpecialTokens,
+) -> io::Result<()> {
+    let vocab = vocab
+        .as_object()
+        .ok_or_else(|| invalid_data("missing vocabulary"))?;
+    let id = |token: &str| vocab.get(token).and_then(|id| id.as_u64());
+    if vocab.len() != tokenizer.end_of_text().to_usize() + 1
+        || id(&special_tokens.start_of_text) != Some(tokenizer.start_of_text().to_u16().into())
+        || id(&special_tokens.end_of_text) != Some(tokenizer.end_of_text().to_u16().into())
+    {
+        return Err(invalid_data("vocabulary does not match merge rules"));
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "huggingface"))]
+fn from_tokenizer_json(_: &[u8], _: u16) -> io::Result<Tokenizer> {
+    Err(huggingface_disabled())
+}
+
+#[cfg(not(feature = "huggingface"))]
+fn load_dir(_: &Path, _: u16) -> io::Result<Tokenizer> {
+    Err(huggingface_disabled())
+}
+
+#[cfg(not(feature = "huggingface"))]
+fn huggingface_disabled() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Other,
+        "loading HuggingFace vocabulary files requires the `huggingface` crate feature",
+    )
+}
+
+#[cfg(feature = "huggingface")]
+pub(crate) fn invalid_data(err: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+#[cfg(all(test, feature = "huggingface"))]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::{json, Map, Value};
+
+    use super::*;
+    use crate::Token;
+
+    const MERGES: usize = 2000;
+
+    /// Returns the first `MERGES` merge rules of the bundled vocabulary file, together with a
+    /// vocabulary of matching size.
+    fn hf_data() -> (Vec<&'static str>, Value) {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let merges = data.lines().skip(1).take(MERGES).collect::<Vec<_>>();
+        // Only the size and special tokens of the vocabulary are checked
+        let mut vocab = (0..512 + MERGES)
+            .map(|id| (format!("token{id}"), json!(id)))
+            .collect::<Map<_, _>>();
+        vocab.insert("<|startoftext|>".into(), json!(512 + MERGES));
+        vocab.insert("<|endoftext|>".into(), json!(512 + MERGES + 1));
+        (merges, Value::Object(vocab))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "instant-clip-tokenizer-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn assert_encodes(tokenizer: &Tokenizer) {
+        let mut tokens = Vec::new();
+        tokenizer.encode("Hi there", &mut tokens);
+        assert_eq!(tokens, [1883, 997].map(Token));
+        assert_eq!(tokenizer.end_of_text(), Token(512 + MERGES as u16 + 1));
+    }
+
+    #[test]
+    fn from_reader_openai() {
+        let data = include_bytes!("../bpe_simple_vocab_16e6.txt");
+        let tokenizer = Tokenizer::from_reader(&data[..], 49408).unwrap();
+        assert_eq!(tokenizer.end_of_text(), Token(49407));
+    }
+
+    #[test]
+    fn from_reader_tokenizer_json() {
+        let (merges, vocab) = hf_data();
+        let pairs = merges
+            .iter()
+            .map(|merge| merge.split(' ').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        for merges in [json!(merges), json!(pairs)] {
+            let data = json!({ "model": { "type": "BPE", "vocab": vocab, "merges": merges } });
+            let tokenizer = Tokenizer::from_reader(data.to_string().as_bytes(), 49408).unwrap();
+            assert_encodes(&tokenizer);
+        }
+
+        // A smaller maximum vocabulary size doesn't match the vocabulary
+        let data = json!({ "model": { "type": "BPE", "vocab": vocab, "merges": merges } });
+        assert!(Tokenizer::from_reader(data.to_string().as_bytes(), 1000).is_err());
+        // A vocab.json file on its own is not sufficient
+        assert!(Tokenizer::from_reader(vocab.to_string().as_bytes(), 49408).is_err());
+    }
+
+    #[test]
+    fn from_path() {
+        let (merges, vocab) = hf_data();
+
+        let dir = temp_dir("tokenizer-json");
+        let data = json!({ "model": { "type": "BPE", "vocab": vocab, "merges": merges } });
+        fs::write(dir.join("tokenizer.json"), data.to_string()).unwrap();
+        assert_encodes(&Tokenizer::from_path(&dir, 49408).unwrap());
+        assert_encodes(&Tokenizer::from_path(dir.join("tokenizer.json"), 49408).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("vocab-merges");
+        fs::write(dir.join("vocab.json"), vocab.to_string()).unwrap();
+        let merges_txt = format!("#version: 0.2\n{}\n", merges.join("\n"));
+        fs::write(dir.join("merges.txt"), merges_txt).unwrap();
+        assert_encodes(&Tokenizer::from_path(&dir, 49408).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+
+        let dir = temp_dir("empty");
+        fs::write(dir.join("merges.txt"), "").unwrap();
+        let Err(err) = Tokenizer::from_path(&dir, 49408) else {
+            panic!("empty directory must be rejected");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn from_dir() {
+        let (merges, mut vocab) = hf_data();
+        let repo = temp_dir("from-dir");
+        let snapshot = repo.join("snapshots").join("0123abcd");
+        fs::create_dir_all(&snapshot).unwrap();
+        fs::create_dir_all(repo.join("refs")).unwrap();
+        fs::write(repo.join("refs").join("main"), "0123abcd\n").unwrap();
+        let data = json!({ "model": { "type": "BPE", "vocab": vocab, "merges": merges } });
+        fs::write(snapshot.join("tokenizer.json"), data.to_string()).unwrap();
+        assert_encodes(&Tokenizer::from_dir(&repo).unwrap());
+        assert_encodes(&Tokenizer::from_dir(&snapshot).unwrap());
+
+        // Special tokens are read from the configuration
+        let map = vocab.as_object_mut().unwrap();
+        let id = map.remove("<|startoftext|>").unwrap();
+        map.insert("<bos>".into(), id);
+        let data = json!({ "model": { "type": "BPE", "vocab": vocab, "merges": merges } });
+        fs::write(snapshot.join("tokenizer.json"), data.to_string()).unwrap();
+        assert!(Tokenizer::from_dir(&repo).is_err());
+        let config = json!({
+            "bos_token": { "content": "<bos>", "lstrip": false },
+            "eos_token": "<|endoftext|>",
+        });
+        fs::write(snapshot.join("special_tokens_map.json"), config.to_string()).unwrap();
+        assert_encodes(&Tokenizer::from_dir(&repo).unwrap());
+        fs::remove_dir_all(&repo).unwrap();
+    }
+}
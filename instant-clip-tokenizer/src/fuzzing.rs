@@ -0,0 +1,66 @@
+use arbitrary::{Arbitrary, Unstructured};
+
+use crate::{Token, VocabInfo, DEFAULT_MAX_VOCABULARY_SIZE};
+
+impl Token {
+    /// Generate an arbitrary `Token` that is valid for the vocabulary of the given `tokenizer`.
+    ///
+    /// Like [`from_u16`](Token::from_u16), this also accepts a [`VocabInfo`]. The [`Arbitrary`]
+    /// implementation of `Token` generates tokens valid for the vocabulary bundled with this crate
+    /// instead.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use arbitrary::Unstructured;
+    /// # use instant_clip_tokenizer::{Token, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let mut u = Unstructured::new(&[0x12, 0x34, 0x56, 0x78]);
+    /// let token = Token::arbitrary_for(&mut u, &tokenizer).unwrap();
+    /// assert!(Token::from_u16(token.to_u16(), &tokenizer).is_some());
+    /// ```
+    pub fn arbitrary_for(
+        u: &mut Unstructured<'_>,
+        tokenizer: impl Into<VocabInfo>,
+    ) -> arbitrary::Result<Token> {
+        let vocab_size = tokenizer.into().vocab_size;
+        u.int_in_range(0..=vocab_size.saturating_sub(1)).map(Token)
+    }
+}
+
+/// Generates tokens valid for the vocabulary bundled with this crate, i.e. below `49408`.
+impl<'a> Arbitrary<'a> for Token {
+    fn arbitrary(u: &mut Unstructured<'a>) -> arbitrary::Result<Token> {
+        u.int_in_range(0..=DEFAULT_MAX_VOCABULARY_SIZE - 1)
+            .map(Token)
+    }
+
+    fn size_hint(depth: usize) -> (usize, Option<usize>) {
+        u16::size_hint(depth)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Tokenizer;
+
+    #[test]
+    fn arbitrary() {
+        let data = (0..=255).cycle().take(4096).collect::<Vec<u8>>();
+        let mut u = Unstructured::new(&data);
+        let tokenizer = Tokenizer::new();
+        while !u.is_empty() {
+            let token = Token::arbitrary(&mut u).unwrap();
+            assert!(Token::from_u16(token.to_u16(), &tokenizer).is_some());
+        }
+
+        let merges = "#version: 0.2\nh i\n";
+        let small = Tokenizer::with_vocabulary(merges.as_bytes(), 1000).unwrap();
+        let mut u = Unstructured::new(&data);
+        while !u.is_empty() {
+            let token = Token::arbitrary_for(&mut u, &small).unwrap();
+            assert!(token.to_u16() < 515);
+        }
+    }
+}
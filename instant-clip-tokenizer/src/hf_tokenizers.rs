@@ -0,0 +1,260 @@
+use std::io;
+use std::str::FromStr;
+
+use serde_json::{json, Map, Value};
+
+use crate::export::{byte_to_char, to_chars};
+use crate::formats::{from_tokenizer_json, invalid_data};
+use crate::{Token, Tokenizer, TokenizerOptions, WordBoundary};
+
+/// The regular expression CLIP uses to split texts into words, minus the special substrings that
+/// HuggingFace handles as added tokens.
+const WORD_SPLIT_PATTERN: &str = r"'s|'t|'re|'ve|'m|'ll|'d|[\p{L}]+|[\p{N}]|[^\s\p{L}\p{N}]+";
+
+/// Convert a tokenizer from the HuggingFace `tokenizers` crate (requires the `tokenizers` crate
+/// feature).
+///
+/// The `tokenizers::Tokenizer` must use a byte-level BPE model with the `</w>` end-of-word suffix
+/// used by CLIP. The resulting `Tokenizer` lowercases its input if the `tokenizers::Tokenizer`
+/// has a lowercasing normalizer.
+///
+/// # Errors
+///
+/// If the pre-tokenizer or model don't match CLIP's byte-level BPE, or the vocabulary doesn't
+/// match the merge rules, then an error is returned.
+impl TryFrom<&tokenizers::Tokenizer> for Tokenizer {
+    type Error = io::Error;
+
+    fn try_from(tokenizer: &tokenizers::Tokenizer) -> io::Result<Tokenizer> {
+        let data = tokenizer.to_string(false).map_err(invalid_data)?;
+        let json = serde_json::from_str::<Value>(&data).map_err(invalid_data)?;
+
+        let model = &json["model"];
+        if model["type"] != "BPE" || model["end_of_word_suffix"] != "</w>" {
+            return Err(invalid_data(
+                "tokenizer model must be BPE with the \"</w>\" end-of-word suffix",
+            ));
+        }
+        if !model["dropout"].is_null()
+            || !matches!(model["continuing_subword_prefix"].as_str(), None | Some(""))
+        {
+            return Err(invalid_data(
+                "BPE dropout and continuing subword prefixes are not supported",
+            ));
+        }
+        if !contains(&json["pre_tokenizer"], "pretokenizers", "ByteLevel") {
+            return Err(invalid_data(
+                "tokenizer must use a byte-level pre-tokenizer",
+            ));
+        }
+
+        let parsed = from_tokenizer_json(data.as_bytes(), u16::MAX)?;
+        let lowercase = contains(&json["normalizer"], "normalizers", "Lowercase");
+        Ok(Tokenizer::from_vocabulary(
+            parsed.vocabulary(),
+            TokenizerOptions::new()
+                .lowercase(lowercase)
+                .context_length(parsed.context_length()),
+        ))
+    }
+}
+
+/// Convert a tokenizer to one from the HuggingFace `tokenizers` crate (requires the
+/// `tokenizers` crate feature).
+///
+/// The resulting `tokenizers::Tokenizer` is set up like the ones published for CLIP models on the
+/// HuggingFace hub, with `<|startoftext|>` and `<|endoftext|>` as special tokens. It encodes texts
+/// as the same tokens as this `Tokenizer`.
+///
+/// # Errors
+///
+/// If the vocabulary uses [`WordBoundary::SpacePrefix`], then an error is returned.
+impl TryFrom<&Tokenizer> for tokenizers::Tokenizer {
+    type Error = io::Error;
+
+    fn try_from(tokenizer: &Tokenizer) -> io::Result<tokenizers::Tokenizer> {
+        if tokenizer.word_boundary() != WordBoundary::EndOfWordSuffix {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "only vocabularies with end-of-word suffixes can be converted",
+            ));
+        }
+
+        let chars = byte_to_char();
+        let token_string = |token: Token| {
+            let bytes = tokenizer.token_bytes(token).unwrap_or_default();
+            match bytes.strip_suffix(b"</w>") {
+                Some(bytes) => format!("{}</w>", to_chars(bytes, &chars)),
+                None => to_chars(bytes, &chars),
+            }
+        };
+
+        let (start_of_text, end_of_text) = (tokenizer.start_of_text(), tokenizer.end_of_text());
+        let mut vocab = Map::new();
+        for (token, _) in tokenizer.vocab_iter() {
+            if token != start_of_text && token != end_of_text {
+                vocab.insert(token_string(token), json!(token.to_u16()));
+            }
+        }
+        vocab.insert("<|startoftext|>".into(), json!(start_of_text.to_u16()));
+        vocab.insert("<|endoftext|>".into(), json!(end_of_text.to_u16()));
+
+        let mut merge_rules = tokenizer.vocabulary.merge_rules.iter().collect::<Vec<_>>();
+        merge_rules.sort_unstable_by_key(|(_, &result)| result);
+        let merges = merge_rules
+            .into_iter()
+            .map(|(&(first, second), _)| {
+                format!("{} {}", token_string(first), token_string(second))
+            })
+            .collect::<Vec<_>>();
+
+        let special_token = |content: &str, token: Token| {
+            json!({
+                "id": token.to_u16(),
+                "content": content,
+                "single_word": false,
+                "lstrip": false,
+                "rstrip": false,
+                "normalized": true,
+                "special": true,
+            })
+        };
+        let normalizer = match tokenizer.lowercase {
+            true => json!({ "type": "Lowercase" }),
+            false => Value::Null,
+        };
+        let json = json!({
+            "version": "1.0",
+            "truncation": null,
+            "padding": null,
+            "added_tokens": [
+                special_token("<|startoftext|>", start_of_text),
+                special_token("<|endoftext|>", end_of_text),
+            ],
+            "normalizer": normalizer,
+            "pre_tokenizer": {
+                "type": "Sequence",
+                "pretokenizers": [
+                    {
+                        "type": "Split",
+                        "pattern": { "Regex": WORD_SPLIT_PATTERN },
+                        "behavior": "Removed",
+                        "invert": true,
+                    },
+                    {
+                        "type": "ByteLevel",
+                        "add_prefix_space": false,
+                        "trim_offsets": true,
+                        "use_regex": false,
+                    },
+                ],
+            },
+            "post_processor": {
+                "type": "RobertaProcessing",
+                "sep": ["<|endoftext|>", end_of_text.to_u16()],
+                "cls": ["<|startoftext|>", start_of_text.to_u16()],
+                "trim_offsets": false,
+                "add_prefix_space": false,
+            },
+            "decoder": {
+                "type": "ByteLevel",
+                "add_prefix_space": true,
+                "trim_offsets": true,
+                "use_regex": true,
+            },
+            "model": {
+                "type": "BPE",
+                "dropout": null,
+                "unk_token": "<|endoftext|>",
+                "continuing_subword_prefix": "",
+                "end_of_word_suffix": "</w>",
+                "fuse_unk": false,
+                "byte_fallback": false,
+                "vocab": vocab,
+                "merges": merges,
+            },
+        });
+        tokenizers::Tokenizer::from_str(&json.to_string()).map_err(invalid_data)
+    }
+}
+
+/// Returns whether `component` (e.g. a pre-tokenizer), or one of the components in the list
+/// `key` of a sequence, is of the given `kind`.
+fn contains(component: &Value, key: &str, kind: &str) -> bool {
+    match component["type"].as_str() {
+        Some("Sequence") => component[key].as_array().map_or(false, |components| {
+            components.iter().any(|c| c["type"] == kind)
+        }),
+        _ => component["type"] == kind,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip() {
+        let tokenizer = Tokenizer::new();
+        let hf = tokenizers::Tokenizer::try_from(&tokenizer).unwrap();
+        assert_eq!(hf.get_vocab_size(true), 49408);
+
+        let texts = [
+            "Hi there",
+            "A photo of a cat sitting on a sofa!!",
+            "I've seen the 2 Éléphants, haven't you? 🦀",
+        ];
+        for text in texts {
+            let mut expected = vec![tokenizer.start_of_text()];
+            tokenizer.encode(text, &mut expected);
+            expected.push(tokenizer.end_of_text());
+            let expected = expected
+                .into_iter()
+                .map(|token| u32::from(token.to_u16()))
+                .collect::<Vec<_>>();
+            assert_eq!(hf.encode(text, true).unwrap().get_ids(), expected, "{text}");
+        }
+
+        let converted = Tokenizer::try_from(&hf).unwrap();
+        assert_eq!(
+            converted.vocabulary.merge_rules,
+            tokenizer.vocabulary.merge_rules
+        );
+        assert_eq!(converted.end_of_text(), tokenizer.end_of_text());
+        let mut tokens = Vec::new();
+        converted.encode("Hi there", &mut tokens);
+        assert_eq!(tokens, [1883, 997].map(Token));
+    }
+
+    #[test]
+    fn lowercase() {
+        let tokenizer = Tokenizer::from_vocabulary(
+            Tokenizer::new().vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        let hf = tokenizers::Tokenizer::try_from(&tokenizer).unwrap();
+        assert!(hf.get_normalizer().is_none());
+        let converted = Tokenizer::try_from(&hf).unwrap();
+        let (mut expected, mut tokens) = (Vec::new(), Vec::new());
+        tokenizer.encode("Hi", &mut expected);
+        converted.encode("Hi", &mut tokens);
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn unsupported() {
+        let hf = tokenizers::Tokenizer::try_from(&Tokenizer::new()).unwrap();
+        let mut json = serde_json::from_str::<Value>(&hf.to_string(false).unwrap()).unwrap();
+        json["model"]["end_of_word_suffix"] = json!(null);
+        let hf = tokenizers::Tokenizer::from_str(&json.to_string()).unwrap();
+        let Err(err) = Tokenizer::try_from(&hf) else {
+            panic!("tokenizers without end-of-word suffix must be rejected");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(&b"\n"[..], 300, WordBoundary::SpacePrefix).unwrap();
+        let err = tokenizers::Tokenizer::try_from(&tokenizer).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
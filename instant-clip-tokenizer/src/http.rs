@@ -0,0 +1,128 @@
+use std::io::{self, Read};
+
+use sha2::{Digest, Sha256};
+
+use crate::Tokenizer;
+
+/// The maximum size of vocabulary data downloaded by [`Tokenizer::from_url`].
+const MAX_DOWNLOAD_SIZE: u64 = 256 << 20;
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` by downloading vocabulary data in any supported format from `url`.
+    ///
+    /// This works like [`from_reader`] for the downloaded data, after verifying that its SHA-256
+    /// checksum matches `sha256`, given as a hexadecimal string. It is intended for deployments
+    /// that host vocabulary data on their own servers or object storage, e.g. using a pre-signed
+    /// URL. Both `http` and `https` URLs are supported, and at most 256 MiB are downloaded.
+    ///
+    /// [`from_reader`]: Tokenizer::from_reader
+    ///
+    /// # Errors
+    ///
+    /// If the request fails, the checksum of the downloaded data doesn't match `sha256`, or the data
+    /// format is not supported or incorrect, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::from_url(
+    ///     "https://storage.example.com/models/clip/bpe_simple_vocab_16e6.txt",
+    ///     "67603cfda2e032ad77b5f8808af37789d590db664b26df8705d2bf8b3c553fc8",
+    ///     49408,
+    /// )?;
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn from_url(url: &str, sha256: &str, max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        let response = ureq::get(url).call().map_err(|err| match err {
+            ureq::Error::Status(..) => io::Error::new(io::ErrorKind::Other, err),
+            ureq::Error::Transport(err) => io::Error::new(
+                io::ErrorKind::Other,
+                format!("failed to download {url}: {err}"),
+            ),
+        })?;
+
+        let mut data = Vec::new();
+        response
+            .into_reader()
+            .take(MAX_DOWNLOAD_SIZE + 1)
+            .read_to_end(&mut data)?;
+        if data.len() as u64 > MAX_DOWNLOAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{url} is larger than {MAX_DOWNLOAD_SIZE} bytes"),
+            ));
+        }
+
+        verify_sha256(&data, sha256)?;
+        Tokenizer::from_reader(data.as_slice(), max_vocabulary_size)
+    }
+}
+
+fn verify_sha256(data: &[u8], expected: &str) -> io::Result<()> {
+    let actual = Sha256::digest(data)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect::<String>();
+    match actual.eq_ignore_ascii_case(expected.trim()) {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("SHA-256 checksum mismatch: expected {expected}, got {actual}"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+
+    /// Serve `body` to the given number of requests on a local port, returning the URL.
+    fn serve(body: &'static [u8], requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/vocab.txt", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            for stream in listener.incoming().take(requests) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(&mut stream);
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 2 {
+                    line.clear();
+                }
+                let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len());
+                stream.write_all(header.as_bytes()).unwrap();
+                stream.write_all(body).unwrap();
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn from_url() {
+        let data = b"#version: 0.2\nh i\n";
+        let url = serve(data, 2);
+        let sha256 = Sha256::digest(data)
+            .iter()
+            .map(|b| format!("{b:02X}"))
+            .collect::<String>();
+        let tokenizer = Tokenizer::from_url(&url, &sha256, 1000).unwrap();
+        assert_eq!(tokenizer.vocab_info().vocab_size(), 515);
+
+        let err = Tokenizer::from_url(&url, &"0".repeat(64), 1000)
+            .err()
+            .unwrap();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().starts_with("SHA-256 checksum mismatch"));
+    }
+
+    #[test]
+    fn sha256() {
+        let expected = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert!(verify_sha256(b"abc", expected).is_ok());
+        assert!(verify_sha256(b"abd", expected).is_err());
+    }
+}
@@ -40,49 +40,320 @@
 //!
 //! # Crate features
 //!
-//! This crate provides two features:
+//! This crate provides the following features:
 //!
+//! * **ahash** - Enables the [`ahash`](https://docs.rs/ahash) dependency, which provides a faster
+//!   hash function for the vocabulary tables than the one from `std`.
+//! * **arbitrary** - Enables the [`arbitrary`](https://docs.rs/arbitrary) dependency and implements
+//!   its `Arbitrary` trait for [`Token`], for fuzzing code that handles tokens. Requires Rust 1.71
+//!   or newer.
+//! * **config** - Enables the [`serde_json`](https://docs.rs/serde_json) dependency and creating a
+//!   tokenizer as described by a JSON configuration file using `Tokenizer::from_config`.
+//! * **dataset** - Enables the [`serde_json`](https://docs.rs/serde_json) dependency and the
+//!   `dataset` module for tokenizing large caption datasets stored as JSONL, CSV or plain text
+//!   files into sharded `.npy` files.
+//! * **huggingface** - Enables the [`serde_json`](https://docs.rs/serde_json) dependency and
+//!   support for loading vocabulary files in the formats used by HuggingFace (`tokenizer.json`, or
+//!   `vocab.json` together with `merges.txt`) using [`Tokenizer::from_reader`] and
+//!   [`Tokenizer::from_path`], as well as loading local HuggingFace snapshots using
+//!   `Tokenizer::from_dir`.
+//! * **http** - Enables the [`ureq`](https://docs.rs/ureq) and [`sha2`](https://docs.rs/sha2)
+//!   dependencies and downloading vocabulary data using `Tokenizer::from_url`, verifying it against
+//!   a SHA-256 checksum. Requires Rust 1.71 or newer.
+//! * **metrics** - Enables the [`metrics`](https://docs.rs/metrics) dependency and emits metrics
+//!   through its facade, so that services using a `metrics` exporter (e.g. for Prometheus or
+//!   OpenTelemetry) can observe tokenization without wrapping every call. The counters
+//!   `instant_clip_tokenizer_texts_total`, `instant_clip_tokenizer_tokens_total` and
+//!   `instant_clip_tokenizer_truncations_total` count the texts encoded (once per distinct text of
+//!   a batch), the tokens they were encoded as and the texts that were truncated. The histogram
+//!   `instant_clip_tokenizer_batch_duration_seconds` records the time taken by the batch methods.
+//!   Requires Rust 1.71.1 or newer.
 //! * **ndarray** - Enables the [`ndarray`](https://docs.rs/ndarray) dependency and the
 //!   `Tokenizer::tokenize_batch` method that can be used to tokenize several input strings at once,
-//!   returning a matrix suitable for directly passing to the CLIP neural network.
+//!   returning a matrix suitable for directly passing to the CLIP neural network, as well as
+//!   `Tokenizer::tokenize_batch_sdxl` for the two text encoders of Stable Diffusion XL.
 //! * **openai-vocabulary-file** - This feature bundles the default vocabulary file used for
 //!   OpenAI's CLIP model together with this crate and allows users to construct a new tokenizer
-//!   simply by calling [`Tokenizer::new`]. When disabled, you will need to supply your own
-//!   vocabulary file and construct the tokenizer using [`Tokenizer::with_vocabulary`].
+//!   simply by calling [`Tokenizer::new`]. The vocabulary is bundled in the compact format read by
+//!   [`Tokenizer::load_binary`], adding about 200 kB to the compiled code. When disabled, you will
+//!   need to supply your own vocabulary file and construct the tokenizer using
+//!   [`Tokenizer::with_vocabulary`].
+//! * **parquet** - Enables the [`parquet`](https://docs.rs/parquet) dependency and reading
+//!   captions from Parquet files using `dataset::tokenize_parquet_dataset`. Implies **dataset**.
+//!   Requires Rust 1.70 or newer.
+//! * **polars** - Enables the [`polars-core`](https://docs.rs/polars-core) dependency and the
+//!   `polars` module for tokenizing string columns of Polars data frames in parallel. Requires
+//!   Rust 1.82 or newer.
+//! * **proptest** - Enables the [`proptest`](https://docs.rs/proptest) dependency and the
+//!   `strategy` module with strategies generating captions, texts and tokens for property tests.
+//!   Requires Rust 1.88 or newer.
+//! * **rand** - Enables the [`rand`](https://docs.rs/rand) dependency and sampling random token
+//!   sequences using `Tokenizer::random_tokens`, e.g. for load-testing text encoders.
+//! * **serde** - Enables the [`serde`](https://docs.rs/serde) dependency and implements its
+//!   `Serialize` trait for [`Encoding`].
+//! * **small** - Reduces the size of the compiled code, e.g. for WebAssembly builds. The hash map
+//!   from `std` is used even if **ahash** is enabled. To also drop the `ahash` dependency, disable
+//!   the default features.
+//! * **test-utils** - Enables the `test_utils` module containing golden test corpora and assertions
+//!   for testing code that embeds this tokenizer, as well as the `assert_tokens!` macro. Implies
+//!   **openai-vocabulary-file**.
+//! * **tokenizers** - Enables the [`tokenizers`](https://docs.rs/tokenizers) dependency and
+//!   conversions between [`Tokenizer`] and `tokenizers::Tokenizer` using `TryFrom`, for sharing a
+//!   vocabulary with code using HuggingFace's tokenizers. Implies **huggingface**.
 //!
-//! The **openai-vocabulary-file** feature is enabled by default. To disable it use
+//! The **ahash** and **openai-vocabulary-file** features are enabled by default. To disable them use
 //! `default-features = false` when specifying the dependency on this crate in your `Cargo.toml`.
+//!
+//! This crate requires Rust 1.65 or newer, except for the features noting a newer version above.
 
+use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::fmt;
 use std::io::{self, BufRead};
+use std::iter;
+use std::mem::{self, MaybeUninit};
+use std::ops::{ControlFlow, Range};
+use std::str;
+use std::sync::Arc;
+use std::time::Instant;
+
+use once_cell::sync::OnceCell;
+
+use crate::cache::{fnv1a, FNV_OFFSET_BASIS};
+use crate::text_cache::{TextCache, MAX_CACHED_TEXT_BYTES};
+use crate::words::Words;
+
+#[cfg(feature = "dataset")]
+pub mod dataset;
+pub mod dedup;
+#[cfg(feature = "polars")]
+pub mod polars;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "test-utils")]
+pub mod test_utils;
+pub mod viz;
+
+#[cfg(feature = "ndarray")]
+mod batch;
+mod binary;
+mod cache;
+#[cfg(feature = "config")]
+mod config;
+mod coverage;
+mod encoding;
+mod explain;
+mod export;
+mod extend;
+mod formats;
+#[cfg(feature = "arbitrary")]
+mod fuzzing;
+#[cfg(feature = "tokenizers")]
+mod hf_tokenizers;
+#[cfg(feature = "http")]
+mod http;
+mod preset;
+#[cfg(feature = "rand")]
+mod random;
+mod reload;
+#[cfg(feature = "ndarray")]
+mod sdxl;
+mod split;
+mod telemetry;
+mod text_cache;
+mod tiktoken;
+mod unicode_tables;
+mod verify;
+mod words;
+#[cfg(feature = "ndarray")]
+pub use batch::BatchEncoding;
+pub use coverage::{CoverageOptions, CoverageReport, FragmentedWord, Script, ScriptCoverage};
+pub use encoding::Encoding;
+pub use explain::{Explanation, MergeStep};
+pub use export::{RawTables, VocabExportFormat};
+pub use extend::ExtendOptions;
+pub use preset::VocabularyPreset;
+pub use reload::ReloadableTokenizer;
+#[cfg(feature = "ndarray")]
+pub use sdxl::SdxlBatch;
+pub use split::TokenBudgetChunks;
+pub use verify::{Mismatch, VerificationReport};
+
+/// The hash map used for the vocabulary tables. Builds optimized for size use the hash map from
+/// `std` instead, which doesn't add any code for its hasher.
+#[cfg(all(feature = "ahash", not(feature = "small")))]
+type HashMap<K, V> = ahash::AHashMap<K, V>;
+#[cfg(any(not(feature = "ahash"), feature = "small"))]
+type HashMap<K, V> = std::collections::HashMap<K, V>;
+
+/// The vocabulary size of OpenAI's CLIP model, used for the bundled vocabulary data.
+#[cfg(any(test, feature = "openai-vocabulary-file"))]
+const DEFAULT_MAX_VOCABULARY_SIZE: u16 = 49408;
 
-use ahash::AHashMap;
-use regex::Regex;
+/// The context length used by the original CLIP models, see [`Tokenizer::context_length`].
+pub const CLIP_CONTEXT_LENGTH: usize = 77;
+
+/// The context length used by [Long-CLIP](https://github.com/beichenzbc/Long-CLIP) models, see
+/// [`TokenizerOptions::long_clip`].
+pub const LONG_CLIP_CONTEXT_LENGTH: usize = 248;
+
+/// The approximate number of bytes lowercased at once when only part of a text is encoded.
+const ENCODE_CHUNK_SIZE: usize = 4096;
+
+/// The environment variable checked by [`Tokenizer::new_with_overrides`].
+#[cfg(any(test, feature = "openai-vocabulary-file"))]
+const VOCABULARY_ENV_VAR: &str = "INSTANT_CLIP_TOKENIZER_VOCABULARY";
 
 /// A text tokenizer for the CLIP neural network.
 ///
 /// See the [module-level documentation](index.html) for more.
+///
+/// Cloning a `Tokenizer` is cheap, as the clone shares the parsed [`Vocabulary`] with the
+/// original.
+#[derive(Clone)]
 pub struct Tokenizer {
+    vocabulary: Arc<Vocabulary>,
+    lowercase: bool,
+    context_length: usize,
+    max_input_bytes: Option<usize>,
+    max_input_words: Option<usize>,
+    pre_tokenizer: PreTokenizer,
+    text_cache: Option<Arc<TextCache>>,
+}
+
+/// The parsed vocabulary data used by a [`Tokenizer`].
+///
+/// Parsing vocabulary data is expensive, so a `Vocabulary` obtained from an existing `Tokenizer`
+/// using [`Tokenizer::vocabulary`] can be shared between multiple tokenizers with different
+/// [`TokenizerOptions`] using [`Tokenizer::from_vocabulary`].
+pub struct Vocabulary {
     byte_to_token: Box<[Token; 256]>,
-    merge_rules: AHashMap<(Token, Token), Token>,
+    merge_rules: HashMap<(Token, Token), Token>,
     start_of_text: Token,
     end_of_text: Token,
-    decoder: AHashMap<Token, Vec<u8>>,
-    word_split: Regex,
+    /// Built on first use by [`Vocabulary::decoder`]
+    decoder: OnceCell<HashMap<Token, Vec<u8>>>,
+    word_boundary: WordBoundary,
+    vocab_size: u16,
+}
+
+impl Vocabulary {
+    /// Returns the bytes represented by every regular token, including a trailing `</w>` for
+    /// end-of-word tokens.
+    ///
+    /// Encoding doesn't need this table, so it is only built once it is first used, e.g. for
+    /// decoding. This saves startup time and memory for services that only ever encode.
+    fn decoder(&self) -> &HashMap<Token, Vec<u8>> {
+        self.decoder.get_or_init(|| {
+            let mut decoder = HashMap::default();
+            for (byte, &token) in self.byte_to_token.iter().enumerate() {
+                let byte = byte as u8;
+                decoder.insert(token, vec![byte]);
+                if self.word_boundary == WordBoundary::EndOfWordSuffix {
+                    let mut bytes = vec![byte];
+                    bytes.extend_from_slice(b"</w>");
+                    decoder.insert(Token(token.0 + 256), bytes);
+                }
+            }
+
+            // Merged tokens always have higher ids than the tokens they are merged from
+            let mut merge_rules = self
+                .merge_rules
+                .iter()
+                .map(|(&pair, &result_token)| (result_token, pair))
+                .collect::<Vec<_>>();
+            merge_rules.sort_unstable();
+            decoder.reserve(merge_rules.len());
+            for (result_token, (first, second)) in merge_rules {
+                let mut bytes = decoder[&first].clone();
+                bytes.extend_from_slice(&decoder[&second]);
+                decoder.insert(result_token, bytes);
+            }
+            decoder
+        })
+    }
 }
 
 impl Tokenizer {
     /// Create a new `Tokenizer` using the vocabulary data bundled with this crate.
     ///
-    /// The resulting `Tokenizer` is suitable for use with the original CLIP model.
+    /// The resulting `Tokenizer` is suitable for use with the original CLIP model. It currently
+    /// uses the vocabulary of [`VocabularyPreset::OpenAiV1`], use [`Tokenizer::from_preset`] to
+    /// pin the vocabulary version explicitly.
     ///
     /// Note that creating a new `Tokenizer` is expensive, so it is recommended to create the
     /// `Tokenizer` once and then reuse it.
     #[cfg(any(test, feature = "openai-vocabulary-file"))]
     pub fn new() -> Tokenizer {
-        static VOCABULARY_DATA: &str = include_str!("../bpe_simple_vocab_16e6.txt");
-        const MAX_VOCABULARY_SIZE: u16 = 49408;
-        Tokenizer::with_vocabulary(io::Cursor::new(VOCABULARY_DATA), MAX_VOCABULARY_SIZE)
-            .expect("bundled vocabulary data is valid")
+        // The vocabulary file in the binary format written by `Tokenizer::save_binary`, which is
+        // about 200 kB instead of 3.2 MB of text, and faster to load
+        static VOCABULARY_DATA: &[u8] = include_bytes!("../bpe_simple_vocab_16e6.bin");
+        Tokenizer::load_binary(VOCABULARY_DATA).expect("bundled vocabulary data is valid")
+    }
+
+    /// Create a new `Tokenizer` for [Long-CLIP](https://github.com/beichenzbc/Long-CLIP) models
+    /// using the vocabulary data bundled with this crate.
+    ///
+    /// Long-CLIP models use the same vocabulary as the original CLIP models, but a context length
+    /// of [`LONG_CLIP_CONTEXT_LENGTH`] tokens. See [`TokenizerOptions::long_clip`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::long_clip();
+    /// assert_eq!(tokenizer.context_length(), 248);
+    /// ```
+    #[cfg(any(test, feature = "openai-vocabulary-file"))]
+    pub fn long_clip() -> Tokenizer {
+        Tokenizer::from_vocabulary(Tokenizer::new().vocabulary(), TokenizerOptions::long_clip())
+    }
+
+    /// Create a new `Tokenizer`, allowing the vocabulary data to be overridden at runtime.
+    ///
+    /// If `vocabulary_path` is given, the vocabulary data is loaded from it. Otherwise, if the
+    /// `INSTANT_CLIP_TOKENIZER_VOCABULARY` environment variable is set, the vocabulary data is
+    /// loaded from the path it contains. If neither is the case, this works like [`new`] and uses
+    /// the vocabulary data bundled with this crate. This allows operators to swap out the
+    /// vocabulary without rebuilding the application.
+    ///
+    /// Vocabulary data is loaded using [`from_path`], so all formats supported by it can be used.
+    /// Vocabulary data in the format used by the original CLIP tokenizer is limited to the same
+    /// vocabulary size as the bundled data.
+    ///
+    /// [`new`]: Tokenizer::new
+    /// [`from_path`]: Tokenizer::from_path
+    ///
+    /// # Errors
+    ///
+    /// If an override is given but the vocabulary data can't be loaded from it, then an error is
+    /// returned. There is no silent fallback to the bundled data in this case.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::io;
+    /// # use std::path::Path;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// # fn main() -> io::Result<()> {
+    /// // Use the vocabulary from the environment, if any, or the bundled one
+    /// let tokenizer = Tokenizer::new_with_overrides(None)?;
+    /// // Use the vocabulary from the configuration
+    /// let tokenizer = Tokenizer::new_with_overrides(Some(Path::new("bpe_simple_vocab_16e6.txt")))?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(any(test, feature = "openai-vocabulary-file"))]
+    pub fn new_with_overrides(vocabulary_path: Option<&std::path::Path>) -> io::Result<Tokenizer> {
+        if let Some(path) = vocabulary_path {
+            return Tokenizer::from_path(path, DEFAULT_MAX_VOCABULARY_SIZE);
+        }
+        match std::env::var_os(VOCABULARY_ENV_VAR) {
+            Some(path) if !path.is_empty() => {
+                Tokenizer::from_path(path, DEFAULT_MAX_VOCABULARY_SIZE)
+            }
+            _ => Ok(Tokenizer::new()),
+        }
     }
 
     /// Create a new `Tokenizer` by reading the vocabulary data from `reader`.
@@ -100,9 +371,167 @@ impl Tokenizer {
         reader: impl BufRead,
         max_vocabulary_size: u16,
     ) -> io::Result<Tokenizer> {
-        let mut string_to_token = AHashMap::default();
+        Tokenizer::with_vocabulary_scheme(reader, max_vocabulary_size, WordBoundary::default())
+    }
+
+    /// Create a new `Tokenizer` by reading vocabulary data using the given word boundary scheme
+    /// from `reader`.
+    ///
+    /// This works like [`with_vocabulary`], but supports byte-level BPE vocabularies that don't
+    /// follow the `</w>` end-of-word convention of the original CLIP vocabulary, e.g. merge rules
+    /// using the `Ġ` space prefix of GPT-2 style vocabularies. See [`WordBoundary`] for the
+    /// supported schemes and how they affect the numbering of tokens.
+    ///
+    /// [`with_vocabulary`]: Tokenizer::with_vocabulary
+    ///
+    /// # Errors
+    ///
+    /// If the data format is incorrect or reading from `reader` fails, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Token, Tokenizer, WordBoundary};
+    /// let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+    /// let tokenizer =
+    ///     Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+    ///         .unwrap();
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode("the the", &mut tokens);
+    /// assert_eq!(tokenizer.decode(tokens.iter().copied()), "the the");
+    /// // Only the second word is preceded by a space, so only it is merged completely
+    /// let tokens = tokens.into_iter().map(Token::to_u16).collect::<Vec<_>>();
+    /// assert_eq!(tokens, [83, 257, 258]);
+    /// ```
+    pub fn with_vocabulary_scheme(
+        reader: impl BufRead,
+        max_vocabulary_size: u16,
+        word_boundary: WordBoundary,
+    ) -> io::Result<Tokenizer> {
+        Tokenizer::from_lines(
+            reader.lines(),
+            None,
+            max_vocabulary_size,
+            word_boundary,
+            |_| ControlFlow::Continue(()),
+        )
+    }
+
+    /// Create a new `Tokenizer` by reading the vocabulary data from `reader`, reporting progress.
+    ///
+    /// This works like [`with_vocabulary`], but calls `on_progress` after every merge rule read
+    /// from `reader`. This can be used to display progress information when loading large
+    /// vocabularies from slow storage. Returning [`ControlFlow::Break`] from `on_progress` cancels
+    /// loading the vocabulary.
+    ///
+    /// [`with_vocabulary`]: Tokenizer::with_vocabulary
+    ///
+    /// # Errors
+    ///
+    /// If the data format is incorrect, reading from `reader` fails, or loading was cancelled by
+    /// `on_progress`, then an error is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::fs::File;
+    /// # use std::io::{self, BufReader};
+    /// # use std::ops::ControlFlow;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// # fn main() -> io::Result<()> {
+    /// let f = BufReader::new(File::open("bpe_simple_vocab_16e6.txt")?);
+    /// let tokenizer = Tokenizer::with_vocabulary_progress(f, 49408, |progress| {
+    ///     if progress.merge_rules % 10_000 == 0 {
+    ///         println!("{} merge rules loaded ({} bytes)", progress.merge_rules, progress.bytes);
+    ///     }
+    ///     ControlFlow::Continue(())
+    /// })?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_vocabulary_progress(
+        reader: impl BufRead,
+        max_vocabulary_size: u16,
+        on_progress: impl FnMut(LoadProgress) -> ControlFlow<()>,
+    ) -> io::Result<Tokenizer> {
+        Tokenizer::from_lines(
+            reader.lines(),
+            None,
+            max_vocabulary_size,
+            WordBoundary::default(),
+            on_progress,
+        )
+    }
+
+    /// Create a new `Tokenizer` from vocabulary data that is already loaded into memory.
+    ///
+    /// This works like [`with_vocabulary`], but is more convenient (and slightly faster) when the
+    /// vocabulary data is available as a `&str`, e.g. because it was embedded into the binary
+    /// using `include_str!`.
+    ///
+    /// [`with_vocabulary`]: Tokenizer::with_vocabulary
+    ///
+    /// # Errors
+    ///
+    /// If the data format is incorrect, then an error is returned.
+    pub fn with_vocabulary_str(data: &str, max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        Tokenizer::from_lines(
+            data.lines().map(Ok),
+            Some(data.lines().count()),
+            max_vocabulary_size,
+            WordBoundary::default(),
+            |_| ControlFlow::Continue(()),
+        )
+    }
+
+    /// Create a new `Tokenizer` from vocabulary data that is already loaded into memory.
+    ///
+    /// This works like [`with_vocabulary_str`], but accepts raw bytes, e.g. as downloaded from the
+    /// network or embedded into the binary using `include_bytes!`.
+    ///
+    /// [`with_vocabulary_str`]: Tokenizer::with_vocabulary_str
+    ///
+    /// # Errors
+    ///
+    /// If `data` is not valid UTF-8 or the data format is incorrect, then an error is returned.
+    pub fn with_vocabulary_bytes(data: &[u8], max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+        let data = std::str::from_utf8(data)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Tokenizer::with_vocabulary_str(data, max_vocabulary_size)
+    }
+
+    /// Parse the vocabulary data given as `lines`, of which there are `expected_lines` if known.
+    fn from_lines<L: AsRef<str>>(
+        lines: impl Iterator<Item = io::Result<L>>,
+        expected_lines: Option<usize>,
+        max_vocabulary_size: u16,
+        word_boundary: WordBoundary,
+        mut on_progress: impl FnMut(LoadProgress) -> ControlFlow<()>,
+    ) -> io::Result<Tokenizer> {
+        let base_tokens = word_boundary.base_tokens();
+        if max_vocabulary_size < base_tokens + 2 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "maximum vocabulary size must be at least {}",
+                    base_tokens + 2
+                ),
+            ));
+        }
+        let end_of_word = word_boundary == WordBoundary::EndOfWordSuffix;
+
+        // Reserve the merge rules up front, as growing them would briefly hold both the old and the
+        // new allocation, which dominates the peak memory usage while loading. Without knowing the
+        // number of lines, reserve for the maximum vocabulary size and shrink afterwards.
+        let max_merge_rules = usize::from(max_vocabulary_size - base_tokens - 2);
+        let expected_merge_rules = expected_lines
+            .map_or(max_merge_rules, |lines| lines.saturating_sub(1))
+            .min(max_merge_rules);
+        let mut lookup = TokenLookup::with_capacity(
+            usize::from(base_tokens) + expected_merge_rules,
+            base_tokens,
+        );
         let mut byte_to_token = Box::new([Token(u16::MAX); 256]);
-        let mut byte_decoder = AHashMap::default();
         let r1 = b'!'..=b'~';
         let r2 = b'\xA1'..=b'\xAC'; // "¡" to "¬"
         let r3 = b'\xAE'..=b'\xFF'; // "®" to "ÿ"
@@ -110,14 +539,10 @@ impl Tokenizer {
         for byte in r1.chain(r2).chain(r3) {
             let token = Token(token_index);
             byte_to_token[usize::from(byte)] = token;
-            let ch = char::from(byte);
-            byte_decoder.insert(ch, byte);
-            // Add token and also its corresponding end-of-word token
-            string_to_token.insert(format!("{ch}"), token);
-            string_to_token.insert(format!("{ch}</w>"), Token(token.0 + 256));
+            lookup.insert_byte_token(char::from(byte), token, end_of_word);
             token_index += 1;
         }
-        for (idx, (byte, token)) in byte_to_token
+        for (idx, (_, token)) in byte_to_token
             .iter_mut()
             .enumerate()
             .filter(|(_, token)| **token == Token(u16::MAX))
@@ -125,25 +550,26 @@ impl Tokenizer {
         {
             *token = Token(token_index);
             let ch = char::from_u32(idx as u32 + 256).unwrap();
-            let byte = u8::try_from(byte).unwrap();
-            byte_decoder.insert(ch, byte);
-            string_to_token.insert(format!("{ch}"), *token);
-            string_to_token.insert(format!("{ch}</w>"), Token(token.0 + 256));
+            lookup.insert_byte_token(ch, *token, end_of_word);
             token_index += 1;
         }
 
         // For every increment of `token_index` above we actually also added the corresponding
         // end-of-word token, so we have to double `token_index` now in order for it to be correct
         // again.
-        token_index *= 2;
+        if end_of_word {
+            token_index *= 2;
+        }
 
-        let mut merge_rules = AHashMap::default();
-        for line in reader
-            .lines()
-            .skip(1)
-            .take((max_vocabulary_size - 512 - 2).into())
-        {
+        let mut merge_rules = HashMap::default();
+        merge_rules.reserve(expected_merge_rules);
+        let mut progress = LoadProgress {
+            merge_rules: 0,
+            bytes: 0,
+        };
+        for line in lines.skip(1).take(max_merge_rules) {
             let line = line?;
+            let line = line.as_ref();
             let mut parts = line.split_whitespace();
             let first = parts.next().ok_or(io::Error::new(
                 io::ErrorKind::Other,
@@ -153,45 +579,144 @@ impl Tokenizer {
                 io::ErrorKind::Other,
                 "lines must contain 2 tokens",
             ))?;
-            let first_token = *string_to_token
+            let first_token = lookup
                 .get(first)
                 .ok_or(io::Error::new(io::ErrorKind::Other, "invalid merge rule"))?;
-            let second_token = *string_to_token
+            let second_token = lookup
                 .get(second)
                 .ok_or(io::Error::new(io::ErrorKind::Other, "invalid merge rule"))?;
 
-            let result_token = Token(token_index);
-            merge_rules.insert((first_token, second_token), result_token);
-            string_to_token.insert(format!("{first}{second}"), result_token);
-            token_index += 1;
+            // Like in the reference implementation, a repeated merge rule is shadowed by its first
+            // occurrence
+            if let Entry::Vacant(entry) = merge_rules.entry((first_token, second_token)) {
+                let result_token = Token(token_index);
+                entry.insert(result_token);
+                lookup.insert_merged_token(
+                    first,
+                    second,
+                    (first_token, second_token),
+                    result_token,
+                );
+                token_index += 1;
+            }
+
+            progress.merge_rules += 1;
+            progress.bytes += line.len() + 1;
+            if on_progress(progress).is_break() {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "loading the vocabulary was cancelled",
+                ));
+            }
         }
 
-        // Note that the values we store in `decoder` are not necessarily valid UTF-8, so we have to
-        // use `Vec<u8>` for them.
-        let decoder = string_to_token
-            .into_iter()
-            .map(|(string, token)| (token, string.chars().map(|ch| byte_decoder[&ch]).collect()))
-            .collect();
-
-        let word_split = Regex::new(
-            r"(?x)
-                # Special substrings - these each get encoded as a single marker token
-                <start_of_text>|<end_of_text>|
-                # Common english contractions
-                's|'t|'re|'ve|'m|'ll|'d|
-                # Consecutive letters, single numbers, or runs of special chars
-                [\p{L}]+|[\p{N}]|[^\s\p{L}\p{N}]+",
-        )
-        .unwrap();
+        // The lookup table is only needed while loading, and the merge rules may have been
+        // reserved for more lines than the vocabulary file contains.
+        drop(lookup);
+        merge_rules.shrink_to_fit();
 
-        Ok(Tokenizer {
+        Tokenizer::from_tables(byte_to_token, merge_rules, token_index, word_boundary)
+    }
+
+    /// Assemble a `Tokenizer` from its tables, `token_index` being the number of regular tokens
+    /// preceding the special marker tokens.
+    ///
+    /// Vocabularies extended using [`extend_vocabulary`] have further regular tokens following the
+    /// special marker tokens, so that the ids of the existing tokens are preserved.
+    ///
+    /// [`extend_vocabulary`]: Tokenizer::extend_vocabulary
+    ///
+    /// Returns an error of kind [`io::ErrorKind::InvalidData`] if the token ids don't fit into a
+    /// `u16`.
+    fn from_tables(
+        byte_to_token: Box<[Token; 256]>,
+        merge_rules: HashMap<(Token, Token), Token>,
+        token_index: u16,
+        word_boundary: WordBoundary,
+    ) -> io::Result<Tokenizer> {
+        let too_many_tokens = || io::Error::new(io::ErrorKind::InvalidData, "too many tokens");
+        // The special marker tokens are the last tokens, unless the vocabulary was extended
+        let end = token_index.checked_add(2).ok_or_else(too_many_tokens)?;
+        let vocab_size = merge_rules
+            .values()
+            .try_fold(end, |size, token| Some(size.max(token.0.checked_add(1)?)))
+            .ok_or_else(too_many_tokens)?;
+        let vocabulary = Vocabulary {
             byte_to_token,
             merge_rules,
             start_of_text: Token(token_index),
             end_of_text: Token(token_index + 1),
-            decoder,
-            word_split,
-        })
+            decoder: OnceCell::new(),
+            word_boundary,
+            vocab_size,
+        };
+        Ok(Tokenizer::from_vocabulary(
+            &Arc::new(vocabulary),
+            TokenizerOptions::new(),
+        ))
+    }
+
+    /// Create a new `Tokenizer` using an already parsed `vocabulary`.
+    ///
+    /// This is cheap compared to the other ways of creating a `Tokenizer`, as the vocabulary data
+    /// doesn't have to be parsed again. It can be used to create multiple tokenizers with
+    /// different `options` sharing the same vocabulary.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, TokenizerOptions};
+    /// let tokenizer = Tokenizer::new();
+    /// let cased = Tokenizer::from_vocabulary(
+    ///     tokenizer.vocabulary(),
+    ///     TokenizerOptions::new().lowercase(false),
+    /// );
+    /// let (mut lowercased_tokens, mut cased_tokens) = (Vec::new(), Vec::new());
+    /// tokenizer.encode("Hi", &mut lowercased_tokens);
+    /// cased.encode("Hi", &mut cased_tokens);
+    /// assert_ne!(lowercased_tokens, cased_tokens);
+    /// ```
+    pub fn from_vocabulary(vocabulary: &Arc<Vocabulary>, options: TokenizerOptions) -> Tokenizer {
+        Tokenizer {
+            vocabulary: Arc::clone(vocabulary),
+            lowercase: options.lowercase,
+            context_length: options.context_length,
+            max_input_bytes: options.max_input_bytes,
+            max_input_words: options.max_input_words,
+            pre_tokenizer: options.pre_tokenizer,
+            text_cache: match options.text_cache {
+                0 => None,
+                capacity => Some(Arc::new(TextCache::new(capacity))),
+            },
+        }
+    }
+
+    /// Returns the vocabulary used by this `Tokenizer`.
+    ///
+    /// See [`from_vocabulary`] for how to share it with other tokenizers.
+    ///
+    /// [`from_vocabulary`]: Tokenizer::from_vocabulary
+    pub fn vocabulary(&self) -> &Arc<Vocabulary> {
+        &self.vocabulary
+    }
+
+    /// Returns the scheme used to represent word boundaries in the vocabulary of this `Tokenizer`.
+    pub fn word_boundary(&self) -> WordBoundary {
+        self.vocabulary.word_boundary
+    }
+
+    /// Returns how texts are split into words, see [`TokenizerOptions::pre_tokenizer`].
+    pub fn pre_tokenizer(&self) -> PreTokenizer {
+        self.pre_tokenizer
+    }
+
+    /// Returns the context length of the model this `Tokenizer` is intended for.
+    ///
+    /// This is [`CLIP_CONTEXT_LENGTH`] unless configured otherwise using
+    /// [`TokenizerOptions::context_length`], and is the recommended value for the
+    /// `context_length` argument of `tokenize_batch` and related methods.
+    pub fn context_length(&self) -> usize {
+        self.context_length
     }
 
     /// Tokenize a batch of multiple input strings.
@@ -204,19 +729,28 @@ impl Tokenizer {
     /// The individual input strings are lowercased before being tokenized, but otherwise no
     /// pre-processing is performed.
     ///
-    /// `context_length` is the maximum number of tokens per each text and should be `77` for all
-    /// current CLIP models. If tokenization results in less than `context_length` tokens the
-    /// resulting row will be padded with trailing zeros. If tokenizing an input text results in too
-    /// many tokens, the token sequence will be truncated to fit within the resulting row of length
-    /// `context_length`, always including the `<start_of_text>` and `<end_of_text>` marker tokens.
+    /// `context_length` is the maximum number of tokens per each text and should usually be
+    /// [`context_length`], which is `77` for the original CLIP models. If tokenization results in
+    /// less than `context_length` tokens the resulting row will be padded with trailing zeros. If
+    /// tokenizing an input text results in too many tokens, the token sequence will be truncated to
+    /// fit within the resulting row of length `context_length`, always including the
+    /// `<start_of_text>` and `<end_of_text>` marker tokens.
     ///
     /// The resulting matrix can be passed directly to the CLIP neural network.
     ///
+    /// Input strings occurring multiple times in `texts` (as is common e.g. for prompts expanded
+    /// from templates) are only tokenized once, with the resulting row copied for every repetition.
+    ///
     /// [`encode`]: Tokenizer::encode
+    /// [`context_length`]: Tokenizer::context_length
     ///
     /// # Panics
     ///
-    /// Panics if `context_length < 3`.
+    /// Panics if `context_length < 3`, or one of the `texts` exceeds the input limits configured
+    /// using [`TokenizerOptions`]. Use [`try_tokenize_batch`] to handle these cases without
+    /// panicking.
+    ///
+    /// [`try_tokenize_batch`]: Tokenizer::try_tokenize_batch
     ///
     /// # Examples
     ///
@@ -232,208 +766,2740 @@ impl Tokenizer {
     /// ```
     #[cfg(feature = "ndarray")]
     pub fn tokenize_batch<'a, I>(&self, texts: I, context_length: usize) -> ndarray::Array2<u16>
+    where
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        self.try_tokenize_batch(texts, context_length)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Tokenize a batch of multiple input strings, returning an error instead of panicking.
+    ///
+    /// This works like [`tokenize_batch`], but returns an error instead of panicking if
+    /// `context_length < 3` or one of the `texts` exceeds the input limits.
+    ///
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    ///
+    /// # Errors
+    ///
+    /// If `context_length < 3`, or one of the `texts` exceeds the input limits (see
+    /// [`try_encode`]), then an error of kind [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// [`try_encode`]: Tokenizer::try_encode
+    #[cfg(feature = "ndarray")]
+    pub fn try_tokenize_batch<'a, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+    ) -> io::Result<ndarray::Array2<u16>>
+    where
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        self.tokenize_batch_with(texts, context_length, &EncodeOptions::new())
+    }
+
+    /// Tokenize a batch of multiple input strings using the given `options`.
+    ///
+    /// This works like [`try_tokenize_batch`], but encodes each text using [`encode_with`] and the
+    /// given `options` instead of [`encode`]. If `options` limits the number of tokens per text,
+    /// the resulting rows contain at most that many tokens, plus the marker tokens.
+    ///
+    /// [`try_tokenize_batch`]: Tokenizer::try_tokenize_batch
+    /// [`encode_with`]: Tokenizer::encode_with
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Errors
+    ///
+    /// If `context_length < 3`, or one of the `texts` exceeds the input limits (see
+    /// [`try_encode`]), then an error of kind [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// [`try_encode`]: Tokenizer::try_encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ndarray::array;
+    /// # use instant_clip_tokenizer::{EncodeOptions, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let options = EncodeOptions::new().max_tokens(2);
+    /// let encoded = tokenizer.tokenize_batch_with(["How are you?"], 6, &options).unwrap();
+    /// assert_eq!(encoded, array![[49406, 829, 631, 49407, 0, 0]]);
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn tokenize_batch_with<'a, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+        options: &EncodeOptions<'_>,
+    ) -> io::Result<ndarray::Array2<u16>>
     where
         I: IntoIterator<Item = &'a str>,
         I::IntoIter: std::iter::ExactSizeIterator,
     {
         if context_length < 3 {
-            panic!("context length must be at least 3");
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "context length must be at least 3",
+            ));
         }
+        let timer = telemetry::BatchTimer::start();
         let texts = texts.into_iter();
         let mut result = ndarray::Array2::zeros((texts.len(), context_length));
-        let mut tokens = Vec::with_capacity(context_length);
-        for (text, mut result_row) in texts.zip(result.rows_mut()) {
-            tokens.clear();
-            tokens.push(self.start_of_text());
-            self.encode(text, &mut tokens);
-            tokens.truncate(context_length - 1);
-            tokens.push(self.end_of_text());
-            for (token, result_element) in tokens.iter().zip(&mut result_row) {
-                *result_element = token.to_u16();
-            }
-        }
-        result
+        // Running out of the work budget leaves the tokens encoded so far as a partial result
+        self.fill_rows(texts, &mut result, options, true)?;
+        timer.finish();
+        Ok(result)
     }
 
-    /// Encode a `text` input as a sequence of tokens.
+    /// Tokenize a batch of multiple input strings using the given `options`, reporting errors per
+    /// text instead of failing the whole batch.
     ///
-    /// The resulting tokens are appended to `out`. `text` is lowercased before being tokenized, but
-    /// otherwise no pre-processing is performed.
+    /// This works like [`tokenize_batch_with`], but texts that exceed the input limits (see
+    /// [`try_encode`]) or the work budget set in `options` (see [`try_encode_with`]) don't cause an
+    /// error for the whole batch. Instead, their rows are filled with zeros, and a [`RowError`] is
+    /// returned for each such text, in order. This allows data pipelines to
+    /// quarantine bad texts while still processing the rest of the batch.
     ///
-    /// The encoded token sequence does not include the special `<start_of_text>` and
-    /// `<end_of_text>` marker tokens. When these are needed you can either use the `tokenize_batch`
-    /// method instead, or add them manually by using the [`start_of_text`] and [`end_of_text`]
-    /// methods, as in the example below.
+    /// [`tokenize_batch_with`]: Tokenizer::tokenize_batch_with
+    /// [`try_encode`]: Tokenizer::try_encode
+    /// [`try_encode_with`]: Tokenizer::try_encode_with
     ///
-    /// [`start_of_text`]: Tokenizer::start_of_text
-    /// [`end_of_text`]: Tokenizer::end_of_text
+    /// # Errors
+    ///
+    /// If `context_length < 3`, then an error of kind [`io::ErrorKind::InvalidInput`] is returned.
     ///
     /// # Examples
     ///
     /// ```
-    /// # use instant_clip_tokenizer::{Token, Tokenizer};
-    /// let tokenizer = Tokenizer::new();
-    /// let mut tokens = vec![tokenizer.start_of_text()];
-    /// tokenizer.encode("Hi there", &mut tokens);
-    /// tokens.push(tokenizer.end_of_text());
-    /// let tokens = tokens.into_iter().map(Token::to_u16).collect::<Vec<_>>();
-    /// assert_eq!(tokens, [49406, 1883, 997, 49407]);
+    /// # use ndarray::array;
+    /// # use instant_clip_tokenizer::{EncodeOptions, Tokenizer, TokenizerOptions};
+    /// let tokenizer = Tokenizer::from_vocabulary(
+    ///     Tokenizer::new().vocabulary(),
+    ///     TokenizerOptions::new().max_input_bytes(16),
+    /// );
+    /// let texts = ["Hi", "A very long caption", "Hi there"];
+    /// let (encoded, errors) = tokenizer
+    ///     .tokenize_batch_with_errors(texts, 5, &EncodeOptions::new())
+    ///     .unwrap();
+    /// assert_eq!(
+    ///     encoded,
+    ///     array![
+    ///         [49406, 1883, 49407, 0, 0],
+    ///         [0, 0, 0, 0, 0],
+    ///         [49406, 1883, 997, 49407, 0],
+    ///     ]
+    /// );
+    /// assert_eq!(errors.len(), 1);
+    /// assert_eq!(errors[0].row, 1);
     /// ```
-    pub fn encode(&self, text: &str, out: &mut Vec<Token>) {
-        let text = text.to_lowercase();
-        out.reserve(text.as_bytes().len());
-        let words = self.word_split.find_iter(&text).map(|m| m.as_str());
-        for word in words {
-            if word == "<start_of_text>" {
-                out.push(self.start_of_text());
-                continue;
-            } else if word == "<end_of_text>" {
-                out.push(self.end_of_text());
-                continue;
-            }
-
-            let start_index = out.len();
-            out.extend(
-                word.as_bytes()
-                    .iter()
-                    .map(|b| self.byte_to_token[usize::from(*b)]),
-            );
-            if start_index < out.len() {
-                // If we added anything, mark last character as end-of-word token
-                out.last_mut().unwrap().0 += 256;
+    #[cfg(feature = "ndarray")]
+    pub fn tokenize_batch_with_errors<'a, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+        options: &EncodeOptions<'_>,
+    ) -> io::Result<(ndarray::Array2<u16>, Vec<RowError>)>
+    where
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        if context_length < 3 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "context length must be at least 3",
+            ));
+        }
+        let timer = telemetry::BatchTimer::start();
+        let texts = texts.into_iter();
+        let mut result = ndarray::Array2::zeros((texts.len(), context_length));
+        let rows = self.fill_rows(texts, &mut result, options, false)?;
+        let mut errors = Vec::<RowError>::new();
+        for (row, encoded) in rows.into_iter().enumerate() {
+            if let Err(error) = encoded {
+                result.row_mut(row).fill(0);
+                errors.push(RowError { row, error });
             }
-            self.apply_merge_rules(start_index, out);
         }
+        timer.finish();
+        Ok((result, errors))
     }
 
-    fn apply_merge_rules(&self, start_index: usize, tokens: &mut Vec<Token>) {
-        loop {
-            let Some(((first, second), result_token)) = tokens[start_index..]
-                .windows(2)
-                .map(|pair| (pair[0], pair[1]))
-                .filter_map(|pair| {
-                    self.merge_rules
-                        .get(&pair)
-                        .map(|result_token| (pair, *result_token))
-                })
-                .min_by_key(|&(_, result_token)| result_token)
-            else {
-                // No merge rules left to apply -> we're done
-                break;
-            };
+    /// Encode each of `texts` into the corresponding row of `result` using `options`, returning
+    /// the number of tokens written and whether the text was truncated, or the error, for each
+    /// row.
+    ///
+    /// A text exceeding the input limits fails the whole batch if `fail_fast` is set, otherwise
+    /// only its row. Texts occurring multiple times in the batch are only encoded once, the later
+    /// rows are copied from the row of the first occurrence, along with its result.
+    #[cfg(feature = "ndarray")]
+    fn fill_rows<'a>(
+        &self,
+        texts: impl Iterator<Item = &'a str>,
+        result: &mut ndarray::Array2<u16>,
+        options: &EncodeOptions<'_>,
+        fail_fast: bool,
+    ) -> io::Result<Vec<io::Result<(usize, bool)>>> {
+        let mut rows = Vec::<io::Result<(usize, bool)>>::with_capacity(result.nrows());
+        let mut seen = HashMap::<&str, usize>::default();
+        for (row, text) in texts.enumerate() {
+            if let Some(&first) = seen.get(text) {
+                let (first_row, mut result_row) =
+                    result.multi_slice_mut((ndarray::s![first, ..], ndarray::s![row, ..]));
+                result_row.assign(&first_row);
+                // Duplicates of a failed text fail the same way
+                let encoded = match &rows[first] {
+                    Ok(encoded) => Ok(*encoded),
+                    Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+                };
+                rows.push(encoded);
+                continue;
+            }
+            seen.insert(text, row);
 
-            // Reduce all occurences of this pair to `result_token`
-            let mut i = start_index;
-            while i < tokens.len() - 1 {
-                if tokens[i] == first && tokens[i + 1] == second {
-                    tokens[i] = result_token;
-                    tokens.remove(i + 1);
+            let encoded = match self.check_input_limits(text) {
+                Err(err) if fail_fast => {
+                    return Err(io::Error::new(err.kind(), format!("text {row}: {err}")));
                 }
-                i += 1;
-            }
+                Err(err) => Err(err),
+                Ok(()) => {
+                    let mut result_row = result.row_mut(row);
+                    // Rows of a freshly allocated array are always contiguous
+                    let result_row = result_row.as_slice_mut().unwrap();
+                    let (length, truncated) =
+                        self.encode_row_with_budget(text, result_row, options);
+                    truncated.map(|truncated| (length, truncated))
+                }
+            };
+            rows.push(encoded);
         }
+        Ok(rows)
     }
 
-    /// Convert a sequence of `tokens` back to a textual representation.
+    /// Encode `text` into a single `row` of a batch, as done by [`tokenize_batch_with`].
     ///
-    /// Due to the way whitespace and lowercasing is handled a sequence of tokens will not always be
-    /// decoded back to the exact same text that `encode` was called with, in other words,
-    /// `decode(encode(text)) == text` does not always hold true. Hence, this function is mostly
-    /// useful for debugging purposes.
+    /// The tokens are written directly into `row`, discarding tokens that don't fit while
+    /// encoding, and the remainder of the row is filled with padding zeros, so that rows can be
+    /// reused without clearing them first.
     ///
-    /// # Examples
+    /// [`tokenize_batch_with`]: Tokenizer::tokenize_batch_with
+    #[cfg(any(feature = "dataset", feature = "polars"))]
+    pub(crate) fn encode_row(&self, text: &str, row: &mut [u16], options: &EncodeOptions<'_>) {
+        // Running out of the work budget leaves the tokens encoded so far as a partial result
+        let _ = self.encode_row_with_budget(text, row, options);
+    }
+
+    /// Encode `text` into a single `row` like [`encode_row`], returning an error if the work
+    /// budget set in `options` is exhausted.
     ///
-    /// ```
-    /// # use instant_clip_tokenizer::Tokenizer;
+    /// Returns the number of tokens written, including the marker tokens, along with whether the
+    /// text was truncated, or the error if the work budget was exhausted. In that case the row
+    /// holds the tokens encoded so far.
+    ///
+    /// [`encode_row`]: Tokenizer::encode_row
+    #[cfg(any(feature = "ndarray", feature = "dataset", feature = "polars"))]
+    fn encode_row_with_budget(
+        &self,
+        text: &str,
+        row: &mut [u16],
+        options: &EncodeOptions<'_>,
+    ) -> (usize, io::Result<bool>) {
+        // Leave room for the marker tokens
+        let capacity = row.len() - 2;
+        let options = EncodeOptions {
+            max_tokens: Some(options.max_tokens.map_or(capacity, |max| max.min(capacity))),
+            ..*options
+        };
+        row[0] = self.start_of_text().to_u16();
+        let mut sink = RowSink {
+            row: &mut row[1..=capacity],
+            len: 0,
+        };
+        let result = self.encode_with_budget(text, &mut sink, &options);
+        let end = 1 + sink.len;
+        row[end] = self.end_of_text().to_u16();
+        row[end + 1..].fill(0);
+        (end + 1, result)
+    }
+
+    /// Tokenize a batch of multiple input strings, also returning the corresponding attention
+    /// mask.
+    ///
+    /// This works like [`tokenize_batch`], but converts the resulting token ids to the numeric type
+    /// `T` expected by the model (usually `i64` or `i32` for CLIP text models exported to ONNX)
+    /// and additionally returns an attention mask of the same shape. The attention mask contains
+    /// `1` for every position holding an actual token (including the `<start_of_text>` and
+    /// `<end_of_text>` marker tokens) and `0` for every padding position.
+    ///
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 3`, or one of the `texts` exceeds the input limits configured
+    /// using [`TokenizerOptions`]. Use [`try_tokenize_batch_with_attention_mask`] to handle these
+    /// cases without panicking.
+    ///
+    /// [`try_tokenize_batch_with_attention_mask`]: Tokenizer::try_tokenize_batch_with_attention_mask
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ndarray::array;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let (input_ids, attention_mask) =
+    ///     tokenizer.tokenize_batch_with_attention_mask::<i64, _>(["Hi"], 5);
+    /// assert_eq!(input_ids, array![[49406, 1883, 49407, 0, 0]]);
+    /// assert_eq!(attention_mask, array![[1, 1, 1, 0, 0]]);
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn tokenize_batch_with_attention_mask<'a, T, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+    ) -> (ndarray::Array2<T>, ndarray::Array2<T>)
+    where
+        T: Copy + From<u16>,
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        self.try_tokenize_batch_with_attention_mask(texts, context_length)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Tokenize a batch of multiple input strings, also returning the corresponding attention
+    /// mask, returning an error instead of panicking.
+    ///
+    /// This works like [`tokenize_batch_with_attention_mask`], but returns an error instead of
+    /// panicking if `context_length < 3` or one of the `texts` exceeds the input limits.
+    ///
+    /// [`tokenize_batch_with_attention_mask`]: Tokenizer::tokenize_batch_with_attention_mask
+    ///
+    /// # Errors
+    ///
+    /// If `context_length < 3`, or one of the `texts` exceeds the input limits (see
+    /// [`try_encode`]), then an error of kind [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// [`try_encode`]: Tokenizer::try_encode
+    #[cfg(feature = "ndarray")]
+    pub fn try_tokenize_batch_with_attention_mask<'a, T, I>(
+        &self,
+        texts: I,
+        context_length: usize,
+    ) -> io::Result<(ndarray::Array2<T>, ndarray::Array2<T>)>
+    where
+        T: Copy + From<u16>,
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        let batch = self.try_tokenize_batch_full(texts, context_length)?;
+        Ok((batch.input_ids, batch.attention_mask))
+    }
+
+    /// Tokenize a stream of input strings in batches of `batch_size` texts.
+    ///
+    /// This works like [`tokenize_batch`], but consumes `texts` lazily, yielding a matrix of shape
+    /// `(batch_size, context_length)` for every `batch_size` texts. Only a single batch of texts is
+    /// held in memory at a time, so `texts` may be an unbounded stream, e.g. captions read from a
+    /// file or received over the network. The last matrix has fewer rows if the number of texts is
+    /// not a multiple of `batch_size`.
+    ///
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 3` or `batch_size` is zero. When the iterator is advanced, it
+    /// panics if one of the `texts` exceeds the input limits configured using
+    /// [`TokenizerOptions`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ndarray::array;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let texts = ["Hi", "How are you?", "Hi"].map(String::from);
+    /// let mut batches = tokenizer.tokenize_batches(texts, 5, 2);
+    /// assert_eq!(batches.next().unwrap(), array![
+    ///     [49406, 1883, 49407, 0, 0],
+    ///     [49406, 829, 631, 592, 49407],
+    /// ]);
+    /// assert_eq!(batches.next().unwrap(), array![[49406, 1883, 49407, 0, 0]]);
+    /// assert_eq!(batches.next(), None);
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn tokenize_batches<'a, I>(
+        &'a self,
+        texts: I,
+        context_length: usize,
+        batch_size: usize,
+    ) -> impl Iterator<Item = ndarray::Array2<u16>> + 'a
+    where
+        I: IntoIterator,
+        I::IntoIter: 'a,
+        I::Item: AsRef<str> + 'a,
+    {
+        assert!(context_length >= 3, "context length must be at least 3");
+        assert!(batch_size > 0, "batch size must be greater than zero");
+        let mut texts = texts.into_iter();
+        let mut batch = Vec::with_capacity(batch_size);
+        iter::from_fn(move || {
+            batch.clear();
+            batch.extend(texts.by_ref().take(batch_size));
+            match batch.is_empty() {
+                true => None,
+                false => Some(self.tokenize_batch(batch.iter().map(AsRef::as_ref), context_length)),
+            }
+        })
+    }
+
+    /// Tokenize a batch of multiple input strings without padding.
+    ///
+    /// Each given input string is encoded using the [`encode`] method, with the special
+    /// `<start_of_text>` token prepended, and `<end_of_text>` appended to each text. The numeric
+    /// representations of all texts are concatenated into a single flat buffer, which is returned
+    /// together with the number of tokens for each text. Unlike [`tokenize_batch`], no padding or
+    /// truncation is performed.
+    ///
+    /// This is useful for consumers working with ragged or nested tensors, or doing their own
+    /// packing of token sequences, since padding every text to the full context length wastes a lot
+    /// of memory when most texts are short.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let (tokens, lengths) = tokenizer.tokenize_batch_ragged(["Hi", "How are you?"]);
+    /// assert_eq!(tokens, [49406, 1883, 49407, 49406, 829, 631, 592, 286, 49407]);
+    /// assert_eq!(lengths, [3, 6]);
+    /// ```
+    pub fn tokenize_batch_ragged<'a>(
+        &self,
+        texts: impl IntoIterator<Item = &'a str>,
+    ) -> (Vec<u16>, Vec<usize>) {
+        let timer = telemetry::BatchTimer::start();
+        let mut result = Vec::new();
+        let mut lengths = Vec::new();
+        let mut tokens = Vec::new();
+        for text in texts {
+            tokens.clear();
+            tokens.push(self.start_of_text());
+            self.encode(text, &mut tokens);
+            tokens.push(self.end_of_text());
+            result.extend(tokens.iter().map(|token| token.to_u16()));
+            lengths.push(tokens.len());
+        }
+        timer.finish();
+        (result, lengths)
+    }
+
+    /// Encode a `text` input as a sequence of tokens.
+    ///
+    /// The resulting tokens are appended to `out`, which can be any collection implementing
+    /// `Extend<Token>`, e.g. a `Vec<Token>` or a stack-allocated buffer such as a `SmallVec`.
+    /// `text` is lowercased before being tokenized (unless disabled using
+    /// [`TokenizerOptions::lowercase`]), but otherwise no pre-processing is performed.
+    ///
+    /// The encoded token sequence does not include the special `<start_of_text>` and
+    /// `<end_of_text>` marker tokens. When these are needed you can either use the `tokenize_batch`
+    /// method instead, or add them manually by using the [`start_of_text`] and [`end_of_text`]
+    /// methods, as in the example below.
+    ///
+    /// [`start_of_text`]: Tokenizer::start_of_text
+    /// [`end_of_text`]: Tokenizer::end_of_text
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Token, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let mut tokens = vec![tokenizer.start_of_text()];
+    /// tokenizer.encode("Hi there", &mut tokens);
+    /// tokens.push(tokenizer.end_of_text());
+    /// let tokens = tokens.into_iter().map(Token::to_u16).collect::<Vec<_>>();
+    /// assert_eq!(tokens, [49406, 1883, 997, 49407]);
+    /// ```
+    pub fn encode(&self, text: &str, out: &mut impl Extend<Token>) {
+        self.encode_with(text, out, &EncodeOptions::new());
+    }
+
+    /// Encode a `text` input given as bytes, which don't need to be valid UTF-8, as a sequence of
+    /// tokens.
+    ///
+    /// The valid UTF-8 parts of `text` are encoded like [`encode`] does. Invalid byte sequences
+    /// are encoded as separate words made of their raw bytes instead of being replaced by
+    /// `U+FFFD`, so the tokens represent exactly the bytes of `text`. This is useful for caption
+    /// dumps containing lines with broken encodings, where decoding with a replacement character
+    /// beforehand would change what the model sees.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
     /// let tokenizer = Tokenizer::new();
     /// let mut tokens = Vec::new();
-    /// tokenizer.encode("Hello world!!!", &mut tokens);
-    /// let decoded = tokenizer.decode(tokens);
-    /// assert_eq!(decoded, "hello world !!! ");
+    /// tokenizer.encode_bytes(b"Caf\xe9 au lait", &mut tokens);
+    /// assert_eq!(tokenizer.decode(tokens), "caf \u{FFFD} au lait ");
     /// ```
-    pub fn decode(&self, tokens: impl IntoIterator<Item = Token>) -> String {
-        let bytes = tokens
-            .into_iter()
-            .flat_map(|token| {
-                if token == self.start_of_text {
-                    "<start_of_text>".as_bytes()
-                } else if token == self.end_of_text {
-                    "<end_of_text>".as_bytes()
-                } else {
-                    &self.decoder[&token]
+    pub fn encode_bytes(&self, text: &[u8], out: &mut impl Extend<Token>) {
+        let mut rest = text;
+        let mut word_tokens = Vec::new();
+        while !rest.is_empty() {
+            let err = match str::from_utf8(rest) {
+                Ok(valid) => return self.encode(valid, out),
+                Err(err) => err,
+            };
+            let (valid, invalid) = rest.split_at(err.valid_up_to());
+            let valid = str::from_utf8(valid).expect("valid up to the error");
+            if !valid.is_empty() {
+                self.encode(valid, out);
+            }
+
+            // An incomplete sequence at the end of `text` has no error length
+            let len = err.error_len().unwrap_or(invalid.len());
+            let follows_whitespace = valid.ends_with(char::is_whitespace);
+            self.initial_word_tokens(&invalid[..len], follows_whitespace, &mut word_tokens);
+            self.apply_merge_rules(&mut word_tokens);
+            out.extend(word_tokens.iter().copied());
+            rest = &invalid[len..];
+        }
+    }
+
+    /// Encode a `text` input as a sequence of tokens using the given `options`.
+    ///
+    /// This works like [`encode`], but allows varying the encoding behavior per call, see
+    /// [`EncodeOptions`] for the available options.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{EncodeOptions, Token, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let options = EncodeOptions::new().special_markers(false).max_tokens(3);
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode_with("Hi<end_of_text> there", &mut tokens, &options);
+    /// let tokens = tokens.into_iter().map(Token::to_u16).collect::<Vec<_>>();
+    /// assert_eq!(tokens, [1883, 27, 945]);
+    /// ```
+    pub fn encode_with(
+        &self,
+        text: &str,
+        out: &mut impl Extend<Token>,
+        options: &EncodeOptions<'_>,
+    ) {
+        // Running out of the work budget leaves the tokens encoded so far as a partial result
+        let _ = self.encode_with_budget(text, out, options);
+    }
+
+    /// Encode `text` like [`encode_with`], stopping once the work budget set in `options` is
+    /// exhausted.
+    ///
+    /// Only complete words are added to `out`, so when an error is returned `out` holds the tokens
+    /// of the words preceding the one being encoded when the budget ran out.
+    ///
+    /// Returns whether tokens were discarded to stay within `options.max_tokens`.
+    ///
+    /// [`encode_with`]: Tokenizer::encode_with
+    fn encode_with_budget(
+        &self,
+        text: &str,
+        out: &mut impl Extend<Token>,
+        options: &EncodeOptions<'_>,
+    ) -> io::Result<bool> {
+        let max_tokens = options.max_tokens.unwrap_or(usize::MAX);
+        let (len, truncated) = match &self.text_cache {
+            Some(cache) if options.is_default_encoding() && text.len() <= MAX_CACHED_TEXT_BYTES => {
+                let tokens = cache.get_or_insert(text, || {
+                    let mut tokens = Vec::new();
+                    let options = EncodeOptions {
+                        max_tokens: None,
+                        ..*options
+                    };
+                    self.encode_uncached(text, &mut tokens, &options)?;
+                    Ok(tokens)
+                })?;
+                out.extend(tokens.iter().take(max_tokens).copied());
+                (tokens.len().min(max_tokens), tokens.len() > max_tokens)
+            }
+            _ => self.encode_uncached(text, out, options)?,
+        };
+        telemetry::record_text(len, truncated);
+        Ok(truncated)
+    }
+
+    /// Encodes `text` without consulting the text cache, returning the number of tokens added to
+    /// `out` and whether the text was truncated.
+    fn encode_uncached(
+        &self,
+        text: &str,
+        out: &mut impl Extend<Token>,
+        options: &EncodeOptions<'_>,
+    ) -> io::Result<(usize, bool)> {
+        let text = match options.normalize {
+            Some(normalize) => Cow::Owned(normalize(text)),
+            None => Cow::Borrowed(text),
+        };
+        let text = match options.lowercase.unwrap_or(self.lowercase) {
+            true => Cow::Owned(text.to_lowercase()),
+            false => text,
+        };
+        let max_tokens = options.max_tokens.unwrap_or(usize::MAX);
+        let mut remaining = max_tokens;
+        let mut truncated = false;
+        let mut merges = 0;
+        let mut word_tokens = Vec::new();
+        for (word, _) in self.split_words(&text, options.special_markers) {
+            if remaining == 0 {
+                truncated = true;
+                break;
+            }
+            if options
+                .deadline
+                .map_or(false, |deadline| Instant::now() >= deadline)
+            {
+                return Err(deadline_exceeded());
+            }
+            let (word, follows_whitespace) = match word {
+                Word::Marker(token) => {
+                    out.extend([token]);
+                    remaining -= 1;
+                    continue;
                 }
-            })
-            .copied()
-            .collect::<Vec<_>>();
+                Word::Text {
+                    word,
+                    follows_whitespace,
+                } => (word, follows_whitespace),
+            };
 
-        String::from_utf8_lossy(&bytes).replace("</w>", " ")
+            self.initial_word_tokens(word.as_bytes(), follows_whitespace, &mut word_tokens);
+            let mut exceeded = None;
+            let _ = self.apply_merge_rules_with(&mut word_tokens, |_, _, _, merged, _| {
+                merges += merged;
+                if let Some(max_merges) = options.max_merges.filter(|&max| merges > max) {
+                    exceeded = Some(max_merges_exceeded(max_merges));
+                } else if options
+                    .deadline
+                    .map_or(false, |deadline| Instant::now() >= deadline)
+                {
+                    exceeded = Some(deadline_exceeded());
+                }
+                match exceeded {
+                    Some(_) => ControlFlow::Break(()),
+                    None => ControlFlow::Continue(()),
+                }
+            });
+            if let Some(err) = exceeded {
+                return Err(err);
+            }
+            truncated |= word_tokens.len() > remaining;
+            word_tokens.truncate(remaining);
+            remaining -= word_tokens.len();
+            out.extend(word_tokens.iter().copied());
+        }
+        Ok((max_tokens - remaining, truncated))
     }
 
-    /// Returns the special `<start_of_text>` marker token.
+    /// Encode a `text` input as a sequence of tokens, unless it exceeds the input limits.
     ///
-    /// See [`encode`] for an example about how to add this token to a token sequence.
+    /// This works like [`encode`], but first checks `text` against the limits configured using
+    /// [`TokenizerOptions::max_input_bytes`] and [`TokenizerOptions::max_input_words`].
     ///
     /// [`encode`]: Tokenizer::encode
-    pub fn start_of_text(&self) -> Token {
-        self.start_of_text
+    ///
+    /// # Errors
+    ///
+    /// If `text` exceeds one of the limits, then an error of kind [`io::ErrorKind::InvalidInput`]
+    /// is returned, and nothing is added to `out`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, TokenizerOptions};
+    /// let tokenizer = Tokenizer::from_vocabulary(
+    ///     Tokenizer::new().vocabulary(),
+    ///     TokenizerOptions::new().max_input_words(3),
+    /// );
+    /// let mut tokens = Vec::new();
+    /// assert!(tokenizer.try_encode("Hi there", &mut tokens).is_ok());
+    /// assert!(tokenizer.try_encode("Hi there, you", &mut tokens).is_err());
+    /// assert_eq!(tokens.len(), 2);
+    /// ```
+    pub fn try_encode(&self, text: &str, out: &mut impl Extend<Token>) -> io::Result<()> {
+        self.try_encode_with(text, out, &EncodeOptions::new())
+    }
+
+    /// Encode a `text` input as a sequence of tokens using the given `options`, unless it exceeds
+    /// the input limits or the work budget.
+    ///
+    /// This works like [`encode_with`], but checks `text` against the input limits like
+    /// [`try_encode`], and returns an error instead of a partial result when the work budget set
+    /// using [`EncodeOptions::max_merges`] or [`EncodeOptions::deadline`] is exhausted.
+    ///
+    /// [`encode_with`]: Tokenizer::encode_with
+    /// [`try_encode`]: Tokenizer::try_encode
+    ///
+    /// # Errors
+    ///
+    /// If `text` exceeds one of the limits, then an error of kind [`io::ErrorKind::InvalidInput`]
+    /// is returned, and nothing is added to `out`.
+    ///
+    /// If more than the maximum number of merge operations would be needed, then an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned, and if the deadline passes, then an error of
+    /// kind [`io::ErrorKind::TimedOut`] is returned. In both cases the tokens of the words encoded
+    /// up to that point have already been added to `out`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{EncodeOptions, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let options = EncodeOptions::new().max_merges(1_000);
+    /// let mut tokens = Vec::new();
+    /// assert!(tokenizer.try_encode_with("Hi there", &mut tokens, &options).is_ok());
+    ///
+    /// let text = "!?".repeat(10_000);
+    /// assert!(tokenizer.try_encode_with(&text, &mut tokens, &options).is_err());
+    /// assert_eq!(tokens.len(), 2);
+    /// ```
+    pub fn try_encode_with(
+        &self,
+        text: &str,
+        out: &mut impl Extend<Token>,
+        options: &EncodeOptions<'_>,
+    ) -> io::Result<()> {
+        self.check_input_limits(text)?;
+        self.encode_with_budget(text, out, options)?;
+        Ok(())
+    }
+
+    /// Checks `text` against the limits configured using [`TokenizerOptions::max_input_bytes`]
+    /// and [`TokenizerOptions::max_input_words`].
+    fn check_input_limits(&self, text: &str) -> io::Result<()> {
+        if let Some(max_input_bytes) = self.max_input_bytes {
+            if text.len() > max_input_bytes {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "input of {} bytes exceeds the maximum of {max_input_bytes} bytes",
+                        text.len()
+                    ),
+                ));
+            }
+        }
+        if let Some(max_input_words) = self.max_input_words {
+            if self.pre_tokenize(text).nth(max_input_words).is_some() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("input exceeds the maximum of {max_input_words} words"),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode at most `max_tokens` tokens of a `text` input, returning whether the whole input was
+    /// encoded.
+    ///
+    /// This produces the same tokens as [`encode`] followed by truncation to `max_tokens` tokens,
+    /// but stops processing the input as soon as `max_tokens` tokens have been produced, so that
+    /// the cost is proportional to `max_tokens` rather than the length of `text`. This makes it
+    /// suitable for checking whether very long inputs fit into the context length. Returns `true`
+    /// if all of `text` was encoded and `false` if tokens were discarded.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut tokens = Vec::new();
+    /// assert!(tokenizer.encode_up_to("Hi there", 2, &mut tokens));
+    /// assert_eq!(tokens.len(), 2);
+    ///
+    /// let document = "Hi there ".repeat(1_000_000);
+    /// tokens.clear();
+    /// assert!(!tokenizer.encode_up_to(&document, 75, &mut tokens));
+    /// assert_eq!(tokens.len(), 75);
+    /// ```
+    pub fn encode_up_to(
+        &self,
+        text: &str,
+        max_tokens: usize,
+        out: &mut impl Extend<Token>,
+    ) -> bool {
+        let mut remaining = max_tokens;
+        let mut word_tokens = Vec::new();
+        for (_, chunk) in self.lowercase_chunks(text) {
+            for (word, _) in self.split_words(&chunk, true) {
+                if remaining == 0 {
+                    return false;
+                }
+                self.encode_word(word, &mut word_tokens);
+                if word_tokens.len() > remaining {
+                    out.extend(word_tokens[..remaining].iter().copied());
+                    return false;
+                }
+                remaining -= word_tokens.len();
+                out.extend(word_tokens.iter().copied());
+            }
+        }
+        true
+    }
+
+    /// Encode a `text` input, writing the numerical token representation into `out`.
+    ///
+    /// This works like [`encode`], but writes the result of [`Token::to_u16`] for each token
+    /// directly into `out`, returning the number of tokens written to its start. If `text` is
+    /// encoded as more tokens than fit into `out`, the remaining tokens are discarded without
+    /// encoding the rest of `text`, so use [`fits_within`] to detect truncation beforehand if
+    /// needed.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    /// [`fits_within`]: Tokenizer::fits_within
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut buffer = [0; 77];
+    /// let count = tokenizer.encode_into("Hello world!!!", &mut buffer);
+    /// assert_eq!(buffer[..count], [3306, 1002, 995]);
+    ///
+    /// let mut buffer = [0; 2];
+    /// assert_eq!(tokenizer.encode_into("Hello world!!!", &mut buffer), 2);
+    /// ```
+    pub fn encode_into(&self, text: &str, out: &mut [u16]) -> usize {
+        let options = EncodeOptions::new().max_tokens(out.len());
+        let mut sink = RowSink { row: out, len: 0 };
+        self.encode_with(text, &mut sink, &options);
+        sink.len
+    }
+
+    /// Returns the number of tokens `text` is encoded as, without the marker tokens.
+    ///
+    /// This counts the tokens produced by [`encode`] without storing them anywhere.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// assert_eq!(tokenizer.count_tokens("Hello world!!!"), 3);
+    /// ```
+    pub fn count_tokens(&self, text: &str) -> usize {
+        let mut counter = CountSink(0);
+        self.encode(text, &mut counter);
+        counter.0
+    }
+
+    /// Returns whether `text` is encoded as at most `max_tokens` tokens, without the marker
+    /// tokens.
+    ///
+    /// This stops encoding as soon as more than `max_tokens` tokens have been produced, like
+    /// [`encode_up_to`], so checking very long texts is cheap. To check whether a caption fits
+    /// into the context length, leave room for the `<start_of_text>` and `<end_of_text>` markers.
+    ///
+    /// [`encode_up_to`]: Tokenizer::encode_up_to
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let max_tokens = tokenizer.context_length() - 2;
+    /// assert!(tokenizer.fits_within("A person riding a motorcycle", max_tokens));
+    /// assert!(!tokenizer.fits_within(&"a person ".repeat(100), max_tokens));
+    /// ```
+    pub fn fits_within(&self, text: &str, max_tokens: usize) -> bool {
+        self.encode_up_to(text, max_tokens, &mut CountSink(0))
+    }
+
+    /// Encode a `text` input as a sequence of tokens, grouped by the words they were produced from.
+    ///
+    /// This produces the same tokens as [`encode`], with one group for every word `text` is split
+    /// into (see [`pre_tokenize`]), including the `<start_of_text>` and `<end_of_text>` markers
+    /// written out in `text`. This is useful for truncating texts at word boundaries, or for
+    /// computing how many tokens words take up.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    /// [`pre_tokenize`]: Tokenizer::pre_tokenize
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let words = tokenizer.encode_words("Hello Båstad!!!");
+    /// let lengths = words.iter().map(Vec::len).collect::<Vec<_>>();
+    /// assert_eq!(lengths, [1, 3, 1]);
+    /// ```
+    pub fn encode_words(&self, text: &str) -> Vec<Vec<Token>> {
+        let mut words = Vec::new();
+        for (_, chunk) in self.lowercase_chunks(text) {
+            for (word, _) in self.split_words(&chunk, true) {
+                let mut word_tokens = Vec::new();
+                self.encode_word(word, &mut word_tokens);
+                words.push(word_tokens);
+            }
+        }
+        words
+    }
+
+    /// Lazily encode a `text` input as a sequence of tokens.
+    ///
+    /// This produces the same tokens as [`encode`], but returns them as an iterator, which makes it
+    /// easy to feed them into arbitrary consumers such as counters or serializers. The input is
+    /// processed in chunks of a few kilobytes as the iterator is advanced, so only the tokens of the
+    /// current chunk are buffered, and dropping the iterator early skips the rest of the input.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let ids = tokenizer.encode_iter("Hello world!!!").map(|token| token.to_u16());
+    /// assert_eq!(ids.collect::<Vec<_>>(), [3306, 1002, 995]);
+    /// ```
+    pub fn encode_iter<'a>(&'a self, text: &'a str) -> impl Iterator<Item = Token> + 'a {
+        // Chunks are already lowercased as needed
+        let options = EncodeOptions {
+            lowercase: Some(false),
+            ..EncodeOptions::new()
+        };
+        let mut chunks = self.lowercase_chunks(text);
+        let mut tokens = Vec::new();
+        let mut next = 0;
+        iter::from_fn(move || loop {
+            if let Some(&token) = tokens.get(next) {
+                next += 1;
+                return Some(token);
+            }
+            let (_, chunk) = chunks.next()?;
+            tokens.clear();
+            next = 0;
+            self.encode_uncached(&chunk, &mut tokens, &options)
+                .expect("encoding without limits can't fail");
+        })
+    }
+
+    /// Split `text` into chunks of roughly [`ENCODE_CHUNK_SIZE`] bytes ending at whitespace,
+    /// lowercased if this tokenizer lowercases its input, together with their start offsets.
+    ///
+    /// Words never span whitespace and lowercasing doesn't look across it, so encoding the chunks
+    /// separately gives the same result as encoding all of `text` at once.
+    fn lowercase_chunks<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (usize, Cow<'a, str>)> + 'a {
+        chunks(text).map(move |(start, chunk)| match self.lowercase {
+            true => (start, Cow::Owned(chunk.to_lowercase())),
+            false => (start, Cow::Borrowed(chunk)),
+        })
+    }
+
+    /// Split the already lowercased `text` into the words that are encoded separately, together
+    /// with their byte ranges in `text`.
+    ///
+    /// This works like [`pre_tokenize`], but also recognizes the special marker strings (unless
+    /// `special_markers` is false) and determines whether each word follows whitespace, so that
+    /// all ways of encoding texts treat words the same.
+    ///
+    /// [`pre_tokenize`]: Tokenizer::pre_tokenize
+    fn split_words<'a>(
+        &'a self,
+        text: &'a str,
+        special_markers: bool,
+    ) -> impl Iterator<Item = (Word<'a>, Range<usize>)> + 'a {
+        self.pre_tokenize(text).map(move |(word, range)| {
+            let word = match word {
+                "<start_of_text>" if special_markers => Word::Marker(self.start_of_text()),
+                "<end_of_text>" if special_markers => Word::Marker(self.end_of_text()),
+                _ => Word::Text {
+                    word,
+                    follows_whitespace: text[..range.start].ends_with(char::is_whitespace),
+                },
+            };
+            (word, range)
+        })
+    }
+
+    /// Encode a single `word` produced by [`split_words`], replacing the contents of `tokens`.
+    ///
+    /// [`split_words`]: Tokenizer::split_words
+    fn encode_word(&self, word: Word<'_>, tokens: &mut Vec<Token>) {
+        match word {
+            Word::Marker(token) => {
+                tokens.clear();
+                tokens.push(token);
+            }
+            Word::Text {
+                word,
+                follows_whitespace,
+            } => {
+                self.initial_word_tokens(word.as_bytes(), follows_whitespace, tokens);
+                self.apply_merge_rules(tokens);
+            }
+        }
+    }
+
+    /// Replace the contents of `tokens` with the byte tokens of `word`, before applying any merge
+    /// rules.
+    fn initial_word_tokens(&self, word: &[u8], follows_whitespace: bool, tokens: &mut Vec<Token>) {
+        tokens.clear();
+        let word_boundary = self.vocabulary.word_boundary;
+        if word_boundary == WordBoundary::SpacePrefix && follows_whitespace {
+            // Words following whitespace start with a single space
+            tokens.push(self.vocabulary.byte_to_token[usize::from(b' ')]);
+        }
+        tokens.extend(
+            word.iter()
+                .map(|b| self.vocabulary.byte_to_token[usize::from(*b)]),
+        );
+        if let Some(last) = tokens.last_mut() {
+            if word_boundary == WordBoundary::EndOfWordSuffix {
+                // Mark last character as end-of-word token
+                last.0 += 256;
+            }
+        }
+    }
+
+    /// Split `text` into the individual words that are encoded separately.
+    ///
+    /// This is the first stage of [`encode`], exposed separately for debugging and for building
+    /// offset mappings. Returns an iterator over the words together with their byte ranges in
+    /// `text`. Note that [`encode`] lowercases its input before splitting it into words while this
+    /// method does not, so use `pre_tokenize(&text.to_lowercase())` to see the exact words
+    /// [`encode`] operates on. How texts are split depends on [`TokenizerOptions::pre_tokenizer`].
+    /// The ranges then refer to the lowercased copy, which may differ in length from `text`, so use
+    /// [`encode_with_offsets`] to map tokens back to `text` itself.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    /// [`encode_with_offsets`]: Tokenizer::encode_with_offsets
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let words = tokenizer.pre_tokenize("i've seen it!!").collect::<Vec<_>>();
+    /// assert_eq!(
+    ///     words,
+    ///     [("i", 0..1), ("'ve", 1..4), ("seen", 5..9), ("it", 10..12), ("!!", 12..14)]
+    /// );
+    /// ```
+    pub fn pre_tokenize<'a>(
+        &'a self,
+        text: &'a str,
+    ) -> impl Iterator<Item = (&'a str, Range<usize>)> + 'a {
+        match self.pre_tokenizer {
+            PreTokenizer::Clip => Words::new(text),
+            PreTokenizer::Graphemes => Words::with_graphemes(text),
+        }
+    }
+
+    /// Encode a single, already isolated `word` using byte-pair encoding.
+    ///
+    /// This only maps the bytes of `word` to byte tokens and applies the merge rules, skipping the
+    /// lowercasing and pre-tokenization steps of [`encode`], which is useful for analyzing how
+    /// individual words are split into tokens. If `end_of_word` is `false`, `word` is treated as
+    /// the prefix of a longer word, so for vocabularies using [`WordBoundary::EndOfWordSuffix`]
+    /// its last token is not an end-of-word token. Vocabularies using
+    /// [`WordBoundary::SpacePrefix`] have no end-of-word tokens, include the leading space in
+    /// `word` instead to encode it as following whitespace.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode("hello", &mut tokens);
+    /// assert_eq!(tokenizer.bpe_word("hello", true), tokens);
+    /// assert_eq!(tokenizer.decode(tokenizer.bpe_word("hel", false)), "hel");
+    /// ```
+    pub fn bpe_word(&self, word: &str, end_of_word: bool) -> Vec<Token> {
+        let mut tokens = Vec::with_capacity(word.len());
+        self.initial_word_tokens(word.as_bytes(), false, &mut tokens);
+        if !end_of_word && self.vocabulary.word_boundary == WordBoundary::EndOfWordSuffix {
+            // Revert the last character to its regular token
+            if let Some(last) = tokens.last_mut() {
+                last.0 -= 256;
+            }
+        }
+        self.apply_merge_rules(&mut tokens);
+        tokens
+    }
+
+    /// Encode a `text` input, writing the numerical token representation into `out`.
+    ///
+    /// This works like [`encode`], but writes the result of [`Token::to_u16`] for each token
+    /// directly into a possibly uninitialized buffer provided by the caller, e.g. memory handed
+    /// over through an FFI boundary or a staging buffer that is to be uploaded to a GPU. Returns
+    /// the number of tokens written to the start of `out`, i.e. after this method returns
+    /// `out[..count]` is initialized. If `text` is encoded as more tokens than fit into `out`, the
+    /// remaining tokens are discarded.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use std::mem::MaybeUninit;
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut buffer = [MaybeUninit::uninit(); 77];
+    /// let count = tokenizer.encode_uninit("Hi there", &mut buffer);
+    /// assert_eq!(count, 2);
+    /// ```
+    pub fn encode_uninit(&self, text: &str, out: &mut [MaybeUninit<u16>]) -> usize {
+        let mut sink = UninitSink { out, len: 0 };
+        self.encode(text, &mut sink);
+        sink.len
+    }
+
+    fn apply_merge_rules(&self, tokens: &mut Vec<Token>) {
+        let _ = self.apply_merge_rules_with(tokens, |_, _, _, _, _| ControlFlow::Continue(()));
+    }
+
+    /// Apply merge rules like [`Tokenizer::apply_merge_rules`], calling `on_merge` after each
+    /// step with the merged pair, the resulting token, the number of merged occurrences and the
+    /// tokens after the step. Returning [`ControlFlow::Break`] from `on_merge` stops applying
+    /// merge rules, leaving `tokens` partially merged.
+    #[allow(clippy::while_let_loop)]
+    fn apply_merge_rules_with(
+        &self,
+        tokens: &mut Vec<Token>,
+        mut on_merge: impl FnMut(Token, Token, Token, usize, &[Token]) -> ControlFlow<()>,
+    ) -> ControlFlow<()> {
+        loop {
+            let Some(((first, second), result_token)) = tokens
+                .windows(2)
+                .map(|pair| (pair[0], pair[1]))
+                .filter_map(|pair| {
+                    self.vocabulary
+                        .merge_rules
+                        .get(&pair)
+                        .map(|result_token| (pair, *result_token))
+                })
+                .min_by_key(|&(_, result_token)| result_token)
+            else {
+                // No merge rules left to apply -> we're done
+                break;
+            };
+
+            // Reduce all occurences of this pair to `result_token`
+            let mut i = 0;
+            let mut merged = 0;
+            while i < tokens.len() - 1 {
+                if tokens[i] == first && tokens[i + 1] == second {
+                    tokens[i] = result_token;
+                    tokens.remove(i + 1);
+                    merged += 1;
+                }
+                i += 1;
+            }
+            on_merge(first, second, result_token, merged, tokens)?;
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Returns the rank of the merge rule combining `first` and `second`, if there is one.
+    ///
+    /// Merge rules are ranked by their position in the vocabulary file, with rank `0` being the
+    /// first rule. When encoding a word, rules with lower rank are always applied before rules
+    /// with a higher rank.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Token, Tokenizer};
+    /// let tokenizer = Tokenizer::new();
+    /// let i = Token::from_u16(72, &tokenizer).unwrap();
+    /// let n = Token::from_u16(77, &tokenizer).unwrap();
+    /// // The first merge rule in the bundled vocabulary file is "i n"
+    /// assert_eq!(tokenizer.merge_rank(i, n), Some(0));
+    /// ```
+    pub fn merge_rank(&self, first: Token, second: Token) -> Option<u32> {
+        // Merged tokens are numbered in order of their merge rules, following the byte tokens
+        // (and their end-of-word variants), and skipping the special marker tokens in extended
+        // vocabularies
+        let vocabulary = &self.vocabulary;
+        vocabulary
+            .merge_rules
+            .get(&(first, second))
+            .map(|result_token| {
+                let rank = u32::from(result_token.0 - vocabulary.word_boundary.base_tokens());
+                match *result_token > vocabulary.end_of_text {
+                    true => rank - 2,
+                    false => rank,
+                }
+            })
+    }
+
+    /// Convert a sequence of `tokens` back to a textual representation.
+    ///
+    /// Due to the way whitespace and lowercasing is handled a sequence of tokens will not always be
+    /// decoded back to the exact same text that `encode` was called with, in other words,
+    /// `decode(encode(text)) == text` does not always hold true. Hence, this function is mostly
+    /// useful for debugging purposes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode("Hello world!!!", &mut tokens);
+    /// let decoded = tokenizer.decode(tokens);
+    /// assert_eq!(decoded, "hello world !!! ");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens` contains a token that is not part of the vocabulary of this `Tokenizer`,
+    /// which can only happen for tokens validated against a different, larger vocabulary. Use
+    /// [`try_decode`] to handle this case without panicking.
+    ///
+    /// [`try_decode`]: Tokenizer::try_decode
+    pub fn decode(&self, tokens: impl IntoIterator<Item = Token>) -> String {
+        self.try_decode(tokens)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Convert a sequence of `tokens` back to a textual representation, returning an error instead
+    /// of panicking.
+    ///
+    /// This works like [`decode`], but returns an error if `tokens` contains a token that is not
+    /// part of the vocabulary of this `Tokenizer`.
+    ///
+    /// [`decode`]: Tokenizer::decode
+    ///
+    /// # Errors
+    ///
+    /// If `tokens` contains an unknown token, then an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned. Its message includes the position and value
+    /// of the token, the range of valid token ids, and hints at likely causes, e.g. a negative
+    /// value cast to an unsigned integer type.
+    pub fn try_decode(&self, tokens: impl IntoIterator<Item = Token>) -> io::Result<String> {
+        let mut text = String::new();
+        self.try_decode_into(tokens, &mut text)?;
+        Ok(text)
+    }
+
+    /// Convert a sequence of `tokens` back to a textual representation, appending it to `out`.
+    ///
+    /// This works like [`decode`], but reuses the buffer of `out` instead of allocating a new
+    /// `String` for every call, which helps when decoding many token sequences, e.g. for logging.
+    ///
+    /// [`decode`]: Tokenizer::decode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut decoded = String::new();
+    /// for text in ["Hello", "world!!!"] {
+    ///     let mut tokens = Vec::new();
+    ///     tokenizer.encode(text, &mut tokens);
+    ///     decoded.clear();
+    ///     tokenizer.decode_into(tokens, &mut decoded);
+    /// }
+    /// assert_eq!(decoded, "world !!! ");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `tokens` contains a token that is not part of the vocabulary of this `Tokenizer`.
+    /// Use [`try_decode_into`] to handle this case without panicking.
+    ///
+    /// [`try_decode_into`]: Tokenizer::try_decode_into
+    pub fn decode_into(&self, tokens: impl IntoIterator<Item = Token>, out: &mut String) {
+        self.try_decode_into(tokens, out)
+            .unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Convert a sequence of `tokens` back to a textual representation appended to `out`,
+    /// returning an error instead of panicking.
+    ///
+    /// This works like [`decode_into`], but returns an error if `tokens` contains a token that is
+    /// not part of the vocabulary of this `Tokenizer`.
+    ///
+    /// [`decode_into`]: Tokenizer::decode_into
+    ///
+    /// # Errors
+    ///
+    /// If `tokens` contains an unknown token, then an error of kind
+    /// [`io::ErrorKind::InvalidInput`] is returned, and `out` is left unchanged.
+    pub fn try_decode_into(
+        &self,
+        tokens: impl IntoIterator<Item = Token>,
+        out: &mut String,
+    ) -> io::Result<()> {
+        // Work on the bytes of `out` directly, so that no intermediate buffers are needed unless
+        // the decoded bytes aren't valid UTF-8
+        let start = out.len();
+        let mut bytes = mem::take(out).into_bytes();
+        for (position, token) in tokens.into_iter().enumerate() {
+            let token_bytes = match self.token_bytes(token) {
+                Some(token_bytes) => token_bytes,
+                None => {
+                    bytes.truncate(start);
+                    *out = String::from_utf8(bytes).expect("truncated to valid UTF-8");
+                    return Err(self.unknown_token_error(token, position));
+                }
+            };
+            bytes.extend_from_slice(token_bytes);
+        }
+        *out = self.finish_decoding(bytes, start);
+        Ok(())
+    }
+
+    /// Turn the concatenated token bytes following the first `start` bytes of `bytes` into text,
+    /// replacing end-of-word markers and invalid UTF-8 sequences.
+    fn finish_decoding(&self, mut bytes: Vec<u8>, start: usize) -> String {
+        if self.vocabulary.word_boundary == WordBoundary::EndOfWordSuffix {
+            replace_end_of_word_markers(&mut bytes, start);
+        }
+        match String::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(err) => {
+                let mut bytes = err.into_bytes();
+                let decoded = String::from_utf8_lossy(&bytes[start..]).into_owned();
+                bytes.truncate(start);
+                let mut text = String::from_utf8(bytes).expect("truncated to valid UTF-8");
+                text.push_str(&decoded);
+                text
+            }
+        }
+    }
+
+    /// Returns the canonical form of `text`, i.e. the result of decoding the tokens of `text`.
+    ///
+    /// This is equivalent to `decode` applied to the output of `encode`, but writes the bytes of
+    /// each token directly into the output instead of collecting the tokens first. Texts with the
+    /// same canonical form are encoded as the same tokens, which makes it useful as a key for
+    /// deduplicating captions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// assert_eq!(tokenizer.canonicalize("Hello   World!!!"), "hello world !!! ");
+    /// assert_eq!(
+    ///     tokenizer.canonicalize("Hello   World!!!"),
+    ///     tokenizer.canonicalize("hello world !!!"),
+    /// );
+    /// ```
+    pub fn canonicalize(&self, text: &str) -> String {
+        // Each word gains an end-of-word marker before these are replaced, so reserve some extra
+        // space up front
+        let mut sink = BytesSink {
+            tokenizer: self,
+            bytes: Vec::with_capacity(text.len() * 2),
+        };
+        self.encode(text, &mut sink);
+        self.finish_decoding(sink.bytes, 0)
+    }
+
+    /// Describe why `token` at `position` can't be decoded, with hints at the likely cause.
+    ///
+    /// Most unknown tokens are the result of casting bugs (e.g. a negative `i64` padding value
+    /// cast to `u16`) or of tokens produced using a different, larger vocabulary.
+    fn unknown_token_error(&self, token: Token, position: usize) -> io::Error {
+        let vocab_size = self.vocabulary.vocab_size;
+        let mut message = format!(
+            "unknown token {} at position {position} (valid token ids are 0..={})",
+            token.0,
+            vocab_size - 1
+        );
+        let signed = token.0 as i16;
+        if signed < 0 && signed > -1024 {
+            message.push_str(&format!(
+                "; this is {signed} as a signed integer, so a negative value was likely cast to \
+                 an unsigned type"
+            ));
+        } else if token.0 >= vocab_size {
+            message
+                .push_str("; the token was likely produced using a different, larger vocabulary");
+        }
+        io::Error::new(io::ErrorKind::InvalidInput, message)
+    }
+
+    /// Lazily convert a sequence of `tokens` back to chunks of bytes.
+    ///
+    /// This works like [`decode`], but instead of building a `String` yields the bytes of each
+    /// token as they are needed, with the end-of-word marker of end-of-word tokens yielded as a
+    /// separate `b" "` chunk. This is useful for consumers that only need to scan the decoded
+    /// bytes once, e.g. when streaming or searching the output.
+    ///
+    /// Note that the bytes of individual tokens are not necessarily valid UTF-8, as multi-byte
+    /// characters may be split across multiple tokens. Tokens that are not part of the vocabulary
+    /// of this `Tokenizer` are yielded as the UTF-8 encoded replacement character `U+FFFD`.
+    ///
+    /// [`decode`]: Tokenizer::decode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode("Hello world!!!", &mut tokens);
+    /// let bytes = tokenizer.decode_iter(tokens).flatten().copied().collect::<Vec<_>>();
+    /// assert_eq!(bytes, b"hello world !!! ");
+    /// ```
+    pub fn decode_iter<'a>(
+        &'a self,
+        tokens: impl IntoIterator<Item = Token> + 'a,
+    ) -> impl Iterator<Item = &'a [u8]> + 'a {
+        tokens.into_iter().flat_map(|token| {
+            let bytes = self.token_bytes(token).unwrap_or("\u{FFFD}".as_bytes());
+            let end_of_word = match self.vocabulary.word_boundary {
+                WordBoundary::EndOfWordSuffix => bytes.strip_suffix(b"</w>"),
+                WordBoundary::SpacePrefix => None,
+            };
+            match end_of_word {
+                Some(bytes) => [Some(bytes), Some(&b" "[..])],
+                None => [Some(bytes), None],
+            }
+            .into_iter()
+            .flatten()
+        })
+    }
+
+    /// Encode a `text` input as a sequence of human-readable subwords.
+    ///
+    /// This works like [`encode`], but returns the text each resulting token represents instead of
+    /// the token itself, so that the result is parallel to the tokens returned by [`encode`].
+    /// End-of-word tokens include a trailing `</w>`. Tokens that don't represent valid UTF-8 on
+    /// their own (e.g. parts of an emoji) contain the replacement character `U+FFFD`.
+    ///
+    /// This is useful for debugging, and for displaying how a text is segmented.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let subwords = tokenizer.encode_to_string_tokens("Hi there!!!");
+    /// assert_eq!(subwords, ["hi</w>", "there</w>", "!!!</w>"]);
+    /// ```
+    pub fn encode_to_string_tokens(&self, text: &str) -> Vec<String> {
+        let mut pieces = Vec::with_capacity(text.len());
+        self.encode_with_pieces(text, &mut pieces);
+        pieces
+            .into_iter()
+            .map(|(_, piece)| piece.into_owned())
+            .collect()
+    }
+
+    /// Encode a `text` input, appending every token together with the text it represents to `out`.
+    ///
+    /// The pieces are the same as returned by [`encode_to_string_tokens`], i.e. end-of-word tokens
+    /// include a trailing `</w>`, and tokens that don't represent valid UTF-8 on their own contain
+    /// the replacement character `U+FFFD`. Pieces are borrowed from the vocabulary where possible,
+    /// so this is considerably faster than decoding every token separately.
+    ///
+    /// [`encode_to_string_tokens`]: Tokenizer::encode_to_string_tokens
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut pieces = Vec::new();
+    /// tokenizer.encode_with_pieces("Hello Båstad", &mut pieces);
+    /// let ids = pieces.iter().map(|(token, _)| token.to_u16()).collect::<Vec<_>>();
+    /// let pieces = pieces.iter().map(|(_, piece)| piece).collect::<Vec<_>>();
+    /// assert_eq!(ids, [3306, 65, 23176, 16485]);
+    /// assert_eq!(pieces, ["hello</w>", "b", "å", "stad</w>"]);
+    /// ```
+    pub fn encode_with_pieces<'a>(
+        &'a self,
+        text: &str,
+        out: &mut impl Extend<(Token, Cow<'a, str>)>,
+    ) {
+        let mut tokens = Vec::with_capacity(text.len());
+        self.encode(text, &mut tokens);
+        out.extend(tokens.into_iter().map(|token| {
+            // Every token produced by `encode` is part of the vocabulary
+            let bytes = self.token_bytes(token).unwrap_or_default();
+            (token, String::from_utf8_lossy(bytes))
+        }));
+    }
+
+    /// Returns the raw bytes represented by `token`, including a trailing `</w>` for end-of-word
+    /// tokens, or `None` if `token` is not part of the vocabulary.
+    fn token_bytes(&self, token: Token) -> Option<&[u8]> {
+        if token == self.vocabulary.start_of_text {
+            Some("<start_of_text>".as_bytes())
+        } else if token == self.vocabulary.end_of_text {
+            Some("<end_of_text>".as_bytes())
+        } else {
+            self.vocabulary.decoder().get(&token).map(Vec::as_slice)
+        }
+    }
+
+    /// Returns an iterator over all entries of the vocabulary, in order of their token ids.
+    ///
+    /// Each entry consists of a token together with the raw bytes it represents. This covers the
+    /// byte tokens, their end-of-word variants, the tokens produced by merge rules, as well as the
+    /// special `<start_of_text>` and `<end_of_text>` marker tokens. The bytes of end-of-word tokens
+    /// include a trailing `</w>`, and are not necessarily valid UTF-8.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let (token, bytes) = tokenizer.vocab_iter().nth(1883).unwrap();
+    /// assert_eq!(token.to_u16(), 1883);
+    /// assert_eq!(bytes, b"hi</w>");
+    /// assert_eq!(tokenizer.vocab_iter().count(), 49408);
+    /// ```
+    pub fn vocab_iter(&self) -> impl Iterator<Item = (Token, &[u8])> + '_ {
+        (0..self.vocabulary.vocab_size)
+            .filter_map(|id| Some((Token(id), self.token_bytes(Token(id))?)))
+    }
+
+    /// Returns the approximate heap memory used by this `Tokenizer`, broken down by table.
+    ///
+    /// The numbers are estimates based on the sizes and capacities of the internal data structures.
+    /// The table used for decoding is only built when first needed, so it doesn't count towards
+    /// the memory usage of tokenizers that only encode.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let usage = tokenizer.memory_usage();
+    /// println!("{} bytes, of which the decoder uses {}", usage.total(), usage.decoder);
+    /// ```
+    pub fn memory_usage(&self) -> MemoryUsage {
+        // Each bucket of a hash map additionally uses one control byte
+        fn hash_map_size<K, V>(map: &HashMap<K, V>) -> usize {
+            map.capacity() * (mem::size_of::<(K, V)>() + 1)
+        }
+
+        MemoryUsage {
+            byte_to_token: mem::size_of_val(&*self.vocabulary.byte_to_token),
+            merge_rules: hash_map_size(&self.vocabulary.merge_rules),
+            decoder: self.vocabulary.decoder.get().map_or(0, |decoder| {
+                hash_map_size(decoder) + decoder.values().map(Vec::capacity).sum::<usize>()
+            }),
+        }
+    }
+
+    /// Returns the special `<start_of_text>` marker token.
+    ///
+    /// See [`encode`] for an example about how to add this token to a token sequence.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    pub fn start_of_text(&self) -> Token {
+        self.vocabulary.start_of_text
+    }
+
+    /// Returns the special `<end_of_text>` marker token.
+    ///
+    /// See [`encode`] for an example about how to add this token to a token sequence.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    pub fn end_of_text(&self) -> Token {
+        self.vocabulary.end_of_text
+    }
+
+    /// Returns a lightweight description of the vocabulary of this `Tokenizer`.
+    ///
+    /// The resulting [`VocabInfo`] can be used to validate token ids using [`Token::from_u16`]
+    /// without needing access to the `Tokenizer` itself, e.g. on a different thread or in a
+    /// different process.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Token, Tokenizer};
+    /// let info = Tokenizer::new().vocab_info();
+    /// assert_eq!(info.vocab_size(), 49408);
+    /// assert!(Token::from_u16(49407, info).is_some());
+    /// assert!(Token::from_u16(49408, info).is_none());
+    /// ```
+    pub fn vocab_info(&self) -> VocabInfo {
+        VocabInfo {
+            start_of_text: self.vocabulary.start_of_text,
+            end_of_text: self.vocabulary.end_of_text,
+            vocab_size: self.vocabulary.vocab_size,
+        }
+    }
+
+    /// Returns the position of the first `<end_of_text>` marker token in `row`.
+    ///
+    /// CLIP models pool the final hidden state at the position of the `<end_of_text>` token, so
+    /// this is useful when running the text encoder yourself, e.g. on a row of the matrix returned
+    /// by `tokenize_batch`. Returns `None` if `row` does not contain the `<end_of_text>` token.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// assert_eq!(tokenizer.end_of_text_position(&[49406, 1883, 49407, 0, 0]), Some(2));
+    /// assert_eq!(tokenizer.end_of_text_position(&[49406, 1883, 997]), None);
+    /// ```
+    pub fn end_of_text_position(&self, row: &[u16]) -> Option<usize> {
+        row.iter()
+            .position(|&id| id == self.vocabulary.end_of_text.to_u16())
+    }
+
+    /// Returns the position of the first `<end_of_text>` marker token in each of the given `rows`.
+    ///
+    /// This is the index at which CLIP models pool the hidden state of each row, see
+    /// [`end_of_text_position`]. Like `argmax` in the original implementation, the first
+    /// occurrence is used, so rows padded with the `<end_of_text>` token instead of zeros are
+    /// handled correctly.
+    ///
+    /// [`end_of_text_position`]: Tokenizer::end_of_text_position
+    ///
+    /// # Errors
+    ///
+    /// If one of the rows does not contain the `<end_of_text>` token, e.g. because it was
+    /// truncated without keeping the marker tokens, an error naming the row is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let rows = [[49406, 1883, 49407, 0], [49406, 1883, 997, 49407]];
+    /// assert_eq!(tokenizer.eot_positions(&rows).unwrap(), [2, 3]);
+    /// assert!(tokenizer.eot_positions([[49406, 1883, 997]]).is_err());
+    /// ```
+    pub fn eot_positions<R: AsRef<[u16]>>(
+        &self,
+        rows: impl IntoIterator<Item = R>,
+    ) -> io::Result<Vec<usize>> {
+        rows.into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                self.end_of_text_position(row.as_ref())
+                    .ok_or_else(|| missing_end_of_text(i))
+            })
+            .collect()
+    }
+
+    /// Returns the position of the first `<end_of_text>` marker token in each row of `ids`, e.g.
+    /// the matrix returned by [`tokenize_batch`].
+    ///
+    /// Works like [`eot_positions`], but also accepts non-contiguous matrices.
+    ///
+    /// [`tokenize_batch`]: Tokenizer::tokenize_batch
+    /// [`eot_positions`]: Tokenizer::eot_positions
+    ///
+    /// # Errors
+    ///
+    /// If one of the rows does not contain the `<end_of_text>` token, an error naming the row is
+    /// returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let ids = tokenizer.tokenize_batch(["Hi", "Hi there"], 77);
+    /// assert_eq!(tokenizer.eot_positions_matrix(ids.view()).unwrap(), [2, 3]);
+    /// ```
+    #[cfg(feature = "ndarray")]
+    pub fn eot_positions_matrix(
+        &self,
+        ids: ndarray::ArrayView2<'_, u16>,
+    ) -> io::Result<Vec<usize>> {
+        let end_of_text = self.vocabulary.end_of_text.to_u16();
+        ids.rows()
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                row.iter()
+                    .position(|&id| id == end_of_text)
+                    .ok_or_else(|| missing_end_of_text(i))
+            })
+            .collect()
+    }
+}
+
+fn missing_end_of_text(row: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("row {row} does not contain the <end_of_text> token"),
+    )
+}
+
+#[cfg(any(test, feature = "openai-vocabulary-file"))]
+impl Default for Tokenizer {
+    fn default() -> Tokenizer {
+        Tokenizer::new()
+    }
+}
+
+/// A lightweight description of the vocabulary of a [`Tokenizer`], as returned by
+/// [`Tokenizer::vocab_info`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VocabInfo {
+    start_of_text: Token,
+    end_of_text: Token,
+    vocab_size: u16,
+}
+
+impl VocabInfo {
+    /// Returns the number of tokens in the vocabulary, including the special marker tokens.
+    pub fn vocab_size(&self) -> usize {
+        usize::from(self.vocab_size)
+    }
+
+    /// Returns the special `<start_of_text>` marker token.
+    pub fn start_of_text(&self) -> Token {
+        self.start_of_text
+    }
+
+    /// Returns the special `<end_of_text>` marker token.
+    pub fn end_of_text(&self) -> Token {
+        self.end_of_text
+    }
+}
+
+impl From<&Tokenizer> for VocabInfo {
+    fn from(tokenizer: &Tokenizer) -> VocabInfo {
+        tokenizer.vocab_info()
+    }
+}
+
+/// How word boundaries are represented in the vocabulary of a [`Tokenizer`].
+///
+/// See [`Tokenizer::with_vocabulary_scheme`] for loading vocabulary data using a particular
+/// scheme.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum WordBoundary {
+    /// The last token of every word is an end-of-word token, written with a `</w>` suffix in
+    /// vocabulary data. Every byte token has an end-of-word variant, whose token id is offset by
+    /// `256`, so merged tokens start at id `512`. This is the scheme used by CLIP.
+    #[default]
+    EndOfWordSuffix,
+    /// Words preceded by whitespace start with a space, written as a `Ġ` prefix in vocabulary
+    /// data. There are no end-of-word tokens, so merged tokens start at id `256`. This is the
+    /// scheme used by GPT-2 style byte-level BPE vocabularies.
+    ///
+    /// Whitespace between words is always represented by a single space, so runs of whitespace
+    /// are not preserved.
+    SpacePrefix,
+}
+
+impl WordBoundary {
+    /// Returns the number of tokens preceding the merged tokens.
+    fn base_tokens(self) -> u16 {
+        match self {
+            WordBoundary::EndOfWordSuffix => 512,
+            WordBoundary::SpacePrefix => 256,
+        }
+    }
+}
+
+/// How a [`Tokenizer`] splits texts into words, see [`TokenizerOptions::pre_tokenizer`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum PreTokenizer {
+    /// Split texts exactly like the regular expression of the original CLIP implementation.
+    ///
+    /// Words are runs of letters, single numeric characters, or runs of other characters except
+    /// whitespace, with the special marker strings and common English contractions split off.
+    /// Combining marks are not letters, so e.g. `"e\u{301}"` (`é` written using a combining
+    /// accent) is split into two words, and adjacent emoji are merged into a single word together
+    /// with any adjacent punctuation.
+    #[default]
+    Clip,
+    /// Split texts using the same rules as [`Clip`](PreTokenizer::Clip), but applied to grapheme
+    /// clusters instead of characters.
+    ///
+    /// Letters followed by combining marks stay in one word, and every emoji (including ZWJ
+    /// sequences like `"👩‍👩‍👧"`, flags and skin tone modifiers) becomes a word of its own. This
+    /// gives more sensible segmentations of emoji and of scripts using combining marks, at the
+    /// cost of deviating from the tokens produced by the original implementation for such texts.
+    Graphemes,
+}
+
+/// Options for creating a [`Tokenizer`] using [`Tokenizer::from_vocabulary`].
+#[derive(Clone, Copy, Debug)]
+pub struct TokenizerOptions {
+    lowercase: bool,
+    context_length: usize,
+    max_input_bytes: Option<usize>,
+    max_input_words: Option<usize>,
+    pre_tokenizer: PreTokenizer,
+    text_cache: usize,
+}
+
+impl TokenizerOptions {
+    /// Create the default options, which match the behavior of the original CLIP tokenizer.
+    pub fn new() -> TokenizerOptions {
+        TokenizerOptions {
+            lowercase: true,
+            context_length: CLIP_CONTEXT_LENGTH,
+            max_input_bytes: None,
+            max_input_words: None,
+            pre_tokenizer: PreTokenizer::Clip,
+            text_cache: 0,
+        }
+    }
+
+    /// Create the options for [Long-CLIP](https://github.com/beichenzbc/Long-CLIP) models, which
+    /// only differ from the default options by using a context length of
+    /// [`LONG_CLIP_CONTEXT_LENGTH`] tokens.
+    pub fn long_clip() -> TokenizerOptions {
+        TokenizerOptions::new().context_length(LONG_CLIP_CONTEXT_LENGTH)
+    }
+
+    /// Set whether input texts are lowercased before being tokenized (enabled by default).
+    pub fn lowercase(mut self, lowercase: bool) -> TokenizerOptions {
+        self.lowercase = lowercase;
+        self
+    }
+
+    /// Set the context length returned by [`Tokenizer::context_length`] (77 by default).
+    pub fn context_length(mut self, context_length: usize) -> TokenizerOptions {
+        self.context_length = context_length;
+        self
+    }
+
+    /// Set the maximum size of input texts in bytes (not limited by default).
+    ///
+    /// Longer texts are rejected with an error by [`Tokenizer::try_encode`] and the other
+    /// fallible encoding methods, before doing any work. This guards services encoding untrusted
+    /// input against spending a lot of time on huge texts. [`Tokenizer::encode`] ignores this
+    /// limit.
+    pub fn max_input_bytes(mut self, max_input_bytes: usize) -> TokenizerOptions {
+        self.max_input_bytes = Some(max_input_bytes);
+        self
+    }
+
+    /// Set the maximum number of words of input texts, as split by [`Tokenizer::pre_tokenize`]
+    /// (not limited by default).
+    ///
+    /// Like [`max_input_bytes`](TokenizerOptions::max_input_bytes), texts with more words are
+    /// rejected before any merge rules are applied.
+    pub fn max_input_words(mut self, max_input_words: usize) -> TokenizerOptions {
+        self.max_input_words = Some(max_input_words);
+        self
+    }
+
+    /// Set how texts are split into words before being encoded ([`PreTokenizer::Clip`] by
+    /// default).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{PreTokenizer, Tokenizer, TokenizerOptions};
+    /// let tokenizer = Tokenizer::from_vocabulary(
+    ///     Tokenizer::new().vocabulary(),
+    ///     TokenizerOptions::new().pre_tokenizer(PreTokenizer::Graphemes),
+    /// );
+    /// let words = tokenizer.pre_tokenize("🇩🇪🇫🇷!").map(|(word, _)| word);
+    /// assert_eq!(words.collect::<Vec<_>>(), ["🇩🇪", "🇫🇷", "!"]);
+    /// ```
+    pub fn pre_tokenizer(mut self, pre_tokenizer: PreTokenizer) -> TokenizerOptions {
+        self.pre_tokenizer = pre_tokenizer;
+        self
+    }
+
+    /// Cache the tokens of up to `capacity` complete texts (disabled by default).
+    ///
+    /// Workloads encoding the same texts over and over, e.g. search queries, can skip encoding
+    /// texts that were recently encoded. The cache is used by [`Tokenizer::encode`] and the batch
+    /// methods, and for all texts encoded using [`EncodeOptions`] that only set
+    /// [`max_tokens`](EncodeOptions::max_tokens). Texts longer than 1 KiB aren't cached.
+    ///
+    /// Clones of the resulting `Tokenizer` share the cache, while tokenizers created from the same
+    /// [`Vocabulary`] using [`Tokenizer::from_vocabulary`] each get their own.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, TokenizerOptions};
+    /// let tokenizer = Tokenizer::from_vocabulary(
+    ///     Tokenizer::new().vocabulary(),
+    ///     TokenizerOptions::new().text_cache(10_000),
+    /// );
+    /// let mut tokens = Vec::new();
+    /// tokenizer.encode("a photo of a dog", &mut tokens);
+    /// // Encoding the same text again looks up the cached tokens
+    /// tokenizer.encode("a photo of a dog", &mut tokens);
+    /// ```
+    pub fn text_cache(mut self, capacity: usize) -> TokenizerOptions {
+        self.text_cache = capacity;
+        self
+    }
+}
+
+impl Default for TokenizerOptions {
+    fn default() -> TokenizerOptions {
+        TokenizerOptions::new()
+    }
+}
+
+/// Options controlling how texts are encoded by [`Tokenizer::encode_with`] and
+/// `Tokenizer::tokenize_batch_with`.
+///
+/// The default options match the behavior of [`Tokenizer::encode`].
+#[derive(Clone, Copy)]
+pub struct EncodeOptions<'a> {
+    lowercase: Option<bool>,
+    normalize: Option<&'a dyn Fn(&str) -> String>,
+    special_markers: bool,
+    max_tokens: Option<usize>,
+    max_merges: Option<usize>,
+    deadline: Option<Instant>,
+}
+
+impl<'a> EncodeOptions<'a> {
+    /// Create the default options.
+    pub fn new() -> EncodeOptions<'a> {
+        EncodeOptions {
+            lowercase: None,
+            normalize: None,
+            special_markers: true,
+            max_tokens: None,
+            max_merges: None,
+            deadline: None,
+        }
+    }
+
+    /// Set whether the text is lowercased before being tokenized.
+    ///
+    /// By default, this follows the [`TokenizerOptions`] the `Tokenizer` was created with.
+    pub fn lowercase(mut self, lowercase: bool) -> EncodeOptions<'a> {
+        self.lowercase = Some(lowercase);
+        self
+    }
+
+    /// Set a function that normalizes the text before it is lowercased and tokenized.
+    ///
+    /// This can be used to replicate pre-processing steps applied by other CLIP implementations,
+    /// e.g. fixing broken Unicode or unescaping HTML entities. By default, no normalization is
+    /// performed.
+    pub fn normalize(mut self, normalize: &'a dyn Fn(&str) -> String) -> EncodeOptions<'a> {
+        self.normalize = Some(normalize);
+        self
+    }
+
+    /// Set whether the `<start_of_text>` and `<end_of_text>` substrings are encoded as the
+    /// corresponding marker tokens (enabled by default).
+    ///
+    /// When disabled, these substrings are encoded like any other text, which prevents untrusted
+    /// input from injecting marker tokens.
+    pub fn special_markers(mut self, special_markers: bool) -> EncodeOptions<'a> {
+        self.special_markers = special_markers;
+        self
+    }
+
+    /// Set the maximum number of tokens the text is encoded as, discarding any further tokens.
+    ///
+    /// By default, the number of tokens is not limited.
+    pub fn max_tokens(mut self, max_tokens: usize) -> EncodeOptions<'a> {
+        self.max_tokens = Some(max_tokens);
+        self
+    }
+
+    /// Set the maximum number of merge operations performed while encoding the text.
+    ///
+    /// Each merge of two adjacent tokens into one counts as one operation, so a word of `n` bytes
+    /// takes at most `n - 1` operations. Encoding stops once the budget is exhausted, so that
+    /// adversarial inputs like long runs of punctuation can't monopolize a thread.
+    /// [`Tokenizer::encode_with`] then returns the tokens of the words encoded so far, while
+    /// [`Tokenizer::try_encode_with`] returns an error. By default, the number of merge operations
+    /// is not limited.
+    pub fn max_merges(mut self, max_merges: usize) -> EncodeOptions<'a> {
+        self.max_merges = Some(max_merges);
+        self
+    }
+
+    /// Set a point in time after which encoding the text stops.
+    ///
+    /// Like [`max_merges`](EncodeOptions::max_merges), this bounds the time spent on a single
+    /// text, e.g. `Instant::now() + Duration::from_millis(10)`. The deadline is checked after
+    /// each merge rule applied to a word, so it may be overrun by the time applying a single rule
+    /// takes. By default, there is no deadline.
+    pub fn deadline(mut self, deadline: Instant) -> EncodeOptions<'a> {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Returns whether texts are encoded exactly like by [`Tokenizer::encode`], apart from
+    /// truncation.
+    fn is_default_encoding(&self) -> bool {
+        self.lowercase.is_none()
+            && self.normalize.is_none()
+            && self.special_markers
+            && self.max_merges.is_none()
+            && self.deadline.is_none()
+    }
+}
+
+impl Default for EncodeOptions<'_> {
+    fn default() -> Self {
+        EncodeOptions::new()
+    }
+}
+
+/// An error encoding a single text of a batch, as returned by
+/// `Tokenizer::tokenize_batch_with_errors`.
+#[derive(Debug)]
+pub struct RowError {
+    /// The index of the text in the batch, which is also its row in the result.
+    pub row: usize,
+    /// The error encoding the text.
+    pub error: io::Error,
+}
+
+impl fmt::Display for RowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "text {}: {}", self.row, self.error)
+    }
+}
+
+impl std::error::Error for RowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Replaces every `</w>` in `bytes[start..]` with a space, in place.
+fn replace_end_of_word_markers(bytes: &mut Vec<u8>, start: usize) {
+    const MARKER: &[u8] = b"</w>";
+    let (mut read, mut write) = (start, start);
+    while read < bytes.len() {
+        if bytes[read..].starts_with(MARKER) {
+            bytes[write] = b' ';
+            read += MARKER.len();
+        } else {
+            bytes[write] = bytes[read];
+            read += 1;
+        }
+        write += 1;
+    }
+    bytes.truncate(write);
+}
+
+/// Split `text` into chunks of roughly [`ENCODE_CHUNK_SIZE`] bytes ending at whitespace, together
+/// with their start offsets.
+fn chunks(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut start = 0;
+    iter::from_fn(move || {
+        if start >= text.len() {
+            return None;
+        }
+        let mut end = (start + ENCODE_CHUNK_SIZE).min(text.len());
+        while !text.is_char_boundary(end) {
+            end += 1;
+        }
+        end = text[end..]
+            .find(char::is_whitespace)
+            .map_or(text.len(), |i| end + i);
+        let chunk_start = mem::replace(&mut start, end);
+        Some((chunk_start, &text[chunk_start..end]))
+    })
+}
+
+fn max_merges_exceeded(max_merges: usize) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("encoding the input takes more than {max_merges} merge operations"),
+    )
+}
+
+fn deadline_exceeded() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "deadline exceeded while encoding")
+}
+
+impl fmt::Debug for EncodeOptions<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncodeOptions")
+            .field("lowercase", &self.lowercase)
+            .field("normalize", &self.normalize.map(|_| ".."))
+            .field("special_markers", &self.special_markers)
+            .field("max_tokens", &self.max_tokens)
+            .field("max_merges", &self.max_merges)
+            .field("deadline", &self.deadline)
+            .finish()
+    }
+}
+
+/// Progress information reported by [`Tokenizer::with_vocabulary_progress`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct LoadProgress {
+    /// The number of merge rules loaded so far.
+    pub merge_rules: usize,
+    /// The approximate number of bytes of vocabulary data processed so far.
+    pub bytes: usize,
+}
+
+/// Approximate heap memory usage of a [`Tokenizer`] in bytes, as returned by
+/// [`Tokenizer::memory_usage`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct MemoryUsage {
+    /// Memory used by the table mapping bytes to their initial tokens.
+    pub byte_to_token: usize,
+    /// Memory used by the merge rules.
+    pub merge_rules: usize,
+    /// Memory used by the table mapping tokens back to bytes for decoding. This is `0` until the
+    /// table is built when first decoding tokens.
+    pub decoder: usize,
+}
+
+impl MemoryUsage {
+    /// Returns the total memory used by all tables.
+    pub fn total(&self) -> usize {
+        self.byte_to_token + self.merge_rules + self.decoder
+    }
+}
+
+/// An `Extend<Token>` sink writing token ids into a fixed-size uninitialized buffer.
+///
+/// Tokens that don't fit into the buffer anymore are silently discarded.
+struct UninitSink<'a> {
+    out: &'a mut [MaybeUninit<u16>],
+    len: usize,
+}
+
+impl Extend<Token> for UninitSink<'_> {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, iter: I) {
+        for (slot, token) in self.out[self.len..].iter_mut().zip(iter) {
+            slot.write(token.to_u16());
+            self.len += 1;
+        }
+    }
+}
+
+/// An `Extend<Token>` sink counting the tokens without storing them.
+struct CountSink(usize);
+
+impl Extend<Token> for CountSink {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, iter: I) {
+        self.0 += iter.into_iter().count();
+    }
+}
+
+/// An `Extend<Token>` sink appending the bytes of each token to a buffer.
+struct BytesSink<'a> {
+    tokenizer: &'a Tokenizer,
+    bytes: Vec<u8>,
+}
+
+impl Extend<Token> for BytesSink<'_> {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, iter: I) {
+        for token in iter {
+            let token_bytes = self.tokenizer.token_bytes(token);
+            self.bytes
+                .extend_from_slice(token_bytes.expect("encoded tokens are part of the vocabulary"));
+        }
+    }
+}
+
+/// A single word of a text, as produced by [`Tokenizer::split_words`].
+#[derive(Clone, Copy, Debug)]
+enum Word<'a> {
+    /// A `<start_of_text>` or `<end_of_text>` marker string, encoded as the marker token.
+    Marker(Token),
+    /// Any other word, encoded using byte-pair encoding.
+    Text {
+        word: &'a str,
+        /// Whether the word follows whitespace, which vocabularies using
+        /// [`WordBoundary::SpacePrefix`] encode as a leading space.
+        follows_whitespace: bool,
+    },
+}
+
+/// Looks up tokens by the strings representing them in vocabulary files while loading a
+/// vocabulary.
+///
+/// Only the hashes of the strings are stored. Candidates are verified by spelling out their string
+/// from the characters of the byte tokens and the pairs the other tokens are merged from, so that
+/// loading doesn't need to hold a string for every token.
+struct TokenLookup {
+    /// The latest token with the string of each hash, continuing at the following hash values when
+    /// the hashes of different strings collide.
+    tokens: HashMap<u64, Token>,
+    /// The character representing each byte token.
+    chars: [char; 256],
+    /// The pair of tokens merged into each token from `first_merged` on.
+    pairs: Vec<(Token, Token)>,
+    first_merged: u16,
+    /// Holds the string of a merged token while it is inserted.
+    buffer: String,
+}
+
+impl TokenLookup {
+    fn with_capacity(capacity: usize, first_merged: u16) -> TokenLookup {
+        let mut tokens = HashMap::default();
+        tokens.reserve(capacity);
+        TokenLookup {
+            tokens,
+            chars: ['\0'; 256],
+            pairs: Vec::with_capacity(capacity.saturating_sub(usize::from(first_merged))),
+            first_merged,
+            buffer: String::new(),
+        }
+    }
+
+    /// Add the byte `token` represented by `ch`, and its end-of-word variant if `end_of_word`.
+    fn insert_byte_token(&mut self, ch: char, token: Token, end_of_word: bool) {
+        self.chars[usize::from(token.0)] = ch;
+        let mut buf = [0; 8];
+        let len = ch.encode_utf8(&mut buf).len();
+        self.insert(str::from_utf8(&buf[..len]).unwrap(), token);
+        if end_of_word {
+            buf[len..len + 4].copy_from_slice(b"</w>");
+            self.insert(
+                str::from_utf8(&buf[..len + 4]).unwrap(),
+                Token(token.0 + 256),
+            );
+        }
+    }
+
+    /// Add the `token` merged from `pair`, whose strings are `first` and `second`.
+    fn insert_merged_token(
+        &mut self,
+        first: &str,
+        second: &str,
+        pair: (Token, Token),
+        token: Token,
+    ) {
+        debug_assert_eq!(usize::from(token.0 - self.first_merged), self.pairs.len());
+        self.pairs.push(pair);
+        let mut buffer = mem::take(&mut self.buffer);
+        buffer.clear();
+        buffer.push_str(first);
+        buffer.push_str(second);
+        self.insert(&buffer, token);
+        self.buffer = buffer;
+    }
+
+    /// Returns the latest token represented by `string`.
+    fn get(&self, string: &str) -> Option<Token> {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, string.as_bytes());
+        while let Some(&token) = self.tokens.get(&hash) {
+            if self.strip(token, string) == Some("") {
+                return Some(token);
+            }
+            hash = hash.wrapping_add(1);
+        }
+        None
+    }
+
+    /// Makes `token` the latest token represented by `string`.
+    fn insert(&mut self, string: &str, token: Token) {
+        let mut hash = fnv1a(FNV_OFFSET_BASIS, string.as_bytes());
+        while let Some(&existing) = self.tokens.get(&hash) {
+            if self.strip(existing, string) == Some("") {
+                break;
+            }
+            hash = hash.wrapping_add(1);
+        }
+        self.tokens.insert(hash, token);
+    }
+
+    /// Returns the rest of `string` if it starts with the string represented by `token`.
+    fn strip<'a>(&self, token: Token, string: &'a str) -> Option<&'a str> {
+        match token.0.checked_sub(self.first_merged) {
+            Some(index) => {
+                let (first, second) = self.pairs[usize::from(index)];
+                self.strip(second, self.strip(first, string)?)
+            }
+            None => {
+                let (ch, suffix) = match token.0 {
+                    0..=255 => (self.chars[usize::from(token.0)], ""),
+                    _ => (self.chars[usize::from(token.0 - 256)], "</w>"),
+                };
+                string.strip_prefix(ch)?.strip_prefix(suffix)
+            }
+        }
+    }
+}
+
+/// An `Extend<Token>` sink writing token ids into a row of a batch or another slice.
+///
+/// Tokens that don't fit into the row anymore are silently discarded.
+struct RowSink<'a> {
+    row: &'a mut [u16],
+    len: usize,
+}
+
+impl Extend<Token> for RowSink<'_> {
+    fn extend<I: IntoIterator<Item = Token>>(&mut self, iter: I) {
+        for (slot, token) in self.row[self.len..].iter_mut().zip(iter) {
+            *slot = token.to_u16();
+            self.len += 1;
+        }
+    }
+}
+
+/// Represents a single token.
+///
+/// Values of this type can only be produced by calls to methods on the [`Tokenizer`] type, mainly
+/// [`Tokenizer::encode`], apart from the constants for the special marker tokens of the bundled
+/// vocabulary. To input tokens into an actual neural network the [`to_u16`] method should be used.
+///
+/// [`to_u16`]: Token::to_u16
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct Token(u16);
+
+impl Token {
+    /// The `<start_of_text>` marker token of the vocabulary bundled with this crate.
+    ///
+    /// This is the token returned by [`Tokenizer::start_of_text`] for [`Tokenizer::new`], usable
+    /// in `match` patterns and static tables. Tokenizers using other vocabularies may use a
+    /// different token. Together with [`CLIP_CONTEXT_LENGTH`], this describes the input expected by
+    /// the original CLIP models.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Token;
+    /// fn describe(token: Token) -> &'static str {
+    ///     match token {
+    ///         Token::START_OF_TEXT => "start",
+    ///         Token::END_OF_TEXT => "end",
+    ///         _ => "text",
+    ///     }
+    /// }
+    /// assert_eq!(describe(Token::END_OF_TEXT), "end");
+    /// ```
+    #[cfg(feature = "openai-vocabulary-file")]
+    pub const START_OF_TEXT: Token = Token(49406);
+
+    /// The `<end_of_text>` marker token of the vocabulary bundled with this crate.
+    ///
+    /// This is the token returned by [`Tokenizer::end_of_text`] for [`Tokenizer::new`], see
+    /// [`START_OF_TEXT`](Token::START_OF_TEXT).
+    #[cfg(feature = "openai-vocabulary-file")]
+    pub const END_OF_TEXT: Token = Token(49407);
+
+    /// Create `Token` from number, validating against the vocabulary of the given `tokenizer`.
+    ///
+    /// Instead of a [`Tokenizer`] this also accepts a [`VocabInfo`] obtained using
+    /// [`Tokenizer::vocab_info`].
+    pub fn from_u16(token: u16, tokenizer: impl Into<VocabInfo>) -> Option<Self> {
+        (token < tokenizer.into().vocab_size).then_some(Self(token))
+    }
+
+    /// Returns the numerical representation of this `Token`.
+    ///
+    /// The resulting number is suitable for feeding into a neural network.
+    pub fn to_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the numerical representation of this `Token` as a `usize`.
+    ///
+    /// This is convenient for indexing into embedding tables or other arrays.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let embeddings = vec![[0.0f32; 4]; 49408];
+    /// let embedding = embeddings[tokenizer.end_of_text().to_usize()];
+    /// ```
+    pub fn to_usize(self) -> usize {
+        usize::from(self.0)
+    }
+}
+
+impl From<Token> for u16 {
+    fn from(token: Token) -> u16 {
+        token.to_u16()
+    }
+}
+
+impl From<Token> for usize {
+    fn from(token: Token) -> usize {
+        token.to_usize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch() {
+        let tokenizer = Tokenizer::new();
+        let encoded = tokenizer.tokenize_batch(["Hi", "How are you?", "I'm fine, thanks!"], 6);
+        let expected = ndarray::array![
+            [49406, 1883, 49407, 0, 0, 0],
+            [49406, 829, 631, 592, 286, 49407],
+            [49406, 328, 880, 3797, 267, 49407],
+        ];
+        assert_eq!(encoded, expected);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch_duplicates() {
+        let tokenizer = Tokenizer::new();
+        let encoded = tokenizer.tokenize_batch(["Hi", "How are you?", "Hi", "Hi"], 6);
+        let expected = ndarray::array![
+            [49406, 1883, 49407, 0, 0, 0],
+            [49406, 829, 631, 592, 286, 49407],
+            [49406, 1883, 49407, 0, 0, 0],
+            [49406, 1883, 49407, 0, 0, 0],
+        ];
+        assert_eq!(encoded, expected);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch_with_attention_mask() {
+        let tokenizer = Tokenizer::new();
+        // Token id 0 is a valid token ("!"), so it must not be treated as padding
+        let (input_ids, attention_mask) =
+            tokenizer.tokenize_batch_with_attention_mask::<i32, _>(["Hi", "Hi!<end_of_text>"], 6);
+        let expected_input_ids = ndarray::array![
+            [49406, 1883, 49407, 0, 0, 0],
+            [49406, 1883, 0, 283, 806, 49407],
+        ];
+        let expected_attention_mask = ndarray::array![[1, 1, 1, 0, 0, 0], [1, 1, 1, 1, 1, 1]];
+        assert_eq!(input_ids, expected_input_ids);
+        assert_eq!(attention_mask, expected_attention_mask);
+
+        // Tokens following a literal `<end_of_text>` marker in the text are not padding
+        let (input_ids, attention_mask) =
+            tokenizer.tokenize_batch_with_attention_mask::<i32, _>(["Hi <end_of_text> there"], 7);
+        assert_eq!(
+            input_ids,
+            ndarray::array![[49406, 1883, 49407, 997, 49407, 0, 0]]
+        );
+        assert_eq!(attention_mask, ndarray::array![[1, 1, 1, 1, 1, 0, 0]]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batches() {
+        let tokenizer = Tokenizer::new();
+        let texts = ["Hi", "", "I'm fine, thanks!", "How are you?", "Hi"];
+        let batches = tokenizer.tokenize_batches(texts, 6, 2).collect::<Vec<_>>();
+        assert_eq!(
+            batches
+                .iter()
+                .map(|batch| batch.nrows())
+                .collect::<Vec<_>>(),
+            [2, 2, 1]
+        );
+        let views = batches.iter().map(|batch| batch.view()).collect::<Vec<_>>();
+        assert_eq!(
+            ndarray::concatenate(ndarray::Axis(0), &views).unwrap(),
+            tokenizer.tokenize_batch(texts, 6)
+        );
+
+        // Unbounded streams are consumed lazily
+        let mut batches = tokenizer.tokenize_batches(iter::repeat("Hi"), 4, 3);
+        for _ in 0..3 {
+            assert_eq!(batches.next().unwrap().dim(), (3, 4));
+        }
+    }
+
+    #[test]
+    fn eot_positions() {
+        let tokenizer = Tokenizer::new();
+        // Rows padded with `<end_of_text>` instead of zeros, as done by some model exports
+        let rows = vec![vec![49406, 49407], vec![49406, 1883, 49407, 49407, 49407]];
+        assert_eq!(tokenizer.eot_positions(&rows).unwrap(), [1, 2]);
+        assert!(tokenizer
+            .eot_positions(Vec::<&[u16]>::new())
+            .unwrap()
+            .is_empty());
+
+        let err = tokenizer
+            .eot_positions([&[49406, 49407][..], &[49406, 1883, 997]])
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("row 1"));
+
+        #[cfg(feature = "ndarray")]
+        {
+            let ids = tokenizer.tokenize_batch(["Hi", "", "I'm fine, thanks!"], 6);
+            assert_eq!(
+                tokenizer.eot_positions_matrix(ids.view()).unwrap(),
+                [2, 1, 5]
+            );
+            // Columns of the transposed matrix are not contiguous
+            let transposed = ids.t().as_standard_layout().into_owned();
+            let ids = transposed.t();
+            assert!(ids.row(0).as_slice().is_none());
+            assert_eq!(tokenizer.eot_positions_matrix(ids).unwrap(), [2, 1, 5]);
+            assert!(tokenizer
+                .eot_positions_matrix(ids.slice(ndarray::s![.., ..4]))
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn tokenize_batch_ragged() {
+        let tokenizer = Tokenizer::new();
+        let (tokens, lengths) = tokenizer.tokenize_batch_ragged(["Hi", "", "I'm fine, thanks!"]);
+        assert_eq!(
+            tokens,
+            [49406, 1883, 49407, 49406, 49407, 49406, 328, 880, 3797, 267, 1085, 256, 49407]
+        );
+        assert_eq!(lengths, [3, 2, 8]);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch_with() {
+        let tokenizer = Tokenizer::new();
+        let options = EncodeOptions::new().max_tokens(1);
+        let encoded = tokenizer
+            .tokenize_batch_with(["Hi", "How are you?", "Hi"], 4, &options)
+            .unwrap();
+        let expected = ndarray::array![
+            [49406, 1883, 49407, 0],
+            [49406, 829, 49407, 0],
+            [49406, 1883, 49407, 0],
+        ];
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn input_limits() {
+        let base = Tokenizer::new();
+        let vocabulary = base.vocabulary();
+        let tokenizer = Tokenizer::from_vocabulary(vocabulary, TokenizerOptions::new());
+        let mut tokens = Vec::new();
+        tokenizer
+            .try_encode(&"a ".repeat(10_000), &mut tokens)
+            .unwrap();
+        assert_eq!(tokens.len(), 10_000);
+
+        let options = TokenizerOptions::new().max_input_bytes(8);
+        let tokenizer = Tokenizer::from_vocabulary(vocabulary, options);
+        let mut tokens = Vec::new();
+        tokenizer.try_encode("Hi there", &mut tokens).unwrap();
+        let err = tokenizer.try_encode("Hi there!", &mut tokens).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(tokens.len(), 2);
+        // The limit is in bytes, not characters
+        assert!(tokenizer.try_encode("🦀🦀🦀", &mut tokens).is_err());
+        // `encode` ignores the limits
+        tokenizer.encode("Hi there!", &mut tokens);
+        assert_eq!(tokens.len(), 5);
+
+        let options = TokenizerOptions::new().max_input_words(3);
+        let tokenizer = Tokenizer::from_vocabulary(vocabulary, options);
+        let mut tokens = Vec::new();
+        tokenizer
+            .try_encode_with("Hi there, you", &mut tokens, &EncodeOptions::new())
+            .unwrap_err();
+        tokenizer
+            .try_encode("   Hi   there!   ", &mut tokens)
+            .unwrap();
+        assert_eq!(tokens.len(), 3);
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn batch_input_limits() {
+        let options = TokenizerOptions::new().max_input_words(2);
+        let tokenizer = Tokenizer::from_vocabulary(Tokenizer::new().vocabulary(), options);
+        let encoded = tokenizer
+            .try_tokenize_batch(["Hi", "Hi there", "Hi"], 5)
+            .unwrap();
+        assert_eq!(encoded.nrows(), 3);
+        let err = tokenizer
+            .try_tokenize_batch(["Hi", "Hi there", "How are you?"], 5)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert!(err.to_string().starts_with("text 2: "), "{err}");
+    }
+
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch_with_errors() {
+        let options = TokenizerOptions::new().max_input_words(2);
+        let tokenizer = Tokenizer::from_vocabulary(Tokenizer::new().vocabulary(), options);
+        // Exceeding the input limits or the work budget only affects the row of the text
+        let punctuation = "!".repeat(100);
+        let texts = [
+            "How are you?",
+            "Hi",
+            "Hi there",
+            "How are you?",
+            &punctuation,
+        ];
+        let options = EncodeOptions::new().max_merges(50);
+        let (encoded, errors) = tokenizer
+            .tokenize_batch_with_errors(texts, 5, &options)
+            .unwrap();
+        let expected = tokenizer
+            .tokenize_batch_with(["Hi", "Hi there"], 5, &options)
+            .unwrap();
+        assert_eq!(encoded.slice(ndarray::s![1..3, ..]), expected);
+        for row in [0, 3, 4] {
+            assert!(encoded.row(row).iter().all(|&t| t == 0));
+        }
+
+        let rows = errors.iter().map(|err| err.row).collect::<Vec<_>>();
+        assert_eq!(rows, [0, 3, 4]);
+        assert_eq!(errors[0].error.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(errors[1].error.to_string(), errors[0].error.to_string());
+        assert_ne!(errors[2].error.to_string(), errors[0].error.to_string());
+        assert!(errors[0].to_string().starts_with("text 0: "));
+
+        assert!(tokenizer
+            .tokenize_batch_with_errors(["Hi"], 2, &options)
+            .is_err());
     }
 
-    /// Returns the special `<end_of_text>` marker token.
-    ///
-    /// See [`encode`] for an example about how to add this token to a token sequence.
-    ///
-    /// [`encode`]: Tokenizer::encode
-    pub fn end_of_text(&self) -> Token {
-        self.end_of_text
+    #[cfg(any(feature = "dataset", feature = "polars"))]
+    #[test]
+    fn encode_row() {
+        let tokenizer = Tokenizer::new();
+        // Longer texts come first, so that the row holds leftover tokens when encoding the others
+        let texts = [
+            "A person riding a motorcycle",
+            "🦀🦀🦀 <end_of_text> 🦀",
+            "Hi",
+            "",
+        ];
+        for context_length in [3, 4, 7, 77] {
+            for max_tokens in [None, Some(0), Some(2)] {
+                let options = EncodeOptions {
+                    max_tokens,
+                    ..EncodeOptions::new()
+                };
+                // Rows are overwritten completely, including the padding
+                let mut row = vec![u16::MAX; context_length];
+                for text in texts {
+                    let mut expected = vec![tokenizer.start_of_text()];
+                    tokenizer.encode_with(text, &mut expected, &options);
+                    expected.truncate(context_length - 1);
+                    expected.push(tokenizer.end_of_text());
+                    let mut expected = expected.iter().map(|t| t.to_u16()).collect::<Vec<_>>();
+                    expected.resize(context_length, 0);
+
+                    tokenizer.encode_row(text, &mut row, &options);
+                    assert_eq!(row, expected, "{text} {context_length} {max_tokens:?}");
+                }
+            }
+        }
     }
-}
 
-#[cfg(any(test, feature = "openai-vocabulary-file"))]
-impl Default for Tokenizer {
-    fn default() -> Tokenizer {
-        Tokenizer::new()
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn try_tokenize_batch() {
+        let tokenizer = Tokenizer::new();
+        let encoded = tokenizer.try_tokenize_batch(["Hi"], 3).unwrap();
+        assert_eq!(encoded, ndarray::array![[49406, 1883, 49407]]);
+        let err = tokenizer.try_tokenize_batch(["Hi"], 2).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let err = tokenizer
+            .try_tokenize_batch_with_attention_mask::<i64, _>(["Hi"], 0)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
     }
-}
 
-/// Represents a single token.
-///
-/// Values of this type can only be produced by calls to methods on the [`Tokenizer`] type, mainly
-/// [`Tokenizer::encode`]. To input tokens into an actual neural network the [`to_u16`] method
-/// should be used.
-///
-/// [`to_u16`]: Token::to_u16
-#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
-pub struct Token(u16);
+    #[cfg(feature = "ndarray")]
+    #[test]
+    fn tokenize_batch_long_clip() {
+        let clip = Tokenizer::new();
+        let long_clip = Tokenizer::long_clip();
+        assert_eq!(clip.context_length(), CLIP_CONTEXT_LENGTH);
+        assert_eq!(long_clip.context_length(), LONG_CLIP_CONTEXT_LENGTH);
 
-impl Token {
-    /// Create `Token` from number, validating against the given `tokenizer`.
-    pub fn from_u16(token: u16, tokenizer: &Tokenizer) -> Option<Self> {
-        (token <= tokenizer.end_of_text().0).then_some(Self(token))
-    }
+        // 100 words encode to 100 tokens, which only fit into the longer context
+        let text = ["a"; 100].join(" ");
+        let encoded = long_clip.tokenize_batch([&*text], long_clip.context_length());
+        assert_eq!(encoded.dim(), (1, 248));
+        assert_eq!(encoded[[0, 101]], 49407);
+        assert!(encoded.row(0).iter().skip(102).all(|&id| id == 0));
+        let encoded = clip.tokenize_batch([&*text], clip.context_length());
+        assert_eq!(encoded.dim(), (1, 77));
+        assert_eq!(encoded[[0, 76]], 49407);
 
-    /// Returns the numerical representation of this `Token`.
-    ///
-    /// The resulting number is suitable for feeding into a neural network.
-    pub fn to_u16(self) -> u16 {
-        self.0
+        // Longer texts are truncated at the end of the longer context
+        let text = ["a"; 300].join(" ");
+        let encoded = long_clip.tokenize_batch([&*text], long_clip.context_length());
+        assert_eq!(encoded[[0, 0]], 49406);
+        assert!(encoded.row(0).iter().skip(1).take(246).all(|&id| id == 320));
+        assert_eq!(encoded[[0, 247]], 49407);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn word_boundary_space_prefix() {
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\nr e\nĠthe re\n";
+        let tokenizer = Tokenizer::from_vocabulary(
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap()
+                .vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        assert_eq!(tokenizer.word_boundary(), WordBoundary::SpacePrefix);
+        assert_eq!(tokenizer.start_of_text(), Token(261));
+        assert_eq!(tokenizer.end_of_text(), Token(262));
+        assert_eq!(tokenizer.merge_rank(Token(256), Token(257)), Some(2));
+
+        let mut tokens = Vec::new();
+        tokenizer.encode("the  there!", &mut tokens);
+        assert_eq!(tokens, [Token(83), Token(257), Token(260), Token(0)]);
+        assert_eq!(tokenizer.decode(tokens.iter().copied()), "the there!");
+        assert_eq!(tokenizer.decode_iter(tokens).count(), 4);
+        assert_eq!(
+            tokenizer.encode_to_string_tokens("The"),
+            ["T", "he"].map(String::from)
+        );
+
+        let Err(err) =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 257, WordBoundary::SpacePrefix)
+        else {
+            panic!("vocabulary size below minimum was accepted");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
 
-    #[cfg(feature = "ndarray")]
     #[test]
-    fn tokenize_batch() {
-        let tokenizer = Tokenizer::new();
-        let encoded = tokenizer.tokenize_batch(["Hi", "How are you?", "I'm fine, thanks!"], 6);
-        let expected = ndarray::array![
-            [49406, 1883, 49407, 0, 0, 0],
-            [49406, 829, 631, 592, 286, 49407],
-            [49406, 328, 880, 3797, 267, 49407],
-        ];
-        assert_eq!(encoded, expected);
+    fn word_boundary_default() {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(data.as_bytes(), 1000, WordBoundary::default())
+                .unwrap();
+        assert_eq!(tokenizer.word_boundary(), WordBoundary::EndOfWordSuffix);
+        let clip = Tokenizer::with_vocabulary_str(data, 1000).unwrap();
+        assert_eq!(
+            tokenizer.vocabulary.merge_rules,
+            clip.vocabulary.merge_rules
+        );
     }
 
     #[test]
@@ -449,6 +3515,134 @@ mod tests {
         assert_eq!(decoded, "hello world !!! ");
     }
 
+    #[test]
+    fn encode_bytes() {
+        let tokenizer = Tokenizer::new();
+        let encode_bytes = |text: &[u8]| {
+            let mut tokens = Vec::new();
+            tokenizer.encode_bytes(text, &mut tokens);
+            tokens
+        };
+        let decode_bytes =
+            |tokens: Vec<Token>| tokenizer.decode_iter(tokens).collect::<Vec<_>>().concat();
+
+        let mut expected = Vec::new();
+        tokenizer.encode("Hi there 🦀", &mut expected);
+        assert_eq!(encode_bytes("Hi there 🦀".as_bytes()), expected);
+        assert!(encode_bytes(b"").is_empty());
+
+        // Invalid bytes are kept, including incomplete sequences at the end
+        let tokens = encode_bytes(b"Hi \xff\xfe There \xe2\x82");
+        assert_eq!(decode_bytes(tokens), b"hi \xff \xfe there \xe2\x82 ");
+    }
+
+    #[test]
+    fn try_decode() {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let small = Tokenizer::with_vocabulary_str(data, 1000).unwrap();
+        let large = Tokenizer::new();
+        let token = Token::from_u16(3306, &large).unwrap();
+        let err = small.try_decode([token]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(
+            err.to_string(),
+            "unknown token 3306 at position 0 (valid token ids are 0..=999); the token was likely \
+             produced using a different, larger vocabulary"
+        );
+        assert_eq!(large.try_decode([token]).unwrap(), "hello ");
+
+        // Negative values cast to `u16`
+        let err = large.try_decode([Token(320), Token(u16::MAX)]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown token 65535 at position 1 (valid token ids are 0..=49407); this is -1 as a \
+             signed integer, so a negative value was likely cast to an unsigned type"
+        );
+    }
+
+    #[test]
+    fn decode_into() {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let small = Tokenizer::with_vocabulary_str(data, 1000).unwrap();
+        let tokenizer = Tokenizer::new();
+        let texts = ["Hello world!!!", "🦀 crab", "a </w> b", ""];
+        let mut out = String::from("prefix: ");
+        for text in texts {
+            let mut tokens = Vec::new();
+            tokenizer.encode(text, &mut tokens);
+            out.truncate("prefix: ".len());
+            tokenizer.decode_into(tokens.iter().copied(), &mut out);
+            assert_eq!(out, format!("prefix: {}", tokenizer.decode(tokens)));
+        }
+
+        // The first byte of a multi-byte character
+        let tokens = [tokenizer.vocabulary.byte_to_token[0xC3], Token(3306)];
+        assert_eq!(tokenizer.decode(tokens), "\u{FFFD}hello ");
+        let mut out = String::from("ü");
+        tokenizer.decode_into(tokens, &mut out);
+        assert_eq!(out, "ü\u{FFFD}hello ");
+
+        // Unknown tokens leave `out` unchanged
+        let token = Token::from_u16(3306, &tokenizer).unwrap();
+        let mut out = String::from("hi ");
+        let err = small
+            .try_decode_into([Token(0), token], &mut out)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(out, "hi ");
+    }
+
+    #[test]
+    fn decode_iter() {
+        let tokenizer = Tokenizer::new();
+        let tokens = [49406, 3306, 65, 23176, 16485, 49407].map(Token);
+        let chunks = tokenizer.decode_iter(tokens).collect::<Vec<_>>();
+        assert_eq!(
+            chunks,
+            [
+                &b"<start_of_text>"[..],
+                b"hello",
+                b" ",
+                b"b",
+                "å".as_bytes(),
+                b"stad",
+                b" ",
+                b"<end_of_text>",
+            ]
+        );
+        let bytes = chunks.concat();
+        assert_eq!(String::from_utf8(bytes).unwrap(), tokenizer.decode(tokens));
+    }
+
+    #[test]
+    fn encode_to_string_tokens() {
+        let tokenizer = Tokenizer::new();
+        let subwords = tokenizer.encode_to_string_tokens("Hello Båstad 🦀");
+        let mut tokens = Vec::new();
+        tokenizer.encode("Hello Båstad 🦀", &mut tokens);
+        assert_eq!(subwords.len(), tokens.len());
+        assert_eq!(
+            subwords,
+            [
+                "hello</w>",
+                "b",
+                "å",
+                "stad</w>",
+                "\u{FFFD}",
+                "\u{FFFD}</w>"
+            ]
+        );
+
+        let mut pieces = Vec::new();
+        tokenizer.encode_with_pieces("Hello Båstad 🦀", &mut pieces);
+        let (pieces_tokens, pieces): (Vec<_>, Vec<_>) = pieces.into_iter().unzip();
+        assert_eq!(pieces_tokens, tokens);
+        assert_eq!(pieces, subwords);
+        // Only pieces that aren't valid UTF-8 on their own are allocated
+        assert!(matches!(pieces[0], Cow::Borrowed(_)));
+        assert!(matches!(pieces[4], Cow::Owned(_)));
+    }
+
     #[test]
     fn encode_apostrophe() {
         let tokens = encode("i've seen it");
@@ -524,7 +3718,7 @@ mod tests {
     fn encode_start_and_end_of_text_with_special_char() {
         let tokens = encode("<start_of_text>Hi!<end_of_text>");
         // Note how the "<end_of_text>" substring is not encoded as the special marker token (which
-        // would be 49407), because the word-splitting regex does not split it as a separate word
+        // would be 49407), because the word-splitting pattern does not split it as a separate word
         // due to the exclamation mark preceeding it. This behavior is somewhat strange, but we
         // preserve it in order to stay compatible with the original Python implementation.
         assert_eq!(
@@ -533,6 +3727,129 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_iter() {
+        let tokenizer = Tokenizer::new();
+        let long = "ΟΔΥΣΣΕΥΣ ΚΑΙ ΣΕΙΡΗΝΕΣ, a long-winded story. ".repeat(500);
+        for text in [
+            "",
+            "Hi there",
+            "<start_of_text>Hi there<end_of_text>",
+            &long,
+        ] {
+            assert_eq!(
+                tokenizer.encode_iter(text).collect::<Vec<_>>(),
+                encode(text)
+            );
+        }
+        assert_eq!(tokenizer.encode_iter(&long).take(3).count(), 3);
+    }
+
+    #[test]
+    fn count_tokens() {
+        let tokenizer = Tokenizer::new();
+        let long = "ΟΔΥΣΣΕΥΣ ΚΑΙ ΣΕΙΡΗΝΕΣ, a long-winded story. ".repeat(500);
+        for text in [
+            "",
+            "Hi there",
+            "<start_of_text>Hi there<end_of_text>",
+            &long,
+        ] {
+            let len = encode(text).len();
+            assert_eq!(tokenizer.count_tokens(text), len);
+            assert!(tokenizer.fits_within(text, len));
+            assert!(len == 0 || !tokenizer.fits_within(text, len - 1));
+        }
+    }
+
+    #[test]
+    fn encode_into() {
+        let tokenizer = Tokenizer::new();
+        let cased = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        let cached = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().text_cache(16),
+        );
+        let long = "ΟΔΥΣΣΕΥΣ ΚΑΙ ΣΕΙΡΗΝΕΣ, a long-winded story. ".repeat(500);
+        let texts = [
+            "",
+            "Hi There",
+            "<start_of_text>Hi there<end_of_text>",
+            "İstanbul Båstad",
+            "Ein <END_OF_TEXT> Straßenköter<end_of_text>ΣΊΣΥΦΟΣ",
+            &long,
+        ];
+        for tokenizer in [&tokenizer, &cased, &cached] {
+            for text in texts {
+                let mut expected = Vec::new();
+                tokenizer.encode(text, &mut expected);
+                let expected = expected.iter().map(|t| t.to_u16()).collect::<Vec<_>>();
+                for len in [0, 1, 3, 5, 10_000] {
+                    let mut buffer = vec![u16::MAX; len];
+                    let count = tokenizer.encode_into(text, &mut buffer);
+                    assert_eq!(count, expected.len().min(len));
+                    assert_eq!(buffer[..count], expected[..count]);
+
+                    let mut truncated = Vec::new();
+                    let options = EncodeOptions::new().max_tokens(len);
+                    tokenizer.encode_with(text, &mut truncated, &options);
+                    let truncated = truncated.iter().map(|t| t.to_u16()).collect::<Vec<_>>();
+                    assert_eq!(truncated, buffer[..count]);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn encode_words() {
+        let tokenizer = Tokenizer::new();
+        let long = "ΟΔΥΣΣΕΥΣ ΚΑΙ ΣΕΙΡΗΝΕΣ, a long-winded story. ".repeat(500);
+        for text in [
+            "",
+            "Hi there",
+            "<start_of_text>Hi there<end_of_text>",
+            &long,
+        ] {
+            let words = tokenizer.encode_words(text);
+            assert_eq!(words.concat(), encode(text));
+            let lowercased = text.to_lowercase();
+            assert_eq!(words.len(), tokenizer.pre_tokenize(&lowercased).count());
+            assert!(words.iter().all(|word| !word.is_empty()));
+        }
+        let words = tokenizer.encode_words("<start_of_text>Hi acetylsalicylic");
+        assert_eq!(words.iter().map(Vec::len).collect::<Vec<_>>(), [1, 1, 5]);
+        assert_eq!(words[0], [tokenizer.start_of_text()]);
+    }
+
+    #[test]
+    fn encode_up_to() {
+        let tokenizer = Tokenizer::new();
+        let long = "ΟΔΥΣΣΕΥΣ ΚΑΙ ΣΕΙΡΗΝΕΣ, a long-winded story. ".repeat(500);
+        let texts = [
+            "",
+            "Hi there",
+            "<start_of_text>Hi there<end_of_text>",
+            "a pneumonoultramicroscopicsilicovolcanoconiosis",
+            &long,
+        ];
+        for text in texts {
+            let expected = encode(text);
+            for max_tokens in [0, 1, 2, 3, 7, 100, 5000, usize::MAX] {
+                let mut tokens = Vec::new();
+                let exhausted = tokenizer.encode_up_to(text, max_tokens, &mut tokens);
+                assert_eq!(tokens, expected[..max_tokens.min(expected.len())]);
+                assert_eq!(
+                    exhausted,
+                    max_tokens >= expected.len(),
+                    "{text:?} {max_tokens}"
+                );
+            }
+        }
+    }
+
     #[test]
     fn decode_start_and_end_of_text() {
         let tokenizer = Tokenizer::new();
@@ -543,6 +3860,393 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encode_with() {
+        let tokenizer = Tokenizer::new();
+        let encode_with = |text, options: &EncodeOptions<'_>| {
+            let mut tokens = Vec::new();
+            tokenizer.encode_with(text, &mut tokens, options);
+            tokens
+        };
+
+        let text = "<start_of_text>Hi<start_of_text>instant labs<end_of_text>";
+        assert_eq!(encode_with(text, &EncodeOptions::new()), encode(text));
+        assert_eq!(
+            encode_with(text, &EncodeOptions::new().max_tokens(4)),
+            [49406, 1883, 49406, 10635].map(Token)
+        );
+        let tokens = encode_with(text, &EncodeOptions::new().special_markers(false));
+        assert!(!tokens.contains(&tokenizer.start_of_text()));
+        assert!(!tokens.contains(&tokenizer.end_of_text()));
+
+        assert_eq!(
+            encode_with("Hello hello", &EncodeOptions::new().lowercase(false)),
+            [39, 2512, 3306].map(Token)
+        );
+        let normalize = |text: &str| text.replace("&amp;", "&");
+        assert_eq!(
+            encode_with("a &amp; b", &EncodeOptions::new().normalize(&normalize)),
+            encode("a & b")
+        );
+    }
+
+    #[test]
+    fn work_budget() {
+        let tokenizer = Tokenizer::new();
+        let try_encode_with = |text, options: &EncodeOptions<'_>| {
+            let mut tokens = Vec::new();
+            let result = tokenizer.try_encode_with(text, &mut tokens, options);
+            (result, tokens)
+        };
+
+        // "hi" takes one merge operation and "there" takes four
+        let (result, tokens) = try_encode_with("Hi there", &EncodeOptions::new().max_merges(5));
+        result.unwrap();
+        assert_eq!(tokens, encode("Hi there"));
+        let (result, tokens) = try_encode_with("Hi there", &EncodeOptions::new().max_merges(4));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(tokens, [Token(1883)]);
+        // `encode_with` returns the partial result
+        let mut tokens = Vec::new();
+        let options = EncodeOptions::new().max_merges(4);
+        tokenizer.encode_with("Hi there", &mut tokens, &options);
+        assert_eq!(tokens, [Token(1883)]);
+
+        let text = ".".repeat(50_000);
+        let (result, tokens) = try_encode_with(&text, &EncodeOptions::new().max_merges(1_000));
+        assert!(result.is_err());
+        assert!(tokens.is_empty());
+
+        let (result, tokens) =
+            try_encode_with("Hi", &EncodeOptions::new().deadline(Instant::now()));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(tokens.is_empty());
+        let deadline = Instant::now() + std::time::Duration::from_secs(3600);
+        let (result, tokens) =
+            try_encode_with("Hi there", &EncodeOptions::new().deadline(deadline));
+        result.unwrap();
+        assert_eq!(tokens, encode("Hi there"));
+
+        let debug = format!(
+            "{:?}",
+            EncodeOptions::new().max_merges(4).deadline(deadline)
+        );
+        assert!(debug.contains("max_merges: Some(4)"));
+        assert!(debug.contains(&format!("deadline: Some({deadline:?})")));
+    }
+
+    #[test]
+    fn encode_into_custom_sink() {
+        let tokenizer = Tokenizer::new();
+        let mut tokens = std::collections::VecDeque::new();
+        tokenizer.encode("A person riding a motorcycle", &mut tokens);
+        assert_eq!(tokens, [320, 2533, 6765, 320, 10297].map(Token));
+    }
+
+    #[test]
+    fn encode_uninit() {
+        let tokenizer = Tokenizer::new();
+        let mut buffer = [MaybeUninit::uninit(); 8];
+        let count = tokenizer.encode_uninit("A person riding a motorcycle", &mut buffer);
+        assert_eq!(count, 5);
+        // SAFETY: `encode_uninit` initialized the first `count` elements
+        let tokens = buffer[..count]
+            .iter()
+            .map(|id| unsafe { id.assume_init() })
+            .collect::<Vec<_>>();
+        assert_eq!(tokens, [320, 2533, 6765, 320, 10297]);
+
+        // Tokens that don't fit are discarded
+        let mut buffer = [MaybeUninit::uninit(); 3];
+        let count = tokenizer.encode_uninit("A person riding a motorcycle", &mut buffer);
+        assert_eq!(count, 3);
+    }
+
+    #[test]
+    fn merge_rank() {
+        let tokenizer = Tokenizer::new();
+        let [i, n, q, x] =
+            [b'i', b'n', b'q', b'x'].map(|b| tokenizer.vocabulary.byte_to_token[usize::from(b)]);
+        assert_eq!(tokenizer.merge_rank(i, n), Some(0));
+        assert_eq!(tokenizer.merge_rank(n, i), Some(185));
+        // "x q" is a merge rule in the vocabulary file, but beyond the maximum vocabulary size
+        assert_eq!(tokenizer.merge_rank(x, q), None);
+        assert_eq!(tokenizer.merge_rank(tokenizer.start_of_text(), i), None);
+    }
+
+    #[test]
+    fn bpe_word() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(tokenizer.bpe_word("hello", true), encode("hello"));
+        assert_eq!(tokenizer.bpe_word("", true), []);
+        // Not split into words or lowercased
+        let tokens = tokenizer.bpe_word("Hi there", true);
+        assert_eq!(tokenizer.decode(tokens), "Hi there ");
+        let prefix = tokenizer.bpe_word("hel", false);
+        assert_eq!(tokenizer.decode(prefix.iter().copied()), "hel");
+        assert_ne!(prefix, tokenizer.bpe_word("hel", true));
+
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        assert_eq!(tokenizer.bpe_word(" the", true), [Token(258)]);
+        assert_eq!(tokenizer.bpe_word(" the", false), [Token(258)]);
+        assert_eq!(tokenizer.bpe_word("the", true), [Token(83), Token(257)]);
+    }
+
+    #[test]
+    fn canonicalize() {
+        let tokenizer = Tokenizer::new();
+        for text in [
+            "",
+            "Hello   World!!!",
+            "<start_of_text>a photo of a cat<end_of_text>",
+            "Ünïcödé 🦀 \u{301} 123",
+        ] {
+            assert_eq!(tokenizer.canonicalize(text), tokenizer.decode(encode(text)));
+        }
+    }
+
+    #[test]
+    fn pre_tokenize() {
+        let tokenizer = Tokenizer::new();
+        let text = "<start_of_text>hi!<end_of_text> 1984  båstad";
+        let words = tokenizer.pre_tokenize(text).collect::<Vec<_>>();
+        assert_eq!(
+            words,
+            [
+                ("<start_of_text>", 0..15),
+                ("hi", 15..17),
+                ("!<", 17..19),
+                ("end", 19..22),
+                ("_", 22..23),
+                ("of", 23..25),
+                ("_", 25..26),
+                ("text", 26..30),
+                (">", 30..31),
+                ("1", 32..33),
+                ("9", 33..34),
+                ("8", 34..35),
+                ("4", 35..36),
+                ("båstad", 38..45),
+            ]
+        );
+        for (word, range) in words {
+            assert_eq!(&text[range], word);
+        }
+    }
+
+    #[cfg(feature = "openai-vocabulary-file")]
+    #[test]
+    fn special_token_constants() {
+        let tokenizer = Tokenizer::new();
+        assert_eq!(Token::START_OF_TEXT, tokenizer.start_of_text());
+        assert_eq!(Token::END_OF_TEXT, tokenizer.end_of_text());
+        assert_eq!(tokenizer.context_length(), CLIP_CONTEXT_LENGTH);
+    }
+
+    #[test]
+    fn grapheme_pre_tokenizer() {
+        let tokenizer = Tokenizer::new();
+        let graphemes = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().pre_tokenizer(PreTokenizer::Graphemes),
+        );
+        assert_ne!(graphemes.fingerprint(), tokenizer.fingerprint());
+
+        let encode_with = |tokenizer: &Tokenizer, text| {
+            let mut tokens = Vec::new();
+            tokenizer.encode(text, &mut tokens);
+            tokens
+        };
+        let text = "a photo of a dog";
+        assert_eq!(encode_with(&graphemes, text), encode(text));
+        let text = "👍🏽👍!! cafe\u{301}";
+        assert_ne!(encode_with(&graphemes, text), encode(text));
+        assert_eq!(
+            graphemes.decode(encode_with(&graphemes, text)),
+            "👍🏽 👍 !! cafe\u{301} "
+        );
+    }
+
+    #[test]
+    fn with_vocabulary_bytes() {
+        let data = include_bytes!("../bpe_simple_vocab_16e6.txt");
+        let tokenizer = Tokenizer::with_vocabulary_bytes(data, 50_000).unwrap();
+        let mut tokens = Vec::new();
+        tokenizer.encode("Hi there", &mut tokens);
+        assert_eq!(tokens, [1883, 997].map(Token));
+        assert_eq!(tokenizer.end_of_text(), Token(49999));
+
+        let Err(err) = Tokenizer::with_vocabulary_bytes(b"#version: 0.2\ni \xFF", 50_000) else {
+            panic!("invalid UTF-8 must be rejected");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn merge_rule_strings() {
+        // Merge rules refer to the latest token with each string
+        let merges = "#version: 0.2\nt h\nh e\nt he\nth e\nthe r\n";
+        let tokenizer = Tokenizer::with_vocabulary_str(merges, 1000).unwrap();
+        let r = tokenizer.vocabulary.byte_to_token[usize::from(b'r')];
+        assert_eq!(
+            tokenizer.vocabulary.merge_rules[&(Token(515), r)],
+            Token(516)
+        );
+
+        let mut lookup = TokenLookup::with_capacity(0, 256);
+        lookup.insert_byte_token('a', Token(0), false);
+        lookup.insert_byte_token('b', Token(1), false);
+        // Another string whose hash collides with the one of "ab"
+        lookup
+            .tokens
+            .insert(fnv1a(FNV_OFFSET_BASIS, b"ab"), Token(1));
+        lookup.insert_merged_token("a", "b", (Token(0), Token(1)), Token(256));
+        assert_eq!(lookup.get("ab"), Some(Token(256)));
+        assert_eq!(lookup.get("b"), Some(Token(1)));
+        assert_eq!(lookup.get("ba"), None);
+        lookup.insert_merged_token("a", "b", (Token(0), Token(1)), Token(257));
+        assert_eq!(lookup.get("ab"), Some(Token(257)));
+    }
+
+    #[test]
+    fn duplicate_merge_rules() {
+        // The repeated merge rule is ignored, without leaving a gap in the token ids
+        let merges = "#version: 0.2\nh e\nt he\nh e\n";
+        let tokenizer = Tokenizer::with_vocabulary_str(merges, 1000).unwrap();
+        let expected = Tokenizer::with_vocabulary_str(&merges[..23], 1000).unwrap();
+        assert_eq!(
+            tokenizer.vocabulary.merge_rules,
+            expected.vocabulary.merge_rules
+        );
+        assert_eq!(tokenizer.end_of_text(), Token(515));
+        assert_eq!(tokenizer.vocab_info().vocab_size(), 516);
+        assert_eq!(Token::from_u16(515, &tokenizer), Some(Token(515)));
+    }
+
+    #[test]
+    fn new_with_overrides() {
+        let path = std::path::Path::new("bpe_simple_vocab_16e6.txt");
+        let tokenizer = Tokenizer::new_with_overrides(Some(path)).unwrap();
+        assert_eq!(tokenizer.end_of_text(), Token(49407));
+        let path = std::path::Path::new("does-not-exist.txt");
+        let result = Tokenizer::new_with_overrides(Some(path));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn with_vocabulary_too_small() {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        for max_vocabulary_size in [0, 100, 513] {
+            let Err(err) = Tokenizer::with_vocabulary_str(data, max_vocabulary_size) else {
+                panic!("too small vocabulary size must be rejected");
+            };
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        let tokenizer = Tokenizer::with_vocabulary_str(data, 514).unwrap();
+        assert_eq!(tokenizer.end_of_text(), Token(513));
+        assert_eq!(tokenizer.decode([Token(0), Token(256)]), "!! ");
+    }
+
+    #[test]
+    fn with_vocabulary_progress() {
+        let data = include_str!("../bpe_simple_vocab_16e6.txt");
+        let mut last = None;
+        Tokenizer::with_vocabulary_progress(data.as_bytes(), 49408, |progress| {
+            last = Some(progress);
+            ControlFlow::Continue(())
+        })
+        .unwrap();
+        let last = last.unwrap();
+        assert_eq!(last.merge_rules, 49408 - 512 - 2);
+        assert!(last.bytes > 0 && last.bytes < data.len());
+
+        let mut calls = 0;
+        let result = Tokenizer::with_vocabulary_progress(data.as_bytes(), 49408, |progress| {
+            calls += 1;
+            if progress.merge_rules == 100 {
+                ControlFlow::Break(())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 100);
+    }
+
+    #[test]
+    fn vocab_iter() {
+        let tokenizer = Tokenizer::new();
+        let vocab = tokenizer.vocab_iter().collect::<Vec<_>>();
+        assert_eq!(vocab.len(), 49408);
+        for (idx, (token, _)) in vocab.iter().enumerate() {
+            assert_eq!(token.to_usize(), idx);
+        }
+        assert_eq!(vocab[0].1, b"!");
+        assert_eq!(vocab[256].1, b"!</w>");
+        assert_eq!(vocab[512].1, b"in");
+        assert_eq!(vocab[49406].1, b"<start_of_text>");
+        assert_eq!(vocab[49407].1, b"<end_of_text>");
+    }
+
+    #[test]
+    fn vocab_info() {
+        let tokenizer = Tokenizer::new();
+        let info = tokenizer.vocab_info();
+        assert_eq!(info.vocab_size(), 49408);
+        assert_eq!(info.start_of_text(), tokenizer.start_of_text());
+        assert_eq!(info.end_of_text(), tokenizer.end_of_text());
+        for id in [0, 49406, 49407, 49408, u16::MAX] {
+            assert_eq!(Token::from_u16(id, info), Token::from_u16(id, &tokenizer));
+        }
+        assert_eq!(Token::from_u16(49408, info), None);
+    }
+
+    #[test]
+    fn from_vocabulary() {
+        let tokenizer = Tokenizer::new();
+        let cased = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::new().lowercase(false),
+        );
+        assert!(Arc::ptr_eq(tokenizer.vocabulary(), cased.vocabulary()));
+        assert_eq!(cased.end_of_text(), tokenizer.end_of_text());
+
+        let mut tokens = Vec::new();
+        cased.encode("Hello hello", &mut tokens);
+        assert_eq!(tokens, [39, 2512, 3306].map(Token));
+        tokens.clear();
+        cased.encode("hello world!!!", &mut tokens);
+        assert_eq!(tokens, encode("hello world!!!"));
+    }
+
+    #[test]
+    fn memory_usage() {
+        let tokenizer = Tokenizer::new();
+        let usage = tokenizer.memory_usage();
+        assert_eq!(usage.byte_to_token, 512);
+        assert!(usage.merge_rules >= (49408 - 512 - 2) * 6);
+        // The decoder is only built when first decoding tokens
+        assert_eq!(usage.decoder, 0);
+        tokenizer.decode([Token(1883)]);
+        let usage = tokenizer.memory_usage();
+        assert!(usage.decoder > usage.merge_rules);
+        assert_eq!(
+            usage.total(),
+            usage.byte_to_token + usage.merge_rules + usage.decoder
+        );
+    }
+
+    #[test]
+    fn token_conversions() {
+        let token = Token(49407);
+        assert_eq!(u16::from(token), 49407);
+        assert_eq!(usize::from(token), 49407);
+        assert_eq!(token.to_usize(), 49407);
+    }
+
     fn encode(input: &str) -> Vec<Token> {
         let tokenizer = Tokenizer::new();
         let mut tokens = Vec::with_capacity(input.len());
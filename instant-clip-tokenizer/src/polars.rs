@@ -0,0 +1,226 @@
+//! Tokenizing string columns of [Polars](https://pola.rs) data frames.
+//!
+//! The functions in this module take a `Series` of strings (e.g. a column of captions) and return
+//! a new `Series` holding the token ids for each string, tokenizing the strings in parallel. This
+//! avoids converting between Polars and Rust (or Python) values for every row.
+//!
+//! # Examples
+//!
+//! ```
+//! # use polars_core::prelude::*;
+//! # use instant_clip_tokenizer::polars::tokenize_series_padded;
+//! # use instant_clip_tokenizer::Tokenizer;
+//! # fn main() -> PolarsResult<()> {
+//! let tokenizer = Tokenizer::new();
+//! let captions = Series::new("caption".into(), ["Hi", "How are you?"]);
+//! let tokens = tokenize_series_padded(&tokenizer, &captions, 5)?;
+//! assert_eq!(tokens.dtype(), &DataType::Array(Box::new(DataType::UInt16), 5));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::thread;
+
+use polars_core::prelude::*;
+
+use crate::{EncodeOptions, Tokenizer};
+
+/// The minimum number of strings tokenized by a single thread.
+const MIN_ROWS_PER_THREAD: usize = 1024;
+
+/// Tokenize a `Series` of strings into a `List(UInt16)` series.
+///
+/// Each string is encoded like by [`Tokenizer::tokenize_batch_ragged`], i.e. with the
+/// `<start_of_text>` and `<end_of_text>` marker tokens, but without padding or truncation. Null
+/// values result in null lists. The resulting series has the same name as `series`.
+///
+/// # Errors
+///
+/// If `series` is not of type `String`, then an error is returned.
+///
+/// # Examples
+///
+/// ```
+/// # use polars_core::prelude::*;
+/// # use instant_clip_tokenizer::polars::tokenize_series;
+/// # use instant_clip_tokenizer::Tokenizer;
+/// # fn main() -> PolarsResult<()> {
+/// let tokenizer = Tokenizer::new();
+/// let captions = Series::new("caption".into(), [Some("Hi"), None]);
+/// let tokens = tokenize_series(&tokenizer, &captions)?;
+/// let first = tokens.list()?.get_as_series(0).unwrap();
+/// assert_eq!(first.u16()?.to_vec(), [Some(49406), Some(1883), Some(49407)]);
+/// assert_eq!(tokens.null_count(), 1);
+/// # Ok(())
+/// # }
+/// ```
+pub fn tokenize_series(tokenizer: &Tokenizer, series: &Series) -> PolarsResult<Series> {
+    let texts = series.str()?.iter().collect::<Vec<_>>();
+
+    // Each thread returns the concatenated tokens of its strings, and the number of tokens for
+    // each string
+    let mut parts = vec![(Vec::new(), Vec::new()); threads(texts.len())];
+    let per_thread = rows_per_thread(texts.len(), parts.len());
+    thread::scope(|scope| {
+        for (texts, (values, lengths)) in texts.chunks(per_thread).zip(&mut parts) {
+            scope.spawn(move || {
+                let mut tokens = Vec::new();
+                for text in texts {
+                    let Some(text) = text else {
+                        lengths.push(None);
+                        continue;
+                    };
+                    tokens.clear();
+                    tokens.push(tokenizer.start_of_text());
+                    tokenizer.encode(text, &mut tokens);
+                    tokens.push(tokenizer.end_of_text());
+                    values.extend(tokens.iter().map(|token| token.to_u16()));
+                    lengths.push(Some(tokens.len()));
+                }
+            });
+        }
+    });
+
+    let values_capacity = parts.iter().map(|(values, _)| values.len()).sum();
+    let mut builder = ListPrimitiveChunkedBuilder::<UInt16Type>::new(
+        series.name().clone(),
+        texts.len(),
+        values_capacity,
+        DataType::UInt16,
+    );
+    for (values, lengths) in &parts {
+        let mut start = 0;
+        for length in lengths {
+            match length {
+                Some(length) => {
+                    builder.append_slice(&values[start..start + length]);
+                    start += length;
+                }
+                None => builder.append_null(),
+            }
+        }
+    }
+    Ok(builder.finish().into_series())
+}
+
+/// Tokenize a `Series` of strings into an `Array(UInt16, context_length)` series.
+///
+/// Each string is encoded like by [`Tokenizer::tokenize_batch`], i.e. with the `<start_of_text>`
+/// and `<end_of_text>` marker tokens, truncated and padded to `context_length` tokens. This is
+/// suitable for directly passing to the CLIP neural network. Null values result in rows
+/// containing only padding. The resulting series has the same name as `series`.
+///
+/// # Errors
+///
+/// If `series` is not of type `String` or `context_length < 3`, then an error is returned.
+pub fn tokenize_series_padded(
+    tokenizer: &Tokenizer,
+    series: &Series,
+    context_length: usize,
+) -> PolarsResult<Series> {
+    polars_ensure!(
+        context_length >= 3,
+        InvalidOperation: "context length must be at least 3"
+    );
+    let texts = series.str()?.iter().collect::<Vec<_>>();
+
+    let mut values = vec![0; texts.len() * context_length];
+    let per_thread = rows_per_thread(texts.len(), threads(texts.len()));
+    thread::scope(|scope| {
+        let chunks = values.chunks_mut(per_thread * context_length);
+        for (texts, values) in texts.chunks(per_thread).zip(chunks) {
+            scope.spawn(move || {
+                for (text, row) in texts.iter().zip(values.chunks_mut(context_length)) {
+                    if let Some(text) = text {
+                        tokenizer.encode_row(text, row, &EncodeOptions::new());
+                    }
+                }
+            });
+        }
+    });
+
+    UInt16Chunked::from_vec(series.name().clone(), values)
+        .into_series()
+        .reshape_array(&[
+            ReshapeDimension::Infer,
+            ReshapeDimension::new(context_length as i64),
+        ])
+}
+
+/// Returns the number of threads to use for tokenizing `rows` strings.
+fn threads(rows: usize) -> usize {
+    let available = thread::available_parallelism().map_or(1, |n| n.get());
+    ((rows + MIN_ROWS_PER_THREAD - 1) / MIN_ROWS_PER_THREAD).clamp(1, available)
+}
+
+fn rows_per_thread(rows: usize, threads: usize) -> usize {
+    ((rows + threads - 1) / threads).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn captions(len: usize) -> Series {
+        let captions = (0..len)
+            .map(|i| match i % 3 {
+                0 => Some("Hi"),
+                1 => Some("How are you?"),
+                _ => None,
+            })
+            .collect::<StringChunked>();
+        captions.with_name("caption".into()).into_series()
+    }
+
+    #[test]
+    fn tokenize_series() {
+        let tokenizer = Tokenizer::new();
+        for len in [0, 5, 3000] {
+            let tokens = super::tokenize_series(&tokenizer, &captions(len)).unwrap();
+            assert_eq!(tokens.name(), "caption");
+            assert_eq!(tokens.len(), len);
+            assert_eq!(tokens.dtype(), &DataType::List(Box::new(DataType::UInt16)));
+            assert_eq!(tokens.null_count(), len / 3);
+
+            if len > 0 {
+                let lists = tokens.list().unwrap();
+                let row = |i| lists.get_as_series(i).unwrap().u16().unwrap().to_vec();
+                let (expected, _) = tokenizer.tokenize_batch_ragged(["Hi"]);
+                let expected = expected.into_iter().map(Some).collect::<Vec<_>>();
+                assert_eq!(row(len - len % 3 - 3), expected);
+                assert!(lists.get_as_series(len - len % 3 - 1).is_none());
+            }
+        }
+
+        let numbers = Series::new("numbers".into(), [1u16, 2]);
+        assert!(super::tokenize_series(&tokenizer, &numbers).is_err());
+    }
+
+    #[test]
+    fn tokenize_series_padded() {
+        let tokenizer = Tokenizer::new();
+        let tokens = super::tokenize_series_padded(&tokenizer, &captions(3000), 5).unwrap();
+        assert_eq!(tokens.name(), "caption");
+        assert_eq!(tokens.len(), 3000);
+        assert_eq!(
+            tokens.dtype(),
+            &DataType::Array(Box::new(DataType::UInt16), 5)
+        );
+
+        let rows = tokens.array().unwrap();
+        let row = |i| {
+            rows.get_as_series(i)
+                .unwrap()
+                .u16()
+                .unwrap()
+                .to_vec_null_aware()
+        };
+        assert_eq!(row(2997).left().unwrap(), [49406, 1883, 49407, 0, 0]);
+        assert_eq!(row(2998).left().unwrap(), [49406, 829, 631, 592, 49407]);
+        assert_eq!(row(2999).left().unwrap(), [0; 5]);
+
+        let empty = super::tokenize_series_padded(&tokenizer, &captions(0), 77).unwrap();
+        assert_eq!(empty.len(), 0);
+        assert!(super::tokenize_series_padded(&tokenizer, &captions(1), 2).is_err());
+    }
+}
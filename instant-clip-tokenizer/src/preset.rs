@@ -0,0 +1,179 @@
+use std::fmt;
+use std::io;
+
+use crate::Tokenizer;
+
+/// A versioned vocabulary with a stable mapping between tokens and token ids.
+///
+/// Each preset identifies one exact vocabulary. The token ids assigned by a preset never change
+/// across releases of this crate: a changed vocabulary would become a new preset instead. Data
+/// derived from token ids, e.g. caches or vector indexes keyed by tokenized text, can record the
+/// preset it was built with and check it at runtime using [`Tokenizer::expect_preset`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[non_exhaustive]
+pub enum VocabularyPreset {
+    /// The vocabulary of OpenAI's CLIP models (`bpe_simple_vocab_16e6.txt`), limited to the
+    /// 49408 tokens used by the models. This is the vocabulary used by [`Tokenizer::new`].
+    OpenAiV1,
+}
+
+impl VocabularyPreset {
+    /// Returns the name of this preset, e.g. `"openai-v1"`.
+    pub fn name(self) -> &'static str {
+        match self {
+            VocabularyPreset::OpenAiV1 => "openai-v1",
+        }
+    }
+
+    /// Returns the hash of the vocabulary of this preset, see `Tokenizer::vocabulary_hash`.
+    ///
+    /// These must never change, as they define the token ids of each preset.
+    fn vocabulary_hash(self) -> u64 {
+        match self {
+            VocabularyPreset::OpenAiV1 => 0xf20e_640b_ec3d_a3e0,
+        }
+    }
+
+    /// Returns the preset with the given [`name`](VocabularyPreset::name), if any.
+    #[cfg(feature = "config")]
+    pub(crate) fn from_name(name: &str) -> Option<VocabularyPreset> {
+        VocabularyPreset::all()
+            .iter()
+            .copied()
+            .find(|preset| preset.name() == name)
+    }
+
+    fn all() -> &'static [VocabularyPreset] {
+        &[VocabularyPreset::OpenAiV1]
+    }
+}
+
+impl fmt::Display for VocabularyPreset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Tokenizer {
+    /// Create a new `Tokenizer` using the vocabulary of the given `preset`, bundled with this
+    /// crate.
+    ///
+    /// Unlike [`Tokenizer::new`], which always uses the default vocabulary of the current release,
+    /// this pins the vocabulary version explicitly.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, VocabularyPreset};
+    /// let tokenizer = Tokenizer::from_preset(VocabularyPreset::OpenAiV1);
+    /// assert_eq!(tokenizer.preset(), Some(VocabularyPreset::OpenAiV1));
+    /// ```
+    #[cfg(any(test, feature = "openai-vocabulary-file"))]
+    pub fn from_preset(preset: VocabularyPreset) -> Tokenizer {
+        match preset {
+            VocabularyPreset::OpenAiV1 => Tokenizer::new(),
+        }
+    }
+
+    /// Returns the preset whose vocabulary this `Tokenizer` uses, if any.
+    ///
+    /// This compares the vocabulary itself, so it also recognizes presets loaded from files, e.g.
+    /// using [`Tokenizer::with_vocabulary`] or [`Tokenizer::load_binary`]. The
+    /// [`TokenizerOptions`](crate::TokenizerOptions) don't affect the result.
+    pub fn preset(&self) -> Option<VocabularyPreset> {
+        let hash = self.vocabulary_hash();
+        VocabularyPreset::all()
+            .iter()
+            .copied()
+            .find(|preset| preset.vocabulary_hash() == hash)
+    }
+
+    /// Check that this `Tokenizer` uses the vocabulary of the given `preset`.
+    ///
+    /// Call this when loading data derived from token ids that were produced by a known preset,
+    /// to fail early instead of silently working with shifted token ids.
+    ///
+    /// # Errors
+    ///
+    /// If this `Tokenizer` uses a different vocabulary, then an error of kind
+    /// [`io::ErrorKind::InvalidData`] is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::{Tokenizer, VocabularyPreset};
+    /// let tokenizer = Tokenizer::new();
+    /// tokenizer.expect_preset(VocabularyPreset::OpenAiV1).unwrap();
+    ///
+    /// let data = include_str!("../bpe_simple_vocab_16e6.txt");
+    /// let smaller = Tokenizer::with_vocabulary(data.as_bytes(), 10_000).unwrap();
+    /// assert!(smaller.expect_preset(VocabularyPreset::OpenAiV1).is_err());
+    /// ```
+    pub fn expect_preset(&self, preset: VocabularyPreset) -> io::Result<()> {
+        match self.preset() {
+            Some(actual) if actual == preset => Ok(()),
+            actual => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected the {preset} vocabulary, but the tokenizer uses {}",
+                    match actual {
+                        Some(actual) => format!("the {actual} vocabulary"),
+                        None => "a different vocabulary".to_owned(),
+                    }
+                ),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{TokenizerOptions, DEFAULT_MAX_VOCABULARY_SIZE};
+
+    #[test]
+    fn openai_v1() {
+        let tokenizer = Tokenizer::from_preset(VocabularyPreset::OpenAiV1);
+        // Changing the token ids of a preset breaks its stability guarantee
+        assert_eq!(
+            tokenizer.vocabulary_hash(),
+            VocabularyPreset::OpenAiV1.vocabulary_hash()
+        );
+        let parsed = Tokenizer::with_vocabulary_str(
+            include_str!("../bpe_simple_vocab_16e6.txt"),
+            DEFAULT_MAX_VOCABULARY_SIZE,
+        )
+        .unwrap();
+        assert_eq!(parsed.preset(), Some(VocabularyPreset::OpenAiV1));
+        let uncased = Tokenizer::from_vocabulary(
+            tokenizer.vocabulary(),
+            TokenizerOptions::long_clip().lowercase(false),
+        );
+        uncased.expect_preset(VocabularyPreset::OpenAiV1).unwrap();
+
+        let mut tokens = Vec::new();
+        tokenizer.encode("A person riding a motorcycle", &mut tokens);
+        assert_eq!(
+            tokens.iter().map(|t| t.to_u16()).collect::<Vec<_>>(),
+            [320, 2533, 6765, 320, 10297]
+        );
+    }
+
+    #[test]
+    fn other_vocabulary() {
+        let tokenizer = Tokenizer::new();
+        let extended = tokenizer
+            .extend_vocabulary(["zqxv zqxv"], &crate::ExtendOptions::new())
+            .unwrap();
+        assert_eq!(extended.preset(), None);
+        let err = extended
+            .expect_preset(VocabularyPreset::OpenAiV1)
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(
+            err.to_string(),
+            "expected the openai-v1 vocabulary, but the tokenizer uses a different vocabulary"
+        );
+        assert_eq!(VocabularyPreset::OpenAiV1.to_string(), "openai-v1");
+    }
+}
@@ -0,0 +1,108 @@
+use rand::Rng;
+
+use crate::{Token, Tokenizer};
+
+impl Tokenizer {
+    /// Sample a random token sequence of `context_length` tokens, structured like the ids of an
+    /// [`Encoding`](crate::Encoding) returned by [`tokenize`](Tokenizer::tokenize).
+    ///
+    /// The sequence starts with the `<start_of_text>` marker, followed by a random number of
+    /// tokens sampled uniformly from the vocabulary (excluding the special marker tokens), the
+    /// `<end_of_text>` marker, and zeros as padding. This is useful for load-testing text
+    /// encoders and fuzzing code that serves them, without needing a text corpus.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `context_length < 2`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// # use rand::rngs::mock::StepRng;
+    /// let tokenizer = Tokenizer::new();
+    /// let mut rng = StepRng::new(0, 0x9e37_79b9_7f4a_7c15);
+    /// let tokens = tokenizer.random_tokens(&mut rng, 77);
+    /// assert_eq!(tokens.len(), 77);
+    /// assert_eq!(tokens[0], tokenizer.start_of_text());
+    /// assert!(tokens.contains(&tokenizer.end_of_text()));
+    /// ```
+    pub fn random_tokens<R: Rng + ?Sized>(&self, rng: &mut R, context_length: usize) -> Vec<Token> {
+        assert!(context_length >= 2, "context length must be at least 2");
+        let mut specials = [self.start_of_text(), self.end_of_text()];
+        specials.sort();
+
+        let mut tokens = Vec::with_capacity(context_length);
+        tokens.push(self.start_of_text());
+        let len = rng.gen_range(0..=context_length - 2);
+        for _ in 0..len {
+            // Skip over the special marker tokens, which may be followed by other tokens in
+            // extended vocabularies
+            let mut id = rng.gen_range(0..self.vocabulary.vocab_size - 2);
+            for special in specials {
+                if id >= special.0 {
+                    id += 1;
+                }
+            }
+            tokens.push(Token(id));
+        }
+        tokens.push(self.end_of_text());
+        tokens.resize(context_length, Token(0));
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::mock::StepRng;
+
+    use super::*;
+    use crate::ExtendOptions;
+
+    fn check(tokenizer: &Tokenizer, tokens: &[Token], context_length: usize) {
+        assert_eq!(tokens.len(), context_length);
+        assert_eq!(tokens[0], tokenizer.start_of_text());
+        let end = tokens
+            .iter()
+            .position(|&token| token == tokenizer.end_of_text())
+            .unwrap();
+        for &token in &tokens[1..end] {
+            assert_ne!(token, tokenizer.start_of_text());
+            assert!(tokenizer.try_decode([token]).is_ok());
+        }
+        assert!(tokens[end + 1..].iter().all(|token| token.0 == 0));
+    }
+
+    #[test]
+    fn random_tokens() {
+        let tokenizer = Tokenizer::new();
+        let mut rng = StepRng::new(0, 0x9e37_79b9_7f4a_7c15);
+        let mut lens = Vec::new();
+        for _ in 0..1000 {
+            let tokens = tokenizer.random_tokens(&mut rng, 8);
+            check(&tokenizer, &tokens, 8);
+            lens.push(tokens.iter().position(|&t| t == tokenizer.end_of_text()));
+        }
+        for len in 1..8 {
+            assert!(lens.contains(&Some(len)), "{len}");
+        }
+        assert_eq!(
+            tokenizer.random_tokens(&mut rng, 2),
+            [tokenizer.start_of_text(), tokenizer.end_of_text()]
+        );
+
+        // The special marker tokens are followed by the new tokens of extended vocabularies
+        let corpus = ["acetylsalicylic acid"; 2];
+        let extended = tokenizer
+            .extend_vocabulary(corpus, &ExtendOptions::new())
+            .unwrap();
+        let vocab_size = extended.vocab_info().vocab_size() as u16;
+        let mut max = 0;
+        for _ in 0..1000 {
+            let tokens = extended.random_tokens(&mut rng, 77);
+            check(&extended, &tokens, 77);
+            max = max.max(tokens.iter().map(|token| token.0).max().unwrap());
+        }
+        assert!(max > 49407 && max < vocab_size);
+    }
+}
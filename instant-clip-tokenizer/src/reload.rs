@@ -0,0 +1,246 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime};
+
+use crate::Tokenizer;
+
+/// A [`Tokenizer`] that can be reloaded from a file while it is in use, e.g. by a long-running
+/// service that should pick up vocabulary or configuration changes without a restart.
+///
+/// A `ReloadableTokenizer` is shared between threads, usually in an [`Arc`]. [`get`] returns a
+/// snapshot of the current tokenizer, which is cheap and offers the full encoding API. Reloading
+/// atomically replaces the tokenizer returned by subsequent calls to [`get`], while existing
+/// snapshots continue to use the tokenizer they were obtained from, so e.g. a batch is never
+/// encoded using a mix of two vocabularies.
+///
+/// [`get`]: ReloadableTokenizer::get
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::{ReloadableTokenizer, Tokenizer};
+/// # let dir = std::env::temp_dir().join(format!("reload-doctest-{}", std::process::id()));
+/// # std::fs::create_dir_all(&dir).unwrap();
+/// # let path = dir.join("vocab.txt");
+/// # std::fs::write(&path, "#version: 0.2\n").unwrap();
+/// let tokenizer = ReloadableTokenizer::new(&path, |path| Tokenizer::from_path(path, 49408))?;
+///
+/// let mut tokens = Vec::new();
+/// tokenizer.get().encode("Hi", &mut tokens);
+///
+/// // After the vocabulary file was changed
+/// tokenizer.reload()?;
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct ReloadableTokenizer {
+    current: RwLock<Tokenizer>,
+    path: PathBuf,
+    load: Box<LoadFn>,
+    /// The modification time of `path` when the current tokenizer was loaded.
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl ReloadableTokenizer {
+    /// Create a new `ReloadableTokenizer` that uses `load` to load the tokenizer from `path`,
+    /// initially and whenever it is reloaded.
+    ///
+    /// `load` is e.g. [`Tokenizer::from_path`] for vocabulary data, or `Tokenizer::from_config`
+    /// for a configuration file (requires the `config` crate feature).
+    ///
+    /// # Errors
+    ///
+    /// If the initial call to `load` fails, then its error is returned.
+    pub fn new(
+        path: impl Into<PathBuf>,
+        load: impl Fn(&Path) -> io::Result<Tokenizer> + Send + Sync + 'static,
+    ) -> io::Result<ReloadableTokenizer> {
+        let path = path.into();
+        let modified = modified(&path);
+        let tokenizer = load(&path)?;
+        Ok(ReloadableTokenizer {
+            current: RwLock::new(tokenizer),
+            path,
+            load: Box::new(load),
+            modified: Mutex::new(modified),
+        })
+    }
+
+    /// Returns a snapshot of the current tokenizer.
+    ///
+    /// The snapshot shares its vocabulary with the `ReloadableTokenizer` and isn't affected by
+    /// later reloads.
+    pub fn get(&self) -> Tokenizer {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replace the current tokenizer with `tokenizer`, e.g. one that was loaded some other way.
+    pub fn set(&self, tokenizer: Tokenizer) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = tokenizer;
+    }
+
+    /// Returns the path the tokenizer is loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Load the tokenizer from the file again and make it the current tokenizer.
+    ///
+    /// Loading happens before the current tokenizer is replaced, so [`get`] can be called
+    /// concurrently without having to wait for the file to be loaded.
+    ///
+    /// [`get`]: ReloadableTokenizer::get
+    ///
+    /// # Errors
+    ///
+    /// If loading fails, then the error is returned and the current tokenizer stays in use.
+    pub fn reload(&self) -> io::Result<()> {
+        let mut last_modified = self.modified.lock().unwrap_or_else(PoisonError::into_inner);
+        let modified = modified(&self.path);
+        self.set((self.load)(&self.path)?);
+        *last_modified = modified;
+        Ok(())
+    }
+
+    /// Reload the tokenizer if the modification time of the file changed since it was last
+    /// loaded, and return whether it was reloaded.
+    ///
+    /// Only the modification time of the file at [`path`] is checked, so e.g. a vocabulary file
+    /// referenced by a configuration file is only reloaded once the configuration file changed.
+    ///
+    /// [`path`]: ReloadableTokenizer::path
+    ///
+    /// # Errors
+    ///
+    /// If loading fails, then the error is returned and the current tokenizer stays in use. The
+    /// tokenizer is loaded again by the next call, even if the file wasn't modified since.
+    pub fn reload_if_modified(&self) -> io::Result<bool> {
+        let modified = modified(&self.path);
+        let last_modified = *self.modified.lock().unwrap_or_else(PoisonError::into_inner);
+        if modified.is_none() || modified == last_modified {
+            return Ok(false);
+        }
+        self.reload().map(|()| true)
+    }
+
+    /// Spawn a thread that checks the file for modifications every `interval`, and reloads the
+    /// tokenizer when it was modified.
+    ///
+    /// `on_reload` is called with the result of every attempted reload, e.g. to log errors. If
+    /// loading fails, then the current tokenizer stays in use. The thread exits once all other
+    /// references to the `ReloadableTokenizer` are dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use std::sync::Arc;
+    /// # use std::time::Duration;
+    /// # use instant_clip_tokenizer::{ReloadableTokenizer, Tokenizer};
+    /// let tokenizer = Arc::new(ReloadableTokenizer::new("vocab.txt", |path| {
+    ///     Tokenizer::from_path(path, 49408)
+    /// })?);
+    /// tokenizer.watch(Duration::from_secs(10), |result| {
+    ///     if let Err(err) = result {
+    ///         eprintln!("failed to reload tokenizer: {err}");
+    ///     }
+    /// });
+    /// # Ok::<(), std::io::Error>(())
+    /// ```
+    pub fn watch(
+        self: &Arc<Self>,
+        interval: Duration,
+        mut on_reload: impl FnMut(io::Result<()>) + Send + 'static,
+    ) -> JoinHandle<()> {
+        let tokenizer = Arc::downgrade(self);
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+            let tokenizer = match tokenizer.upgrade() {
+                Some(tokenizer) => tokenizer,
+                None => break,
+            };
+            match tokenizer.reload_if_modified() {
+                Ok(false) => {}
+                Ok(true) => on_reload(Ok(())),
+                Err(err) => on_reload(Err(err)),
+            }
+        })
+    }
+}
+
+impl fmt::Debug for ReloadableTokenizer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReloadableTokenizer")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+type LoadFn = dyn Fn(&Path) -> io::Result<Tokenizer> + Send + Sync;
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    fn load(path: &Path) -> io::Result<Tokenizer> {
+        Tokenizer::from_path(path, 1000)
+    }
+
+    #[test]
+    fn reload() {
+        let dir = std::env::temp_dir().join(format!("reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("vocab.txt");
+        fs::write(&path, "#version: 0.2\n").unwrap();
+
+        let tokenizer = Arc::new(ReloadableTokenizer::new(&path, load).unwrap());
+        let snapshot = tokenizer.get();
+        assert_eq!(snapshot.vocab_info().vocab_size(), 514);
+        assert!(!tokenizer.reload_if_modified().unwrap());
+
+        fs::write(&path, "#version: 0.2\nh i\n").unwrap();
+        tokenizer.reload().unwrap();
+        assert_eq!(tokenizer.get().vocab_info().vocab_size(), 515);
+        assert_eq!(snapshot.vocab_info().vocab_size(), 514);
+
+        // Failed reloads keep the current tokenizer
+        fs::write(&path, "#version: 0.2\nh\n").unwrap();
+        assert!(tokenizer.reload().is_err());
+        assert_eq!(tokenizer.get().vocab_info().vocab_size(), 515);
+
+        // Modifications are picked up by the watcher
+        let (sender, receiver) = mpsc::channel();
+        let handle = tokenizer.watch(Duration::from_millis(10), move |result| {
+            let _ = sender.send(result.is_ok());
+        });
+        fs::write(&path, "#version: 0.2\nh i\nt o\n").unwrap();
+        set_modified(&path);
+        assert!(receiver.recv_timeout(Duration::from_secs(10)).unwrap());
+        assert_eq!(tokenizer.get().vocab_info().vocab_size(), 516);
+
+        drop(tokenizer);
+        handle.join().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Make sure the modification time differs from earlier writes, even with coarse timestamps.
+    fn set_modified(path: &Path) {
+        let time = SystemTime::now() + Duration::from_secs(60);
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(time).unwrap();
+    }
+}
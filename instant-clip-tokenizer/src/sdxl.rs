@@ -0,0 +1,129 @@
+use std::io;
+
+use crate::Tokenizer;
+
+impl Tokenizer {
+    /// Tokenize a batch of prompts for the two text encoders of Stable Diffusion XL.
+    ///
+    /// SDXL encodes every prompt using both OpenAI's CLIP ViT-L/14 and OpenCLIP's ViT-bigG/14
+    /// text encoders. Both use the same vocabulary and context length, but their tokenizers pad
+    /// rows differently: the first pads with the `<end_of_text>` token, while the second pads with
+    /// token id `0`. Feeding either encoder rows with the other padding produces subtly different
+    /// embeddings, so this method returns the rows for both encoders, together with the attention
+    /// mask, which is the same for both.
+    ///
+    /// Rows have [`context_length`] tokens, `77` for the tokenizer returned by [`Tokenizer::new`],
+    /// and the token ids are converted to the numeric type `T` expected by the model, as done by
+    /// [`tokenize_batch_with_attention_mask`].
+    ///
+    /// [`context_length`]: Tokenizer::context_length
+    /// [`tokenize_batch_with_attention_mask`]: Tokenizer::tokenize_batch_with_attention_mask
+    ///
+    /// # Errors
+    ///
+    /// If one of the `prompts` exceeds the input limits (see [`try_encode`]), then an error of
+    /// kind [`io::ErrorKind::InvalidInput`] is returned.
+    ///
+    /// [`try_encode`]: Tokenizer::try_encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use ndarray::{arr1, s};
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let batch = tokenizer.tokenize_batch_sdxl::<i64, _>(["a photo of a cat"]).unwrap();
+    /// assert_eq!(batch.input_ids.dim(), (1, 77));
+    /// assert_eq!(
+    ///     batch.input_ids.slice(s![0, ..8]),
+    ///     arr1(&[49406, 320, 1125, 539, 320, 2368, 49407, 49407])
+    /// );
+    /// assert_eq!(
+    ///     batch.input_ids_2.slice(s![0, ..8]),
+    ///     arr1(&[49406, 320, 1125, 539, 320, 2368, 49407, 0])
+    /// );
+    /// assert_eq!(batch.attention_mask.sum(), 7);
+    /// ```
+    pub fn tokenize_batch_sdxl<'a, T, I>(&self, prompts: I) -> io::Result<SdxlBatch<T>>
+    where
+        T: Copy + From<u16>,
+        I: IntoIterator<Item = &'a str>,
+        I::IntoIter: std::iter::ExactSizeIterator,
+    {
+        let (input_ids_2, attention_mask) =
+            self.try_tokenize_batch_with_attention_mask::<u16, _>(prompts, self.context_length())?;
+        let end_of_text = self.end_of_text().to_u16();
+        let mut input_ids = input_ids_2.clone();
+        input_ids.zip_mut_with(&attention_mask, |id, &mask| {
+            if mask == 0 {
+                *id = end_of_text;
+            }
+        });
+        Ok(SdxlBatch {
+            input_ids: input_ids.mapv(T::from),
+            input_ids_2: input_ids_2.mapv(T::from),
+            attention_mask: attention_mask.mapv(T::from),
+        })
+    }
+}
+
+/// The token ids for the two text encoders of Stable Diffusion XL, as returned by
+/// [`Tokenizer::tokenize_batch_sdxl`].
+///
+/// All matrices have one row per prompt. The field names follow the `tokenizer` and
+/// `tokenizer_2` components of SDXL pipelines.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SdxlBatch<T> {
+    /// The token ids for the first text encoder (OpenAI's CLIP ViT-L/14), padded with the
+    /// `<end_of_text>` token.
+    pub input_ids: ndarray::Array2<T>,
+    /// The token ids for the second text encoder (OpenCLIP's ViT-bigG/14), padded with `0`.
+    pub input_ids_2: ndarray::Array2<T>,
+    /// The attention mask for both text encoders, `1` for every position holding an actual token
+    /// (including the marker tokens) and `0` for every padding position.
+    pub attention_mask: ndarray::Array2<T>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tokenize_batch_sdxl() {
+        let tokenizer = Tokenizer::new();
+        let prompts = ["An astronaut riding a horse", "", &"a ".repeat(100)];
+        let batch = tokenizer.tokenize_batch_sdxl::<u16, _>(prompts).unwrap();
+        let (zero_padded, attention_mask) =
+            tokenizer.tokenize_batch_with_attention_mask::<u16, _>(prompts, 77);
+        assert_eq!(batch.input_ids_2, zero_padded);
+        assert_eq!(batch.attention_mask, attention_mask);
+        assert_eq!(batch.input_ids.dim(), (3, 77));
+
+        for (row, eos_padded) in batch.input_ids.rows().into_iter().enumerate() {
+            let length = attention_mask
+                .row(row)
+                .iter()
+                .filter(|&&mask| mask == 1)
+                .count();
+            assert_eq!(
+                eos_padded.slice(ndarray::s![..length]),
+                zero_padded.row(row).slice(ndarray::s![..length])
+            );
+            assert!(eos_padded.iter().skip(length).all(|&id| id == 49407));
+            // Both conventions pool at the same position
+            assert_eq!(
+                tokenizer.end_of_text_position(eos_padded.as_slice().unwrap()),
+                Some(length - 1)
+            );
+        }
+        assert_eq!(
+            batch.input_ids.row(1).as_slice().unwrap()[..3],
+            [49406, 49407, 49407]
+        );
+        assert_eq!(batch.attention_mask.row(2).sum(), 77);
+
+        let long_clip = Tokenizer::long_clip();
+        let batch = long_clip.tokenize_batch_sdxl::<i64, _>(["Hi"]).unwrap();
+        assert_eq!(batch.input_ids.dim(), (1, 248));
+    }
+}
@@ -0,0 +1,258 @@
+use std::borrow::Cow;
+
+use crate::{Tokenizer, Word, WordBoundary};
+
+impl Tokenizer {
+    /// Split `text` into a head encoded as at most `budget` tokens and the remaining tail.
+    ///
+    /// The text is cut after the last whole word that fits into the budget, so that the head is
+    /// encoded exactly as the first tokens of [`encode`]. Only if not even the first word fits,
+    /// the text is cut within that word, after the characters covered by the first `budget`
+    /// tokens. Unless `budget` is `0`, the head always contains at least one character though,
+    /// even if that is encoded as more than `budget` tokens. The tail starts with any whitespace
+    /// following the head, so that concatenating both parts results in `text` again.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let text = "A photo of a dog. It is sleeping.";
+    /// assert_eq!(
+    ///     tokenizer.split_at_token_budget(text, 6),
+    ///     ("A photo of a dog.", " It is sleeping.")
+    /// );
+    /// assert_eq!(tokenizer.split_at_token_budget(text, 100), (text, ""));
+    /// ```
+    pub fn split_at_token_budget<'a>(&self, text: &'a str, budget: usize) -> (&'a str, &'a str) {
+        let mut cut = self.token_budget_cut(text, budget);
+        let first = text
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(text.len());
+        if cut <= first && first < text.len() && budget > 0 {
+            // Include the first character, even if it is encoded as more tokens than the budget
+            cut = first + text[first..].chars().next().map_or(0, char::len_utf8);
+        }
+        text.split_at(cut)
+    }
+
+    /// Returns an iterator over consecutive slices of `text` that are each encoded as at most
+    /// `budget` tokens.
+    ///
+    /// The slices are obtained by repeatedly calling [`split_at_token_budget`], with whitespace
+    /// between the slices removed. This is useful for embedding long documents in chunks that fit
+    /// into the context length.
+    ///
+    /// [`split_at_token_budget`]: Tokenizer::split_at_token_budget
+    ///
+    /// # Panics
+    ///
+    /// Panics if `budget` is `0`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let text = "A photo of a dog. It is sleeping on a sofa.\n";
+    /// let chunks = tokenizer.chunks_by_token_budget(text, 6).collect::<Vec<_>>();
+    /// assert_eq!(chunks, ["A photo of a dog.", "It is sleeping on a sofa", "."]);
+    /// ```
+    pub fn chunks_by_token_budget<'a>(
+        &'a self,
+        text: &'a str,
+        budget: usize,
+    ) -> TokenBudgetChunks<'a> {
+        assert!(budget > 0, "token budget must be at least 1");
+        TokenBudgetChunks {
+            tokenizer: self,
+            rest: text,
+            budget,
+        }
+    }
+
+    /// Returns the byte offset at which [`Tokenizer::split_at_token_budget`] splits `text`.
+    fn token_budget_cut(&self, text: &str, budget: usize) -> usize {
+        let mut remaining = budget;
+        let mut cut = 0;
+        let mut word_tokens = Vec::new();
+        for (start, chunk) in self.lowercase_chunks(text) {
+            let lowercased = matches!(chunk, Cow::Owned(_));
+            let original =
+                &text[start..start + original_offset(&text[start..], lowercased, chunk.len())];
+            // End of the last word fitting into the budget, as an offset into `chunk`
+            let mut fitting_end = None;
+            for (word, range) in self.split_words(&chunk, true) {
+                self.encode_word(word, &mut word_tokens);
+                let len = word_tokens.len();
+                if len <= remaining {
+                    remaining -= len;
+                    fitting_end = Some(range.end);
+                    continue;
+                }
+
+                if let Some(end) = fitting_end {
+                    return start + original_offset(original, lowercased, end);
+                } else if cut > 0 || remaining == 0 {
+                    return cut;
+                }
+
+                // Not even the first word fits, so cut within it after the bytes covered by the
+                // tokens fitting into the budget
+                let mut end = range.start
+                    + word_tokens[..remaining.min(word_tokens.len())]
+                        .iter()
+                        .map(|&token| self.token_bytes(token).map_or(0, <[u8]>::len))
+                        .sum::<usize>();
+                let follows_whitespace = matches!(
+                    word,
+                    Word::Text {
+                        follows_whitespace: true,
+                        ..
+                    }
+                );
+                if self.vocabulary.word_boundary == WordBoundary::SpacePrefix && follows_whitespace
+                {
+                    end -= 1;
+                }
+                while !chunk.is_char_boundary(end) {
+                    end -= 1;
+                }
+                return start + original_offset(original, lowercased, end);
+            }
+
+            if let Some(end) = fitting_end {
+                cut = start + original_offset(original, lowercased, end);
+            }
+        }
+        text.len()
+    }
+}
+
+/// An iterator over slices of a text encoded as a limited number of tokens each.
+///
+/// This is returned by [`Tokenizer::chunks_by_token_budget`].
+#[derive(Clone)]
+pub struct TokenBudgetChunks<'a> {
+    tokenizer: &'a Tokenizer,
+    rest: &'a str,
+    budget: usize,
+}
+
+impl<'a> Iterator for TokenBudgetChunks<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        self.rest = self.rest.trim_start();
+        if self.rest.is_empty() {
+            return None;
+        }
+        let (head, tail) = self.tokenizer.split_at_token_budget(self.rest, self.budget);
+        self.rest = tail;
+        Some(head.trim_end())
+    }
+}
+
+/// Maps a byte `offset` into a chunk returned by [`Tokenizer::lowercase_chunks`] back to the
+/// corresponding offset into the `original` text.
+fn original_offset(original: &str, lowercased: bool, offset: usize) -> usize {
+    if !lowercased {
+        return offset;
+    }
+    // Characters lowercased as several characters can't be split, so round down
+    let mut lowercased_offset = 0;
+    for (i, c) in original.char_indices() {
+        lowercased_offset += c.to_lowercase().map(char::len_utf8).sum::<usize>();
+        if lowercased_offset > offset {
+            return i;
+        }
+    }
+    original.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::Tokenizer;
+
+    #[test]
+    fn split_at_token_budget() {
+        let tokenizer = Tokenizer::new();
+        let text = "Hi there, how are you?";
+        let mut tokens = Vec::new();
+        tokenizer.encode(text, &mut tokens);
+        assert_eq!(tokens.len(), 7);
+        for budget in 0..=tokens.len() {
+            let (head, tail) = tokenizer.split_at_token_budget(text, budget);
+            assert_eq!(format!("{head}{tail}"), text);
+            let mut head_tokens = Vec::new();
+            tokenizer.encode(head, &mut head_tokens);
+            assert_eq!(head_tokens, tokens[..budget]);
+        }
+        assert_eq!(tokenizer.split_at_token_budget(text, 0), ("", text));
+        assert_eq!(tokenizer.split_at_token_budget("", 5), ("", ""));
+        assert_eq!(tokenizer.split_at_token_budget("  ", 5), ("  ", ""));
+        assert_eq!(
+            tokenizer.split_at_token_budget("<start_of_text>Hi", 1),
+            ("<start_of_text>", "Hi")
+        );
+    }
+
+    #[test]
+    fn split_within_word() {
+        let tokenizer = Tokenizer::new();
+        let word = "pneumonoultramicroscopicsilicovolcanoconiosis";
+        let (head, tail) = tokenizer.split_at_token_budget(word, 2);
+        assert_eq!(
+            (head, tail),
+            ("pneumon", "oultramicroscopicsilicovolcanoconiosis")
+        );
+        assert_eq!(
+            tokenizer.split_at_token_budget("Hi pneumonoultra", 2).0,
+            "Hi"
+        );
+
+        // Multi-byte characters encoded as several tokens are never split
+        let (head, tail) = tokenizer.split_at_token_budget("🦀🦀", 1);
+        assert_eq!((head, tail), ("🦀", "🦀"));
+    }
+
+    #[test]
+    fn split_lowercased() {
+        let tokenizer = Tokenizer::new();
+        // "İ" is lowercased as two characters, changing the byte offsets
+        let text = "İİ ΟΔΥΣΣΕΥΣ ".repeat(1000);
+        let mut tokens = Vec::new();
+        tokenizer.encode(&text, &mut tokens);
+        for budget in [2, 10, 1001, tokens.len() - 1] {
+            let (head, tail) = tokenizer.split_at_token_budget(&text, budget);
+            assert_eq!(format!("{head}{tail}"), text);
+            let mut head_tokens = Vec::new();
+            tokenizer.encode(head, &mut head_tokens);
+            assert!(head_tokens.len() <= budget);
+            assert_eq!(head_tokens, tokens[..head_tokens.len()]);
+        }
+
+        // The first character is always included, even though it is encoded as two tokens
+        assert_eq!(tokenizer.split_at_token_budget(&text, 1).0, "İ");
+    }
+
+    #[test]
+    fn chunks_by_token_budget() {
+        let tokenizer = Tokenizer::new();
+        let text = " a b c d e f g  \n";
+        let chunks = tokenizer
+            .chunks_by_token_budget(text, 3)
+            .collect::<Vec<_>>();
+        assert_eq!(chunks, ["a b c", "d e f", "g"]);
+        assert_eq!(tokenizer.chunks_by_token_budget("  ", 3).count(), 0);
+
+        let text = "ΟΔΥΣΣΕΥΣ and the sirens. ".repeat(100);
+        for chunk in tokenizer.chunks_by_token_budget(&text, 7) {
+            let mut tokens = Vec::new();
+            tokenizer.encode(chunk, &mut tokens);
+            assert!(!chunk.is_empty() && tokens.len() <= 7);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+//! [`proptest`](https://docs.rs/proptest) strategies for property-testing code that handles texts
+//! and tokens.
+//!
+//! This module is only available with the `proptest` [crate feature](crate#crate-features). The
+//! [`caption`] strategy generates realistic image captions and search queries, while [`text`] also
+//! covers the edge cases the tokenizer has to deal with, like special marker strings, unusual
+//! whitespace and arbitrary Unicode. [`token`] and [`tokens`] generate tokens valid for a given
+//! vocabulary.
+//!
+//! # Examples
+//!
+//! Checking a property of the tokens of generated captions (in a test module, the function would
+//! be annotated with `#[test]` instead of being called):
+//!
+//! ```
+//! # use instant_clip_tokenizer::{strategy, Tokenizer};
+//! # use proptest::prelude::*;
+//! proptest! {
+//!     fn encode(text in strategy::caption()) {
+//!         let tokenizer = Tokenizer::new();
+//!         let mut tokens = Vec::new();
+//!         tokenizer.encode(&text, &mut tokens);
+//!         prop_assert!(!tokens.is_empty() && tokens.len() <= text.len());
+//!     }
+//! }
+//! # encode();
+//! ```
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use proptest::sample::select;
+
+use crate::{Token, VocabInfo};
+
+const ARTICLES: &[&str] = &["a", "an", "the", "two", "three", "some", "many"];
+
+const ADJECTIVES: &[&str] = &[
+    "red", "small", "happy", "old", "wooden", "blurry", "vintage", "snowy", "golden", "bright",
+    "dark", "cute", "large", "shiny", "colorful",
+];
+
+const NOUNS: &[&str] = &[
+    "dog",
+    "cat",
+    "person",
+    "man",
+    "woman",
+    "child",
+    "car",
+    "bicycle",
+    "motorcycle",
+    "house",
+    "tree",
+    "mountain",
+    "beach",
+    "city",
+    "street",
+    "table",
+    "pizza",
+    "flower",
+    "bird",
+    "horse",
+];
+
+const VERBS: &[&str] = &[
+    "sitting", "standing", "running", "riding", "walking", "sleeping", "playing", "eating",
+];
+
+const PREPOSITIONS: &[&str] = &["on", "in", "next to", "under", "behind", "near", "with"];
+
+const STYLES: &[&str] = &[
+    "a photo of",
+    "a picture of",
+    "an illustration of",
+    "a painting of",
+    "a close-up photo of",
+    "a black and white photo of",
+];
+
+const SUFFIXES: &[&str] = &[
+    "",
+    ".",
+    "!",
+    ", 4k",
+    ", high resolution",
+    " at sunset",
+    " in the rain",
+    " 🐶",
+    " 📸",
+];
+
+/// A strategy generating realistic image captions in English, like `"a photo of a small dog
+/// sitting on the beach at sunset"`.
+///
+/// The captions vary in case and punctuation and sometimes contain numbers or emoji, but consist
+/// of common words only. Use [`text`] to also cover unusual input.
+pub fn caption() -> impl Strategy<Value = String> {
+    let noun_phrase = (
+        select(ARTICLES),
+        prop::option::of(select(ADJECTIVES)),
+        select(NOUNS),
+    )
+        .prop_map(|(article, adjective, noun)| match adjective {
+            Some(adjective) => format!("{article} {adjective} {noun}"),
+            None => format!("{article} {noun}"),
+        });
+    (
+        prop::option::of(select(STYLES)),
+        noun_phrase.clone(),
+        prop::option::of((select(VERBS), select(PREPOSITIONS), noun_phrase)),
+        select(SUFFIXES),
+        0..3u8,
+    )
+        .prop_map(|(style, subject, action, suffix, case)| {
+            let mut caption = match style {
+                Some(style) => format!("{style} {subject}"),
+                None => subject,
+            };
+            if let Some((verb, preposition, object)) = action {
+                caption = format!("{caption} {verb} {preposition} {object}");
+            }
+            caption.push_str(suffix);
+            match case {
+                0 => caption,
+                1 => caption.to_uppercase(),
+                _ => {
+                    let mut chars = caption.chars();
+                    let first = chars.next().map(|c| c.to_uppercase().to_string());
+                    first.unwrap_or_default() + chars.as_str()
+                }
+            }
+        })
+}
+
+/// A strategy generating texts that mix [captions](caption) with the edge cases the tokenizer has
+/// to deal with.
+///
+/// This includes the special `<start_of_text>` and `<end_of_text>` marker strings, contractions,
+/// repeated punctuation, unusual whitespace, long words and arbitrary Unicode, including
+/// characters changing their length when lowercased.
+pub fn text() -> impl Strategy<Value = String> {
+    let fragment = prop_oneof![
+        4 => caption(),
+        1 => select(&[
+            "<start_of_text>",
+            "<end_of_text>",
+            "<|endoftext|>",
+            "don't",
+            "it's",
+            "!!!",
+            "...",
+            "İstanbul",
+            "straße",
+            "東京タワー",
+            "👩‍👩‍👧‍👦",
+            "🇩🇪",
+        ][..])
+        .prop_map(str::to_owned),
+        1 => "[a-z]{20,60}",
+        1 => "[0-9]{1,12}",
+        2 => any::<String>(),
+    ];
+    let separator = select(&[" ", "  ", "\t", "\n", "\u{a0}", "", ", "][..]);
+    vec((fragment, separator), 0..6).prop_map(|parts| {
+        parts
+            .into_iter()
+            .flat_map(|(fragment, separator)| [fragment, separator.to_owned()])
+            .collect()
+    })
+}
+
+/// A strategy generating tokens that are valid for the vocabulary of the given `tokenizer`.
+///
+/// Like [`Token::from_u16`], this also accepts a [`VocabInfo`].
+pub fn token(tokenizer: impl Into<VocabInfo>) -> impl Strategy<Value = Token> {
+    let vocab_size = tokenizer.into().vocab_size;
+    (0..vocab_size).prop_map(Token)
+}
+
+/// A strategy generating sequences of tokens that are valid for the vocabulary of the given
+/// `tokenizer`, with a length in the given range.
+pub fn tokens(
+    tokenizer: impl Into<VocabInfo>,
+    len: impl Into<prop::collection::SizeRange>,
+) -> impl Strategy<Value = Vec<Token>> {
+    vec(token(tokenizer), len)
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::test_runner::TestRunner;
+
+    use super::*;
+    use crate::Tokenizer;
+
+    #[test]
+    fn strategies() {
+        let tokenizer = Tokenizer::new();
+        let mut runner = TestRunner::deterministic();
+        runner
+            .run(&caption(), |caption| {
+                prop_assert!(!caption.is_empty());
+                prop_assert!(caption.is_ascii() || caption.contains(['🐶', '📸']));
+                Ok(())
+            })
+            .unwrap();
+        runner
+            .run(&text(), |text| {
+                let mut tokens = Vec::new();
+                tokenizer.encode(&text, &mut tokens);
+                prop_assert!(tokenizer.try_decode(tokens).is_ok());
+                Ok(())
+            })
+            .unwrap();
+        runner
+            .run(&tokens(&tokenizer, 0..10), |tokens| {
+                prop_assert!(tokens.len() < 10);
+                prop_assert!(tokenizer.try_decode(tokens).is_ok());
+                Ok(())
+            })
+            .unwrap();
+    }
+}
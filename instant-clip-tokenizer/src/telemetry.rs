@@ -0,0 +1,55 @@
+//! Metrics emitted through the [`metrics`](https://docs.rs/metrics) facade when the **metrics**
+//! feature is enabled. Without it, all of these functions compile to nothing.
+
+#[cfg(feature = "metrics")]
+use std::time::Instant;
+
+/// Counter of the texts encoded.
+#[cfg(feature = "metrics")]
+const TEXTS: &str = "instant_clip_tokenizer_texts_total";
+/// Counter of the tokens produced when encoding texts, excluding the marker tokens added by the
+/// batch methods.
+#[cfg(feature = "metrics")]
+const TOKENS: &str = "instant_clip_tokenizer_tokens_total";
+/// Counter of the texts whose tokens were truncated.
+#[cfg(feature = "metrics")]
+const TRUNCATIONS: &str = "instant_clip_tokenizer_truncations_total";
+/// Histogram of the time taken to tokenize a batch, in seconds.
+#[cfg(feature = "metrics")]
+const BATCH_DURATION: &str = "instant_clip_tokenizer_batch_duration_seconds";
+
+/// Records that a text was encoded as `tokens` tokens, possibly after discarding further tokens.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_text(tokens: usize, truncated: bool) {
+    metrics::counter!(TEXTS).increment(1);
+    metrics::counter!(TOKENS).increment(tokens as u64);
+    if truncated {
+        metrics::counter!(TRUNCATIONS).increment(1);
+    }
+}
+
+#[cfg(not(feature = "metrics"))]
+#[inline(always)]
+pub(crate) fn record_text(_tokens: usize, _truncated: bool) {}
+
+/// Measures the time taken to tokenize a batch, recorded when calling [`BatchTimer::finish`].
+pub(crate) struct BatchTimer {
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl BatchTimer {
+    #[inline(always)]
+    pub(crate) fn start() -> BatchTimer {
+        BatchTimer {
+            #[cfg(feature = "metrics")]
+            start: Instant::now(),
+        }
+    }
+
+    #[inline(always)]
+    pub(crate) fn finish(self) {
+        #[cfg(feature = "metrics")]
+        metrics::histogram!(BATCH_DURATION).record(self.start.elapsed().as_secs_f64());
+    }
+}
@@ -0,0 +1,369 @@
+//! Golden test corpora and assertions for testing code that embeds this tokenizer.
+//!
+//! This module is only available with the `test-utils` [crate feature](crate#crate-features). The
+//! corpora contain texts covering known edge cases together with the token ids produced by the
+//! original Python implementation when using the bundled vocabulary data. The texts were chosen
+//! such that the preprocessing performed by the original implementation (`ftfy` fixes and
+//! whitespace cleanup) leaves them unchanged, so they can be passed to [`Tokenizer::encode`]
+//! directly.
+//!
+//! # Examples
+//!
+//! Checking that a wrapper around [`Tokenizer`] still produces the expected token ids:
+//!
+//! ```
+//! # use instant_clip_tokenizer::{test_utils, Tokenizer};
+//! let tokenizer = Tokenizer::new();
+//! let my_encode = |text: &str| {
+//!     let mut tokens = Vec::new();
+//!     tokenizer.encode(text, &mut tokens);
+//!     tokens.into_iter().map(|token| token.to_u16()).collect::<Vec<_>>()
+//! };
+//! test_utils::assert_golden_with(test_utils::all(), my_encode);
+//! ```
+//!
+//! Checking the tokens of a single text using the [`assert_tokens!`](crate::assert_tokens) macro:
+//!
+//! ```
+//! # use instant_clip_tokenizer::{assert_tokens, Tokenizer};
+//! let tokenizer = Tokenizer::new();
+//! assert_tokens!(tokenizer, "a photo of a dog", [320, 1125, 539, 320, 1929]);
+//! ```
+
+use std::fmt::Write;
+
+use crate::{Token, Tokenizer};
+
+/// A text together with the token ids it is expected to be encoded as.
+#[derive(Clone, Copy, Debug)]
+pub struct GoldenCase {
+    /// The input text.
+    pub text: &'static str,
+    /// The expected token ids, not including the `<start_of_text>` and `<end_of_text>` marker
+    /// tokens.
+    pub ids: &'static [u16],
+}
+
+/// Returns an iterator over all golden test cases included in this module.
+pub fn all() -> impl Iterator<Item = &'static GoldenCase> {
+    UNICODE
+        .iter()
+        .chain(EMOJI)
+        .chain(LONG_WORDS)
+        .chain(SPECIAL_MARKERS)
+}
+
+/// Assert that `tokenizer` encodes all `cases` as expected.
+///
+/// # Panics
+///
+/// Panics with a report listing all mismatches if any case is not encoded as expected.
+#[track_caller]
+pub fn assert_golden<'a>(tokenizer: &Tokenizer, cases: impl IntoIterator<Item = &'a GoldenCase>) {
+    let report = tokenizer.verify_against(cases.into_iter().map(|case| (case.text, case.ids)));
+    assert!(report.is_ok(), "{report}");
+}
+
+/// Assert that `encode` produces the expected token ids for all `cases`.
+///
+/// This is useful for testing your own code wrapping a [`Tokenizer`]. `encode` must return the
+/// token ids for the given text without the `<start_of_text>` and `<end_of_text>` marker tokens.
+///
+/// # Panics
+///
+/// Panics with a message listing all mismatches if `encode` doesn't produce the expected token
+/// ids for any case.
+#[track_caller]
+pub fn assert_golden_with<'a>(
+    cases: impl IntoIterator<Item = &'a GoldenCase>,
+    mut encode: impl FnMut(&str) -> Vec<u16>,
+) {
+    let mut total = 0;
+    let mut mismatches = Vec::new();
+    for case in cases {
+        total += 1;
+        let actual = encode(case.text);
+        if actual != case.ids {
+            mismatches.push(format!(
+                "{:?}: expected {:?}, got {:?}",
+                case.text, case.ids, actual
+            ));
+        }
+    }
+    assert!(
+        mismatches.is_empty(),
+        "{} of {} texts don't match\n{}",
+        mismatches.len(),
+        total,
+        mismatches.join("\n")
+    );
+}
+
+/// Assert that `tokenizer` encodes `text` as the `expected` token ids.
+///
+/// This is the function behind the [`assert_tokens!`](crate::assert_tokens) macro. The expected
+/// ids must not include the `<start_of_text>` and `<end_of_text>` marker tokens, i.e. they should
+/// match the output of [`Tokenizer::encode`] exactly.
+///
+/// # Panics
+///
+/// Panics if the tokens don't match, with a message listing the expected and actual tokens side by
+/// side together with their textual representation, marking the positions that differ.
+#[track_caller]
+pub fn assert_tokens(tokenizer: &Tokenizer, text: &str, expected: &[u16]) {
+    let mut tokens = Vec::new();
+    tokenizer.encode(text, &mut tokens);
+    let actual = tokens
+        .iter()
+        .map(|token| token.to_u16())
+        .collect::<Vec<_>>();
+    if actual == expected {
+        return;
+    }
+
+    let describe = |id: Option<u16>| match id {
+        Some(id) => match Token::from_u16(id, tokenizer) {
+            Some(token) => format!("{id} {:?}", tokenizer.piece(token)),
+            None => format!("{id} (unknown token)"),
+        },
+        None => String::new(),
+    };
+    let rows = (0..expected.len().max(actual.len()))
+        .map(|i| {
+            let (expected, actual) = (expected.get(i).copied(), actual.get(i).copied());
+            (expected != actual, describe(expected), describe(actual))
+        })
+        .collect::<Vec<_>>();
+    let width = rows
+        .iter()
+        .map(|(_, expected, _)| expected.chars().count())
+        .chain(["expected".len()])
+        .max()
+        .unwrap_or_default();
+
+    let mut message = format!(
+        "tokens of {text:?} don't match\n      {:width$}  actual",
+        "expected"
+    );
+    for (i, (differs, expected, actual)) in rows.iter().enumerate() {
+        let marker = if *differs { '>' } else { ' ' };
+        let line = format!("{marker}{i:>4} {expected:width$}  {actual}");
+        let _ = write!(message, "\n{}", line.trim_end());
+    }
+    panic!("{message}");
+}
+
+/// Assert that a [`Tokenizer`] encodes a text as the expected token ids.
+///
+/// The expected ids must not include the `<start_of_text>` and `<end_of_text>` marker tokens. On
+/// failure, the expected and actual tokens are shown side by side together with their textual
+/// representation, see [`test_utils::assert_tokens`](crate::test_utils::assert_tokens).
+///
+/// This macro is only available with the `test-utils` [crate feature](crate#crate-features).
+///
+/// # Examples
+///
+/// ```
+/// # use instant_clip_tokenizer::{assert_tokens, Tokenizer};
+/// let tokenizer = Tokenizer::new();
+/// assert_tokens!(tokenizer, "Hi there", [1883, 997]);
+/// assert_tokens!(tokenizer, "", []);
+/// ```
+#[macro_export]
+macro_rules! assert_tokens {
+    ($tokenizer:expr, $text:expr, $expected:expr $(,)?) => {
+        $crate::test_utils::assert_tokens(&$tokenizer, $text, &$expected)
+    };
+}
+
+/// Texts using non-ASCII characters from various scripts, including characters that change their
+/// length when lowercased.
+pub const UNICODE: &[GoldenCase] = &[
+    GoldenCase {
+        text: "Hello Båstad",
+        ids: &[3306, 65, 23176, 16485],
+    },
+    GoldenCase {
+        text: "naïve café",
+        ids: &[1097, 35689, 563, 15304],
+    },
+    GoldenCase {
+        text: "straße",
+        ids: &[1894, 127, 253, 324],
+    },
+    GoldenCase {
+        text: "İstanbul",
+        ids: &[328, 16384, 11231],
+    },
+    GoldenCase {
+        text: "ΑΘΗΝΑ",
+        ids: &[138, 109, 138, 116, 138, 115, 138, 121, 138, 365],
+    },
+    GoldenCase {
+        text: "東京タワー",
+        ids: &[48338, 21078, 105, 34941, 2429, 107, 30391],
+    },
+    GoldenCase {
+        text: "привет мир",
+        ids: &[
+            26302, 16370, 29503, 110, 22176, 141, 480, 38018, 16701, 141, 478,
+        ],
+    },
+    GoldenCase {
+        text: "مرحبا بالعالم",
+        ids: &[
+            12986, 10948, 25722, 16378, 22625, 16378, 12973, 18843, 12973, 29945,
+        ],
+    },
+    GoldenCase {
+        text: "½ ① ² 10",
+        ids: &[33613, 158, 239, 510, 41175, 272, 271],
+    },
+    GoldenCase {
+        text: "Ǆemal",
+        ids: &[131, 228, 908, 566],
+    },
+    GoldenCase {
+        text: "é vs é",
+        ids: &[324, 136, 479, 1547, 4166],
+    },
+];
+
+/// Texts containing emoji, including ZWJ sequences, flags, and skin tone modifiers.
+pub const EMOJI: &[GoldenCase] = &[
+    GoldenCase {
+        text: "🦀",
+        ids: &[6040, 478],
+    },
+    GoldenCase {
+        text: "👍🏽",
+        ids: &[43722],
+    },
+    GoldenCase {
+        text: "👨\u{200d}👩\u{200d}👧",
+        ids: &[25023, 26304, 964, 356],
+    },
+    GoldenCase {
+        text: "🇸🇪",
+        ids: &[39260],
+    },
+    GoldenCase {
+        text: "i ❤\u{fe0f} rust",
+        ids: &[328, 1752, 14212],
+    },
+    GoldenCase {
+        text: "😂😂😂",
+        ids: &[4887],
+    },
+    GoldenCase {
+        text: "a🙂b",
+        ids: &[320, 14860, 321],
+    },
+];
+
+/// Long words and runs of repeated characters resulting in many tokens.
+pub const LONG_WORDS: &[GoldenCase] = &[
+    GoldenCase {
+        text: "donaudampfschifffahrtsgesellschaftskapitänsmütze",
+        ids: &[
+            1067, 627, 1880, 16680, 13731, 1021, 778, 4810, 2290, 619, 10279, 45588, 83, 909, 688,
+            529, 42787, 978, 6522, 83, 1298,
+        ],
+    },
+    GoldenCase {
+        text: "pneumonoultramicroscopicsilicovolcanoconiosis",
+        ids: &[
+            28714, 749, 20253, 9800, 535, 532, 1065, 901, 1556, 13697, 9916, 78, 39031, 13903,
+        ],
+    },
+    GoldenCase {
+        text: "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa",
+        ids: &[23126, 23126, 23126, 23126, 23126, 23126, 3894],
+    },
+    GoldenCase {
+        text: "!!!!!!!!!!!!!!!!!!!!!!!!!!!!!!",
+        ids: &[30146, 11281, 9168],
+    },
+    GoldenCase {
+        text: "12345678901234567890",
+        ids: &[
+            272, 273, 274, 275, 276, 277, 278, 279, 280, 271, 272, 273, 274, 275, 276, 277, 278,
+            279, 280, 271,
+        ],
+    },
+];
+
+/// Texts containing the special `<start_of_text>` and `<end_of_text>` markers (including quirks
+/// in how they are recognized) and English contractions.
+pub const SPECIAL_MARKERS: &[GoldenCase] = &[
+    GoldenCase {
+        text: "<start_of_text>Hi<start_of_text>instant labs<end_of_text>",
+        ids: &[49406, 1883, 49406, 10635, 12021, 49407],
+    },
+    GoldenCase {
+        text: "<start_of_text>Hi!<end_of_text>",
+        ids: &[49406, 1883, 0, 283, 806, 318, 539, 318, 4160, 285],
+    },
+    GoldenCase {
+        text: "<START_OF_TEXT>hi",
+        ids: &[49406, 1883],
+    },
+    GoldenCase {
+        text: "<end_of_text",
+        ids: &[283, 806, 318, 539, 318, 4160],
+    },
+    GoldenCase {
+        text: "i've seen it",
+        ids: &[328, 1200, 2041, 585],
+    },
+    GoldenCase {
+        text: "you're i'm he'll she'd it's can't",
+        ids: &[
+            592, 982, 328, 880, 797, 1342, 1043, 1896, 585, 568, 753, 713,
+        ],
+    },
+    GoldenCase {
+        text: "I'VE",
+        ids: &[328, 1200],
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn golden() {
+        let tokenizer = Tokenizer::new();
+        assert_golden(&tokenizer, all());
+    }
+
+    #[test]
+    #[should_panic(expected = "1 of 1 texts don't match")]
+    fn golden_with_mismatch() {
+        assert_golden_with(&UNICODE[..1], |_| vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn assert_tokens() {
+        let tokenizer = Tokenizer::new();
+        crate::assert_tokens!(tokenizer, "Hi there", [1883, 997]);
+        let expected: Vec<u16> = [1883, 997].to_vec();
+        crate::assert_tokens!(&tokenizer, "Hi there", expected,);
+
+        let message = std::panic::catch_unwind(|| {
+            crate::assert_tokens!(tokenizer, "Hi there", [1883, 996, 60000]);
+        })
+        .unwrap_err()
+        .downcast::<String>()
+        .unwrap();
+        assert_eq!(
+            *message,
+            "tokens of \"Hi there\" don't match
+      expected               actual
+    0 1883 \"hi</w>\"          1883 \"hi</w>\"
+>   1 996 \"clu\"              997 \"there</w>\"
+>   2 60000 (unknown token)"
+        );
+    }
+}
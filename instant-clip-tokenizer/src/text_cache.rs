@@ -0,0 +1,172 @@
+use std::io;
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{HashMap, Token};
+
+/// Texts longer than this are encoded without consulting the cache.
+pub(crate) const MAX_CACHED_TEXT_BYTES: usize = 1024;
+
+/// A bounded cache of the tokens of complete texts, see [`TokenizerOptions::text_cache`].
+///
+/// Entries are kept in two generations: new entries are added to the current generation, and once
+/// it holds half of the capacity, it replaces the previous generation, whose entries are dropped.
+/// Hits in the previous generation are moved to the current one, so frequently encoded texts stay
+/// cached, like with a least-recently-used cache but without having to track every access.
+///
+/// [`TokenizerOptions::text_cache`]: crate::TokenizerOptions::text_cache
+pub(crate) struct TextCache {
+    capacity: usize,
+    generations: Mutex<Generations>,
+}
+
+#[derive(Default)]
+struct Generations {
+    current: HashMap<String, Arc<[Token]>>,
+    previous: HashMap<String, Arc<[Token]>>,
+}
+
+impl TextCache {
+    pub(crate) fn new(capacity: usize) -> TextCache {
+        TextCache {
+            capacity,
+            generations: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Returns the cached tokens of `text`, or caches the tokens returned by `encode`.
+    ///
+    /// The lock isn't held while encoding, so concurrent misses for the same text may both encode
+    /// it.
+    pub(crate) fn get_or_insert(
+        &self,
+        text: &str,
+        encode: impl FnOnce() -> io::Result<Vec<Token>>,
+    ) -> io::Result<Arc<[Token]>> {
+        {
+            let mut generations = self.lock();
+            if let Some(tokens) = generations.current.get(text) {
+                return Ok(Arc::clone(tokens));
+            }
+            if let Some(tokens) = generations.previous.remove(text) {
+                generations.insert(text, Arc::clone(&tokens), self.capacity);
+                return Ok(tokens);
+            }
+        }
+
+        let tokens = Arc::<[Token]>::from(encode()?);
+        self.lock().insert(text, Arc::clone(&tokens), self.capacity);
+        Ok(tokens)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Generations> {
+        self.generations
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Generations {
+    fn insert(&mut self, text: &str, tokens: Arc<[Token]>, capacity: usize) {
+        if self.current.len() >= (capacity / 2).max(1) {
+            self.previous = mem::take(&mut self.current);
+        }
+        self.current.insert(text.to_owned(), tokens);
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.current.len() + self.previous.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{EncodeOptions, Tokenizer, TokenizerOptions};
+
+    fn encode(tokenizer: &Tokenizer, text: &str, options: &EncodeOptions<'_>) -> Vec<Token> {
+        let mut tokens = Vec::new();
+        tokenizer.encode_with(text, &mut tokens, options);
+        tokens
+    }
+
+    #[test]
+    fn text_cache() {
+        let uncached = Tokenizer::new();
+        let tokenizer = Tokenizer::from_vocabulary(
+            uncached.vocabulary(),
+            TokenizerOptions::new().text_cache(4),
+        );
+        let cache = tokenizer.text_cache.as_deref().unwrap();
+
+        let uppercase = |text: &str| text.to_uppercase();
+        let options = [
+            EncodeOptions::new(),
+            EncodeOptions::new().max_tokens(2),
+            EncodeOptions::new().max_tokens(100),
+            EncodeOptions::new().special_markers(false),
+            EncodeOptions::new().lowercase(false),
+            EncodeOptions::new().normalize(&uppercase),
+        ];
+        for text in [
+            "a photo of a dog",
+            "Hi<end_of_text>",
+            "a photo of a dog",
+            "",
+        ] {
+            for options in &options {
+                assert_eq!(
+                    encode(&tokenizer, text, options),
+                    encode(&uncached, text, options),
+                    "{text}"
+                );
+            }
+        }
+        // Only texts encoded with the default options are cached
+        assert_eq!(cache.lock().len(), 3);
+
+        // The number of cached texts is bounded
+        for i in 0..100 {
+            let text = format!("a photo of {i} dogs");
+            assert_eq!(
+                encode(&tokenizer, &text, &EncodeOptions::new()),
+                encode(&uncached, &text, &EncodeOptions::new())
+            );
+            assert!(cache.lock().len() <= 4);
+        }
+
+        // Clones share the cache, while other tokenizers using the vocabulary don't
+        let clone = tokenizer.clone();
+        assert!(Arc::ptr_eq(
+            clone.text_cache.as_ref().unwrap(),
+            tokenizer.text_cache.as_ref().unwrap()
+        ));
+        assert!(uncached.text_cache.is_none());
+    }
+
+    #[test]
+    fn generations() {
+        let cache = TextCache::new(4);
+        let encode = |text: &str| {
+            cache
+                .get_or_insert(text, || Ok(vec![Token(text.len() as u16)]))
+                .unwrap()
+        };
+        for text in ["a", "bb", "ccc"] {
+            encode(text);
+        }
+        // "a" was moved to the previous generation, and is moved back on a hit
+        assert!(cache.lock().previous.contains_key("a"));
+        assert_eq!(&*encode("a"), [Token(1)]);
+        assert!(cache.lock().current.contains_key("a"));
+
+        let err = cache.get_or_insert("dddd", || Err(io::ErrorKind::TimedOut.into()));
+        assert!(err.is_err());
+        assert!(!cache.lock().current.contains_key("dddd"));
+    }
+}
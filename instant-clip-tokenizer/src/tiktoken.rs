@@ -0,0 +1,277 @@
+use std::io;
+use std::ops::Range;
+
+use crate::{HashMap, Token, Tokenizer, WordBoundary};
+
+/// Returns whether `data` looks like a `.tiktoken` file, whose lines consist of a base64-encoded
+/// token followed by its rank.
+pub(crate) fn is_tiktoken(data: &[u8]) -> bool {
+    let line = std::str::from_utf8(data)
+        .ok()
+        .and_then(|data| data.lines().map(str::trim).find(|line| !line.is_empty()));
+    line.map_or(false, |line| parse_line(line).is_ok())
+}
+
+/// Create a `Tokenizer` from vocabulary data in the `.tiktoken` format.
+///
+/// The ranks of the tokens are used as token ids. The first 256 ranks must be the tokens of the
+/// individual bytes, and every further token is turned into a merge rule combining the two tokens
+/// its bytes are split into by byte pair encoding them using only the tokens of lower rank. The
+/// special marker tokens follow the last token, and words are marked by a space prefix like in
+/// GPT-2 style vocabularies.
+pub(crate) fn from_tiktoken(data: &[u8], max_vocabulary_size: u16) -> io::Result<Tokenizer> {
+    let word_boundary = WordBoundary::SpacePrefix;
+    let base_tokens = word_boundary.base_tokens();
+    if max_vocabulary_size < base_tokens + 2 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "maximum vocabulary size must be at least {}",
+                base_tokens + 2
+            ),
+        ));
+    }
+
+    let data = std::str::from_utf8(data).map_err(|_| invalid_data("invalid UTF-8"))?;
+    let mut tokens = Vec::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        let (bytes, rank) = parse_line(line)?;
+        if rank != tokens.len() {
+            return Err(invalid_data("ranks must be consecutive, starting at 0"));
+        }
+        tokens.push(bytes);
+    }
+    tokens.truncate(usize::from(max_vocabulary_size - 2));
+
+    let mut byte_to_token = Box::new([Token(u16::MAX); 256]);
+    for (rank, bytes) in tokens.iter().take(256).enumerate() {
+        let token = Token(rank as u16);
+        match bytes.as_slice() {
+            &[byte] if byte_to_token[usize::from(byte)] == Token(u16::MAX) => {
+                byte_to_token[usize::from(byte)] = token;
+            }
+            _ => {
+                return Err(invalid_data(
+                    "the first 256 tokens must be the individual bytes",
+                ))
+            }
+        }
+    }
+    if tokens.len() < 256 {
+        return Err(invalid_data(
+            "the first 256 tokens must be the individual bytes",
+        ));
+    }
+
+    let ranks = tokens
+        .iter()
+        .enumerate()
+        .map(|(rank, bytes)| (bytes.as_slice(), rank as u16))
+        .collect::<HashMap<_, _>>();
+    let mut merge_rules = HashMap::default();
+    for (rank, bytes) in tokens.iter().enumerate().skip(256) {
+        let rank = rank as u16;
+        let (first, second) = split(bytes, rank, &ranks)
+            .ok_or_else(|| invalid_data("token can't be built from tokens of lower rank"))?;
+        merge_rules.insert((Token(first), Token(second)), Token(rank));
+    }
+
+    Tokenizer::from_tables(
+        byte_to_token,
+        merge_rules,
+        tokens.len() as u16,
+        word_boundary,
+    )
+}
+
+/// Splits `bytes` into the two tokens resulting from byte pair encoding them using only the tokens
+/// with a rank lower than `rank`, and returns their ranks.
+fn split(bytes: &[u8], rank: u16, ranks: &HashMap<&[u8], u16>) -> Option<(u16, u16)> {
+    let lower_rank = |range: Range<usize>| ranks.get(&bytes[range]).filter(|&&r| r < rank);
+    let mut parts = (0..bytes.len()).map(|i| i..i + 1).collect::<Vec<_>>();
+    while parts.len() > 2 {
+        let (_, i) = parts
+            .windows(2)
+            .enumerate()
+            .filter_map(|(i, pair)| Some((lower_rank(pair[0].start..pair[1].end)?, i)))
+            .min()?;
+        parts[i].end = parts[i + 1].end;
+        parts.remove(i + 1);
+    }
+    match parts.as_slice() {
+        [first, second] => Some((*lower_rank(first.clone())?, *lower_rank(second.clone())?)),
+        _ => None,
+    }
+}
+
+fn parse_line(line: &str) -> io::Result<(Vec<u8>, usize)> {
+    let invalid_line = || invalid_data("lines must contain a base64-encoded token and its rank");
+    let mut parts = line.split(' ');
+    let (token, rank) = match (parts.next(), parts.next(), parts.next()) {
+        (Some(token), Some(rank), None) => (token, rank),
+        _ => return Err(invalid_line()),
+    };
+    let rank = Some(rank)
+        .filter(|rank| rank.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|rank| rank.parse().ok())
+        .ok_or_else(invalid_line)?;
+    let token = decode_base64(token.as_bytes()).ok_or_else(invalid_line)?;
+    if token.is_empty() {
+        return Err(invalid_line());
+    }
+    Ok((token, rank))
+}
+
+/// Decodes padded base64 data using the standard alphabet.
+fn decode_base64(data: &[u8]) -> Option<Vec<u8>> {
+    fn value(b: u8) -> Option<u32> {
+        Some(match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        } as u32)
+    }
+
+    if data.len() % 4 != 0 {
+        return None;
+    }
+    let mut decoded = Vec::with_capacity(data.len() / 4 * 3);
+    for (i, chunk) in data.chunks(4).enumerate() {
+        let padding = match chunk {
+            [.., b'=', b'='] => 2,
+            [.., b'='] => 1,
+            _ => 0,
+        };
+        if padding > 0 && i != data.len() / 4 - 1 {
+            return None;
+        }
+        let mut bits = 0;
+        for &b in &chunk[..4 - padding] {
+            bits = bits << 6 | value(b)?;
+        }
+        bits <<= 6 * padding;
+        decoded.extend_from_slice(&bits.to_be_bytes()[1..4 - padding]);
+    }
+    Some(decoded)
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut encoded = String::new();
+        for chunk in bytes.chunks(3) {
+            let mut buf = [0; 3];
+            buf[..chunk.len()].copy_from_slice(chunk);
+            let bits = u32::from_be_bytes([0, buf[0], buf[1], buf[2]]);
+            for i in 0..4 {
+                match i <= chunk.len() {
+                    true => {
+                        encoded.push(char::from(ALPHABET[(bits >> (18 - 6 * i) & 63) as usize]))
+                    }
+                    false => encoded.push('='),
+                }
+            }
+        }
+        encoded
+    }
+
+    /// Returns a GPT-2 style tokenizer together with its vocabulary in the `.tiktoken` format.
+    fn tokenizer_and_data() -> (Tokenizer, String) {
+        let merges = "#version: 0.2\nĠ t\nh e\nĠt he\ni n\nĠ a\nn d\nĠa nd\nĠ in\nt he\n";
+        let tokenizer =
+            Tokenizer::with_vocabulary_scheme(merges.as_bytes(), 300, WordBoundary::SpacePrefix)
+                .unwrap();
+        let data = tokenizer
+            .vocab_iter()
+            .take_while(|(token, _)| *token != tokenizer.start_of_text())
+            .map(|(token, bytes)| format!("{} {}\n", encode_base64(bytes), token.to_u16()))
+            .collect::<String>();
+        (tokenizer, data)
+    }
+
+    #[test]
+    fn load() {
+        let (expected, data) = tokenizer_and_data();
+        assert!(data.starts_with("IQ== 0\n"));
+        assert!(is_tiktoken(data.as_bytes()));
+
+        let tokenizer = Tokenizer::from_reader(data.as_bytes(), 300).unwrap();
+        assert_eq!(tokenizer.word_boundary(), WordBoundary::SpacePrefix);
+        assert!(tokenizer.vocab_iter().eq(expected.vocab_iter()));
+        assert_eq!(
+            tokenizer.vocabulary.merge_rules,
+            expected.vocabulary.merge_rules
+        );
+        for text in ["the theme", "in and and", "hi there!! 🦀"] {
+            let (mut tokens, mut expected_tokens) = (Vec::new(), Vec::new());
+            tokenizer.encode(text, &mut tokens);
+            expected.encode(text, &mut expected_tokens);
+            assert_eq!(tokens, expected_tokens, "{text}");
+        }
+
+        // The vocabulary is limited to the maximum size, including the special marker tokens
+        let tokenizer = Tokenizer::from_reader(data.as_bytes(), 260).unwrap();
+        assert_eq!(tokenizer.end_of_text().to_u16(), 259);
+        assert!(Tokenizer::from_reader(data.as_bytes(), 257).is_err());
+    }
+
+    #[test]
+    fn invalid() {
+        let (_, data) = tokenizer_and_data();
+        let lines = data.lines().collect::<Vec<_>>();
+        let load = |lines: &[&str]| from_tiktoken(lines.join("\n").as_bytes(), 300);
+        assert!(load(&lines).is_ok());
+
+        // Gap in the ranks
+        assert!(load(&[&lines[..100], &lines[101..]].concat()).is_err());
+        // Missing byte token
+        assert!(load(&lines[..200]).is_err());
+        // Token of lower rank than the tokens it is made of
+        let token = |line: &str| line.split(' ').next().unwrap().to_owned();
+        let swapped = [
+            format!("{} 256", token(lines[258])),
+            format!("{} 257", token(lines[257])),
+            format!("{} 258", token(lines[256])),
+        ];
+        let swapped = lines[..256]
+            .iter()
+            .copied()
+            .chain(swapped.iter().map(String::as_str))
+            .collect::<Vec<_>>();
+        assert!(load(&swapped).is_err());
+        // Invalid base64
+        assert!(load(&["IQ=A 0"]).is_err());
+        assert!(!is_tiktoken(b"#version: 0.2\ni n\n"));
+        assert!(!is_tiktoken(b"1 0\n"));
+    }
+
+    #[test]
+    fn base64() {
+        for bytes in [&b""[..], b"a", b"ab", b"abc", b"abcd", b"\xff\x00\xfe\x01"] {
+            assert_eq!(
+                decode_base64(encode_base64(bytes).as_bytes()).as_deref(),
+                Some(bytes)
+            );
+        }
+        assert_eq!(decode_base64(b"IHRoZQ==").unwrap(), b" the");
+        assert_eq!(decode_base64(b"IQ=="), Some(b"!".to_vec()));
+        assert_eq!(decode_base64(b"IQ="), None);
+        assert_eq!(decode_base64(b"IQ==IQ=="), None);
+        assert_eq!(decode_base64(b"I!=="), None);
+    }
+}
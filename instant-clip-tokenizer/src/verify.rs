@@ -0,0 +1,175 @@
+use std::fmt;
+
+use crate::{Token, Tokenizer};
+
+impl Tokenizer {
+    /// Check that this `Tokenizer` encodes texts exactly as expected.
+    ///
+    /// Each case consists of an input text and the token ids it is expected to be encoded as, e.g.
+    /// as recorded from the original Python implementation. The expected ids must not include the
+    /// `<start_of_text>` and `<end_of_text>` marker tokens or any padding, i.e. they should match
+    /// the output of [`encode`] exactly. Returns a report listing every case for which the
+    /// output of [`encode`] differs from the expected ids.
+    ///
+    /// [`encode`]: Tokenizer::encode
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use instant_clip_tokenizer::Tokenizer;
+    /// let tokenizer = Tokenizer::new();
+    /// let report = tokenizer.verify_against([
+    ///     ("Hi there", vec![1883, 997]),
+    ///     ("Hello world!!!", vec![3306, 1002, 995]),
+    /// ]);
+    /// assert!(report.is_ok(), "{report}");
+    /// ```
+    pub fn verify_against<'a, I, E>(&self, cases: I) -> VerificationReport
+    where
+        I: IntoIterator<Item = (&'a str, E)>,
+        E: AsRef<[u16]>,
+    {
+        let mut total = 0;
+        let mut mismatches = Vec::new();
+        let mut tokens = Vec::new();
+        for (index, (text, expected)) in cases.into_iter().enumerate() {
+            total += 1;
+            tokens.clear();
+            self.encode(text, &mut tokens);
+            let expected = expected.as_ref();
+            let actual = tokens.iter().map(|token| token.to_u16());
+            if actual.clone().eq(expected.iter().copied()) {
+                continue;
+            }
+
+            let first_difference = actual
+                .clone()
+                .zip(expected)
+                .position(|(actual, &expected)| actual != expected)
+                .unwrap_or_else(|| tokens.len().min(expected.len()));
+            mismatches.push(Mismatch {
+                index,
+                text: text.to_owned(),
+                expected: expected.to_vec(),
+                actual: actual.collect(),
+                first_difference,
+                actual_pieces: tokens.iter().map(|&token| self.piece(token)).collect(),
+            });
+        }
+
+        VerificationReport { total, mismatches }
+    }
+
+    pub(crate) fn piece(&self, token: Token) -> String {
+        String::from_utf8_lossy(self.token_bytes(token).unwrap_or_default()).into_owned()
+    }
+}
+
+/// The result of [`Tokenizer::verify_against`].
+///
+/// The `Display` implementation renders a human-readable summary of all mismatches.
+#[derive(Clone, Debug)]
+pub struct VerificationReport {
+    /// The number of cases that were checked.
+    pub total: usize,
+    /// All cases for which the tokenizer output differs from the expected token ids.
+    pub mismatches: Vec<Mismatch>,
+}
+
+impl VerificationReport {
+    /// Returns `true` if the tokenizer output matched the expected token ids for all cases.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+impl fmt::Display for VerificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_ok() {
+            return write!(f, "all {} texts match", self.total);
+        }
+
+        write!(
+            f,
+            "{} of {} texts don't match",
+            self.mismatches.len(),
+            self.total
+        )?;
+        for mismatch in &self.mismatches {
+            write!(f, "\n{mismatch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A single case for which the tokenizer output differs from the expected token ids.
+#[derive(Clone, Debug)]
+pub struct Mismatch {
+    /// The position of the case in the input to [`Tokenizer::verify_against`].
+    pub index: usize,
+    /// The input text.
+    pub text: String,
+    /// The expected token ids.
+    pub expected: Vec<u16>,
+    /// The token ids actually produced by the tokenizer.
+    pub actual: Vec<u16>,
+    /// The position of the first token id that differs between `expected` and `actual`.
+    pub first_difference: usize,
+    /// The textual representation of each token in `actual`.
+    pub actual_pieces: Vec<String>,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {:?}: expected {:?}, got {:?} {:?} (first difference at position {})",
+            self.index,
+            self.text,
+            self.expected,
+            self.actual,
+            self.actual_pieces,
+            self.first_difference
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_against() {
+        let tokenizer = Tokenizer::new();
+        let report = tokenizer.verify_against([
+            ("Hi there", &[1883, 997][..]),
+            ("Hello world!!!", &[3306, 1002, 996]),
+            ("A person", &[320]),
+        ]);
+        assert!(!report.is_ok());
+        assert_eq!(report.total, 3);
+        assert_eq!(report.mismatches.len(), 2);
+
+        let mismatch = &report.mismatches[0];
+        assert_eq!(mismatch.index, 1);
+        assert_eq!(mismatch.actual, [3306, 1002, 995]);
+        assert_eq!(mismatch.first_difference, 2);
+        assert_eq!(
+            mismatch.actual_pieces,
+            ["hello</w>", "world</w>", "!!!</w>"]
+        );
+
+        let mismatch = &report.mismatches[1];
+        assert_eq!(mismatch.index, 2);
+        assert_eq!(mismatch.first_difference, 1);
+
+        assert_eq!(
+            report.to_string(),
+            "2 of 3 texts don't match\n\
+             #1 \"Hello world!!!\": expected [3306, 1002, 996], got [3306, 1002, 995] \
+             [\"hello</w>\", \"world</w>\", \"!!!</w>\"] (first difference at position 2)\n\
+             #2 \"A person\": expected [320], got [320, 2533] [\"a</w>\", \"person</w>\"] \
+             (first difference at position 1)"
+        );
+    }
+}
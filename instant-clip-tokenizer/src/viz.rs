@@ -0,0 +1,133 @@
+//! Visualizations of tokenization results.
+//!
+//! # Examples
+//!
+//! ```
+//! # use instant_clip_tokenizer::{viz, Tokenizer};
+//! let tokenizer = Tokenizer::new();
+//! let html = viz::to_html(&tokenizer, "Hi there");
+//! assert!(html.contains(r#"<span title="1883" "#));
+//! ```
+
+use std::fmt::Write;
+
+use crate::{Token, Tokenizer};
+
+/// Background colors for consecutive tokens, cycled through in order.
+const COLORS: [&str; 5] = ["#fde68a", "#bbf7d0", "#bfdbfe", "#fbcfe8", "#ddd6fe"];
+
+/// Render the tokenization of `text` as a self-contained HTML snippet.
+///
+/// `text` is encoded using [`Tokenizer::encode`] and each resulting token is rendered as a
+/// `<span>` element with a distinct background color, showing the text the token represents and
+/// its numerical representation as a tooltip. Word boundaries are rendered as spaces between the
+/// spans.
+///
+/// The snippet only uses inline styles, so it can be embedded into any HTML document as is.
+pub fn to_html(tokenizer: &Tokenizer, text: &str) -> String {
+    let mut tokens = Vec::new();
+    tokenizer.encode(text, &mut tokens);
+    tokens_to_html(tokenizer, &tokens)
+}
+
+/// Render a sequence of `tokens` as a self-contained HTML snippet.
+///
+/// See [`to_html`] for details about the output format. Tokens that don't represent valid UTF-8
+/// on their own (e.g. parts of an emoji) are combined with the following tokens into a single
+/// `<span>` element, whose tooltip lists all of their numerical representations.
+pub fn tokens_to_html(tokenizer: &Tokenizer, tokens: &[Token]) -> String {
+    let mut html = String::from(
+        r#"<div style="font-family: monospace; line-height: 2; white-space: pre-wrap">"#,
+    );
+    let mut pending_bytes = Vec::new();
+    let mut pending_ids = Vec::new();
+    let mut spans = 0;
+    for (i, &token) in tokens.iter().enumerate() {
+        // Unknown tokens are rendered as replacement characters, like invalid UTF-8
+        let bytes = tokenizer
+            .token_bytes(token)
+            .unwrap_or("\u{FFFD}".as_bytes());
+        pending_bytes.extend_from_slice(bytes);
+        pending_ids.push(token.to_u16());
+        let piece = match std::str::from_utf8(&pending_bytes) {
+            Ok(piece) => piece.to_owned(),
+            // Wait for the following tokens to complete the UTF-8 sequence
+            Err(err) if err.error_len().is_none() && i + 1 < tokens.len() => continue,
+            Err(_) => String::from_utf8_lossy(&pending_bytes).into_owned(),
+        };
+
+        let (piece, end_of_word) = match piece.strip_suffix("</w>") {
+            Some(piece) => (piece, true),
+            None => (piece.as_str(), false),
+        };
+        let ids = pending_ids
+            .iter()
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        write!(
+            html,
+            r#"<span title="{ids}" style="background-color: {}; border-radius: 3px; padding: 1px 2px">{}</span>"#,
+            COLORS[spans % COLORS.len()],
+            escape(piece),
+        )
+        .unwrap();
+        if end_of_word {
+            html.push(' ');
+        }
+
+        spans += 1;
+        pending_bytes.clear();
+        pending_ids.clear();
+    }
+    html.push_str("</div>");
+    html
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '&' => escaped.push_str("&amp;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_html() {
+        let tokenizer = Tokenizer::new();
+        let html = super::to_html(&tokenizer, "Hi <there>");
+        assert_eq!(
+            html,
+            concat!(
+                r#"<div style="font-family: monospace; line-height: 2; white-space: pre-wrap">"#,
+                r#"<span title="1883" style="background-color: #fde68a; border-radius: 3px; padding: 1px 2px">hi</span> "#,
+                r#"<span title="283" style="background-color: #bbf7d0; border-radius: 3px; padding: 1px 2px">&lt;</span> "#,
+                r#"<span title="997" style="background-color: #bfdbfe; border-radius: 3px; padding: 1px 2px">there</span> "#,
+                r#"<span title="285" style="background-color: #fbcfe8; border-radius: 3px; padding: 1px 2px">&gt;</span> "#,
+                "</div>",
+            )
+        );
+    }
+
+    #[test]
+    fn to_html_incomplete_utf8() {
+        let tokenizer = Tokenizer::new();
+        let mut tokens = Vec::new();
+        tokenizer.encode("🦀", &mut tokens);
+        assert!(tokens.len() > 1);
+        let html = tokens_to_html(&tokenizer, &tokens);
+        assert_eq!(html.matches("<span").count(), 1);
+        assert!(html.contains(">🦀</span> "));
+    }
+}
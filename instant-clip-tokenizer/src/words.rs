@@ -0,0 +1,352 @@
+use std::cmp::Ordering;
+use std::ops::Range;
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::unicode_tables::{EXTENDED_PICTOGRAPHIC, LETTER, NUMBER};
+
+/// Substrings that are each encoded as a single marker token.
+const SPECIAL: [&str; 2] = ["<start_of_text>", "<end_of_text>"];
+
+/// Common English contractions, which are split off from the preceding word.
+const CONTRACTIONS: [&str; 7] = ["'s", "'t", "'re", "'ve", "'m", "'ll", "'d"];
+
+/// An iterator over the words of a text, together with their byte ranges.
+///
+/// This is a dedicated implementation of the regular expression the original CLIP implementation
+/// uses to split texts into words:
+///
+/// ```text
+/// <start_of_text>|<end_of_text>|'s|'t|'re|'ve|'m|'ll|'d|[\p{L}]+|[\p{N}]|[^\s\p{L}\p{N}]+
+/// ```
+///
+/// That is, words are the special marker substrings, contractions, runs of letters, single
+/// numeric characters, or runs of other characters except whitespace. Like the regular
+/// expression, alternatives are tried in this order at every position, and whitespace is skipped.
+///
+/// With `graphemes`, the same rules are applied to grapheme clusters instead of characters, see
+/// [`grapheme_word_len`].
+pub(crate) struct Words<'a> {
+    text: &'a str,
+    pos: usize,
+    graphemes: bool,
+}
+
+impl<'a> Words<'a> {
+    pub(crate) fn new(text: &'a str) -> Self {
+        Words {
+            text,
+            pos: 0,
+            graphemes: false,
+        }
+    }
+
+    pub(crate) fn with_graphemes(text: &'a str) -> Self {
+        Words {
+            text,
+            pos: 0,
+            graphemes: true,
+        }
+    }
+}
+
+impl<'a> Iterator for Words<'a> {
+    type Item = (&'a str, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rest = &self.text[self.pos..];
+        let (offset, first) = rest
+            .char_indices()
+            .find(|&(_, c)| CharClass::of(c) != CharClass::Whitespace)?;
+        let start = self.pos + offset;
+        let rest = &self.text[start..];
+
+        let prefixes = match first {
+            '<' => &SPECIAL[..],
+            '\'' => &CONTRACTIONS[..],
+            _ => &[],
+        };
+        let len = match prefixes.iter().find(|prefix| rest.starts_with(*prefix)) {
+            Some(prefix) => prefix.len(),
+            None if self.graphemes => grapheme_word_len(rest),
+            None => match CharClass::of(first) {
+                CharClass::Letter => run_len(rest, CharClass::Letter),
+                CharClass::Number => first.len_utf8(),
+                _ => run_len(rest, CharClass::Other),
+            },
+        };
+
+        self.pos = start + len;
+        Some((&self.text[start..self.pos], start..self.pos))
+    }
+}
+
+/// Returns the length in bytes of the run of characters of the given `class` at the start of
+/// `text`.
+fn run_len(text: &str, class: CharClass) -> usize {
+    text.char_indices()
+        .find(|&(_, c)| CharClass::of(c) != class)
+        .map_or(text.len(), |(i, _)| i)
+}
+
+/// Returns the length in bytes of the word at the start of `text`, keeping grapheme clusters
+/// together.
+///
+/// Clusters are classified by their first character, so e.g. letters followed by combining marks
+/// form a single run of letters. Clusters containing emoji (including ZWJ sequences, flags and
+/// skin tone modifiers) form a word on their own instead of being merged with adjacent emoji or
+/// punctuation. A number is a single cluster, like a number is a single character by default.
+fn grapheme_word_len(text: &str) -> usize {
+    let mut len = grapheme_len(text);
+    let class = GraphemeClass::of(&text[..len]);
+    if class == GraphemeClass::Emoji || class == GraphemeClass::Char(CharClass::Number) {
+        return len;
+    }
+    while len < text.len() {
+        let next = grapheme_len(&text[len..]);
+        if GraphemeClass::of(&text[len..len + next]) != class {
+            break;
+        }
+        len += next;
+    }
+    len
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum GraphemeClass {
+    Emoji,
+    Char(CharClass),
+}
+
+impl GraphemeClass {
+    fn of(cluster: &str) -> GraphemeClass {
+        let is_emoji = |c: char| {
+            !c.is_ascii()
+                && (is_regional_indicator(c)
+                    || c == '\u{20e3}'
+                    || in_table(EXTENDED_PICTOGRAPHIC, c))
+        };
+        match cluster.chars().any(is_emoji) {
+            true => GraphemeClass::Emoji,
+            false => GraphemeClass::Char(CharClass::of(cluster.chars().next().unwrap_or(' '))),
+        }
+    }
+}
+
+/// Returns the length in bytes of the extended grapheme cluster at the start of `text`, as defined
+/// by [Unicode Standard Annex #29][uax29].
+///
+/// [uax29]: https://www.unicode.org/reports/tr29/#Grapheme_Cluster_Boundary_Rules
+pub(crate) fn grapheme_len(text: &str) -> usize {
+    text.graphemes(true).next().map_or(0, str::len)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1f1e6}'..='\u{1f1ff}').contains(&c)
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum CharClass {
+    Letter,
+    Number,
+    Whitespace,
+    Other,
+}
+
+impl CharClass {
+    fn of(c: char) -> CharClass {
+        if c.is_ascii() {
+            return match c {
+                'a'..='z' | 'A'..='Z' => CharClass::Letter,
+                '0'..='9' => CharClass::Number,
+                // Unlike `char::is_ascii_whitespace`, this includes the vertical tab
+                '\t'..='\r' | ' ' => CharClass::Whitespace,
+                _ => CharClass::Other,
+            };
+        }
+
+        if in_table(LETTER, c) {
+            CharClass::Letter
+        } else if in_table(NUMBER, c) {
+            CharClass::Number
+        } else if c.is_whitespace() {
+            CharClass::Whitespace
+        } else {
+            CharClass::Other
+        }
+    }
+}
+
+fn in_table(table: &[(char, char)], c: char) -> bool {
+    table
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                Ordering::Less
+            } else if start > c {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            }
+        })
+        .is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::*;
+
+    fn regex() -> Regex {
+        Regex::new(
+            r"<start_of_text>|<end_of_text>|'s|'t|'re|'ve|'m|'ll|'d|[\p{L}]+|[\p{N}]|[^\s\p{L}\p{N}]+",
+        )
+        .unwrap()
+    }
+
+    fn assert_same_words(regex: &Regex, text: &str) {
+        let expected = regex
+            .find_iter(text)
+            .map(|m| (m.as_str(), m.range()))
+            .collect::<Vec<_>>();
+        assert_eq!(Words::new(text).collect::<Vec<_>>(), expected, "{text:?}");
+    }
+
+    #[test]
+    fn words() {
+        let words = Words::new("i've seen it!! <start_of_text>42")
+            .map(|(word, _)| word)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            words,
+            ["i", "'ve", "seen", "it", "!!", "<start_of_text>", "4", "2"]
+        );
+        assert_eq!(Words::new(" \t\n").count(), 0);
+    }
+
+    #[test]
+    fn char_classes() {
+        let letter = Regex::new(r"^\p{L}$").unwrap();
+        let number = Regex::new(r"^\p{N}$").unwrap();
+        let whitespace = Regex::new(r"^\s$").unwrap();
+        let pictographic = Regex::new(r"^\p{Extended_Pictographic}$").unwrap();
+        for c in (0..=char::MAX as u32).filter_map(char::from_u32) {
+            let s = c.encode_utf8(&mut [0; 4]).to_owned();
+            assert_eq!(
+                in_table(EXTENDED_PICTOGRAPHIC, c),
+                pictographic.is_match(&s),
+                "{c:?}"
+            );
+            let expected = if letter.is_match(&s) {
+                CharClass::Letter
+            } else if number.is_match(&s) {
+                CharClass::Number
+            } else if whitespace.is_match(&s) {
+                CharClass::Whitespace
+            } else {
+                CharClass::Other
+            };
+            assert_eq!(CharClass::of(c), expected, "{c:?}");
+        }
+    }
+
+    #[test]
+    fn equivalent_to_regex() {
+        let regex = regex();
+        for text in [
+            "",
+            "Hi there",
+            "I'm fine, thanks!",
+            "they'RE 'S 'll've''s '",
+            "!'s <<start_of_text>> <end_of_text<end_of_text>",
+            "<start_of_text",
+            "x²³ ½ Ⅻ 123 ٣٤",
+            "İstanbul ΟΔΥΣΣΕΥΣ 東京タワー 🦀🦀 e\u{301}",
+            "a\u{b}b\u{85}c\u{a0}d\u{3000}e\u{200b}f",
+        ] {
+            assert_same_words(&regex, text);
+        }
+
+        // Random texts over an alphabet covering all character classes and the special substrings
+        let alphabet = [
+            "a",
+            "Z",
+            "é",
+            "ß",
+            "東",
+            "1",
+            "٣",
+            "½",
+            " ",
+            "\n",
+            "\u{a0}",
+            "!",
+            "<",
+            ">",
+            "'",
+            "s",
+            "re",
+            "ll",
+            "_",
+            "🦀",
+            "\u{301}",
+            "<start_of_text>",
+            "<end_of_text>",
+        ];
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        for _ in 0..10_000 {
+            let mut text = String::new();
+            for _ in 0..(state % 16) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                text.push_str(alphabet[(state % alphabet.len() as u64) as usize]);
+            }
+            assert_same_words(&regex, &text);
+        }
+    }
+
+    #[test]
+    fn graphemes() {
+        fn clusters(text: &str) -> Vec<&str> {
+            let mut clusters = Vec::new();
+            let mut rest = text;
+            while !rest.is_empty() {
+                let len = grapheme_len(rest);
+                clusters.push(&rest[..len]);
+                rest = &rest[len..];
+            }
+            clusters
+        }
+        assert_eq!(clusters("e\u{301}x\r\n\n"), ["e\u{301}", "x", "\r\n", "\n"]);
+        assert_eq!(clusters("👩‍👩‍👧‍👦👍🏽"), ["👩‍👩‍👧‍👦", "👍🏽"]);
+        assert_eq!(clusters("🇩🇪🇫🇷🇮"), ["🇩🇪", "🇫🇷", "🇮"]);
+        assert_eq!(clusters("1️⃣❤️"), ["1️⃣", "❤️"]);
+        assert_eq!(clusters("한국어"), ["한", "국", "어"]);
+        assert_eq!(
+            clusters("\u{1100}\u{1161}\u{11a8}"),
+            ["\u{1100}\u{1161}\u{11a8}"]
+        );
+        assert_eq!(clusters("a\u{200d}👍"), ["a\u{200d}", "👍"]);
+        assert_eq!(clusters("नमस्ते"), ["न", "म", "स्ते"]);
+
+        fn words(text: &str) -> Vec<&str> {
+            Words::with_graphemes(text).map(|(word, _)| word).collect()
+        }
+        assert_eq!(
+            words("i've seen it!! <start_of_text>42"),
+            ["i", "'ve", "seen", "it", "!!", "<start_of_text>", "4", "2"]
+        );
+        assert_eq!(words("cafe\u{301}!"), ["cafe\u{301}", "!"]);
+        assert_eq!(words("नमस्ते"), ["नमस्ते"]);
+        assert_eq!(
+            words("hi👩‍👩‍👧‍👦👍🏽!! 🇩🇪🇫🇷 1️⃣2"),
+            ["hi", "👩‍👩‍👧‍👦", "👍🏽", "!!", "🇩🇪", "🇫🇷", "1️⃣", "2"]
+        );
+        assert_eq!(Words::with_graphemes(" \t\n").count(), 0);
+
+        // Without combining marks and emoji, words are the same
+        let text = "Hi there, I'm fine!! <end_of_text> x²³ ½ 東京タワー";
+        assert!(Words::with_graphemes(text).eq(Words::new(text)));
+    }
+}